@@ -0,0 +1,878 @@
+use crate::{
+    credentials::Credentials,
+    domain::Domain,
+    error::{ApiError, PorkbunError},
+    ip_utils::RecordType,
+    record::RecordUpdate,
+};
+use reqwest::header::RETRY_AFTER;
+use reqwest::Client;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::field::Empty;
+use tracing::{debug, error, info, info_span, instrument, warn, Instrument, Span};
+
+/// A record as returned by Porkbun, which sends numbers sometimes as strings and sometimes
+/// as numbers, so these fields are accepted in both forms. Other providers' records are
+/// decoded into the same structure.
+#[derive(Debug, Deserialize)]
+pub struct DnsRecord {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub record_type: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(
+        default,
+        alias = "priority",
+        deserialize_with = "optional_string_or_number"
+    )]
+    pub prio: Option<String>,
+    #[serde(default, deserialize_with = "optional_string_or_number")]
+    pub ttl: Option<String>,
+}
+
+impl DnsRecord {
+    /// Creates a record for providers whose responses aren't decoded into this structure directly.
+    pub fn new(id: String, name: &str, record_type: &RecordType, content: String) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            record_type: record_type.as_str().to_string(),
+            content,
+            prio: None,
+            ttl: None,
+        }
+    }
+}
+
+/// A JSON value which may be sent as string or number.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrNumber {
+    String(String),
+    Number(serde_json::Number),
+}
+
+impl From<StringOrNumber> for String {
+    fn from(value: StringOrNumber) -> Self {
+        match value {
+            StringOrNumber::String(value) => value,
+            StringOrNumber::Number(value) => value.to_string(),
+        }
+    }
+}
+
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    StringOrNumber::deserialize(deserializer).map(String::from)
+}
+
+fn optional_string_or_number<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(Option::<StringOrNumber>::deserialize(deserializer)?.map(String::from))
+}
+
+/// The fields every Porkbun response has, checked before the typed response is decoded.
+#[derive(Debug, Deserialize)]
+struct StatusResponse {
+    #[serde(default)]
+    status: String,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExistingRecordsResponse {
+    #[serde(default)]
+    records: Vec<DnsRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateDnsRecordResponse {
+    #[serde(default, deserialize_with = "optional_string_or_number")]
+    id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListDomainsResponse {
+    #[serde(default)]
+    domains: Vec<AccountDomain>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PingResponse {
+    #[serde(rename = "yourIp")]
+    your_ip: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountDomain {
+    domain: String,
+}
+
+/// The base URL of the Porkbun API, used unless the client is created with another one
+pub const API_BASE_URL: &str = "https://api.porkbun.com/api/json/v3";
+/// The base URL of the Porkbun API reachable over IPv4 only, e.g. to look up the public IPv4 address
+pub const IPV4_API_BASE_URL: &str = "https://api-ipv4.porkbun.com/api/json/v3";
+/// The TTL in seconds of records created without one, Porkbun's minimum
+pub const DEFAULT_TTL: u64 = 600;
+/// Porkbun returns at most 1000 domains per `domain/listAll` call
+const LIST_DOMAINS_PAGE_SIZE: usize = 1000;
+/// Malformed responses (e.g. HTML maintenance pages) are logged up to this many characters
+const MAX_LOGGED_BODY_LENGTH: usize = 1000;
+
+/// Environment variable with the number of attempts of a Porkbun call (including the first one)
+pub const RETRY_ATTEMPTS_ENV: &str = "PORKDYN_RETRY_ATTEMPTS";
+/// Environment variable with the delay before the first retry in milliseconds, doubled for every
+/// further retry
+pub const RETRY_BASE_DELAY_ENV: &str = "PORKDYN_RETRY_BASE_DELAY_MS";
+const DEFAULT_RETRY_ATTEMPTS: u32 = 3;
+const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+/// Backoff and `Retry-After` delays are capped, so the retries fit into the Lambda timeout
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Environment variable with the timeout for establishing a connection to Porkbun in seconds
+pub const CONNECT_TIMEOUT_ENV: &str = "PORKDYN_CONNECT_TIMEOUT_SECONDS";
+/// Environment variable with the timeout of a single Porkbun call (including the response) in seconds
+pub const REQUEST_TIMEOUT_ENV: &str = "PORKDYN_REQUEST_TIMEOUT_SECONDS";
+const DEFAULT_CONNECT_TIMEOUT_SECONDS: u64 = 5;
+const DEFAULT_REQUEST_TIMEOUT_SECONDS: u64 = 10;
+
+/// How failed Porkbun calls are retried, only transient failures (network errors,
+/// `429 Too Many Requests` and `5xx` responses) are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    attempts: u32,
+    base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: DEFAULT_RETRY_ATTEMPTS,
+            base_delay: DEFAULT_RETRY_BASE_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates the policy, unset or zero values use the defaults.
+    pub fn new(attempts: Option<u32>, base_delay: Option<Duration>) -> Self {
+        let default = Self::default();
+        Self {
+            attempts: attempts
+                .filter(|attempts| *attempts > 0)
+                .unwrap_or(default.attempts),
+            base_delay: base_delay.unwrap_or(default.base_delay),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::var(RETRY_ATTEMPTS_ENV)
+                .ok()
+                .and_then(|attempts| attempts.parse().ok()),
+            std::env::var(RETRY_BASE_DELAY_ENV)
+                .ok()
+                .and_then(|millis| millis.parse().ok())
+                .map(Duration::from_millis),
+        )
+    }
+
+    /// Exponential backoff with jitter: between half and the full `base_delay * 2^(attempt - 1)`.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(MAX_RETRY_DELAY);
+        delay / 2 + (delay / 2).mul_f64(jitter())
+    }
+}
+
+/// A random factor between 0 and 1, the std hasher is randomly seeded per instance.
+fn jitter() -> f64 {
+    RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64
+}
+
+/// The Porkbun operations whose calls are timed, named like the `action` field of their spans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endpoint {
+    Ping,
+    List,
+    Retrieve,
+    Create,
+    Edit,
+    Delete,
+}
+
+impl Endpoint {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Endpoint::Ping => "ping",
+            Endpoint::List => "list",
+            Endpoint::Retrieve => "retrieve",
+            Endpoint::Create => "create",
+            Endpoint::Edit => "edit",
+            Endpoint::Delete => "delete",
+        }
+    }
+
+    /// Whether sending the call twice has the same effect as once. A repeated create would add
+    /// a duplicate record, so it is only retried if the first call never reached Porkbun.
+    pub fn is_idempotent(&self) -> bool {
+        !matches!(self, Endpoint::Create)
+    }
+}
+
+/// The time every Porkbun call (each attempt) took, collected for the metrics of a request.
+/// Clones share the calls, so the collector can be kept while the client is passed on.
+#[derive(Debug, Clone, Default)]
+pub struct Latencies(Arc<Mutex<Vec<(Endpoint, Duration)>>>);
+
+impl Latencies {
+    fn record(&self, endpoint: Endpoint, latency: Duration) {
+        self.0.lock().unwrap().push((endpoint, latency));
+    }
+
+    /// The calls in the order they finished.
+    pub fn calls(&self) -> Vec<(Endpoint, Duration)> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Client for the Porkbun API, created once at cold start so connections are reused.
+#[derive(Debug, Clone)]
+pub struct PorkbunClient {
+    client: Client,
+    retry: RetryPolicy,
+    base_url: String,
+    deadline: Option<Instant>,
+    latencies: Option<Latencies>,
+}
+
+impl Default for PorkbunClient {
+    fn default() -> Self {
+        Self::new(Client::default(), RetryPolicy::default())
+    }
+}
+
+impl PorkbunClient {
+    pub fn new(client: Client, retry: RetryPolicy) -> Self {
+        Self {
+            client,
+            retry,
+            base_url: API_BASE_URL.to_string(),
+            deadline: None,
+            latencies: None,
+        }
+    }
+
+    /// Sends the calls to another base URL instead of [`API_BASE_URL`], e.g. a mock server in tests.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Stops sending calls and retries at `deadline`, e.g. shortly before the Lambda invocation
+    /// times out. Calls still running then fail with [`PorkbunError::DeadlineExceeded`].
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Records the latency of every call in `latencies`, e.g. for the metrics of a request.
+    pub fn with_latencies(mut self, latencies: Latencies) -> Self {
+        self.latencies = Some(latencies);
+        self
+    }
+
+    /// The base URL the calls are sent to, without a trailing slash.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Creates the client with the timeouts and retry policy configured in the environment,
+    /// so a hanging Porkbun endpoint can't use up the whole Lambda duration.
+    pub fn from_env() -> Self {
+        let seconds = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|seconds| seconds.parse().ok())
+                .map(Duration::from_secs)
+        };
+        Self::with_timeouts(
+            seconds(CONNECT_TIMEOUT_ENV),
+            seconds(REQUEST_TIMEOUT_ENV),
+            RetryPolicy::from_env(),
+        )
+    }
+
+    /// Creates the client with the given timeouts and retry policy, unset timeouts use the defaults.
+    pub fn with_timeouts(
+        connect_timeout: Option<Duration>,
+        request_timeout: Option<Duration>,
+        retry: RetryPolicy,
+    ) -> Self {
+        let client = Client::builder()
+            .connect_timeout(
+                connect_timeout.unwrap_or(Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECONDS)),
+            )
+            .timeout(
+                request_timeout.unwrap_or(Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECONDS)),
+            )
+            .build()
+            .expect("Failed to create the HTTP client");
+        Self::new(client, retry)
+    }
+
+    /// The underlying HTTP client, shared with the other providers.
+    pub fn http_client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Sends a request to the Porkbun API and decodes the response, retrying transient failures
+    /// as long as the deadline (if any) leaves time for it. Every attempt is traced as a client
+    /// span, and its latency logged and recorded by endpoint. A response without
+    /// `"status": "SUCCESS"` is turned into a typed error.
+    async fn post<T: DeserializeOwned>(
+        &self,
+        endpoint: Endpoint,
+        url: &str,
+        body: &impl Serialize,
+    ) -> Result<T, PorkbunError> {
+        let mut attempt = 1;
+        loop {
+            let span = info_span!(
+                "porkbun.request",
+                otel.kind = "client",
+                otel.status_code = Empty,
+                http.request.method = "POST",
+                http.response.status_code = Empty,
+                url.full = url,
+                attempt,
+            );
+            let request = self.post_once(url, body).instrument(span.clone());
+            let started = Instant::now();
+            let (result, retry_after) = match self.remaining() {
+                Some(remaining) if remaining.is_zero() => {
+                    return Err(PorkbunError::DeadlineExceeded(
+                        "No time left to call Porkbun".to_string(),
+                    ))
+                }
+                Some(remaining) => tokio::time::timeout(remaining, request)
+                    .await
+                    .unwrap_or_else(|_| {
+                        // Porkbun may or may not have applied a change it didn't confirm
+                        let message = format!("Porkbun didn't answer within {:?}", remaining);
+                        (Err(PorkbunError::DeadlineExceeded(message)), None)
+                    }),
+                None => request.await,
+            };
+            let latency = started.elapsed();
+            if result.is_err() {
+                span.record("otel.status_code", "ERROR");
+            }
+            span.in_scope(|| {
+                info!(
+                    endpoint = endpoint.as_str(),
+                    attempt,
+                    latency_ms = latency.as_millis() as u64,
+                    success = result.is_ok(),
+                    "Porkbun call finished"
+                )
+            });
+            if let Some(latencies) = &self.latencies {
+                latencies.record(endpoint, latency);
+            }
+            let retryable = |e: &PorkbunError| {
+                e.is_transient() && (endpoint.is_idempotent() || e.was_not_applied())
+            };
+            match result {
+                Err(e) if retryable(&e) && attempt < self.retry.attempts => {
+                    let delay = retry_after
+                        .map(|delay| delay.min(MAX_RETRY_DELAY))
+                        .unwrap_or_else(|| self.retry.backoff(attempt));
+                    if self.remaining().is_some_and(|remaining| remaining <= delay) {
+                        warn!(
+                            attempt,
+                            attempts = self.retry.attempts,
+                            error = %e,
+                            "Porkbun call failed, no time left to retry"
+                        );
+                        return Err(e);
+                    }
+                    warn!(
+                        attempt,
+                        attempts = self.retry.attempts,
+                        delay = ?delay,
+                        error = %e,
+                        "Porkbun call failed, retrying"
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// The time until the deadline, `None` without deadline.
+    fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Sends a single request, also returning the delay requested by a `Retry-After` header.
+    async fn post_once<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &impl Serialize,
+    ) -> (Result<T, PorkbunError>, Option<Duration>) {
+        let response = match self.client.post(url).json(body).send().await {
+            Ok(response) => response,
+            Err(e) => return (Err(e.into()), None),
+        };
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|seconds| seconds.trim().parse().ok())
+            .map(Duration::from_secs);
+        let http_status = response.status().as_u16();
+        Span::current().record("http.response.status_code", http_status);
+        (decode(http_status, response.text().await), retry_after)
+    }
+}
+
+/// Decodes the body of a Porkbun response.
+fn decode<T: DeserializeOwned>(
+    http_status: u16,
+    text: Result<String, reqwest::Error>,
+) -> Result<T, PorkbunError> {
+    // The body is read as text first, so it can be logged if it isn't the expected JSON
+    let text = text?;
+    let malformed = |e: serde_json::Error| {
+        error!(
+            http_status = %http_status,
+            body = truncate(&text, MAX_LOGGED_BODY_LENGTH),
+            "Malformed Porkbun response"
+        );
+        PorkbunError::MalformedResponse(e.to_string())
+    };
+    let body: serde_json::Value = match serde_json::from_str(&text) {
+        Ok(body) => body,
+        // Rate limited or unavailable servers may answer with an HTML page instead of JSON
+        Err(_) if http_status == 429 || http_status >= 500 => {
+            return Err(PorkbunError::from_response(
+                http_status,
+                format!("HTTP {}", http_status),
+            ))
+        }
+        Err(e) => return Err(malformed(e)),
+    };
+    let status: StatusResponse = serde_json::from_value(body.clone()).map_err(malformed)?;
+    if status.status != "SUCCESS" {
+        let message = status
+            .message
+            .unwrap_or_else(|| "Unknown error".to_string());
+        return Err(PorkbunError::from_response(http_status, message));
+    }
+    serde_json::from_value(body).map_err(malformed)
+}
+
+/// Shortens a text to at most `max_length` characters for logging.
+fn truncate(text: &str, max_length: usize) -> &str {
+    match text.char_indices().nth(max_length) {
+        Some((index, _)) => &text[..index],
+        None => text,
+    }
+}
+
+/// Validates the credentials, returning the public IP address Porkbun sees the request from.
+#[instrument(name = "porkbun.ping", skip_all, fields(action = "ping"))]
+pub async fn ping(client: &PorkbunClient, credentials: &Credentials) -> Result<String, ApiError> {
+    let url = format!("{}/ping", client.base_url());
+    info!(url = %url, "Validate credentials");
+    let response: PingResponse = client
+        .post(
+            Endpoint::Ping,
+            &url,
+            &serde_json::json!({
+                "apikey": credentials.api_key(),
+                "secretapikey": credentials.secret_key()
+            }),
+        )
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to validate credentials"))
+        .map_err(ApiError::PingFailed)?;
+    Ok(response.your_ip.unwrap_or_default())
+}
+
+/// Lists all domains of the account the credentials belong to.
+#[instrument(name = "porkbun.list_domains", skip_all, fields(action = "list"))]
+pub async fn list_domains(
+    client: &PorkbunClient,
+    credentials: &Credentials,
+) -> Result<Vec<String>, ApiError> {
+    let url = format!("{}/domain/listAll", client.base_url());
+    let mut domains = Vec::new();
+    loop {
+        info!(start = domains.len(), url = %url, "List domains of account");
+        let response: ListDomainsResponse = client
+            .post(
+                Endpoint::List,
+                &url,
+                &serde_json::json!({
+                    "apikey": credentials.api_key(),
+                    "secretapikey": credentials.secret_key(),
+                    "start": domains.len().to_string()
+                }),
+            )
+            .await
+            .inspect_err(|e| error!(error = %e, "Failed to list domains"))
+            .map_err(ApiError::ListDomainsFailed)?;
+
+        let page_size = response.domains.len();
+        domains.extend(response.domains.into_iter().map(|domain| domain.domain));
+        if page_size < LIST_DOMAINS_PAGE_SIZE {
+            break;
+        }
+    }
+    debug!(domains = domains.len(), "Found domains in account");
+    Ok(domains)
+}
+
+/// Retrieves all records of the given type for the domain, e.g. multiple TXT records of a name.
+#[instrument(
+    name = "porkbun.retrieve",
+    skip_all,
+    fields(domain = domain.qualified_name(), record_type = record_type.as_str(), action = "retrieve")
+)]
+pub async fn get_dns_records(
+    client: &PorkbunClient,
+    credentials: &Credentials,
+    domain: &Domain,
+    record_type: &RecordType,
+) -> Result<Vec<DnsRecord>, ApiError> {
+    let domain_name = domain.domain_name();
+    let qualified_name = domain.qualified_name();
+    let record_type_str = record_type.as_str();
+    let url = name_type_url(client, "retrieveByNameType", domain, record_type);
+    info!(record_type = record_type_str, domain = domain_name, url = %url, "Get existing record");
+    let response: ExistingRecordsResponse = client
+        .post(
+            Endpoint::Retrieve,
+            &url,
+            &serde_json::json!({
+                "apikey": credentials.api_key(),
+                "secretapikey": credentials.secret_key()
+            }),
+        )
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to retrieve DNS records"))
+        .map_err(ApiError::RetrieveRecordFailed)?;
+
+    let records = response.records;
+    info!(records = ?records, "Found records");
+    Ok(records
+        .into_iter()
+        .filter(|record| {
+            debug!(record = ?record, qualified_name, "Checking record");
+            record.name.eq_ignore_ascii_case(qualified_name)
+        })
+        .collect())
+}
+
+/// Retrieves all records of the domain's zone, e.g. to audit what is managed.
+#[instrument(
+    name = "porkbun.retrieve",
+    skip_all,
+    fields(domain = domain_name, action = "retrieve")
+)]
+pub async fn get_all_dns_records(
+    client: &PorkbunClient,
+    credentials: &Credentials,
+    domain_name: &str,
+) -> Result<Vec<DnsRecord>, ApiError> {
+    let url = format!("{}/dns/retrieve/{}", client.base_url(), domain_name);
+    info!(domain = domain_name, url = %url, "Get all records of domain");
+    let response: ExistingRecordsResponse = client
+        .post(
+            Endpoint::Retrieve,
+            &url,
+            &serde_json::json!({
+                "apikey": credentials.api_key(),
+                "secretapikey": credentials.secret_key()
+            }),
+        )
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to retrieve DNS records"))
+        .map_err(ApiError::RetrieveRecordFailed)?;
+    debug!(records = response.records.len(), "Found records");
+    Ok(response.records)
+}
+
+/// Builds the URL of a `*ByNameType` endpoint, the subdomain segment is omitted for records on
+/// the apex/root domain.
+fn name_type_url(
+    client: &PorkbunClient,
+    endpoint: &str,
+    domain: &Domain,
+    record_type: &RecordType,
+) -> String {
+    let url = format!(
+        "{}/dns/{}/{}/{}",
+        client.base_url(),
+        endpoint,
+        domain.domain_name(),
+        record_type.as_str()
+    );
+    if domain.is_apex() {
+        url
+    } else {
+        format!("{}/{}", url, domain.subdomain())
+    }
+}
+
+/// Deletes all records of the name and type, e.g. a stale dynamic record.
+#[instrument(
+    name = "porkbun.delete",
+    skip_all,
+    fields(domain = domain.qualified_name(), record_type = record_type.as_str(), action = "delete")
+)]
+pub async fn delete_dns_records_by_name_type(
+    client: &PorkbunClient,
+    credentials: &Credentials,
+    domain: &Domain,
+    record_type: &RecordType,
+) -> Result<(), ApiError> {
+    let url = name_type_url(client, "deleteByNameType", domain, record_type);
+    info!(url = %url, "Delete DNS records by name and type");
+    client
+        .post::<IgnoredAny>(
+            Endpoint::Delete,
+            &url,
+            &serde_json::json!({
+                "apikey": credentials.api_key(),
+                "secretapikey": credentials.secret_key()
+            }),
+        )
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to delete DNS records by name and type"))
+        .map_err(ApiError::DeleteRecordFailed)?;
+    info!(
+        record_type = record_type.as_str(),
+        domain = domain.qualified_name(),
+        "Deleted records"
+    );
+    Ok(())
+}
+
+/// Deletes a single record by its id.
+#[instrument(
+    name = "porkbun.delete",
+    skip_all,
+    fields(domain = domain.qualified_name(), record_id = record_id, action = "delete")
+)]
+pub async fn delete_dns_record(
+    client: &PorkbunClient,
+    credentials: &Credentials,
+    domain: &Domain,
+    record_id: &str,
+) -> Result<(), ApiError> {
+    let url = format!(
+        "{}/dns/delete/{}/{}",
+        client.base_url(),
+        domain.domain_name(),
+        record_id
+    );
+    info!(url = %url, "Delete DNS record");
+    client
+        .post::<IgnoredAny>(
+            Endpoint::Delete,
+            &url,
+            &serde_json::json!({
+                "apikey": credentials.api_key(),
+                "secretapikey": credentials.secret_key()
+            }),
+        )
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to delete DNS record"))
+        .map_err(ApiError::DeleteRecordFailed)?;
+    info!(record_id, "Deleted DNS record");
+    Ok(())
+}
+
+/// Replaces the record with the id, e.g. a record found with [`get_dns_records`].
+#[instrument(
+    name = "porkbun.edit",
+    skip_all,
+    fields(
+        domain = domain.qualified_name(),
+        record_type = record.record_type.as_str(),
+        record_id = record_id,
+        action = "edit"
+    )
+)]
+pub async fn update_dns_record(
+    client: &PorkbunClient,
+    credentials: &Credentials,
+    domain: &Domain,
+    record_id: &str,
+    record: &RecordUpdate,
+) -> Result<(), ApiError> {
+    let domain_name = domain.domain_name();
+    let subdomain = domain.subdomain();
+    let url: String = format!(
+        "{}/dns/edit/{}/{}",
+        client.base_url(),
+        domain_name,
+        record_id
+    );
+    let request_body: CreateUpdateDnsRecordRequest =
+        CreateUpdateDnsRecordRequest::new(credentials, subdomain, record);
+    info!(url = %url, subdomain, "Update DNS record");
+    client
+        .post::<IgnoredAny>(Endpoint::Edit, &url, &request_body)
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to update DNS record"))
+        .map_err(ApiError::UpdateRecordFailed)?;
+    info!(record_id, "Updated DNS record");
+    Ok(())
+}
+
+/// Updates all records of the name and type in a single call, without retrieving their ids first.
+/// Fails if no such record exists.
+#[instrument(
+    name = "porkbun.edit",
+    skip_all,
+    fields(domain = domain.qualified_name(), record_type = record.record_type.as_str(), action = "edit")
+)]
+pub async fn update_dns_record_by_name_type(
+    client: &PorkbunClient,
+    credentials: &Credentials,
+    domain: &Domain,
+    record: &RecordUpdate,
+) -> Result<(), ApiError> {
+    let url = name_type_url(client, "editByNameType", domain, &record.record_type);
+    let request_body: CreateUpdateDnsRecordRequest =
+        CreateUpdateDnsRecordRequest::new(credentials, domain.subdomain(), record);
+    info!(url = %url, "Update DNS record by name and type");
+    client
+        .post::<IgnoredAny>(Endpoint::Edit, &url, &request_body)
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to update DNS record by name and type"))
+        .map_err(ApiError::UpdateRecordFailed)?;
+    info!(domain = domain.qualified_name(), "Updated DNS record");
+    Ok(())
+}
+
+/// Creates a new record, existing records of the name and type are kept. Returns the id Porkbun
+/// assigned to the record, if it reported one.
+#[instrument(
+    name = "porkbun.create",
+    skip_all,
+    fields(domain = domain.qualified_name(), record_type = record.record_type.as_str(), action = "create")
+)]
+pub async fn create_dns_record(
+    client: &PorkbunClient,
+    credentials: &Credentials,
+    domain: &Domain,
+    record: &RecordUpdate,
+) -> Result<Option<String>, ApiError> {
+    let domain_name = domain.domain_name();
+    let subdomain = domain.subdomain();
+    let url = format!("{}/dns/create/{}", client.base_url(), domain_name);
+    let request_body: CreateUpdateDnsRecordRequest =
+        CreateUpdateDnsRecordRequest::new(credentials, subdomain, record);
+    info!(url = %url, subdomain, "Create DNS record");
+    let create_response: CreateDnsRecordResponse = client
+        .post(Endpoint::Create, &url, &request_body)
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to create DNS record"))
+        .map_err(ApiError::CreateRecordFailed)?;
+    info!(record_id = ?create_response.id, "Created DNS record");
+    Ok(create_response.id)
+}
+
+/// Borrows the keys from the credentials, so no unwiped copies of them are left behind.
+#[derive(Serialize)]
+struct CreateUpdateDnsRecordRequest<'a> {
+    apikey: &'a str,
+    #[serde(rename = "secretapikey")]
+    secret_api_key: &'a str,
+    name: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    content: String,
+    ttl: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prio: Option<u16>,
+}
+
+impl<'a> CreateUpdateDnsRecordRequest<'a> {
+    pub fn new(credentials: &'a Credentials, subdomain: &str, record: &RecordUpdate) -> Self {
+        CreateUpdateDnsRecordRequest {
+            apikey: credentials.api_key(),
+            secret_api_key: credentials.secret_key(),
+            name: subdomain.into(),
+            record_type: record.record_type.as_str().into(),
+            content: record.content.clone(),
+            ttl: record.ttl.unwrap_or(DEFAULT_TTL),
+            prio: record.prio,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tolerant_record_deserialization() {
+        let response: ExistingRecordsResponse = serde_json::from_value(serde_json::json!({
+            "status": "SUCCESS",
+            "records": [
+                {"id": "106926652", "name": "home.example.org", "type": "A", "content": "1.2.3.4", "ttl": "600", "prio": null},
+                {"id": 106926653, "name": "home.example.org", "type": "MX", "content": "mail.example.org", "ttl": 600, "prio": 10}
+            ]
+        }))
+        .unwrap();
+        let records = response.records;
+        assert_eq!(records[0].id, "106926652");
+        assert_eq!(records[0].ttl.as_deref(), Some("600"));
+        assert_eq!(records[0].prio, None);
+        assert_eq!(records[1].id, "106926653");
+        assert_eq!(records[1].ttl.as_deref(), Some("600"));
+        assert_eq!(records[1].prio.as_deref(), Some("10"));
+        assert_eq!(records[1].record_type, "MX");
+
+        let response: ExistingRecordsResponse =
+            serde_json::from_value(serde_json::json!({ "status": "SUCCESS" })).unwrap();
+        assert!(response.records.is_empty());
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let retry = RetryPolicy::default();
+        for (attempt, max_delay) in [(1, 200), (2, 400), (3, 800), (10, 5000)] {
+            let delay = retry.backoff(attempt);
+            assert!(delay >= Duration::from_millis(max_delay / 2), "{:?}", delay);
+            assert!(delay <= Duration::from_millis(max_delay), "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("<html>", 10), "<html>");
+        assert_eq!(truncate("<html>", 2), "<h");
+        assert_eq!(truncate("äöü", 2), "äö");
+    }
+}
@@ -0,0 +1,107 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Number of leading characters kept when redacting a secret, enough for Porkbun's `pk1_`/`sk1_`
+const REDACTED_PREFIX_LEN: usize = 4;
+
+/// Porkbun API credentials, the key material is wiped from memory when dropped.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct Credentials {
+    api_key: String,
+    secret_key: String,
+}
+
+impl Credentials {
+    pub fn new(api_key: String, secret_key: String) -> Self {
+        Self {
+            api_key,
+            secret_key,
+        }
+    }
+
+    /// Parses credentials from a Basic Auth `Authorization` header value,
+    /// where the username is the API key and the password is the secret API key.
+    pub fn from_basic_auth(header_value: &str) -> Option<Self> {
+        let (scheme, encoded) = header_value.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return None;
+        }
+        let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+        let (api_key, secret_key) = decoded.split_once(':')?;
+        if api_key.is_empty() || secret_key.is_empty() {
+            return None;
+        }
+        Some(Self::new(api_key.to_string(), secret_key.to_string()))
+    }
+
+    pub fn api_key(&self) -> &str {
+        &self.api_key
+    }
+
+    pub fn secret_key(&self) -> &str {
+        &self.secret_key
+    }
+}
+
+/// Keys are masked, so credentials can't leak when logged with `{:?}`.
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("api_key", &redact(&self.api_key))
+            .field("secret_key", &redact(&self.secret_key))
+            .finish()
+    }
+}
+
+/// Masks a secret for logging, only a short prefix (e.g. `pk1_`) of long secrets is kept.
+pub fn redact(secret: &str) -> String {
+    match secret.get(..REDACTED_PREFIX_LEN) {
+        Some(prefix) if secret.len() > 2 * REDACTED_PREFIX_LEN => format!("{}***", prefix),
+        _ => "***".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_basic_auth() {
+        let header_value = format!("Basic {}", STANDARD.encode("pk1_key:sk1_secret"));
+        let credentials = Credentials::from_basic_auth(&header_value).unwrap();
+        assert_eq!(credentials.api_key(), "pk1_key");
+        assert_eq!(credentials.secret_key(), "sk1_secret");
+
+        let header_value = format!("basic {}", STANDARD.encode("pk1_key:sk1:secret"));
+        let credentials = Credentials::from_basic_auth(&header_value).unwrap();
+        assert_eq!(credentials.secret_key(), "sk1:secret");
+    }
+
+    #[test]
+    fn test_debug_redacts_keys() {
+        let credentials = Credentials::new("pk1_0123456789".into(), "sk1_9876543210".into());
+        let debug = format!("{:?}", credentials);
+        assert_eq!(
+            debug,
+            r#"Credentials { api_key: "pk1_***", secret_key: "sk1_***" }"#
+        );
+        assert_eq!(redact("short"), "***");
+        assert_eq!(redact(""), "***");
+    }
+
+    #[test]
+    fn test_from_invalid_basic_auth() {
+        assert!(Credentials::from_basic_auth("Bearer abc").is_none());
+        assert!(Credentials::from_basic_auth("Basic not-base64!").is_none());
+        assert!(
+            Credentials::from_basic_auth(&format!("Basic {}", STANDARD.encode("nocolon")))
+                .is_none()
+        );
+        assert!(
+            Credentials::from_basic_auth(&format!("Basic {}", STANDARD.encode(":secret")))
+                .is_none()
+        );
+        assert!(Credentials::from_basic_auth("Basic").is_none());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::error::DomainError;
+
+#[derive(Debug, Clone)]
+pub struct Domain {
+    domain_name: String,    // e.g., "example.org"
+    subdomain: String,      // e.g., "api"
+    qualified_name: String, // e.g., "api.example.org" (punycode for internationalized names)
+    display_name: String,   // e.g., "api.bücher.example" (Unicode form of the qualified name)
+}
+
+impl Domain {
+    /// Splits a qualified domain name into its registrable domain and subdomain,
+    /// using the Public Suffix List to handle multi-label TLDs like `co.uk`.
+    /// The apex/root domain itself (e.g. `example.com`) results in an empty subdomain.
+    /// Internationalized names (e.g. `bücher.example.de`) are converted to punycode.
+    /// Names are canonicalized: a trailing dot is removed and ASCII letters are lowercased.
+    pub fn new(input: &str) -> Result<Self, DomainError> {
+        let input = input.strip_suffix('.').unwrap_or(input);
+        let qualified_name = if input.is_ascii() {
+            input.to_ascii_lowercase()
+        } else {
+            idna::domain_to_ascii(input).map_err(|e| {
+                DomainError::DomainValidationError(format!(
+                    "Domain is not a valid internationalized domain name: {}",
+                    e
+                ))
+            })?
+        };
+        let qualified_name = qualified_name.as_str();
+
+        if qualified_name.split('.').any(|part| part.is_empty()) {
+            return Err(DomainError::DomainValidationError(
+                "Domain contains empty parts".to_string(),
+            ));
+        }
+
+        // A wildcard is only allowed as the complete left-most label (e.g. `*.example.com`)
+        let name_without_wildcard = qualified_name.strip_prefix("*.").unwrap_or(qualified_name);
+        if !name_without_wildcard
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        {
+            return Err(DomainError::DomainValidationError(
+                "Domain contains invalid characters".to_string(),
+            ));
+        }
+
+        let domain = psl::domain(name_without_wildcard.as_bytes()).ok_or_else(|| {
+            DomainError::DomainValidationError(
+                "Domain has no registrable part (e.g., example.com)".to_string(),
+            )
+        })?;
+
+        if !domain.suffix().is_known() {
+            return Err(DomainError::DomainValidationError(
+                "Domain has an unknown top-level domain".to_string(),
+            ));
+        }
+
+        // Only ASCII input passes the check above, so the registrable part is valid UTF-8
+        let domain_name = std::str::from_utf8(domain.as_bytes()).unwrap_or_default();
+
+        let subdomain = if domain_name.len() == qualified_name.len() {
+            ""
+        } else {
+            &qualified_name[..qualified_name.len() - domain_name.len() - 1]
+        };
+
+        let display_name = if qualified_name.contains("xn--") {
+            idna::domain_to_unicode(qualified_name).0
+        } else {
+            qualified_name.to_string()
+        };
+
+        Ok(Self {
+            domain_name: domain_name.to_string(),
+            subdomain: subdomain.to_string(),
+            qualified_name: qualified_name.to_string(),
+            display_name,
+        })
+    }
+
+    pub fn domain_name(&self) -> &str {
+        &self.domain_name
+    }
+
+    pub fn subdomain(&self) -> &str {
+        &self.subdomain
+    }
+
+    pub fn qualified_name(&self) -> &str {
+        &self.qualified_name
+    }
+
+    /// The human readable (Unicode) form of the qualified name, used in responses.
+    pub fn display_name(&self) -> &str {
+        &self.display_name
+    }
+
+    /// Whether this domain is a wildcard record (e.g. `*.example.com`).
+    pub fn is_wildcard(&self) -> bool {
+        self.subdomain == "*" || self.subdomain.starts_with("*.")
+    }
+
+    /// Whether this domain refers to the apex/root of the zone (no subdomain).
+    pub fn is_apex(&self) -> bool {
+        self.subdomain.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_valid_domain() {
+        let qualified_name_input = "api.example.com";
+        let domain = Domain::new(qualified_name_input);
+        assert!(
+            domain.is_ok(),
+            "Domain: {} should be valid",
+            qualified_name_input
+        );
+        let domain = domain.unwrap();
+        assert_eq!(domain.domain_name(), "example.com");
+        assert_eq!(domain.subdomain(), "api");
+        assert_eq!(domain.qualified_name(), "api.example.com");
+    }
+
+    #[test]
+    fn test_valid_domains() {
+        let valid_domains = [
+            "api.example.com",
+            "very-very-very-very-very-very-very-long-subdomain.example.com",
+        ];
+
+        for domain in valid_domains {
+            let result = Domain::new(domain);
+            assert!(result.is_ok(), "Domain: {}", domain);
+            let domain = result.unwrap();
+            assert_eq!(domain.domain_name(), "example.com");
+        }
+    }
+
+    #[test]
+    fn test_multi_label_public_suffix() {
+        let domain = Domain::new("host.example.co.uk").unwrap();
+        assert_eq!(domain.domain_name(), "example.co.uk");
+        assert_eq!(domain.subdomain(), "host");
+        assert_eq!(domain.qualified_name(), "host.example.co.uk");
+
+        let domain = Domain::new("a.b.example.com.au").unwrap();
+        assert_eq!(domain.domain_name(), "example.com.au");
+        assert_eq!(domain.subdomain(), "a.b");
+    }
+
+    #[test]
+    fn test_apex_domain() {
+        let domain = Domain::new("example.com").unwrap();
+        assert_eq!(domain.domain_name(), "example.com");
+        assert_eq!(domain.subdomain(), "");
+        assert_eq!(domain.qualified_name(), "example.com");
+        assert!(domain.is_apex());
+
+        let domain = Domain::new("example.co.uk").unwrap();
+        assert_eq!(domain.domain_name(), "example.co.uk");
+        assert!(domain.is_apex());
+
+        assert!(!Domain::new("api.example.com").unwrap().is_apex());
+    }
+
+    #[test]
+    fn test_wildcard_domain() {
+        let domain = Domain::new("*.example.com").unwrap();
+        assert_eq!(domain.domain_name(), "example.com");
+        assert_eq!(domain.subdomain(), "*");
+        assert!(domain.is_wildcard());
+        assert!(!domain.is_apex());
+
+        let domain = Domain::new("*.home.example.co.uk").unwrap();
+        assert_eq!(domain.domain_name(), "example.co.uk");
+        assert_eq!(domain.subdomain(), "*.home");
+        assert!(domain.is_wildcard());
+
+        assert!(!Domain::new("api.example.com").unwrap().is_wildcard());
+    }
+
+    #[test]
+    fn test_internationalized_domain() {
+        let domain = Domain::new("bücher.example.de").unwrap();
+        assert_eq!(domain.domain_name(), "example.de");
+        assert_eq!(domain.subdomain(), "xn--bcher-kva");
+        assert_eq!(domain.qualified_name(), "xn--bcher-kva.example.de");
+        assert_eq!(domain.display_name(), "bücher.example.de");
+
+        let domain = Domain::new("home.bücher.de").unwrap();
+        assert_eq!(domain.domain_name(), "xn--bcher-kva.de");
+        assert_eq!(domain.subdomain(), "home");
+        assert_eq!(domain.display_name(), "home.bücher.de");
+
+        let domain = Domain::new("xn--bcher-kva.example.de").unwrap();
+        assert_eq!(domain.display_name(), "bücher.example.de");
+    }
+
+    #[test]
+    fn test_canonicalization() {
+        let domain = Domain::new("API.Example.COM.").unwrap();
+        assert_eq!(domain.domain_name(), "example.com");
+        assert_eq!(domain.subdomain(), "api");
+        assert_eq!(domain.qualified_name(), "api.example.com");
+        assert_eq!(domain.display_name(), "api.example.com");
+
+        let domain = Domain::new("Bücher.Example.DE.").unwrap();
+        assert_eq!(domain.qualified_name(), "xn--bcher-kva.example.de");
+    }
+
+    #[test]
+    fn test_invalid_domains() {
+        let invalid_domains = ["co.uk", "a..example.com", "api.example", "api@invalid.com"];
+
+        for domain in invalid_domains {
+            let result = Domain::new(domain);
+            assert!(result.is_err(), "Domain: {}", domain);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use thiserror::Error;
+
+/// Why a domain name was rejected.
+#[derive(Error, Debug)]
+pub enum DomainError {
+    #[error("Domain validation error: {0}")]
+    DomainValidationError(String),
+}
+
+/// A failed DNS operation, wrapping the cause reported by the provider.
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("Failed to create DNS record: {0}")]
+    CreateRecordFailed(PorkbunError),
+
+    #[error("Failed to update DNS record: {0}")]
+    UpdateRecordFailed(PorkbunError),
+
+    #[error("Failed to delete DNS record: {0}")]
+    DeleteRecordFailed(PorkbunError),
+
+    #[error("Failed to retrieve DNS record: {0}")]
+    RetrieveRecordFailed(PorkbunError),
+
+    #[error("Failed to list domains: {0}")]
+    ListDomainsFailed(PorkbunError),
+
+    #[error("Failed to validate credentials: {0}")]
+    PingFailed(PorkbunError),
+}
+
+impl ApiError {
+    /// The Porkbun error which caused the failed operation.
+    pub fn porkbun_error(&self) -> &PorkbunError {
+        match self {
+            ApiError::CreateRecordFailed(e)
+            | ApiError::UpdateRecordFailed(e)
+            | ApiError::DeleteRecordFailed(e)
+            | ApiError::RetrieveRecordFailed(e)
+            | ApiError::ListDomainsFailed(e)
+            | ApiError::PingFailed(e) => e,
+        }
+    }
+
+    /// The HTTP status code reported to the caller for the failed operation.
+    pub fn status_code(&self) -> u16 {
+        self.porkbun_error().status_code()
+    }
+}
+
+/// Why a call to the Porkbun API failed.
+#[derive(Error, Debug)]
+pub enum PorkbunError {
+    #[error("Invalid API key or secret: {0}")]
+    InvalidCredentials(String),
+
+    #[error("API access is not enabled for the domain: {0}")]
+    ApiAccessDisabled(String),
+
+    #[error("Domain not found: {0}")]
+    DomainNotFound(String),
+
+    #[error("Rate limit exceeded: {0}")]
+    RateLimited(String),
+
+    #[error("Malformed response: {0}")]
+    MalformedResponse(String),
+
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("{0}")]
+    Failed(String),
+
+    #[error("Not supported: {0}")]
+    Unsupported(String),
+
+    #[error("Timed out: {0}")]
+    Timeout(reqwest::Error),
+
+    /// The call wasn't answered before the client's deadline, see `PorkbunClient::with_deadline`
+    #[error("Deadline exceeded: {0}")]
+    DeadlineExceeded(String),
+
+    #[error("Network error: {0}")]
+    NetworkError(reqwest::Error),
+}
+
+impl From<reqwest::Error> for PorkbunError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            PorkbunError::Timeout(error)
+        } else {
+            PorkbunError::NetworkError(error)
+        }
+    }
+}
+
+impl PorkbunError {
+    /// Classifies an error response by its HTTP status and message,
+    /// Porkbun reports most errors as `400` with a message like `Invalid API key. (002)`.
+    pub fn from_response(http_status: u16, message: String) -> Self {
+        let lowercase = message.to_ascii_lowercase();
+        if http_status == 429 || lowercase.contains("rate limit") {
+            PorkbunError::RateLimited(message)
+        } else if http_status == 401 || lowercase.contains("invalid api key") {
+            PorkbunError::InvalidCredentials(message)
+        } else if http_status == 403 || lowercase.contains("not opted in") {
+            PorkbunError::ApiAccessDisabled(message)
+        } else if http_status == 404
+            || lowercase.contains("invalid domain")
+            || lowercase.contains("domain not found")
+        {
+            PorkbunError::DomainNotFound(message)
+        } else if http_status >= 500 {
+            PorkbunError::Unavailable(message)
+        } else {
+            PorkbunError::Failed(message)
+        }
+    }
+
+    /// Whether the call may succeed if it is retried.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            PorkbunError::RateLimited(_)
+                | PorkbunError::Unavailable(_)
+                | PorkbunError::Timeout(_)
+                | PorkbunError::NetworkError(_)
+        )
+    }
+
+    /// Whether the call provably had no effect, because it never reached Porkbun (the connection
+    /// failed) or was rejected by the rate limit. Only such calls of non-idempotent endpoints are
+    /// retried.
+    pub fn was_not_applied(&self) -> bool {
+        match self {
+            PorkbunError::RateLimited(_) => true,
+            PorkbunError::Timeout(e) | PorkbunError::NetworkError(e) => e.is_connect(),
+            _ => false,
+        }
+    }
+
+    /// The HTTP status code reported to the caller, upstream failures result in `502 Bad Gateway`
+    /// and timeouts in `504 Gateway Timeout`.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            PorkbunError::InvalidCredentials(_) => 401,
+            PorkbunError::ApiAccessDisabled(_) => 403,
+            PorkbunError::Unsupported(_) => 400,
+            PorkbunError::DomainNotFound(_) => 404,
+            PorkbunError::RateLimited(_) => 429,
+            PorkbunError::Timeout(_) | PorkbunError::DeadlineExceeded(_) => 504,
+            PorkbunError::MalformedResponse(_)
+            | PorkbunError::Unavailable(_)
+            | PorkbunError::Failed(_)
+            | PorkbunError::NetworkError(_) => 502,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_porkbun_error_classification() {
+        let cases = [
+            (400, "Invalid API key. (002)", 401),
+            (400, "Domain is not opted in to API access.", 403),
+            (400, "Invalid domain.", 404),
+            (429, "Too many requests", 429),
+            (
+                400,
+                "Edit error: We were unable to edit the DNS record.",
+                502,
+            ),
+            (503, "HTTP 503", 502),
+        ];
+        for (http_status, message, status_code) in cases {
+            assert_eq!(
+                PorkbunError::from_response(http_status, message.to_string()).status_code(),
+                status_code,
+                "{}",
+                message
+            );
+        }
+        assert!(PorkbunError::from_response(503, "HTTP 503".to_string()).is_transient());
+        assert!(!PorkbunError::from_response(400, "Invalid domain.".to_string()).is_transient());
+    }
+}
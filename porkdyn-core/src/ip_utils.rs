@@ -0,0 +1,279 @@
+use std::net::{IpAddr, Ipv6Addr};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IpType {
+    V4,
+    V6,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum RecordType {
+    A,
+    AAAA,
+    MX,
+    SRV,
+    CAA,
+    ALIAS,
+    TXT,
+}
+
+impl RecordType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordType::A => "A",
+            RecordType::AAAA => "AAAA",
+            RecordType::MX => "MX",
+            RecordType::SRV => "SRV",
+            RecordType::CAA => "CAA",
+            RecordType::ALIAS => "ALIAS",
+            RecordType::TXT => "TXT",
+        }
+    }
+}
+
+impl FromStr for RecordType {
+    type Err = String;
+
+    /// Parses a record type case-insensitively, e.g. `aaaa`
+    fn from_str(record_type: &str) -> Result<Self, Self::Err> {
+        match record_type.to_ascii_uppercase().as_str() {
+            "A" => Ok(RecordType::A),
+            "AAAA" => Ok(RecordType::AAAA),
+            "MX" => Ok(RecordType::MX),
+            "SRV" => Ok(RecordType::SRV),
+            "CAA" => Ok(RecordType::CAA),
+            "ALIAS" => Ok(RecordType::ALIAS),
+            "TXT" => Ok(RecordType::TXT),
+            _ => Err(format!("Invalid record type: {}", record_type)),
+        }
+    }
+}
+
+impl From<IpType> for RecordType {
+    fn from(ip_type: IpType) -> Self {
+        match ip_type {
+            IpType::V4 => RecordType::A,
+            IpType::V6 => RecordType::AAAA,
+        }
+    }
+}
+
+/// Validates and determines the type of an IP address
+pub fn validate_and_classify_ip(ip_str: &str) -> Result<IpType, String> {
+    match IpAddr::from_str(ip_str) {
+        Ok(IpAddr::V4(_)) => Ok(IpType::V4),
+        Ok(IpAddr::V6(_)) => Ok(IpType::V6),
+        Err(_) => Err(format!("Invalid IP address: {}", ip_str)),
+    }
+}
+
+/// The canonical form of an address (RFC 5952 for IPv6), e.g. `2001:db8::1` for
+/// `2001:0DB8:0:0::1`, so equal addresses compare equal as text. Anything else is kept as is.
+pub fn canonical_ip(ip: &str) -> String {
+    ip.trim()
+        .parse::<IpAddr>()
+        .map_or_else(|_| ip.to_string(), |ip| ip.to_string())
+}
+
+/// Whether the address is reachable from the Internet, i.e. not private (RFC 1918, unique local),
+/// shared (carrier-grade NAT), loopback, link-local, documentation, benchmarking, multicast,
+/// reserved or unspecified.
+pub fn is_public_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, third, _] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                // 100.64.0.0/10
+                || (first == 100 && second & 0xc0 == 64)
+                // 192.0.0.0/24 (IETF protocol assignments)
+                || (first == 192 && second == 0 && third == 0)
+                // 198.18.0.0/15 (benchmarking)
+                || (first == 198 && second & 0xfe == 18)
+                // 240.0.0.0/4 (reserved)
+                || first & 0xf0 == 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(&IpAddr::V4(ip)),
+            None => {
+                let [first, second, ..] = ip.segments();
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // fc00::/7, fe80::/10 and 2001:db8::/32
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80
+                    || (first == 0x2001 && second == 0xdb8))
+            }
+        },
+    }
+}
+
+/// Prefix length of an IPv6 prefix given without one, the size of a single network
+pub const DEFAULT_IPV6_PREFIX_LEN: u8 = 64;
+
+/// Parses an IPv6 prefix like `2001:db8:1200::/56`, an address without length is taken as `/64`.
+pub fn parse_ipv6_prefix(prefix: &str) -> Result<(Ipv6Addr, u8), String> {
+    let (address, length) = match prefix.split_once('/') {
+        Some((address, length)) => match length.parse() {
+            Ok(length) if length <= 128 => (address, length),
+            _ => return Err(format!("Invalid IPv6 prefix length: {}", length)),
+        },
+        None => (prefix, DEFAULT_IPV6_PREFIX_LEN),
+    };
+    let address = address
+        .parse()
+        .map_err(|_| format!("Invalid IPv6 prefix: {}", prefix))?;
+    Ok((address, length))
+}
+
+/// Combines the first `prefix_len` bits of `prefix` with the remaining bits of `suffix`, e.g.
+/// `2001:db8:1:2::/64` and `::5` to `2001:db8:1:2::5`. Bits of the suffix within the prefix are
+/// ignored, so a full address of the host can be passed as suffix as well.
+pub fn compose_ipv6(prefix: Ipv6Addr, prefix_len: u8, suffix: Ipv6Addr) -> Ipv6Addr {
+    let mask = u128::MAX
+        .checked_shl(128 - u32::from(prefix_len.min(128)))
+        .unwrap_or(0);
+    Ipv6Addr::from(u128::from(prefix) & mask | u128::from(suffix) & !mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_ipv4() {
+        assert_eq!(validate_and_classify_ip("192.168.1.1"), Ok(IpType::V4));
+        assert_eq!(validate_and_classify_ip("127.0.0.1"), Ok(IpType::V4));
+        assert_eq!(validate_and_classify_ip("0.0.0.0"), Ok(IpType::V4));
+        assert_eq!(validate_and_classify_ip("255.255.255.255"), Ok(IpType::V4));
+    }
+
+    #[test]
+    fn test_valid_ipv6() {
+        assert_eq!(validate_and_classify_ip("::1"), Ok(IpType::V6));
+        assert_eq!(validate_and_classify_ip("2001:db8::1"), Ok(IpType::V6));
+        assert_eq!(validate_and_classify_ip("fe80::1"), Ok(IpType::V6));
+        assert_eq!(
+            validate_and_classify_ip("2001:0db8:85a3:0000:0000:8a2e:0370:7334"),
+            Ok(IpType::V6)
+        );
+        assert_eq!(
+            validate_and_classify_ip("2001:db8:85a3::8a2e:370:7334"),
+            Ok(IpType::V6)
+        );
+    }
+
+    #[test]
+    fn test_invalid_ip() {
+        assert!(validate_and_classify_ip("256.1.1.1").is_err());
+        assert!(validate_and_classify_ip("192.168.1").is_err());
+        assert!(validate_and_classify_ip("not_an_ip").is_err());
+        assert!(validate_and_classify_ip("").is_err());
+        assert!(validate_and_classify_ip("2001:db8::g1").is_err());
+        // Zone identifiers are not supported in std::net parsing
+        assert!(validate_and_classify_ip("fe80::1%lo0").is_err());
+    }
+
+    #[test]
+    fn test_canonical_ip() {
+        assert_eq!(canonical_ip("2001:db8:0:0::1"), "2001:db8::1");
+        assert_eq!(
+            canonical_ip("2001:0DB8:0000:0000:0000:0000:0000:0001"),
+            "2001:db8::1"
+        );
+        assert_eq!(canonical_ip("2001:db8:0:1:0:0:0:1"), "2001:db8:0:1::1");
+        assert_eq!(canonical_ip("1.2.3.4"), "1.2.3.4");
+        assert_eq!(canonical_ip("mail.example.org"), "mail.example.org");
+    }
+
+    #[test]
+    fn test_is_public_ip() {
+        for ip in [
+            "1.2.3.4",
+            "100.128.0.1",
+            "192.0.1.1",
+            "198.20.0.1",
+            "223.255.255.1",
+            "2a00:1450:4001::1",
+            "::ffff:1.2.3.4",
+        ] {
+            assert!(is_public_ip(&ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "100.64.0.1",
+            "127.0.0.1",
+            "169.254.1.1",
+            "203.0.113.7",
+            "192.0.0.8",
+            "198.18.0.1",
+            "198.19.255.254",
+            "240.0.0.1",
+            "0.0.0.0",
+            "::1",
+            "fe80::1",
+            "fd00::1",
+            "2001:db8::1",
+            "::ffff:192.168.1.1",
+        ] {
+            assert!(!is_public_ip(&ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_compose_ipv6() {
+        let (prefix, length) = parse_ipv6_prefix("2001:db8:1200::/56").unwrap();
+        assert_eq!(length, 56);
+        assert_eq!(
+            compose_ipv6(prefix, length, "::34:0:0:0:5".parse().unwrap()),
+            "2001:db8:1200:34::5".parse::<Ipv6Addr>().unwrap()
+        );
+        let (prefix, length) = parse_ipv6_prefix("2001:db8:1:2:a:b:c:d").unwrap();
+        assert_eq!(length, 64);
+        assert_eq!(
+            compose_ipv6(prefix, length, "fd00::211:22ff:fe33:4455".parse().unwrap()),
+            "2001:db8:1:2:211:22ff:fe33:4455"
+                .parse::<Ipv6Addr>()
+                .unwrap()
+        );
+        assert_eq!(compose_ipv6(prefix, 128, "::5".parse().unwrap()), prefix);
+        assert_eq!(
+            compose_ipv6(prefix, 0, "::5".parse().unwrap()),
+            "::5".parse::<Ipv6Addr>().unwrap()
+        );
+        assert!(parse_ipv6_prefix("2001:db8::/129").is_err());
+        assert!(parse_ipv6_prefix("1.2.3.0/24").is_err());
+    }
+
+    #[test]
+    fn test_record_type_conversion() {
+        assert_eq!(RecordType::from(IpType::V4), RecordType::A);
+        assert_eq!(RecordType::from(IpType::V6), RecordType::AAAA);
+    }
+
+    #[test]
+    fn test_record_type_as_str() {
+        assert_eq!(RecordType::A.as_str(), "A");
+        assert_eq!(RecordType::AAAA.as_str(), "AAAA");
+        assert_eq!(RecordType::MX.as_str(), "MX");
+        assert_eq!(RecordType::SRV.as_str(), "SRV");
+        assert_eq!(RecordType::CAA.as_str(), "CAA");
+        assert_eq!(RecordType::ALIAS.as_str(), "ALIAS");
+        assert_eq!(RecordType::TXT.as_str(), "TXT");
+        assert_eq!("aaaa".parse::<RecordType>(), Ok(RecordType::AAAA));
+        assert_eq!(
+            "PTR".parse::<RecordType>(),
+            Err("Invalid record type: PTR".to_string())
+        );
+    }
+}
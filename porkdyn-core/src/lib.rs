@@ -0,0 +1,29 @@
+//! The Porkbun API client and domain handling of porkDyn, independent of AWS Lambda.
+//!
+//! ```no_run
+//! use porkdyn_core::api::{get_dns_records, PorkbunClient};
+//! use porkdyn_core::credentials::Credentials;
+//! use porkdyn_core::domain::Domain;
+//! use porkdyn_core::ip_utils::RecordType;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = PorkbunClient::from_env();
+//! let credentials = Credentials::new("pk1_...".to_string(), "sk1_...".to_string());
+//! let domain = Domain::new("home.example.org")?;
+//! let records = get_dns_records(&client, &credentials, &domain, &RecordType::A).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+/// Calls to the Porkbun API with retries and typed errors.
+pub mod api;
+/// Porkbun API credentials which are wiped from memory when dropped.
+pub mod credentials;
+/// Parsing of qualified domain names into registrable domain and subdomain.
+pub mod domain;
+/// The errors of domain parsing and API calls.
+pub mod error;
+/// IP address validation and the supported record types.
+pub mod ip_utils;
+/// The desired state of a DNS record.
+pub mod record;
@@ -0,0 +1,170 @@
+use crate::ip_utils::{canonical_ip, IpType, RecordType};
+
+/// The desired state of a single DNS record, independent of the domain it belongs to.
+#[derive(Debug, Clone)]
+pub struct RecordUpdate {
+    pub content: String,
+    pub record_type: RecordType,
+    pub prio: Option<u16>,
+    pub ttl: Option<u64>,
+}
+
+impl RecordUpdate {
+    /// The address is stored in canonical form, as providers return it.
+    pub fn ip(address: String, ip_type: IpType) -> Self {
+        Self {
+            content: canonical_ip(&address),
+            record_type: RecordType::from(ip_type),
+            prio: None,
+            ttl: None,
+        }
+    }
+
+    pub fn mx(mail_server: String, prio: u16) -> Self {
+        Self {
+            content: mail_server,
+            record_type: RecordType::MX,
+            prio: Some(prio),
+            ttl: None,
+        }
+    }
+
+    /// Porkbun expects the SRV content as "weight port target", the priority is sent separately.
+    pub fn srv(prio: u16, weight: u16, port: u16, target: &str) -> Self {
+        Self {
+            content: format!("{} {} {}", weight, port, target),
+            record_type: RecordType::SRV,
+            prio: Some(prio),
+            ttl: None,
+        }
+    }
+
+    /// CAA content is sent as "flags tag \"value\"", e.g. `0 issue "letsencrypt.org"`.
+    pub fn caa(flags: u8, tag: &str, value: &str) -> Self {
+        Self {
+            content: format!("{} {} \"{}\"", flags, tag, value),
+            record_type: RecordType::CAA,
+            prio: None,
+            ttl: None,
+        }
+    }
+
+    /// ALIAS records point (usually apex) names at another host name, resolved by Porkbun.
+    pub fn alias(target: String) -> Self {
+        Self {
+            content: target,
+            record_type: RecordType::ALIAS,
+            prio: None,
+            ttl: None,
+        }
+    }
+
+    /// TXT records hold arbitrary text, e.g. ACME DNS-01 challenge tokens.
+    pub fn txt(value: String) -> Self {
+        Self {
+            content: value,
+            record_type: RecordType::TXT,
+            prio: None,
+            ttl: None,
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Option<u64>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// The record data in zone file notation, as used by providers managing whole record sets:
+    /// TXT values are quoted, MX and SRV records start with the priority and end with a fully
+    /// qualified target.
+    pub fn rdata(&self) -> String {
+        match self.record_type {
+            RecordType::TXT => format!("\"{}\"", self.content.replace('"', "\\\"")),
+            RecordType::MX | RecordType::SRV => format!(
+                "{} {}.",
+                self.prio.unwrap_or_default(),
+                self.content.trim_end_matches('.')
+            ),
+            _ => self.content.clone(),
+        }
+    }
+}
+
+/// Splits record data in zone file notation into the content and priority of a record.
+pub fn parse_rdata(record_type: &RecordType, rdata: &str) -> (String, Option<String>) {
+    match record_type {
+        RecordType::TXT => (
+            rdata
+                .strip_prefix('"')
+                .and_then(|rdata| rdata.strip_suffix('"'))
+                .unwrap_or(rdata)
+                .replace("\\\"", "\""),
+            None,
+        ),
+        RecordType::MX | RecordType::SRV => match rdata.split_once(' ') {
+            Some((prio, content)) => (
+                content.trim_end_matches('.').to_string(),
+                Some(prio.to_string()),
+            ),
+            None => (rdata.to_string(), None),
+        },
+        _ => (rdata.to_string(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rdata() {
+        let txt = RecordUpdate::txt("LPsIwTo7o8BoG0-vjCyGQGBWSVIPxI-i_X336eUOQZo".to_string());
+        assert_eq!(
+            txt.rdata(),
+            "\"LPsIwTo7o8BoG0-vjCyGQGBWSVIPxI-i_X336eUOQZo\""
+        );
+        assert_eq!(parse_rdata(&RecordType::TXT, &txt.rdata()).0, txt.content);
+
+        let mx = RecordUpdate::mx("mail.example.org".to_string(), 10);
+        assert_eq!(mx.rdata(), "10 mail.example.org.");
+        assert_eq!(
+            parse_rdata(&RecordType::MX, &mx.rdata()),
+            ("mail.example.org".to_string(), Some("10".to_string()))
+        );
+
+        let srv = RecordUpdate::srv(10, 5, 5060, "sip.example.org");
+        assert_eq!(srv.rdata(), "10 5 5060 sip.example.org.");
+        assert_eq!(parse_rdata(&RecordType::SRV, &srv.rdata()).0, srv.content);
+    }
+
+    #[test]
+    fn test_ip_record() {
+        let update = RecordUpdate::ip("2001:DB8:0:0::1".into(), IpType::V6);
+        assert_eq!(update.content, "2001:db8::1");
+        assert_eq!(update.record_type, RecordType::AAAA);
+        assert_eq!(update.prio, None);
+        assert_eq!(update.ttl, None);
+    }
+
+    #[test]
+    fn test_srv_record_content() {
+        let update = RecordUpdate::srv(10, 5, 5060, "sip.example.org");
+        assert_eq!(update.content, "5 5060 sip.example.org");
+        assert_eq!(update.record_type, RecordType::SRV);
+        assert_eq!(update.prio, Some(10));
+    }
+
+    #[test]
+    fn test_caa_record_content() {
+        let update = RecordUpdate::caa(0, "issue", "letsencrypt.org");
+        assert_eq!(update.content, "0 issue \"letsencrypt.org\"");
+        assert_eq!(update.record_type, RecordType::CAA);
+        assert_eq!(update.prio, None);
+    }
+
+    #[test]
+    fn test_with_ttl() {
+        let update = RecordUpdate::ip("1.2.3.4".into(), IpType::V4).with_ttl(Some(3600));
+        assert_eq!(update.ttl, Some(3600));
+    }
+}
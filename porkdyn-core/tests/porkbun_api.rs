@@ -0,0 +1,285 @@
+//! Calls the Porkbun API functions against a mock server, checking the requests sent and how
+//! responses are turned into results and typed errors.
+
+use porkdyn_core::api::{
+    create_dns_record, get_dns_records, ping, update_dns_record, Endpoint, Latencies, PorkbunClient,
+};
+use porkdyn_core::credentials::Credentials;
+use porkdyn_core::domain::Domain;
+use porkdyn_core::error::{ApiError, PorkbunError};
+use porkdyn_core::ip_utils::{IpType, RecordType};
+use porkdyn_core::record::RecordUpdate;
+use serde_json::json;
+use std::time::{Duration, Instant};
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn credentials() -> Credentials {
+    Credentials::new("pk1_key".to_string(), "sk1_secret".to_string())
+}
+
+fn client(server: &MockServer) -> PorkbunClient {
+    PorkbunClient::default().with_base_url(&server.uri())
+}
+
+#[tokio::test]
+async fn test_create_record() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/dns/create/example.org"))
+        .and(body_partial_json(json!({
+            "apikey": "pk1_key",
+            "secretapikey": "sk1_secret",
+            "name": "home",
+            "type": "A",
+            "content": "1.2.3.4",
+            "ttl": 600
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "status": "SUCCESS",
+            "id": 106926659
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let domain = Domain::new("home.example.org").unwrap();
+    let record = RecordUpdate::ip("1.2.3.4".to_string(), IpType::V4);
+    let record_id = create_dns_record(&client(&server), &credentials(), &domain, &record)
+        .await
+        .unwrap();
+    assert_eq!(record_id.as_deref(), Some("106926659"));
+}
+
+#[tokio::test]
+async fn test_update_record() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/dns/retrieveByNameType/example.org/AAAA/home"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "status": "SUCCESS",
+            "records": [
+                {"id": "106926652", "name": "home.example.org", "type": "AAAA", "content": "2001:db8::1", "ttl": "600", "prio": "0", "notes": ""}
+            ]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/dns/edit/example.org/106926652"))
+        .and(body_partial_json(json!({
+            "name": "home",
+            "type": "AAAA",
+            "content": "2001:db8::2"
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"status": "SUCCESS"})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let latencies = Latencies::default();
+    let client = client(&server).with_latencies(latencies.clone());
+    let domain = Domain::new("home.example.org").unwrap();
+    let records = get_dns_records(&client, &credentials(), &domain, &RecordType::AAAA)
+        .await
+        .unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].content, "2001:db8::1");
+
+    let record = RecordUpdate::ip("2001:db8::2".to_string(), IpType::V6);
+    update_dns_record(&client, &credentials(), &domain, &records[0].id, &record)
+        .await
+        .unwrap();
+    let endpoints: Vec<Endpoint> = latencies
+        .calls()
+        .into_iter()
+        .map(|(endpoint, _)| endpoint)
+        .collect();
+    assert_eq!(endpoints, [Endpoint::Retrieve, Endpoint::Edit]);
+}
+
+#[tokio::test]
+async fn test_domain_not_found() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/dns/retrieveByNameType/example.org/A/home"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "status": "ERROR",
+            "message": "Invalid domain."
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let domain = Domain::new("home.example.org").unwrap();
+    let error = get_dns_records(&client(&server), &credentials(), &domain, &RecordType::A)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        ApiError::RetrieveRecordFailed(PorkbunError::DomainNotFound(_))
+    ));
+    assert_eq!(error.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_invalid_credentials() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/ping"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "status": "ERROR",
+            "message": "Invalid API key. (002)"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let error = ping(&client(&server), &credentials()).await.unwrap_err();
+    assert!(matches!(
+        error,
+        ApiError::PingFailed(PorkbunError::InvalidCredentials(_))
+    ));
+    assert_eq!(error.status_code(), 401);
+}
+
+#[tokio::test]
+async fn test_malformed_response() {
+    let server = MockServer::start().await;
+    // Malformed responses aren't transient, so the call isn't retried
+    Mock::given(method("POST"))
+        .and(path("/dns/create/example.org"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_string("<html><body>Maintenance</body></html>"),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let domain = Domain::new("example.org").unwrap();
+    let record = RecordUpdate::ip("1.2.3.4".to_string(), IpType::V4);
+    let error = create_dns_record(&client(&server), &credentials(), &domain, &record)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        ApiError::CreateRecordFailed(PorkbunError::MalformedResponse(_))
+    ));
+    assert_eq!(error.status_code(), 502);
+}
+
+#[tokio::test]
+async fn test_create_not_retried_after_server_error() {
+    let server = MockServer::start().await;
+    // Porkbun may have created the record before failing, a retry could duplicate it
+    Mock::given(method("POST"))
+        .and(path("/dns/create/example.org"))
+        .respond_with(ResponseTemplate::new(502))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let domain = Domain::new("home.example.org").unwrap();
+    let record = RecordUpdate::ip("1.2.3.4".to_string(), IpType::V4);
+    let error = create_dns_record(&client(&server), &credentials(), &domain, &record)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        ApiError::CreateRecordFailed(PorkbunError::Unavailable(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_create_retried_when_rate_limited() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/dns/create/example.org"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/dns/create/example.org"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "status": "SUCCESS",
+            "id": 106926659
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let domain = Domain::new("home.example.org").unwrap();
+    let record = RecordUpdate::ip("1.2.3.4".to_string(), IpType::V4);
+    let record_id = create_dns_record(&client(&server), &credentials(), &domain, &record)
+        .await
+        .unwrap();
+    assert_eq!(record_id.as_deref(), Some("106926659"));
+}
+
+#[tokio::test]
+async fn test_create_retried_when_connection_failed() {
+    // Nothing listens on the port any more, so the connection is refused
+    let address = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let latencies = Latencies::default();
+    let client = PorkbunClient::default()
+        .with_base_url(&format!("http://{}", address))
+        .with_latencies(latencies.clone());
+
+    let domain = Domain::new("home.example.org").unwrap();
+    let record = RecordUpdate::ip("1.2.3.4".to_string(), IpType::V4);
+    let error = create_dns_record(&client, &credentials(), &domain, &record)
+        .await
+        .unwrap_err();
+    assert!(error.porkbun_error().was_not_applied());
+    assert_eq!(latencies.calls().len(), 3);
+}
+
+#[tokio::test]
+async fn test_retry_stops_at_deadline() {
+    let server = MockServer::start().await;
+    // Waiting for the requested retry would pass the deadline, so the call isn't retried
+    Mock::given(method("POST"))
+        .and(path("/ping"))
+        .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "2"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = client(&server).with_deadline(Instant::now() + Duration::from_secs(1));
+    let error = ping(&client, &credentials()).await.unwrap_err();
+    assert!(matches!(
+        error,
+        ApiError::PingFailed(PorkbunError::Unavailable(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_deadline_exceeded() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/ping"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "status": "SUCCESS", "yourIp": "1.2.3.4" }))
+                .set_delay(Duration::from_secs(2)),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = client(&server).with_deadline(Instant::now() + Duration::from_millis(200));
+    let error = ping(&client, &credentials()).await.unwrap_err();
+    assert!(matches!(
+        error,
+        ApiError::PingFailed(PorkbunError::DeadlineExceeded(_))
+    ));
+    assert_eq!(error.status_code(), 504);
+    // No further calls are sent once the deadline passed
+    let error = ping(&client, &credentials()).await.unwrap_err();
+    assert_eq!(error.status_code(), 504);
+}
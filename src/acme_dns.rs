@@ -0,0 +1,307 @@
+use crate::credentials::redact;
+use crate::domain::Domain;
+use crate::error::{ApiError, CredentialsError, DomainError};
+use crate::ip_utils::RecordType;
+use crate::provider::DnsProvider;
+use crate::record::RecordUpdate;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use lambda_http::tracing::info;
+use serde::Deserialize;
+use sha2::Sha256;
+use std::fmt;
+
+/// Environment variable with the zone below which acme-dns challenge records are created,
+/// e.g. `acme.example.com`
+pub const ACME_DNS_ZONE_ENV: &str = "PORKDYN_ACME_DNS_ZONE";
+/// Environment variable with the secret the acme-dns account passwords are derived from
+pub const ACME_DNS_SECRET_ENV: &str = "PORKDYN_ACME_DNS_SECRET";
+/// acme-dns keeps the two most recent challenges, so a certificate can cover both
+/// `example.com` and `*.example.com`
+const MAX_TXT_RECORDS: usize = 2;
+/// ACME DNS-01 challenge tokens are base64url encoded SHA-256 digests
+const CHALLENGE_LENGTH: usize = 43;
+/// The label ACME servers look up the DNS-01 challenge TXT records at
+const CHALLENGE_LABEL: &str = "_acme-challenge";
+/// Path of the account registration
+pub(crate) const REGISTER_PATH: &str = "/register";
+/// Header with the account of an update, which tells acme-dns updates apart from regular ones
+/// sent to `/update`
+pub(crate) const API_USER_HEADER: &str = "X-Api-User";
+
+/// The acme-dns compatible endpoints.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum AcmeDnsRequest {
+    /// `POST /register` creates a new account
+    Register,
+    /// `POST /update` sets the challenge TXT record of an account
+    Update,
+}
+
+/// Configuration of the acme-dns compatible API.
+/// Accounts are stateless: the subdomain is a random UUID and the password is derived from it
+/// with an HMAC, so no storage is required to verify updates.
+pub struct AcmeDnsConfig {
+    zone: String,
+    secret: String,
+}
+
+impl fmt::Debug for AcmeDnsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcmeDnsConfig")
+            .field("zone", &self.zone)
+            .field("secret", &redact(&self.secret))
+            .finish()
+    }
+}
+
+/// The body of an acme-dns `/update` request.
+#[derive(Debug, Deserialize)]
+pub(crate) struct UpdateRequest {
+    pub subdomain: String,
+    pub txt: String,
+}
+
+impl AcmeDnsConfig {
+    pub fn new(zone: &str, secret: &str) -> Self {
+        Self {
+            zone: zone.trim_end_matches('.').to_ascii_lowercase(),
+            secret: secret.to_string(),
+        }
+    }
+
+    /// Loads the configuration from the environment, the API is disabled without a zone.
+    pub fn from_env() -> Result<Option<Self>, CredentialsError> {
+        let Ok(zone) = std::env::var(ACME_DNS_ZONE_ENV) else {
+            return Ok(None);
+        };
+        match std::env::var(ACME_DNS_SECRET_ENV) {
+            Ok(secret) if !secret.is_empty() => Ok(Some(Self::new(&zone, &secret))),
+            _ => Err(CredentialsError::InvalidAcmeDns(format!(
+                "{} is required if {} is set",
+                ACME_DNS_SECRET_ENV, ACME_DNS_ZONE_ENV
+            ))),
+        }
+    }
+
+    /// Registers a new account, returning the same JSON structure as acme-dns.
+    /// The caller has to point `_acme-challenge.<domain>` at `fulldomain` with a CNAME record.
+    pub(crate) fn register(&self) -> serde_json::Value {
+        let subdomain = uuid::Uuid::new_v4().to_string();
+        info!(subdomain = %subdomain, "Registered acme-dns account");
+        serde_json::json!({
+            "username": subdomain,
+            "password": self.password(&subdomain),
+            "fulldomain": format!("{}.{}", subdomain, self.zone),
+            "subdomain": subdomain,
+            "allowfrom": [],
+        })
+    }
+
+    /// Verifies the `X-Api-User`/`X-Api-Key` headers and the body of an `/update` request,
+    /// returning the domain of the challenge record or the acme-dns error on failure.
+    pub(crate) fn authorize_update(
+        &self,
+        username: Option<&str>,
+        password: Option<&str>,
+        request: &UpdateRequest,
+    ) -> Result<Domain, (u16, &'static str)> {
+        let (Some(username), Some(password)) = (username, password) else {
+            return Err((401, "forbidden"));
+        };
+        if !self.verify_password(username, password) {
+            return Err((401, "forbidden"));
+        }
+        if request.subdomain != username {
+            return Err((401, "forbidden"));
+        }
+        if !is_valid_challenge(&request.txt) {
+            return Err((400, "bad_txt"));
+        }
+        Domain::new(&format!("{}.{}", request.subdomain, self.zone))
+            .map_err(|_| (400, "bad_subdomain"))
+    }
+
+    fn mac(&self, username: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(username.as_bytes());
+        mac
+    }
+
+    fn password(&self, username: &str) -> String {
+        URL_SAFE_NO_PAD.encode(self.mac(username).finalize().into_bytes())
+    }
+
+    /// Compares the password in constant time.
+    fn verify_password(&self, username: &str, password: &str) -> bool {
+        URL_SAFE_NO_PAD
+            .decode(password)
+            .is_ok_and(|password| self.mac(username).verify_slice(&password).is_ok())
+    }
+}
+
+/// Whether the value looks like an ACME DNS-01 challenge token.
+pub(crate) fn is_valid_challenge(txt: &str) -> bool {
+    txt.len() == CHALLENGE_LENGTH
+        && txt
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// The challenge record name of a domain, e.g. `_acme-challenge.example.com` for both
+/// `example.com` and `*.example.com`.
+pub(crate) fn challenge_domain(domain: &Domain) -> Result<Domain, DomainError> {
+    let name = domain.qualified_name();
+    Domain::new(&format!(
+        "{}.{}",
+        CHALLENGE_LABEL,
+        name.strip_prefix("*.").unwrap_or(name)
+    ))
+}
+
+/// Adds the challenge as TXT record next to existing ones, returns `false` if it already exists.
+pub(crate) async fn add_challenge(
+    provider: &impl DnsProvider,
+    domain: &Domain,
+    txt: &str,
+) -> Result<bool, ApiError> {
+    let records = provider.get_records(domain, &RecordType::TXT).await?;
+    if records.iter().any(|record| record.content == txt) {
+        info!(domain = domain.qualified_name(), "Challenge is already set");
+        return Ok(false);
+    }
+    provider
+        .create_record(domain, &RecordUpdate::txt(txt.to_string()))
+        .await?;
+    Ok(true)
+}
+
+/// Deletes the challenge TXT records of the domain, only the given challenge if `txt` is set.
+/// Returns the number of deleted records.
+pub(crate) async fn clear_challenge(
+    provider: &impl DnsProvider,
+    domain: &Domain,
+    txt: Option<&str>,
+) -> Result<usize, ApiError> {
+    let records = provider.get_records(domain, &RecordType::TXT).await?;
+    let mut deleted = 0;
+    for record in records
+        .iter()
+        .filter(|record| txt.is_none_or(|txt| record.content == txt))
+    {
+        provider.delete_record(domain, &record.id).await?;
+        deleted += 1;
+    }
+    Ok(deleted)
+}
+
+/// Adds the challenge as TXT record, removing all but the most recent previous challenge.
+/// Returns whether the challenge was created and the number of deleted challenges.
+pub(crate) async fn set_challenge(
+    provider: &impl DnsProvider,
+    domain: &Domain,
+    txt: &str,
+) -> Result<(bool, usize), ApiError> {
+    let mut records = provider.get_records(domain, &RecordType::TXT).await?;
+    if records.iter().any(|record| record.content == txt) {
+        info!(domain = domain.qualified_name(), "Challenge is already set");
+        return Ok((false, 0));
+    }
+    provider
+        .create_record(domain, &RecordUpdate::txt(txt.to_string()))
+        .await?;
+
+    // Record ids are increasing, so the oldest challenges come first
+    records.sort_by_key(|record| record.id.parse::<u64>().unwrap_or_default());
+    let outdated = records.len().saturating_sub(MAX_TXT_RECORDS - 1);
+    for record in &records[..outdated] {
+        provider.delete_record(domain, &record.id).await?;
+    }
+    Ok((true, outdated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHALLENGE: &str = "LPsIwTo7o8BoG0-vjCyGQGBWSVIPxI-i_X336eUOQZo";
+
+    fn update(subdomain: &str, txt: &str) -> UpdateRequest {
+        UpdateRequest {
+            subdomain: subdomain.to_string(),
+            txt: txt.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_register_and_authorize() {
+        let config = AcmeDnsConfig::new("Acme.Example.org.", "secret");
+        let account = config.register();
+        let username = account["username"].as_str().unwrap();
+        let password = account["password"].as_str().unwrap();
+        assert_eq!(
+            account["fulldomain"].as_str().unwrap(),
+            format!("{}.acme.example.org", username)
+        );
+
+        let domain = config
+            .authorize_update(Some(username), Some(password), &update(username, CHALLENGE))
+            .unwrap();
+        assert_eq!(domain.domain_name(), "example.org");
+        assert_eq!(domain.subdomain(), format!("{}.acme", username));
+    }
+
+    #[test]
+    fn test_authorize_update_failures() {
+        let config = AcmeDnsConfig::new("acme.example.org", "secret");
+        let account = config.register();
+        let username = account["username"].as_str().unwrap();
+        let password = account["password"].as_str().unwrap();
+        let other = AcmeDnsConfig::new("acme.example.org", "other-secret").register();
+
+        assert_eq!(
+            config
+                .authorize_update(Some(username), None, &update(username, CHALLENGE))
+                .unwrap_err(),
+            (401, "forbidden")
+        );
+        assert_eq!(
+            config
+                .authorize_update(
+                    other["username"].as_str(),
+                    other["password"].as_str(),
+                    &update(other["username"].as_str().unwrap(), CHALLENGE)
+                )
+                .unwrap_err(),
+            (401, "forbidden")
+        );
+        assert_eq!(
+            config
+                .authorize_update(
+                    Some(username),
+                    Some(password),
+                    &update(other["username"].as_str().unwrap(), CHALLENGE)
+                )
+                .unwrap_err(),
+            (401, "forbidden")
+        );
+        assert_eq!(
+            config
+                .authorize_update(Some(username), Some(password), &update(username, "short"))
+                .unwrap_err(),
+            (400, "bad_txt")
+        );
+    }
+
+    #[test]
+    fn test_challenge_domain() {
+        for name in ["home.example.org", "*.home.example.org"] {
+            let domain = challenge_domain(&Domain::new(name).unwrap()).unwrap();
+            assert_eq!(domain.domain_name(), "example.org");
+            assert_eq!(domain.subdomain(), "_acme-challenge.home");
+        }
+        let domain = challenge_domain(&Domain::new("example.org").unwrap()).unwrap();
+        assert_eq!(domain.subdomain(), "_acme-challenge");
+    }
+}
@@ -0,0 +1,244 @@
+use crate::credentials::redact;
+use crate::http_handler::{InternalRequest, UpdateAction};
+use crate::state::AUDIT_TABLE_ENV;
+use aws_sdk_dynamodb::types::AttributeValue;
+use futures::future::join_all;
+use lambda_http::request::RequestContext;
+use lambda_http::tracing::error;
+use lambda_http::{Request, RequestExt};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A record a request created, updated or deleted.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Change {
+    pub(crate) domain: String,
+    pub(crate) record_type: &'static str,
+    pub(crate) action: UpdateAction,
+    /// The provider the record was changed at
+    pub(crate) provider: &'static str,
+    /// Who made the change, see `caller`
+    pub(crate) caller: String,
+    /// The address an A or AAAA record pointed to before, if it was looked up
+    pub(crate) old_ip: Option<String>,
+    /// The address an A or AAAA record points to
+    pub(crate) new_ip: Option<String>,
+}
+
+/// The record changes of a request, collected as they happen, so they are audited even if the
+/// request is aborted at the Lambda timeout. Clones share the changes, so the collector can be
+/// kept while the request is passed on.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ChangeLog(Arc<Mutex<Vec<Change>>>);
+
+impl ChangeLog {
+    /// Makes the request record its changes in this log.
+    pub(crate) fn attach(&self, event: &mut Request) {
+        event.extensions_mut().insert(self.clone());
+    }
+
+    /// The changes in the order they were recorded.
+    pub(crate) fn changes(&self) -> Vec<Change> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Records changes of the request for the audit log, right after they were made.
+pub(crate) fn record(event: &Request, changes: impl IntoIterator<Item = Change>) {
+    if let Some(log) = event.extensions().get::<ChangeLog>() {
+        log.0.lock().unwrap().extend(changes);
+    }
+}
+
+/// Who made a request: `internal` for updates porkDyn makes itself (e.g. on a schedule), the IAM
+/// principal of signed requests, the masked access token or `anonymous` without any.
+pub(crate) fn caller(event: &Request, token: Option<&str>) -> String {
+    if event.extensions().get::<InternalRequest>().is_some() {
+        return "internal".to_string();
+    }
+    let principal = match event.request_context_ref() {
+        Some(RequestContext::ApiGatewayV2(context)) => context
+            .authorizer
+            .as_ref()
+            .and_then(|authorizer| authorizer.iam.as_ref())
+            .and_then(|iam| iam.user_arn.clone()),
+        Some(RequestContext::ApiGatewayV1(context)) => context.identity.user_arn.clone(),
+        _ => None,
+    };
+    principal
+        .or_else(|| token.map(|token| format!("token {}", redact(token))))
+        .unwrap_or_else(|| "anonymous".to_string())
+}
+
+/// The DynamoDB table every record change is appended to (`PORKDYN_AUDIT_TABLE`), with the
+/// domain as partition key and `id` as sort key. The Lambda's role only needs `dynamodb:PutItem`,
+/// entries are never updated or deleted.
+#[derive(Debug)]
+pub struct AuditLog {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
+impl AuditLog {
+    /// Creates the audit log with the Lambda's IAM role, if a table is configured.
+    pub async fn from_env() -> Option<Self> {
+        let table = std::env::var(AUDIT_TABLE_ENV)
+            .ok()
+            .filter(|table| !table.is_empty())?;
+        let config = aws_config::load_from_env().await;
+        Some(Self {
+            client: aws_sdk_dynamodb::Client::new(&config),
+            table,
+        })
+    }
+
+    /// Appends an entry for every record the request changed, sent from `source_ip`. Failures
+    /// are only logged, the records have been changed already.
+    pub(crate) async fn write(
+        &self,
+        changes: &[Change],
+        source_ip: Option<&str>,
+        request_id: &str,
+    ) {
+        let now = SystemTime::now();
+        let writes = changes.iter().map(|change| {
+            self.client
+                .put_item()
+                .table_name(&self.table)
+                .set_item(Some(item(change, source_ip, request_id, now)))
+                // An entry is never overwritten, even if the ids of two entries collided
+                .condition_expression("attribute_not_exists(id)")
+                .send()
+        });
+        for (change, result) in changes.iter().zip(join_all(writes).await) {
+            if let Err(e) = result {
+                error!(
+                    table = %self.table,
+                    domain = %change.domain,
+                    action = change.action.as_str(),
+                    error = ?e,
+                    "Failed to write audit entry"
+                );
+            }
+        }
+    }
+}
+
+/// The audit entry of a change. Its id starts with the time in milliseconds, so the entries of a
+/// domain are sorted chronologically.
+fn item(
+    change: &Change,
+    source_ip: Option<&str>,
+    request_id: &str,
+    now: SystemTime,
+) -> HashMap<String, AttributeValue> {
+    let timestamp = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let mut item = HashMap::from([
+        (
+            "domain".to_string(),
+            AttributeValue::S(change.domain.clone()),
+        ),
+        (
+            "id".to_string(),
+            AttributeValue::S(format!("{:013}#{}", timestamp, uuid::Uuid::new_v4())),
+        ),
+        (
+            "timestamp".to_string(),
+            AttributeValue::N(timestamp.to_string()),
+        ),
+        (
+            "record_type".to_string(),
+            AttributeValue::S(change.record_type.to_string()),
+        ),
+        (
+            "action".to_string(),
+            AttributeValue::S(change.action.as_str().to_string()),
+        ),
+        (
+            "provider".to_string(),
+            AttributeValue::S(change.provider.to_string()),
+        ),
+        (
+            "caller".to_string(),
+            AttributeValue::S(change.caller.clone()),
+        ),
+        (
+            "request_id".to_string(),
+            AttributeValue::S(request_id.to_string()),
+        ),
+    ]);
+    let optional = [
+        ("old_ip", change.old_ip.as_deref()),
+        ("new_ip", change.new_ip.as_deref()),
+        ("source_ip", source_ip),
+    ];
+    item.extend(optional.into_iter().filter_map(|(name, value)| {
+        Some((name.to_string(), AttributeValue::S(value?.to_string())))
+    }));
+    item
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn change() -> Change {
+        Change {
+            domain: "home.example.com".to_string(),
+            record_type: "A",
+            action: UpdateAction::Updated,
+            provider: "porkbun",
+            caller: "token rout***".to_string(),
+            old_ip: Some("198.51.100.1".to_string()),
+            new_ip: Some("203.0.113.7".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        // Requests without a change log, e.g. in tests, aren't audited
+        record(&Request::default(), [change()]);
+
+        let log = ChangeLog::default();
+        let mut event = Request::default();
+        log.attach(&mut event);
+        record(&event, [change()]);
+        record(&event, []);
+        assert_eq!(log.changes(), vec![change()]);
+    }
+
+    #[test]
+    fn test_caller() {
+        let mut event = Request::default();
+        assert_eq!(caller(&event, None), "anonymous");
+        assert_eq!(caller(&event, Some("router-token")), "token rout***");
+        event.extensions_mut().insert(InternalRequest);
+        assert_eq!(caller(&event, Some("router-token")), "internal");
+    }
+
+    #[test]
+    fn test_item() {
+        let now = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        let item = item(&change(), None, "request-1", now);
+        let value = |name: &str| match item.get(name) {
+            Some(AttributeValue::S(value) | AttributeValue::N(value)) => Some(value.as_str()),
+            _ => None,
+        };
+        assert_eq!(value("domain"), Some("home.example.com"));
+        assert!(value("id").unwrap().starts_with("1700000000123#"));
+        assert_eq!(value("timestamp"), Some("1700000000123"));
+        assert_eq!(value("record_type"), Some("A"));
+        assert_eq!(value("action"), Some("updated"));
+        assert_eq!(value("provider"), Some("porkbun"));
+        assert_eq!(value("old_ip"), Some("198.51.100.1"));
+        assert_eq!(value("new_ip"), Some("203.0.113.7"));
+        assert_eq!(value("caller"), Some("token rout***"));
+        assert_eq!(value("request_id"), Some("request-1"));
+        assert_eq!(value("source_ip"), None);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::domain::Domain;
+use crate::error::CredentialsError;
+use crate::state::{AppState, ACCESS_TOKEN_ENV};
+use lambda_http::tracing::warn;
+use std::collections::HashMap;
+use subtle::ConstantTimeEq;
+
+/// Environment variable with a JSON object mapping caller tokens to the domains they may update,
+/// e.g. `{"router-token": ["home.example.com", "*.lab.example.com"]}`
+pub const TOKEN_DOMAINS_ENV: &str = "PORKDYN_TOKEN_DOMAINS";
+
+/// Environment variable which lets callers without a token use the server-side credentials, e.g.
+/// if the endpoint is only reachable with IAM auth or from a private network
+pub const ALLOW_UNAUTHENTICATED_ENV: &str = "PORKDYN_ALLOW_UNAUTHENTICATED";
+
+/// Mapping of caller tokens to the domain patterns each token may update.
+pub type TokenDomains = HashMap<String, Vec<String>>;
+
+/// Which domains an authenticated caller may update.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Access<'a> {
+    /// No token is configured or the caller used the global access token
+    All,
+    /// The caller used a token which is restricted to the given domain patterns
+    Domains(&'a [String]),
+    /// The server only manages the given domain patterns, on top of the caller's access
+    Restricted(&'a [String], Box<Access<'a>>),
+}
+
+/// Checks the caller token against the configured access token and token-to-domain mapping.
+/// Returns `None` if a token is required but missing or unknown.
+pub(crate) fn authenticate<'a>(state: &'a AppState, token: Option<&str>) -> Option<Access<'a>> {
+    if state.access_token.is_none() && state.token_domains.is_empty() {
+        return Some(Access::All);
+    }
+    let token = token?;
+    if state
+        .access_token
+        .as_deref()
+        .is_some_and(|access_token| token_matches(access_token, token))
+    {
+        return Some(Access::All);
+    }
+    // Compare against every configured token, so the time taken doesn't reveal which one matched
+    let mut access = None;
+    for (candidate, patterns) in &state.token_domains {
+        if token_matches(candidate, token) {
+            access = Some(Access::Domains(patterns.as_slice()));
+        }
+    }
+    access
+}
+
+/// Refuses server-side credentials without an access token or token mapping, which would let
+/// anyone who finds the endpoint change the records, unless `allow_unauthenticated` opts out.
+pub(crate) fn check_server_credentials(
+    state: &AppState,
+    allow_unauthenticated: bool,
+) -> Result<(), CredentialsError> {
+    if state.server_credentials.is_none()
+        || state.access_token.is_some()
+        || !state.token_domains.is_empty()
+    {
+        return Ok(());
+    }
+    if allow_unauthenticated {
+        warn!(
+            env = ALLOW_UNAUTHENTICATED_ENV,
+            "Server-side credentials are used for callers without a token"
+        );
+        return Ok(());
+    }
+    Err(CredentialsError::InvalidConfig(format!(
+        "server-side credentials require {} or {}, set {}=true to allow callers without a token",
+        ACCESS_TOKEN_ENV, TOKEN_DOMAINS_ENV, ALLOW_UNAUTHENTICATED_ENV
+    )))
+}
+
+/// Whether callers without a token were explicitly allowed (`PORKDYN_ALLOW_UNAUTHENTICATED`).
+pub(crate) fn allow_unauthenticated() -> bool {
+    std::env::var(ALLOW_UNAUTHENTICATED_ENV).is_ok_and(|allow| allow == "true")
+}
+
+/// Compares tokens in constant time to prevent timing attacks.
+fn token_matches(expected: &str, provided: &str) -> bool {
+    expected.as_bytes().ct_eq(provided.as_bytes()).into()
+}
+
+/// Emits a structured audit event for a failed authentication or authorization attempt.
+/// All events share the `porkdyn::audit` target and `event = "auth_failure"` field,
+/// so they can be counted (and alerted on) with a CloudWatch metric filter.
+pub(crate) fn log_auth_failure(reason: &str, source_ip: Option<&str>, domain: Option<&str>) {
+    warn!(
+        target: "porkdyn::audit",
+        event = "auth_failure",
+        reason,
+        source_ip = source_ip.unwrap_or("unknown"),
+        domain = domain.unwrap_or(""),
+        "Authentication failed"
+    );
+}
+
+impl<'a> Access<'a> {
+    /// Whether the domain may be updated, a pattern `*.example.com` matches all names below
+    /// `example.com` (but not `example.com` itself).
+    pub(crate) fn allows(&self, domain: &Domain) -> bool {
+        match self {
+            Access::All => true,
+            Access::Domains(patterns) => matches_any(patterns, domain),
+            Access::Restricted(patterns, access) => {
+                matches_any(patterns, domain) && access.allows(domain)
+            }
+        }
+    }
+
+    /// Restricts the access to the domains the server manages, no patterns means no restriction.
+    pub(crate) fn within(self, patterns: &'a [String]) -> Self {
+        if patterns.is_empty() {
+            self
+        } else {
+            Access::Restricted(patterns, Box::new(self))
+        }
+    }
+
+    /// Whether the domain is refused by the server, regardless of the caller's token.
+    pub(crate) fn server_denies(&self, domain: &Domain) -> bool {
+        matches!(self, Access::Restricted(patterns, _) if !matches_any(patterns, domain))
+    }
+}
+
+fn matches_any(patterns: &[String], domain: &Domain) -> bool {
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+        let name = domain.qualified_name();
+        match pattern.strip_prefix("*.") {
+            Some(parent) => name
+                .strip_suffix(parent)
+                .is_some_and(|prefix| prefix.ends_with('.')),
+            None => name == pattern,
+        }
+    })
+}
+
+/// Parses the token-to-domain mapping from its JSON representation.
+pub(crate) fn parse_token_domains(json: &str) -> Result<TokenDomains, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credentials::Credentials;
+    use crate::secrets::ServerCredentials;
+
+    fn state(access_token: Option<&str>, token_domains: &str) -> AppState {
+        AppState {
+            access_token: access_token.map(str::to_string),
+            token_domains: parse_token_domains(token_domains).unwrap(),
+            ..AppState::default()
+        }
+    }
+
+    #[test]
+    fn test_authenticate_without_configuration() {
+        let state = state(None, "{}");
+        assert_eq!(authenticate(&state, None), Some(Access::All));
+        assert_eq!(authenticate(&state, Some("any")), Some(Access::All));
+    }
+
+    #[test]
+    fn test_authenticate_with_tokens() {
+        let state = state(
+            Some("admin"),
+            r#"{"router": ["home.example.com"], "lab": ["*.lab.example.com"]}"#,
+        );
+        assert_eq!(authenticate(&state, None), None);
+        assert_eq!(authenticate(&state, Some("unknown")), None);
+        assert_eq!(authenticate(&state, Some("admin")), Some(Access::All));
+        assert!(matches!(
+            authenticate(&state, Some("router")),
+            Some(Access::Domains(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_server_credentials() {
+        let credentials = Credentials::new("pk1_key".to_string(), "sk1_secret".to_string());
+        let mut state = state(None, "{}");
+        assert!(check_server_credentials(&state, false).is_ok());
+
+        state.server_credentials = Some(ServerCredentials::Static(credentials));
+        assert!(matches!(
+            check_server_credentials(&state, false),
+            Err(CredentialsError::InvalidConfig(_))
+        ));
+        assert!(check_server_credentials(&state, true).is_ok());
+
+        state.token_domains = parse_token_domains(r#"{"router": ["home.example.com"]}"#).unwrap();
+        assert!(check_server_credentials(&state, false).is_ok());
+        state.token_domains.clear();
+        state.access_token = Some("admin".to_string());
+        assert!(check_server_credentials(&state, false).is_ok());
+    }
+
+    #[test]
+    fn test_access_allows() {
+        let patterns = vec![
+            "Home.Example.com".to_string(),
+            "*.lab.example.com".to_string(),
+        ];
+        let access = Access::Domains(&patterns);
+        assert!(access.allows(&Domain::new("home.example.com").unwrap()));
+        assert!(access.allows(&Domain::new("nas.lab.example.com").unwrap()));
+        assert!(access.allows(&Domain::new("a.b.lab.example.com").unwrap()));
+        assert!(!access.allows(&Domain::new("lab.example.com").unwrap()));
+        assert!(!access.allows(&Domain::new("evillab.example.com").unwrap()));
+        assert!(!access.allows(&Domain::new("vpn.example.com").unwrap()));
+        assert!(Access::All.allows(&Domain::new("vpn.example.com").unwrap()));
+    }
+
+    #[test]
+    fn test_access_within_allowed_domains() {
+        let allowed = vec!["*.example.com".to_string()];
+        let token = vec![
+            "home.example.com".to_string(),
+            "home.example.org".to_string(),
+        ];
+        let access = Access::Domains(&token).within(&allowed);
+        assert!(access.allows(&Domain::new("home.example.com").unwrap()));
+        assert!(!access.allows(&Domain::new("home.example.org").unwrap()));
+        assert!(access.server_denies(&Domain::new("home.example.org").unwrap()));
+        assert!(!access.server_denies(&Domain::new("vpn.example.com").unwrap()));
+        assert_eq!(Access::All.within(&[]), Access::All);
+    }
+
+    #[test]
+    fn test_token_matches() {
+        assert!(token_matches("router-token", "router-token"));
+        assert!(!token_matches("router-token", "router-tokeN"));
+        assert!(!token_matches("router-token", "router"));
+        assert!(!token_matches("router-token", ""));
+    }
+
+    #[test]
+    fn test_parse_invalid_token_domains() {
+        assert!(parse_token_domains(r#"{"router": "home.example.com"}"#).is_err());
+    }
+}
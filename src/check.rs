@@ -0,0 +1,166 @@
+use crate::api::{list_domains, ping, IPV4_API_BASE_URL};
+use crate::domain::Domain;
+use crate::state::AppState;
+use lambda_http::Error;
+use std::fmt;
+
+/// The outcome of the self-test, one line per check.
+#[derive(Debug, Default)]
+pub(crate) struct Report {
+    lines: Vec<(bool, String, String)>,
+}
+
+impl Report {
+    fn pass(&mut self, check: &str, detail: impl fmt::Display) {
+        self.lines
+            .push((true, check.to_string(), detail.to_string()));
+    }
+
+    fn fail(&mut self, check: &str, detail: impl fmt::Display) {
+        self.lines
+            .push((false, check.to_string(), detail.to_string()));
+    }
+
+    /// The number of failed checks.
+    pub(crate) fn failures(&self) -> usize {
+        self.lines.iter().filter(|(passed, _, _)| !passed).count()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (passed, check, detail) in &self.lines {
+            let outcome = if *passed { "ok" } else { "FAILED" };
+            writeln!(f, "[{:>6}] {}: {}", outcome, check, detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Validates the loaded configuration, the Porkbun credentials and that the domains belong to the
+/// account, without changing any record. Later checks are skipped once one fails they depend on.
+/// The domains default to the configured allowed domains.
+pub(crate) async fn run_checks(state: Result<AppState, Error>, domains: Option<&str>) -> Report {
+    let mut report = Report::default();
+    let state = match state {
+        Ok(state) => {
+            report.pass(
+                "Configuration",
+                format!("default provider {}", state.provider.as_str()),
+            );
+            state
+        }
+        Err(e) => {
+            report.fail("Configuration", e);
+            return report;
+        }
+    };
+    let credentials = match &state.server_credentials {
+        Some(server_credentials) => match server_credentials.get().await {
+            Ok(credentials) => credentials,
+            Err(e) => {
+                report.fail("Credentials", e);
+                return report;
+            }
+        },
+        None => {
+            report.fail(
+                "Credentials",
+                "No Porkbun credentials configured, set PORKBUN_API_KEY and PORKBUN_SECRET_KEY",
+            );
+            return report;
+        }
+    };
+    match ping(&state.porkbun, &credentials).await {
+        Ok(ip) => {
+            // Dual-stack hosts reach the regular endpoint over IPv6, the IPv4 one reveals the other
+            let ipv4_client = state.porkbun.clone().with_base_url(IPV4_API_BASE_URL);
+            let detail = match ping(&ipv4_client, &credentials).await {
+                Ok(ipv4) if ipv4 != ip => {
+                    format!("credentials valid, seen from {} and {}", ip, ipv4)
+                }
+                _ => format!("credentials valid, seen from {}", ip),
+            };
+            report.pass("Porkbun API", detail)
+        }
+        Err(e) => {
+            report.fail("Porkbun API", e);
+            return report;
+        }
+    }
+
+    let names: Vec<String> = match domains {
+        Some(domains) => domains
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect(),
+        // A pattern `*.example.com` needs `example.com` in the account
+        None => state
+            .allowed_domains
+            .iter()
+            .map(|pattern| pattern.trim_start_matches("*.").to_string())
+            .collect(),
+    };
+    if names.is_empty() {
+        return report;
+    }
+    let account_domains = match list_domains(&state.porkbun, &credentials).await {
+        Ok(account_domains) => account_domains,
+        Err(e) => {
+            report.fail("Domains", e);
+            return report;
+        }
+    };
+    for name in names {
+        let domain = match Domain::new(&name) {
+            Ok(domain) => domain,
+            Err(e) => {
+                report.fail(&name, e);
+                continue;
+            }
+        };
+        if account_domains
+            .iter()
+            .any(|account_domain| account_domain.eq_ignore_ascii_case(domain.domain_name()))
+        {
+            report.pass(&name, "part of the Porkbun account");
+        } else {
+            report.fail(
+                &name,
+                format!(
+                    "{} is not part of the Porkbun account",
+                    domain.domain_name()
+                ),
+            );
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_checks_stops_at_first_failure() {
+        let report = run_checks(Ok(AppState::default()), Some("home.example.com")).await;
+        assert_eq!(report.failures(), 1);
+        let output = report.to_string();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "[    ok] Configuration: default provider porkbun",
+                "[FAILED] Credentials: No Porkbun credentials configured, set PORKBUN_API_KEY and PORKBUN_SECRET_KEY",
+            ]
+        );
+
+        let report = run_checks(Err("Invalid configuration: bad".into()), None).await;
+        assert_eq!(report.failures(), 1);
+        assert!(report
+            .to_string()
+            .contains("Configuration: Invalid configuration"));
+    }
+}
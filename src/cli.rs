@@ -0,0 +1,379 @@
+use crate::check::run_checks;
+#[cfg(feature = "prometheus")]
+use crate::exporter;
+use crate::http_handler::function_handler;
+use crate::ip_source::public_ip;
+use crate::ip_utils::IpType;
+use crate::reload::ConfigWatcher;
+use crate::secrets::{load_from_file, ServerCredentials};
+use crate::shutdown::shutdown_signal;
+use crate::state::AppState;
+use crate::systemd;
+use clap::{Args, Parser, Subcommand};
+use lambda_http::tracing::{error, info};
+use lambda_http::{Error, Request, RequestExt};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::{Interval, MissedTickBehavior};
+
+/// Value of `--ip`/`--ipv6` which looks up the public address of this host
+const AUTO_IP: &str = "auto";
+const DEFAULT_POLL_INTERVAL_SECONDS: u64 = 300;
+/// Polling more often would hit Porkbun's rate limit without detecting changes any sooner
+const MIN_POLL_INTERVAL_SECONDS: u64 = 30;
+
+/// Updates DNS records from the command line, e.g. from a cron job.
+#[derive(Debug, Parser)]
+#[command(name = "porkdyn", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Updates the A and/or AAAA records of a domain once
+    Update(UpdateArgs),
+    /// Keeps running and updates the records whenever the public IP changes
+    Daemon(DaemonArgs),
+    /// Validates the configuration and credentials without changing any record
+    Check(CheckArgs),
+}
+
+#[derive(Debug, Args)]
+struct UpdateArgs {
+    /// The fully qualified domain name, several can be passed comma-separated
+    #[arg(long)]
+    domain: String,
+    /// The IPv4 address, `auto` uses the public address of this host (the default if neither
+    /// address is given)
+    #[arg(long)]
+    ip: Option<String>,
+    /// The IPv6 address, `auto` uses the public address of this host
+    #[arg(long)]
+    ipv6: Option<String>,
+    /// Interface identifier of another host in the network, e.g. `::5`, appended to the /64 of
+    /// the IPv6 address (`auto` if `--ipv6` is not given)
+    #[arg(long)]
+    ipv6_suffix: Option<String>,
+    /// The DNS provider, defaults to `PORKDYN_PROVIDER` or Porkbun
+    #[arg(long)]
+    provider: Option<String>,
+    /// Rewrites the records even if they are already up to date
+    #[arg(long)]
+    force: bool,
+    /// JSON file with the Porkbun credentials (`{"apikey": "...", "secretapikey": "..."}`),
+    /// instead of `PORKBUN_API_KEY` and `PORKBUN_SECRET_KEY`
+    #[arg(long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct DaemonArgs {
+    #[command(flatten)]
+    update: UpdateArgs,
+    /// Seconds between two checks of the public IP
+    #[arg(
+        long,
+        default_value_t = DEFAULT_POLL_INTERVAL_SECONDS,
+        value_parser = clap::value_parser!(u64).range(MIN_POLL_INTERVAL_SECONDS..)
+    )]
+    interval: u64,
+}
+
+#[derive(Debug, Args)]
+struct CheckArgs {
+    /// The domains which have to be part of the Porkbun account, comma-separated,
+    /// defaults to the allowed domains of the configuration
+    #[arg(long)]
+    domain: Option<String>,
+    /// JSON file with the Porkbun credentials, as for `update`
+    #[arg(long)]
+    config: Option<PathBuf>,
+}
+
+/// Whether the command line asks for the CLI instead of the Lambda runtime.
+pub fn is_cli_invocation() -> bool {
+    matches!(
+        std::env::args().nth(1).as_deref(),
+        Some("update") | Some("daemon") | Some("check")
+    )
+}
+
+/// Runs the command, a failed update results in an error (and a non-zero exit code).
+pub async fn run() -> Result<(), Error> {
+    match Cli::parse().command {
+        Command::Update(args) => {
+            let state = load_state(args.config.as_deref()).await?;
+            let (ip, ipv6) = resolve_ips(&state, &args).await?;
+            update(&state, &args, ip, ipv6).await
+        }
+        Command::Daemon(args) => daemon(args).await,
+        Command::Check(args) => {
+            let state = load_state(args.config.as_deref()).await;
+            let report = run_checks(state, args.domain.as_deref()).await;
+            print!("{}", report);
+            match report.failures() {
+                0 => Ok(()),
+                failures => Err(format!("{} check(s) failed", failures).into()),
+            }
+        }
+    }
+}
+
+/// Polls the public IP and updates the records only if it differs from the last applied one,
+/// a self-contained replacement for ddclient. Failures are retried at the next poll.
+/// Changes of the configuration file are picked up before each poll.
+/// Run as a systemd `Type=notify` service, it reports readiness and pings the watchdog.
+async fn daemon(args: DaemonArgs) -> Result<(), Error> {
+    let mut state = load_state(args.update.config.as_deref()).await?;
+    let mut watcher = ConfigWatcher::from_env()?;
+    #[cfg(feature = "prometheus")]
+    exporter::spawn_from_env().await?;
+    let mut interval = tokio::time::interval(Duration::from_secs(args.interval));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut watchdog = systemd::watchdog_interval().map(tokio::time::interval);
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut applied = None;
+    info!(
+        domain = ?args.update.domain,
+        interval = args.interval,
+        "Checking the public IP periodically"
+    );
+    systemd::notify(systemd::READY);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            // Pinged from this loop, so a hanging update makes systemd restart the daemon
+            _ = tick(&mut watchdog) => {
+                systemd::notify(systemd::WATCHDOG);
+                continue;
+            }
+            _ = &mut shutdown => {
+                systemd::notify(systemd::STOPPING);
+                return Ok(());
+            }
+        }
+        if let Some(watcher) = watcher.as_mut() {
+            if let Some(reloaded) = watcher.reload().await {
+                state = apply_args(reloaded, args.update.config.as_deref())?;
+                // The provider or TTL may have changed, so the records are written again
+                applied = None;
+            }
+        }
+        let ips = match resolve_ips(&state, &args.update).await {
+            Ok(ips) => ips,
+            Err(e) => {
+                error!(error = %e, "Failed to look up the public IP");
+                continue;
+            }
+        };
+        if applied.as_ref() == Some(&ips) {
+            info!(ip = ?ips.0, ipv6 = ?ips.1, "Public IP unchanged");
+            continue;
+        }
+        info!(ip = ?ips.0, ipv6 = ?ips.1, "Public IP changed, updating records");
+        match update(&state, &args.update, ips.0.clone(), ips.1.clone()).await {
+            Ok(()) => {
+                systemd::status(&format!("Records point at {}", describe_ips(&ips)));
+                applied = Some(ips);
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to update records, retrying at the next poll");
+                systemd::status(&format!("Failed to update records: {}", e));
+            }
+        }
+    }
+}
+
+/// Completes at the next tick of the interval, never if there is none.
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// The addresses for status messages, e.g. `1.2.3.4, 2001:db8::1`.
+fn describe_ips((ip, ipv6): &(Option<String>, Option<String>)) -> String {
+    [ip, ipv6]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Loads the state like the Lambda does, with the credentials file taking precedence.
+async fn load_state(credentials_file: Option<&Path>) -> Result<AppState, Error> {
+    apply_args(AppState::from_env().await?, credentials_file)
+}
+
+/// Applies the command line options on top of the loaded state.
+fn apply_args(mut state: AppState, credentials_file: Option<&Path>) -> Result<AppState, Error> {
+    if let Some(path) = credentials_file {
+        state.server_credentials = Some(ServerCredentials::Static(load_from_file(path)?));
+    }
+    // The caller runs locally, access tokens only protect the Lambda
+    state.access_token = None;
+    state.token_domains.clear();
+    Ok(state)
+}
+
+/// The IPv4 and IPv6 address to set, with `auto` resolved to the public addresses.
+/// Without any address the public IPv4 address is used, an IPv6 suffix needs the IPv6 address
+/// for the prefix.
+async fn resolve_ips(
+    state: &AppState,
+    args: &UpdateArgs,
+) -> Result<(Option<String>, Option<String>), Error> {
+    let ipv6 = match (&args.ipv6, &args.ipv6_suffix) {
+        (None, Some(_)) => Some(AUTO_IP),
+        (ipv6, _) => ipv6.as_deref(),
+    };
+    let ip = match (&args.ip, ipv6) {
+        (None, None) => Some(AUTO_IP),
+        (ip, _) => ip.as_deref(),
+    };
+    Ok((
+        resolve_ip(state, ip, IpType::V4).await?,
+        resolve_ip(state, ipv6, IpType::V6).await?,
+    ))
+}
+
+/// Updates the records with the same logic as the Lambda handler and prints its response.
+async fn update(
+    state: &AppState,
+    args: &UpdateArgs,
+    ip: Option<String>,
+    ipv6: Option<String>,
+) -> Result<(), Error> {
+    let event = Request::default().with_query_string_parameters(query_parameters(args, ip, ipv6));
+    let response = function_handler(event, state).await?;
+    println!("{}", String::from_utf8_lossy(response.body()));
+    if !response.status().is_success() {
+        return Err(format!("Update failed with status {}", response.status()).into());
+    }
+    Ok(())
+}
+
+/// Looks up the public address if `auto` is passed.
+async fn resolve_ip(
+    state: &AppState,
+    ip: Option<&str>,
+    ip_type: IpType,
+) -> Result<Option<String>, Error> {
+    match ip {
+        Some(AUTO_IP) => Ok(Some(public_ip(state, ip_type).await?)),
+        ip => Ok(ip.map(str::to_string)),
+    }
+}
+
+/// The query-parameters of the equivalent request to the Lambda.
+fn query_parameters(
+    args: &UpdateArgs,
+    ip: Option<String>,
+    ipv6: Option<String>,
+) -> HashMap<String, String> {
+    let mut parameters = HashMap::from([("domain".to_string(), args.domain.clone())]);
+    let optional = [
+        ("ip", ip),
+        ("ipv6", ipv6),
+        ("ipv6suffix", args.ipv6_suffix.clone()),
+        ("provider", args.provider.clone()),
+        ("force", args.force.then(|| "true".to_string())),
+    ];
+    for (name, value) in optional {
+        if let Some(value) = value {
+            parameters.insert(name.to_string(), value);
+        }
+    }
+    parameters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_update() {
+        let cli = Cli::try_parse_from([
+            "porkdyn",
+            "update",
+            "--domain",
+            "api.example.com",
+            "--ip",
+            "auto",
+            "--provider",
+            "desec",
+            "--force",
+        ])
+        .unwrap();
+        let Command::Update(args) = cli.command else {
+            panic!("Expected the update command");
+        };
+        assert_eq!(args.ip.as_deref(), Some("auto"));
+        assert_eq!(args.config, None);
+
+        let parameters = query_parameters(&args, Some("1.2.3.4".to_string()), None);
+        assert_eq!(parameters.len(), 4);
+        assert_eq!(parameters["force"], "true");
+        assert_eq!(parameters["domain"], "api.example.com");
+        assert_eq!(parameters["ip"], "1.2.3.4");
+        assert_eq!(parameters["provider"], "desec");
+
+        assert!(Cli::try_parse_from(["porkdyn", "update", "--ip", "auto"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_daemon() {
+        let cli =
+            Cli::try_parse_from(["porkdyn", "daemon", "--domain", "api.example.com"]).unwrap();
+        let Command::Daemon(args) = cli.command else {
+            panic!("Expected the daemon command");
+        };
+        assert_eq!(args.interval, 300);
+        assert_eq!(args.update.domain, "api.example.com");
+
+        let cli = Cli::try_parse_from([
+            "porkdyn",
+            "daemon",
+            "--domain",
+            "nas.example.com",
+            "--ipv6-suffix",
+            "::5",
+        ])
+        .unwrap();
+        let Command::Daemon(args) = cli.command else {
+            panic!("Expected the daemon command");
+        };
+        let parameters =
+            query_parameters(&args.update, None, Some("2001:db8:1:2::abc".to_string()));
+        assert_eq!(parameters["ipv6"], "2001:db8:1:2::abc");
+        assert_eq!(parameters["ipv6suffix"], "::5");
+
+        let too_short = [
+            "porkdyn",
+            "daemon",
+            "--domain",
+            "a.example.com",
+            "--interval",
+            "5",
+        ];
+        assert!(Cli::try_parse_from(too_short).is_err());
+    }
+
+    #[test]
+    fn test_parse_check() {
+        let cli = Cli::try_parse_from(["porkdyn", "check"]).unwrap();
+        let Command::Check(args) = cli.command else {
+            panic!("Expected the check command");
+        };
+        assert_eq!(args.domain, None);
+        assert_eq!(args.config, None);
+    }
+}
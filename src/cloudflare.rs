@@ -0,0 +1,273 @@
+use crate::api::{DnsRecord, DEFAULT_TTL};
+use crate::domain::Domain;
+use crate::error::{ApiError, PorkbunError};
+use crate::ip_utils::RecordType;
+use crate::provider::DnsProvider;
+use crate::record::RecordUpdate;
+use lambda_http::tracing::{error, info};
+use reqwest::{Client, RequestBuilder};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Environment variable with the Cloudflare API token, it needs the `Zone:Read` and
+/// `DNS:Edit` permissions for the zones records are managed in
+pub const CLOUDFLARE_API_TOKEN_ENV: &str = "CLOUDFLARE_API_TOKEN";
+
+const API_BASE_URL: &str = "https://api.cloudflare.com/client/v4";
+
+/// The envelope of every Cloudflare response.
+#[derive(Debug, Deserialize)]
+struct CloudflareResponse<T> {
+    #[serde(default)]
+    success: bool,
+    #[serde(default)]
+    errors: Vec<CloudflareMessage>,
+    result: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudflareMessage {
+    #[serde(default)]
+    code: u64,
+    #[serde(default)]
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Zone {
+    id: String,
+}
+
+#[derive(Serialize)]
+struct CloudflareRecordRequest<'a> {
+    #[serde(rename = "type")]
+    record_type: &'static str,
+    name: &'a str,
+    content: &'a str,
+    ttl: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<u16>,
+}
+
+/// The Cloudflare API, authenticated with an API token.
+pub(crate) struct Cloudflare<'a> {
+    client: &'a Client,
+    token: &'a str,
+    /// Zone ids by domain name, so a request updating several records looks each zone up once.
+    zones: Mutex<HashMap<String, String>>,
+}
+
+impl<'a> Cloudflare<'a> {
+    pub fn new(client: &'a Client, token: &'a str) -> Self {
+        Self {
+            client,
+            token,
+            zones: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sends a request and decodes the `result` of the response.
+    /// A response without `"success": true` is turned into a typed error.
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, PorkbunError> {
+        let response = request.bearer_auth(self.token).send().await?;
+        let http_status = response.status().as_u16();
+        let text = response.text().await?;
+        let body: CloudflareResponse<T> = match serde_json::from_str(&text) {
+            Ok(body) => body,
+            Err(_) if http_status == 429 || http_status >= 500 => {
+                return Err(PorkbunError::from_response(
+                    http_status,
+                    format!("HTTP {}", http_status),
+                ))
+            }
+            Err(e) => {
+                error!(http_status = %http_status, "Malformed Cloudflare response");
+                return Err(PorkbunError::MalformedResponse(e.to_string()));
+            }
+        };
+        if !body.success {
+            let message = body
+                .errors
+                .iter()
+                .map(|e| format!("{} ({})", e.message, e.code))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(PorkbunError::from_response(http_status, message));
+        }
+        body.result
+            .ok_or_else(|| PorkbunError::MalformedResponse("Missing result".to_string()))
+    }
+
+    /// Looks up the id of the zone the domain belongs to.
+    async fn zone_id(&self, domain: &Domain) -> Result<String, PorkbunError> {
+        let domain_name = domain.domain_name();
+        if let Some(zone_id) = self.zones.lock().unwrap().get(domain_name) {
+            return Ok(zone_id.clone());
+        }
+        let url = format!("{}/zones?name={}", API_BASE_URL, domain_name);
+        info!(url = %url, "Look up Cloudflare zone");
+        let zones: Vec<Zone> = self.send(self.client.get(&url)).await?;
+        let zone_id = zones
+            .into_iter()
+            .next()
+            .map(|zone| zone.id)
+            .ok_or_else(|| {
+                PorkbunError::DomainNotFound(format!("No Cloudflare zone for {}", domain_name))
+            })?;
+        self.zones
+            .lock()
+            .unwrap()
+            .insert(domain_name.to_string(), zone_id.clone());
+        Ok(zone_id)
+    }
+}
+
+/// Builds the request body, Cloudflare expects the fully qualified name of the record.
+fn record_request<'a>(
+    domain: &'a Domain,
+    record: &'a RecordUpdate,
+) -> Result<CloudflareRecordRequest<'a>, PorkbunError> {
+    let record_type = record.record_type.as_str();
+    if matches!(
+        record.record_type,
+        RecordType::SRV | RecordType::CAA | RecordType::ALIAS
+    ) {
+        return Err(PorkbunError::Unsupported(format!(
+            "{} records are not supported by Cloudflare",
+            record_type
+        )));
+    }
+    Ok(CloudflareRecordRequest {
+        record_type,
+        name: domain.qualified_name(),
+        content: &record.content,
+        ttl: record.ttl.unwrap_or(DEFAULT_TTL),
+        priority: record.prio,
+    })
+}
+
+impl DnsProvider for Cloudflare<'_> {
+    async fn get_records(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<Vec<DnsRecord>, ApiError> {
+        async {
+            let zone_id = self.zone_id(domain).await?;
+            let url = format!(
+                "{}/zones/{}/dns_records?type={}&name={}",
+                API_BASE_URL,
+                zone_id,
+                record_type.as_str(),
+                domain.qualified_name()
+            );
+            info!(url = %url, "Get existing records");
+            self.send(self.client.get(&url)).await
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to retrieve DNS records"))
+        .map_err(ApiError::RetrieveRecordFailed)
+    }
+
+    async fn create_record(
+        &self,
+        domain: &Domain,
+        record: &RecordUpdate,
+    ) -> Result<Option<String>, ApiError> {
+        async {
+            let request_body = record_request(domain, record)?;
+            let zone_id = self.zone_id(domain).await?;
+            let url = format!("{}/zones/{}/dns_records", API_BASE_URL, zone_id);
+            info!(url = %url, domain = domain.qualified_name(), "Create DNS record");
+            let created: DnsRecord = self
+                .send(self.client.post(&url).json(&request_body))
+                .await?;
+            info!(record_id = %created.id, "Created DNS record");
+            Ok::<_, PorkbunError>(Some(created.id))
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to create DNS record"))
+        .map_err(ApiError::CreateRecordFailed)
+    }
+
+    async fn update_record(
+        &self,
+        domain: &Domain,
+        record_id: &str,
+        record: &RecordUpdate,
+    ) -> Result<(), ApiError> {
+        async {
+            let request_body = record_request(domain, record)?;
+            let zone_id = self.zone_id(domain).await?;
+            let url = format!(
+                "{}/zones/{}/dns_records/{}",
+                API_BASE_URL, zone_id, record_id
+            );
+            info!(url = %url, "Update DNS record");
+            self.send::<IgnoredAny>(self.client.put(&url).json(&request_body))
+                .await?;
+            info!(record_id, "Updated DNS record");
+            Ok::<_, PorkbunError>(())
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to update DNS record"))
+        .map_err(ApiError::UpdateRecordFailed)
+    }
+
+    async fn delete_record(&self, domain: &Domain, record_id: &str) -> Result<(), ApiError> {
+        async {
+            let zone_id = self.zone_id(domain).await?;
+            let url = format!(
+                "{}/zones/{}/dns_records/{}",
+                API_BASE_URL, zone_id, record_id
+            );
+            info!(url = %url, "Delete DNS record");
+            self.send::<IgnoredAny>(self.client.delete(&url)).await?;
+            info!(record_id, "Deleted DNS record");
+            Ok::<_, PorkbunError>(())
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to delete DNS record"))
+        .map_err(ApiError::DeleteRecordFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ip_utils::IpType;
+
+    #[test]
+    fn test_record_deserialization() {
+        let response: CloudflareResponse<Vec<DnsRecord>> = serde_json::from_value(serde_json::json!({
+            "success": true,
+            "errors": [],
+            "result": [
+                {"id": "023e105f4ecef8ad9ca31a8372d0c353", "name": "example.org", "type": "MX", "content": "mail.example.org", "ttl": 1, "priority": 10, "proxied": false}
+            ]
+        }))
+        .unwrap();
+        let records = response.result.unwrap();
+        assert_eq!(records[0].id, "023e105f4ecef8ad9ca31a8372d0c353");
+        assert_eq!(records[0].prio.as_deref(), Some("10"));
+        assert_eq!(records[0].ttl.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_record_request() {
+        let domain = Domain::new("home.example.org").unwrap();
+        let update = RecordUpdate::ip("1.2.3.4".to_string(), IpType::V4);
+        let request = record_request(&domain, &update).unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({"type": "A", "name": "home.example.org", "content": "1.2.3.4", "ttl": 600})
+        );
+        let caa = RecordUpdate::caa(0, "issue", "letsencrypt.org");
+        assert_eq!(
+            record_request(&domain, &caa).err().unwrap().status_code(),
+            400
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use crate::api::{
+    CONNECT_TIMEOUT_ENV, REQUEST_TIMEOUT_ENV, RETRY_ATTEMPTS_ENV, RETRY_BASE_DELAY_ENV,
+};
+use crate::auth::{parse_token_domains, TokenDomains, TOKEN_DOMAINS_ENV};
+use crate::credentials::redact;
+use crate::error::CredentialsError;
+use crate::ip_source::{IpSource, IP_SOURCES_ENV};
+use crate::provider::{ProviderKind, FALLBACK_PROVIDER_ENV, MIRROR_PROVIDERS_ENV, PROVIDER_ENV};
+use crate::state::ACCESS_TOKEN_ENV;
+use serde::Deserialize;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Environment variable with the path of the TOML configuration file
+pub const CONFIG_FILE_ENV: &str = "PORKDYN_CONFIG_FILE";
+/// Environment variable with the TTL of records a request doesn't set one for
+pub const DEFAULT_TTL_ENV: &str = "PORKDYN_DEFAULT_TTL";
+/// Environment variable with the comma-separated domain patterns the server manages,
+/// e.g. `home.example.com,*.lab.example.com`
+pub const ALLOWED_DOMAINS_ENV: &str = "PORKDYN_ALLOWED_DOMAINS";
+/// Environment variable with the comma-separated domains push notifications to `/webhook` update
+pub const WEBHOOK_DOMAINS_ENV: &str = "PORKDYN_WEBHOOK_DOMAINS";
+/// Environment variable which allows private and reserved addresses as record content if `true`
+pub const ALLOW_PRIVATE_ENV: &str = "PORKDYN_ALLOW_PRIVATE";
+/// Environment variable with the comma-separated addresses records point to while their DynDNS2
+/// client reports itself offline
+pub const PARKING_IPS_ENV: &str = "PORKDYN_PARKING_IPS";
+/// Environment variable which rejects requests with unknown parameters if `true`
+pub const STRICT_PARAMETERS_ENV: &str = "PORKDYN_STRICT_PARAMETERS";
+/// Environment variable with the comma-separated origins browsers may call from, `*` for any,
+/// e.g. `https://dash.example.com`
+pub const CORS_ORIGINS_ENV: &str = "PORKDYN_CORS_ORIGINS";
+/// Environment variable with the comma-separated methods preflights allow, e.g. `GET,POST`
+pub const CORS_METHODS_ENV: &str = "PORKDYN_CORS_METHODS";
+/// Environment variable with the CloudWatch namespace metrics are written to, e.g. `porkDyn`
+pub const METRICS_NAMESPACE_ENV: &str = "PORKDYN_METRICS_NAMESPACE";
+
+/// Settings read from the TOML configuration file, environment variables take precedence.
+/// All settings are optional, a missing file is the same as an empty one.
+#[derive(Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The provider used if a request doesn't select one
+    pub provider: ProviderKind,
+    /// The provider records are updated at if the selected one is unreachable
+    pub fallback_provider: Option<ProviderKind>,
+    /// The providers every record change is mirrored to
+    pub mirror_providers: Vec<ProviderKind>,
+    /// The TTL of records a request doesn't set one for, otherwise the provider's default
+    pub default_ttl: Option<u64>,
+    /// Domain patterns the server manages, empty allows all domains
+    pub allowed_domains: Vec<String>,
+    /// Whether private and reserved addresses may be published, e.g. for split-horizon DNS
+    pub allow_private: bool,
+    /// Domains updated by push notifications to `/webhook` which don't name any
+    pub webhook_domains: Vec<String>,
+    /// Addresses (IPv4 and/or IPv6) records point to while offline, deleted instead if empty
+    pub parking_ips: Vec<IpAddr>,
+    /// Whether updates with unknown parameters (e.g. a misspelled `secretapikey`) are rejected
+    pub strict_parameters: bool,
+    /// Sources the public IP is looked up with, in order, if no address is given
+    pub ip_sources: Vec<IpSource>,
+    /// Shared secret callers have to send as `token` parameter
+    pub access_token: Option<String>,
+    /// Caller tokens which may only update the listed domain patterns
+    pub token_domains: TokenDomains,
+    pub timeouts: Timeouts,
+    pub retry: Retry,
+    pub cors: Cors,
+    pub metrics: Metrics,
+}
+
+/// Caller tokens are masked, so the configuration can be logged safely.
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("provider", &self.provider)
+            .field("fallback_provider", &self.fallback_provider)
+            .field("mirror_providers", &self.mirror_providers)
+            .field("default_ttl", &self.default_ttl)
+            .field("allowed_domains", &self.allowed_domains)
+            .field("allow_private", &self.allow_private)
+            .field("webhook_domains", &self.webhook_domains)
+            .field("parking_ips", &self.parking_ips)
+            .field("strict_parameters", &self.strict_parameters)
+            .field("ip_sources", &self.ip_sources)
+            .field("access_token", &self.access_token.as_deref().map(redact))
+            .field(
+                "token_domains",
+                &self
+                    .token_domains
+                    .iter()
+                    .map(|(token, domains)| (redact(token), domains))
+                    .collect::<Vec<_>>(),
+            )
+            .field("timeouts", &self.timeouts)
+            .field("retry", &self.retry)
+            .field("cors", &self.cors)
+            .field("metrics", &self.metrics)
+            .finish()
+    }
+}
+
+/// Timeouts of the calls to the Porkbun API.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timeouts {
+    pub connect_seconds: Option<u64>,
+    pub request_seconds: Option<u64>,
+}
+
+/// Retries of failed calls to the Porkbun API.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Retry {
+    pub attempts: Option<u32>,
+    pub base_delay_ms: Option<u64>,
+}
+
+/// Cross-origin requests of browsers, e.g. from a web dashboard. Disabled if no origin is allowed.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Cors {
+    /// Origins allowed to call, `*` allows any origin
+    pub origins: Vec<String>,
+    /// Methods preflights allow, the ones the endpoint accepts if empty
+    pub methods: Vec<String>,
+}
+
+/// Metrics of the handler, written to the log in CloudWatch's Embedded Metric Format.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Metrics {
+    /// The CloudWatch namespace of the metrics, none are written without
+    pub namespace: Option<String>,
+}
+
+impl Config {
+    /// Reads the file named by `PORKDYN_CONFIG_FILE` (if set) and applies the environment.
+    pub fn load() -> Result<Self, CredentialsError> {
+        let mut config = match std::env::var(CONFIG_FILE_ENV) {
+            Ok(path) => Self::read(Path::new(&path))?,
+            Err(_) => Self::default(),
+        };
+        config.apply_overrides(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    fn read(path: &Path) -> Result<Self, CredentialsError> {
+        let toml = std::fs::read_to_string(path).map_err(|e| {
+            CredentialsError::InvalidConfig(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        Self::parse(&toml)
+    }
+
+    pub fn parse(toml: &str) -> Result<Self, CredentialsError> {
+        toml::from_str(toml).map_err(|e| CredentialsError::InvalidConfig(e.to_string()))
+    }
+
+    /// Overrides the settings with the environment variables which are set.
+    fn apply_overrides(
+        &mut self,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), CredentialsError> {
+        if let Some(provider) = var(PROVIDER_ENV) {
+            self.provider = provider
+                .parse()
+                .map_err(CredentialsError::InvalidProvider)?;
+        }
+        if let Some(provider) = var(FALLBACK_PROVIDER_ENV) {
+            self.fallback_provider = Some(
+                provider
+                    .parse()
+                    .map_err(CredentialsError::InvalidProvider)?,
+            );
+        }
+        if let Some(providers) = var(MIRROR_PROVIDERS_ENV) {
+            self.mirror_providers = split_list(&providers)
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(CredentialsError::InvalidProvider)?;
+        }
+        if let Some(domains) = var(ALLOWED_DOMAINS_ENV) {
+            self.allowed_domains = split_list(&domains).map(str::to_string).collect();
+        }
+        if let Some(domains) = var(WEBHOOK_DOMAINS_ENV) {
+            self.webhook_domains = split_list(&domains).map(str::to_string).collect();
+        }
+        if let Some(value) = var(ALLOW_PRIVATE_ENV) {
+            self.allow_private = parse_value(ALLOW_PRIVATE_ENV, &value)?;
+        }
+        if let Some(addresses) = var(PARKING_IPS_ENV) {
+            self.parking_ips = split_list(&addresses)
+                .map(|address| parse_value(PARKING_IPS_ENV, address))
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(value) = var(STRICT_PARAMETERS_ENV) {
+            self.strict_parameters = parse_value(STRICT_PARAMETERS_ENV, &value)?;
+        }
+        if let Some(sources) = var(IP_SOURCES_ENV) {
+            self.ip_sources = split_list(&sources)
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(CredentialsError::InvalidConfig)?;
+        }
+        if let Some(token) = var(ACCESS_TOKEN_ENV) {
+            self.access_token = Some(token).filter(|token| !token.is_empty());
+        }
+        if let Some(json) = var(TOKEN_DOMAINS_ENV) {
+            self.token_domains = parse_token_domains(&json)
+                .map_err(|e| CredentialsError::InvalidTokenDomains(e.to_string()))?;
+        }
+        let numbers = [
+            (DEFAULT_TTL_ENV, &mut self.default_ttl),
+            (CONNECT_TIMEOUT_ENV, &mut self.timeouts.connect_seconds),
+            (REQUEST_TIMEOUT_ENV, &mut self.timeouts.request_seconds),
+            (RETRY_BASE_DELAY_ENV, &mut self.retry.base_delay_ms),
+        ];
+        for (name, setting) in numbers {
+            if let Some(value) = var(name) {
+                *setting = Some(parse_value(name, &value)?);
+            }
+        }
+        if let Some(value) = var(RETRY_ATTEMPTS_ENV) {
+            self.retry.attempts = Some(parse_value(RETRY_ATTEMPTS_ENV, &value)?);
+        }
+        if let Some(origins) = var(CORS_ORIGINS_ENV) {
+            self.cors.origins = split_list(&origins).map(str::to_string).collect();
+        }
+        if let Some(methods) = var(CORS_METHODS_ENV) {
+            self.cors.methods = split_list(&methods).map(str::to_ascii_uppercase).collect();
+        }
+        if let Some(namespace) = var(METRICS_NAMESPACE_ENV) {
+            self.metrics.namespace = Some(namespace.trim().to_string()).filter(|ns| !ns.is_empty());
+        }
+        Ok(())
+    }
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T, CredentialsError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| CredentialsError::InvalidConfig(format!("Invalid {}: {}", name, value)))
+}
+
+/// The non-empty items of a comma-separated list.
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            r#"
+            provider = "desec"
+            mirror_providers = ["hetzner"]
+            default_ttl = 300
+            allowed_domains = ["home.example.com", "*.lab.example.com"]
+            parking_ips = ["203.0.113.1", "2001:db8::1"]
+            strict_parameters = true
+
+            [timeouts]
+            request_seconds = 20
+
+            [retry]
+            attempts = 5
+
+            [cors]
+            origins = ["https://dash.example.com"]
+
+            [metrics]
+            namespace = "porkDyn"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.provider, ProviderKind::Desec);
+        assert_eq!(config.fallback_provider, None);
+        assert_eq!(config.mirror_providers, vec![ProviderKind::Hetzner]);
+        assert_eq!(config.default_ttl, Some(300));
+        assert_eq!(config.allowed_domains.len(), 2);
+        assert_eq!(config.parking_ips.len(), 2);
+        assert!(config.strict_parameters);
+        assert_eq!(config.timeouts.connect_seconds, None);
+        assert_eq!(config.timeouts.request_seconds, Some(20));
+        assert_eq!(config.retry.attempts, Some(5));
+        assert_eq!(config.cors.origins, vec!["https://dash.example.com"]);
+        assert!(config.cors.methods.is_empty());
+        assert_eq!(config.metrics.namespace.as_deref(), Some("porkDyn"));
+
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse(r#"provider = "gandi""#).is_err());
+        assert!(Config::parse("default_tll = 300").is_err());
+    }
+
+    #[test]
+    fn test_environment_overrides_file() {
+        let mut config = Config::parse(
+            r#"
+            provider = "desec"
+            default_ttl = 300
+            allowed_domains = ["home.example.com"]
+            "#,
+        )
+        .unwrap();
+        let env = HashMap::from([
+            (PROVIDER_ENV, "hetzner"),
+            (ALLOWED_DOMAINS_ENV, "a.example.com, *.lab.example.com,"),
+            (RETRY_BASE_DELAY_ENV, "100"),
+            (ALLOW_PRIVATE_ENV, "true"),
+            (PARKING_IPS_ENV, "0.0.0.0, ::"),
+            (CORS_METHODS_ENV, "get, post"),
+            (METRICS_NAMESPACE_ENV, " "),
+        ]);
+        config
+            .apply_overrides(|name| env.get(name).map(|value| value.to_string()))
+            .unwrap();
+        assert_eq!(config.provider, ProviderKind::Hetzner);
+        assert_eq!(config.default_ttl, Some(300));
+        assert_eq!(
+            config.allowed_domains,
+            vec!["a.example.com", "*.lab.example.com"]
+        );
+        assert_eq!(config.retry.base_delay_ms, Some(100));
+        assert!(config.allow_private);
+        assert_eq!(config.cors.methods, vec!["GET", "POST"]);
+        assert_eq!(config.metrics.namespace, None);
+        assert_eq!(
+            config.parking_ips,
+            vec!["0.0.0.0".parse::<IpAddr>().unwrap(), "::".parse().unwrap()]
+        );
+
+        let invalid = |name: &str, value: &str| {
+            Config::default()
+                .apply_overrides(|var| (var == name).then(|| value.to_string()))
+                .is_err()
+        };
+        assert!(invalid(DEFAULT_TTL_ENV, "ten minutes"));
+        assert!(invalid(MIRROR_PROVIDERS_ENV, "hetzner,gandi"));
+        assert!(invalid(ALLOW_PRIVATE_ENV, "yes"));
+        assert!(invalid(PARKING_IPS_ENV, "parking.example.com"));
+        assert!(invalid(STRICT_PARAMETERS_ENV, "on"));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::config::Cors;
+use lambda_http::http::header::{
+    ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+    ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_METHOD, VARY,
+};
+use lambda_http::http::{HeaderValue, Method};
+use lambda_http::{Body, Request, Response};
+
+/// Request headers browsers may send, the credentials headers and a caller's request id
+const ALLOWED_HEADERS: &str =
+    "Authorization, Content-Type, X-Api-Key, X-Secret-Api-Key, X-Request-Id";
+/// Response headers scripts may read besides the CORS-safelisted ones
+const EXPOSED_HEADERS: &str = "X-Request-Id";
+/// Browsers cache a preflight response for this many seconds
+const MAX_AGE_SECONDS: &str = "600";
+
+/// The `Access-Control-Allow-Origin` value for the `Origin` of a request, `None` if the origin
+/// isn't allowed. `*` in the configured origins allows any origin.
+pub(crate) fn allowed_origin(cors: &Cors, origin: &HeaderValue) -> Option<HeaderValue> {
+    if cors.origins.iter().any(|allowed| allowed == "*") {
+        return Some(HeaderValue::from_static("*"));
+    }
+    let origin_str = origin.to_str().ok()?;
+    cors.origins
+        .iter()
+        .any(|allowed| {
+            allowed
+                .trim_end_matches('/')
+                .eq_ignore_ascii_case(origin_str)
+        })
+        .then(|| origin.clone())
+}
+
+/// Whether the request is a CORS preflight, which is only answered if CORS is configured.
+pub(crate) fn is_preflight(cors: &Cors, event: &Request) -> bool {
+    !cors.origins.is_empty()
+        && event.method() == Method::OPTIONS
+        && event.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+/// Answers a preflight of an allowed origin, the methods default to the ones the endpoint
+/// accepts. The origin is allowed by [`attach`] like for any other response.
+pub(crate) fn preflight(cors: &Cors, methods: &[Method]) -> Response<Body> {
+    let methods = if cors.methods.is_empty() {
+        methods
+            .iter()
+            .map(Method::as_str)
+            .collect::<Vec<_>>()
+            .join(", ")
+    } else {
+        cors.methods.join(", ")
+    };
+    let mut response = Response::builder()
+        .status(204)
+        .header(ACCESS_CONTROL_ALLOW_HEADERS, ALLOWED_HEADERS)
+        .header(ACCESS_CONTROL_MAX_AGE, MAX_AGE_SECONDS)
+        .body(Body::Empty)
+        .expect("Failed to build the preflight response");
+    if let Ok(methods) = methods.parse() {
+        response
+            .headers_mut()
+            .insert(ACCESS_CONTROL_ALLOW_METHODS, methods);
+    }
+    response
+}
+
+/// Allows the origin to read the response.
+pub(crate) fn attach(mut response: Response<Body>, allowed_origin: HeaderValue) -> Response<Body> {
+    let headers = response.headers_mut();
+    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allowed_origin);
+    headers.insert(
+        ACCESS_CONTROL_EXPOSE_HEADERS,
+        HeaderValue::from_static(EXPOSED_HEADERS),
+    );
+    // Caches must not serve a response allowing one origin to another
+    headers.append(VARY, HeaderValue::from_static("Origin"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cors(origins: &[&str], methods: &[&str]) -> Cors {
+        Cors {
+            origins: origins.iter().map(|origin| origin.to_string()).collect(),
+            methods: methods.iter().map(|method| method.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_allowed_origin() {
+        let origin = HeaderValue::from_static("https://dash.example.com");
+        let dashboard = cors(&["https://Dash.example.com/"], &[]);
+        assert_eq!(allowed_origin(&dashboard, &origin), Some(origin.clone()));
+        let other = HeaderValue::from_static("https://evil.example.net");
+        assert_eq!(allowed_origin(&dashboard, &other), None);
+        assert_eq!(
+            allowed_origin(&cors(&["*"], &[]), &other),
+            Some(HeaderValue::from_static("*"))
+        );
+        assert_eq!(allowed_origin(&Cors::default(), &origin), None);
+    }
+
+    #[test]
+    fn test_preflight() {
+        let dashboard = cors(&["https://dash.example.com"], &[]);
+        let response = preflight(&dashboard, &[Method::GET, Method::POST]);
+        assert_eq!(response.status(), 204);
+        assert_eq!(
+            response.headers()[ACCESS_CONTROL_ALLOW_METHODS],
+            "GET, POST"
+        );
+
+        let response = preflight(&cors(&["*"], &["GET"]), &[Method::GET, Method::POST]);
+        assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_METHODS], "GET");
+    }
+
+    #[test]
+    fn test_attach() {
+        let origin = HeaderValue::from_static("https://dash.example.com");
+        let response = attach(Response::new(Body::Empty), origin.clone());
+        assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], origin);
+        assert_eq!(
+            response.headers()[ACCESS_CONTROL_EXPOSE_HEADERS],
+            "X-Request-Id"
+        );
+        assert_eq!(response.headers()[VARY], "Origin");
+    }
+}
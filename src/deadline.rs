@@ -0,0 +1,83 @@
+use lambda_http::{Request, RequestExt};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Porkbun calls aren't started or retried later than this before the Lambda timeout, so the
+/// records still report why they weren't updated
+const CALL_MARGIN: Duration = Duration::from_secs(1);
+/// Time left to log, audit the changes made so far and send the response of an aborted request
+const RESPONSE_MARGIN: Duration = Duration::from_millis(500);
+/// Audit entries not written this long before the Lambda timeout are given up on, so the
+/// response is still sent
+const AUDIT_MARGIN: Duration = Duration::from_millis(100);
+
+/// When the Lambda invocation is killed, taken from the Lambda context of the request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Deadline(Instant);
+
+impl Deadline {
+    /// The deadline of the invocation, `None` outside of Lambda (e.g. `serve`).
+    pub(crate) fn from_event(event: &Request) -> Option<Self> {
+        let context = event.lambda_context_ref()?;
+        // The runtime always sets the deadline, a default context has none
+        if context.deadline == 0 {
+            return None;
+        }
+        Some(Self::from_epoch_millis(context.deadline, SystemTime::now()))
+    }
+
+    fn from_epoch_millis(millis: u64, now: SystemTime) -> Self {
+        let remaining = (UNIX_EPOCH + Duration::from_millis(millis))
+            .duration_since(now)
+            .unwrap_or(Duration::ZERO);
+        Deadline(Instant::now() + remaining)
+    }
+
+    /// The time until the invocation is killed.
+    pub(crate) fn remaining(self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+
+    /// Until when Porkbun calls may be sent.
+    pub(crate) fn calls(self) -> Instant {
+        self.before(CALL_MARGIN)
+    }
+
+    /// When a request still running is aborted.
+    pub(crate) fn response(self) -> Instant {
+        self.before(RESPONSE_MARGIN)
+    }
+
+    /// Until when the audit log may be written.
+    #[cfg_attr(not(feature = "dynamodb"), allow(dead_code))]
+    pub(crate) fn audit(self) -> Instant {
+        self.before(AUDIT_MARGIN)
+    }
+
+    fn before(self, margin: Duration) -> Instant {
+        self.0.checked_sub(margin).unwrap_or_else(Instant::now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadline() {
+        let now = SystemTime::now();
+        let millis = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+
+        let deadline = Deadline::from_epoch_millis(millis(now + Duration::from_secs(30)), now);
+        assert!(deadline.remaining() > Duration::from_secs(29));
+        assert!(deadline.calls() < deadline.response());
+        assert!(deadline.response() < deadline.audit());
+        assert!(deadline.audit() < deadline.0);
+
+        // An invocation past its deadline has no time left
+        let deadline = Deadline::from_epoch_millis(millis(now - Duration::from_secs(1)), now);
+        assert_eq!(deadline.remaining(), Duration::ZERO);
+        assert!(deadline.calls() <= Instant::now());
+
+        assert_eq!(Deadline::from_event(&Request::default()), None);
+    }
+}
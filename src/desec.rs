@@ -0,0 +1,298 @@
+use crate::api::{DnsRecord, DEFAULT_TTL};
+use crate::domain::Domain;
+use crate::error::{ApiError, PorkbunError};
+use crate::ip_utils::RecordType;
+use crate::provider::{DnsProvider, RECORD_SET_TYPES};
+use crate::record::{parse_rdata, RecordUpdate};
+use lambda_http::tracing::{error, info};
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Environment variable with the deSEC API token
+pub const DESEC_TOKEN_ENV: &str = "DESEC_TOKEN";
+
+const API_BASE_URL: &str = "https://desec.io/api/v1";
+/// deSEC rejects record sets with a TTL below one hour
+const MIN_TTL: u64 = 3600;
+
+/// A record set as sent and returned by the deSEC RRset API.
+#[derive(Debug, Serialize, Deserialize)]
+struct RRset {
+    subname: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ttl: Option<u64>,
+    records: Vec<String>,
+}
+
+/// The body of a deSEC error response, validation errors don't have a `detail`.
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    detail: Option<String>,
+}
+
+/// deSEC.io, authenticated with an API token.
+/// deSEC manages record sets without ids, so a record is identified by its value.
+pub(crate) struct Desec<'a> {
+    client: &'a Client,
+    token: &'a str,
+}
+
+impl<'a> Desec<'a> {
+    pub fn new(client: &'a Client, token: &'a str) -> Self {
+        Self { client, token }
+    }
+
+    /// Sends a request, returning the body of a successful response.
+    async fn send(&self, request: RequestBuilder) -> Result<String, PorkbunError> {
+        let response = request
+            .header(AUTHORIZATION, format!("Token {}", self.token))
+            .send()
+            .await?;
+        let http_status = response.status();
+        let text = response.text().await?;
+        if http_status.is_success() {
+            return Ok(text);
+        }
+        let message = serde_json::from_str::<ErrorResponse>(&text)
+            .ok()
+            .and_then(|response| response.detail)
+            .unwrap_or(text);
+        Err(PorkbunError::from_response(http_status.as_u16(), message))
+    }
+
+    /// Retrieves the record set of the name and type.
+    async fn rrset(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<Option<RRset>, PorkbunError> {
+        let url = format!(
+            "{}/domains/{}/rrsets/{}/{}/",
+            API_BASE_URL,
+            domain.domain_name(),
+            subname_segment(domain),
+            record_type.as_str()
+        );
+        info!(url = %url, "Get existing record set");
+        match self.send(self.client.get(&url)).await {
+            Ok(text) => serde_json::from_str(&text)
+                .map(Some)
+                .map_err(|e| PorkbunError::MalformedResponse(e.to_string())),
+            // deSEC answers `404 Not Found` for names without records of the type
+            Err(PorkbunError::DomainNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replaces the record set of the name and type, an empty record set is deleted.
+    async fn patch(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+        ttl: u64,
+        records: Vec<String>,
+    ) -> Result<(), PorkbunError> {
+        if record_type == &RecordType::ALIAS {
+            return Err(PorkbunError::Unsupported(
+                "ALIAS records are not supported by deSEC".to_string(),
+            ));
+        }
+        let url = format!("{}/domains/{}/rrsets/", API_BASE_URL, domain.domain_name());
+        let rrset = RRset {
+            subname: domain.subdomain().to_string(),
+            record_type: record_type.as_str().to_string(),
+            ttl: (!records.is_empty()).then_some(ttl.max(MIN_TTL)),
+            records,
+        };
+        info!(rrset = ?rrset, url = %url, "Change deSEC record set");
+        // The bulk endpoint creates, updates or deletes the record set as needed
+        self.send(self.client.patch(&url).json(&[rrset])).await?;
+        Ok(())
+    }
+}
+
+/// The subname in a record set URL, `@` addresses the apex.
+fn subname_segment(domain: &Domain) -> &str {
+    if domain.is_apex() {
+        "@"
+    } else {
+        domain.subdomain()
+    }
+}
+
+impl DnsProvider for Desec<'_> {
+    async fn get_records(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<Vec<DnsRecord>, ApiError> {
+        let rrset = self
+            .rrset(domain, record_type)
+            .await
+            .inspect_err(|e| error!(error = %e, "Failed to retrieve DNS records"))
+            .map_err(ApiError::RetrieveRecordFailed)?;
+        let Some(rrset) = rrset else {
+            return Ok(Vec::new());
+        };
+        let ttl = rrset.ttl.map(|ttl| ttl.to_string());
+        Ok(rrset
+            .records
+            .into_iter()
+            .map(|value| {
+                let (content, prio) = parse_rdata(record_type, &value);
+                let mut record =
+                    DnsRecord::new(value, domain.qualified_name(), record_type, content);
+                record.prio = prio;
+                record.ttl = ttl.clone();
+                record
+            })
+            .collect())
+    }
+
+    /// Adds the value to the record set, which is created if it doesn't exist. The value is also
+    /// the id of the record.
+    async fn create_record(
+        &self,
+        domain: &Domain,
+        record: &RecordUpdate,
+    ) -> Result<Option<String>, ApiError> {
+        async {
+            let rrset = self.rrset(domain, &record.record_type).await?;
+            let ttl = record
+                .ttl
+                .or_else(|| rrset.as_ref().and_then(|rrset| rrset.ttl))
+                .unwrap_or(DEFAULT_TTL);
+            let mut records = rrset.map(|rrset| rrset.records).unwrap_or_default();
+            records.push(record.rdata());
+            self.patch(domain, &record.record_type, ttl, records)
+                .await
+                .map(|_| Some(record.rdata()))
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to create DNS record"))
+        .map_err(ApiError::CreateRecordFailed)
+    }
+
+    /// Replaces the value `record_id` of the record set.
+    async fn update_record(
+        &self,
+        domain: &Domain,
+        record_id: &str,
+        record: &RecordUpdate,
+    ) -> Result<(), ApiError> {
+        async {
+            let mut records = self
+                .rrset(domain, &record.record_type)
+                .await?
+                .map(|rrset| rrset.records)
+                .unwrap_or_default();
+            records.retain(|value| value != record_id);
+            records.push(record.rdata());
+            self.patch(
+                domain,
+                &record.record_type,
+                record.ttl.unwrap_or(DEFAULT_TTL),
+                records,
+            )
+            .await
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to update DNS record"))
+        .map_err(ApiError::UpdateRecordFailed)
+    }
+
+    /// Removes the value `record_id` from the record sets of the domain.
+    async fn delete_record(&self, domain: &Domain, record_id: &str) -> Result<(), ApiError> {
+        async {
+            // Record ids don't carry the type, so all types of the name are searched
+            for record_type in RECORD_SET_TYPES {
+                let Some(rrset) = self.rrset(domain, &record_type).await? else {
+                    continue;
+                };
+                if !rrset.records.iter().any(|value| value == record_id) {
+                    continue;
+                }
+                let mut records = rrset.records;
+                records.retain(|value| value != record_id);
+                let ttl = rrset.ttl.unwrap_or(DEFAULT_TTL);
+                return self.patch(domain, &record_type, ttl, records).await;
+            }
+            Err(PorkbunError::Failed(format!(
+                "Record {} of {} not found",
+                record_id,
+                domain.qualified_name()
+            )))
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to delete DNS record"))
+        .map_err(ApiError::DeleteRecordFailed)
+    }
+
+    /// deSEC replaces a whole record set with a single call.
+    async fn update_records(&self, domain: &Domain, record: &RecordUpdate) -> Result<(), ApiError> {
+        self.patch(
+            domain,
+            &record.record_type,
+            record.ttl.unwrap_or(DEFAULT_TTL),
+            vec![record.rdata()],
+        )
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to update DNS record"))
+        .map_err(ApiError::UpdateRecordFailed)
+    }
+
+    async fn delete_records(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<(), ApiError> {
+        self.patch(domain, record_type, DEFAULT_TTL, Vec::new())
+            .await
+            .inspect_err(|e| error!(error = %e, "Failed to delete DNS records"))
+            .map_err(ApiError::DeleteRecordFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rrset_serialization() {
+        let rrset = RRset {
+            subname: "home".to_string(),
+            record_type: "A".to_string(),
+            ttl: None,
+            records: Vec::new(),
+        };
+        assert_eq!(
+            serde_json::to_value(&rrset).unwrap(),
+            serde_json::json!({"subname": "home", "type": "A", "records": []})
+        );
+        let rrset: RRset = serde_json::from_value(serde_json::json!({
+            "created": "2024-01-01T00:00:00Z",
+            "domain": "example.dedyn.io",
+            "subname": "",
+            "name": "example.dedyn.io.",
+            "type": "MX",
+            "records": ["10 mail.example.org."],
+            "ttl": 3600,
+            "touched": "2024-01-01T00:00:00Z"
+        }))
+        .unwrap();
+        assert_eq!(rrset.ttl, Some(3600));
+        assert_eq!(rrset.records, vec!["10 mail.example.org."]);
+    }
+
+    #[test]
+    fn test_subname_segment() {
+        assert_eq!(subname_segment(&Domain::new("example.org").unwrap()), "@");
+        assert_eq!(
+            subname_segment(&Domain::new("home.example.org").unwrap()),
+            "home"
+        );
+    }
+}
@@ -0,0 +1,410 @@
+use crate::credentials::Credentials;
+use crate::domain::Domain;
+use crate::front_door::source_ip;
+use crate::http_handler::UpdateAction;
+use crate::ip_utils::{validate_and_classify_ip, IpType};
+use lambda_http::aws_lambda_events::query_map::QueryMap;
+use lambda_http::http::header::AUTHORIZATION;
+use lambda_http::Request;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// Path of the de-facto DynDNS2 update protocol used by routers and clients like ddclient
+pub(crate) const NIC_UPDATE_PATH: &str = "/nic/update";
+
+/// Path of the DynDNS-style IP check, e.g. used as `checkip-server` by inadyn
+pub(crate) const CHECKIP_PATH: &str = "/checkip";
+
+/// DynDNS2 options porkDyn doesn't support, sent by ddclient and UniFi gateways (e.g.
+/// `system=dyndns&wildcard=NOCHG&backmx=NO`)
+const IGNORED_PARAMETERS: [&str; 3] = ["system", "wildcard", "backmx"];
+
+/// Parameters carrying addresses, which clients send as placeholders if they have none. Other
+/// values like a password may look like a placeholder, e.g. `<secret>`, and are kept.
+const ADDRESS_PARAMETERS: [&str; 3] = ["myip", "ip", "ipv6"];
+
+/// The body of a DynDNS-style IP check, clients like inadyn and ddclient parse the first
+/// IP address out of it.
+pub(crate) fn checkip_body(ip: &str) -> String {
+    format!(
+        "<html><head><title>Current IP Check</title></head><body>Current IP Address: {}</body></html>",
+        ip
+    )
+}
+
+/// Translates the DynDNS2 parameters into the regular query-parameters.
+/// - `myip` may contain an IPv4 and/or IPv6 address (comma-separated), if it is missing the
+///   address the request was sent from is used
+/// - `myip=delete` removes the A and AAAA records of the host name (`action=delete`), e.g. when
+///   a device is decommissioned
+/// - `offline=YES` points the records at the parking addresses instead of `myip`, or removes
+///   them if none are configured; the next update without it restores them
+/// - `hostname` is already accepted as alias of `domain`
+/// - Empty or unsubstituted address placeholders (e.g. Synology's `__MYIP__` without an address)
+///   are ignored
+/// - Unsupported DynDNS2 options are ignored, just like `mx=NOCHG` (keep the MX record as it is)
+/// - `username` and `password` are accepted instead of Basic Auth, for clients which can only
+///   put the credentials into the URL (e.g. inadyn's `custom` provider with `%u` and `%p`)
+/// - With server-side credentials, the password is used as access token, since DynDNS2
+///   clients can only be configured with a username and password
+pub(crate) fn translate_parameters(
+    event: &Request,
+    query_params: &QueryMap,
+    server_credentials: bool,
+    parking_ips: &[IpAddr],
+) -> QueryMap {
+    let mut parameters: HashMap<String, String> = query_params
+        .iter()
+        .filter(|(name, value)| !(ADDRESS_PARAMETERS.contains(name) && is_placeholder(value)))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    for name in IGNORED_PARAMETERS {
+        parameters.remove(name);
+    }
+    if parameters
+        .get("mx")
+        .is_some_and(|mx| mx.eq_ignore_ascii_case("NOCHG"))
+    {
+        parameters.remove("mx");
+    }
+
+    let offline = parameters
+        .remove("offline")
+        .is_some_and(|offline| offline.trim().eq_ignore_ascii_case("YES"));
+    let myip = match parameters.remove("myip") {
+        _ if offline && !parking_ips.is_empty() => Some(
+            parking_ips
+                .iter()
+                .map(IpAddr::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+        Some(myip) if !offline && !myip.trim().eq_ignore_ascii_case("delete") => Some(myip),
+        None if !offline => source_ip(event),
+        _ => {
+            parameters.insert("action".to_string(), "delete".to_string());
+            None
+        }
+    };
+    if let Some(myip) = myip {
+        for address in myip
+            .split(',')
+            .map(str::trim)
+            .filter(|address| !address.is_empty())
+        {
+            // Invalid addresses end up in `ip`, so they are rejected by the regular validation
+            let name = match validate_and_classify_ip(address) {
+                Ok(IpType::V6) => "ipv6",
+                _ => "ip",
+            };
+            parameters
+                .entry(name.to_string())
+                .or_insert_with(|| address.to_string());
+        }
+    }
+
+    let username = parameters.remove("username");
+    let password = parameters.remove("password");
+    if server_credentials {
+        let basic_auth_password = event
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Credentials::from_basic_auth)
+            .map(|basic_auth| basic_auth.secret_key().to_string());
+        if let Some(token) = basic_auth_password.or(password) {
+            parameters.entry("token".to_string()).or_insert(token);
+        }
+    } else if let (Some(username), Some(password)) = (username, password) {
+        parameters.entry("apikey".to_string()).or_insert(username);
+        parameters
+            .entry("secretapikey".to_string())
+            .or_insert(password);
+    }
+
+    QueryMap::from(parameters)
+}
+
+/// Whether a parameter value was left empty or as unsubstituted placeholder by the client,
+/// e.g. `__MYIP__` (Synology DSM), `<ipaddr>` (FRITZ!Box), `%i` (inadyn) or `%IP%` (pfSense).
+fn is_placeholder(value: &str) -> bool {
+    let value = value.trim();
+    value.is_empty()
+        || (value.len() > 4 && value.starts_with("__") && value.ends_with("__"))
+        || (value.starts_with('<') && value.ends_with('>'))
+        || (value.len() == 2 && value.starts_with('%'))
+        || (value.len() > 2 && value.starts_with('%') && value.ends_with('%'))
+}
+
+/// Whether the request contains at least one host name and all of them are valid.
+pub(crate) fn has_valid_hostnames(query_params: &QueryMap) -> bool {
+    let Some(hostnames) = query_params
+        .first("domain")
+        .or_else(|| query_params.first("hostname"))
+    else {
+        return false;
+    };
+    let hostnames = hostnames
+        .split(',')
+        .map(str::trim)
+        .filter(|hostname| !hostname.is_empty())
+        .collect::<Vec<_>>();
+    !hostnames.is_empty()
+        && hostnames
+            .iter()
+            .all(|hostname| Domain::new(hostname).is_ok())
+}
+
+/// The DynDNS2 return code of a single record, e.g. `good 1.2.3.4` or `nochg 1.2.3.4`.
+pub(crate) fn return_code(action: UpdateAction, status_code: u16, content: &str) -> String {
+    match action {
+        UpdateAction::Created | UpdateAction::Updated => format!("good {}", content),
+        UpdateAction::Unchanged => format!("nochg {}", content),
+        UpdateAction::Deleted => "good".to_string(),
+        UpdateAction::Failed => error_code(status_code).to_string(),
+    }
+}
+
+/// The DynDNS2 return code of a failed request or record:
+/// - `badauth`: Invalid credentials or access token
+/// - `nohost`: The host name may not be updated or is not part of the account
+/// - `911`: Any other (usually upstream) error, clients retry later
+pub(crate) fn error_code(status_code: u16) -> &'static str {
+    match status_code {
+        401 => "badauth",
+        403 | 404 => "nohost",
+        _ => "911",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lambda_http::RequestExt;
+
+    fn request(parameters: &[(&str, &str)]) -> Request {
+        let query_string_parameters: HashMap<String, String> = parameters
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Request::default().with_query_string_parameters(query_string_parameters)
+    }
+
+    #[test]
+    fn test_translate_url_credentials() {
+        let event = request(&[
+            ("hostname", "home.example.org"),
+            ("username", "pk1_key"),
+            ("password", "sk1_secret"),
+        ]);
+        let translated = translate_parameters(&event, &event.query_string_parameters(), false, &[]);
+        assert_eq!(translated.first("apikey"), Some("pk1_key"));
+        assert_eq!(translated.first("secretapikey"), Some("sk1_secret"));
+        assert_eq!(translated.first("password"), None);
+
+        let translated = translate_parameters(&event, &event.query_string_parameters(), true, &[]);
+        assert_eq!(translated.first("token"), Some("sk1_secret"));
+        assert_eq!(translated.first("secretapikey"), None);
+    }
+
+    #[test]
+    fn test_translate_synology_query() {
+        let mut event = request(&[
+            ("hostname", "nas.example.org"),
+            ("myip", "__MYIP__"),
+            ("username", "pk1_key"),
+            ("password", "sk1_secret"),
+        ]);
+        event
+            .headers_mut()
+            .insert("X-Forwarded-For", "203.0.113.7".parse().unwrap());
+        let translated = translate_parameters(&event, &event.query_string_parameters(), false, &[]);
+        assert_eq!(translated.first("ip"), Some("203.0.113.7"));
+        assert_eq!(translated.first("myip"), None);
+        assert_eq!(translated.first("apikey"), Some("pk1_key"));
+    }
+
+    #[test]
+    fn test_translate_keeps_placeholder_like_credentials() {
+        let event = request(&[
+            ("hostname", "nas.example.org"),
+            ("myip", "__MYIP__"),
+            ("username", "__pk1_key__"),
+            ("password", "<secret>"),
+        ]);
+        let translated = translate_parameters(&event, &event.query_string_parameters(), false, &[]);
+        assert_eq!(translated.first("ip"), None);
+        assert_eq!(translated.first("apikey"), Some("__pk1_key__"));
+        assert_eq!(translated.first("secretapikey"), Some("<secret>"));
+
+        let translated = translate_parameters(&event, &event.query_string_parameters(), true, &[]);
+        assert_eq!(translated.first("token"), Some("<secret>"));
+    }
+
+    #[test]
+    fn test_translate_pfsense_ipv6_request() {
+        let mut event = request(&[("hostname", "fw.example.org"), ("myip", "2001:db8::1")]);
+        event.headers_mut().insert(
+            AUTHORIZATION,
+            "Basic cG9ya0R5bjpzZWNyZXQ=".parse().unwrap(), // porkDyn:secret
+        );
+        let translated = translate_parameters(&event, &event.query_string_parameters(), true, &[]);
+        assert_eq!(translated.first("ip"), None);
+        assert_eq!(translated.first("ipv6"), Some("2001:db8::1"));
+        assert_eq!(translated.first("token"), Some("secret"));
+    }
+
+    #[test]
+    fn test_translate_unifi_request() {
+        let event = request(&[
+            ("system", "dyndns"),
+            ("hostname", "gw.example.org"),
+            ("myip", "1.2.3.4"),
+            ("wildcard", "NOCHG"),
+            ("mx", "NOCHG"),
+            ("backmx", "NOCHG"),
+        ]);
+        let translated = translate_parameters(&event, &event.query_string_parameters(), false, &[]);
+        assert_eq!(translated.first("ip"), Some("1.2.3.4"));
+        assert_eq!(translated.first("mx"), None);
+        assert_eq!(translated.first("wildcard"), None);
+        assert_eq!(translated.first("system"), None);
+
+        let event = request(&[("hostname", "gw.example.org"), ("mx", "mail.example.org")]);
+        let translated = translate_parameters(&event, &event.query_string_parameters(), false, &[]);
+        assert_eq!(translated.first("mx"), Some("mail.example.org"));
+    }
+
+    #[test]
+    fn test_is_placeholder() {
+        assert!(is_placeholder(""));
+        assert!(is_placeholder("__MYIP__"));
+        assert!(is_placeholder("<ip6addr>"));
+        assert!(is_placeholder("%i"));
+        assert!(is_placeholder("%IP%"));
+        assert!(!is_placeholder("1.2.3.4"));
+        assert!(!is_placeholder("_acme-challenge.example.org"));
+    }
+
+    #[test]
+    fn test_checkip_body() {
+        assert!(checkip_body("1.2.3.4").contains("Current IP Address: 1.2.3.4"));
+    }
+
+    #[test]
+    fn test_has_valid_hostnames() {
+        let valid = request(&[("hostname", "home.example.org, vpn.example.org")]);
+        assert!(has_valid_hostnames(&valid.query_string_parameters()));
+        let invalid = request(&[("hostname", "home.example.org,invalid@example.org")]);
+        assert!(!has_valid_hostnames(&invalid.query_string_parameters()));
+        assert!(!has_valid_hostnames(
+            &request(&[("hostname", " ")]).query_string_parameters()
+        ));
+        assert!(!has_valid_hostnames(
+            &request(&[]).query_string_parameters()
+        ));
+    }
+
+    #[test]
+    fn test_return_codes() {
+        assert_eq!(
+            return_code(UpdateAction::Updated, 200, "1.2.3.4"),
+            "good 1.2.3.4"
+        );
+        assert_eq!(
+            return_code(UpdateAction::Unchanged, 200, "::1"),
+            "nochg ::1"
+        );
+        assert_eq!(return_code(UpdateAction::Failed, 403, "1.2.3.4"), "nohost");
+        assert_eq!(error_code(401), "badauth");
+        assert_eq!(error_code(502), "911");
+    }
+
+    #[test]
+    fn test_translate_myip() {
+        let event = request(&[
+            ("hostname", "home.example.org"),
+            ("myip", "1.2.3.4, 2001:db8::1"),
+        ]);
+        let translated = translate_parameters(&event, &event.query_string_parameters(), false, &[]);
+        assert_eq!(translated.first("hostname"), Some("home.example.org"));
+        assert_eq!(translated.first("ip"), Some("1.2.3.4"));
+        assert_eq!(translated.first("ipv6"), Some("2001:db8::1"));
+    }
+
+    #[test]
+    fn test_translate_myip_delete() {
+        let mut event = request(&[("hostname", "old.example.org"), ("myip", "DELETE")]);
+        event
+            .headers_mut()
+            .insert("X-Forwarded-For", "2001:db8::2".parse().unwrap());
+        let translated = translate_parameters(&event, &event.query_string_parameters(), false, &[]);
+        assert_eq!(translated.first("action"), Some("delete"));
+        assert_eq!(translated.first("myip"), None);
+        assert_eq!(translated.first("ip"), None);
+        assert_eq!(translated.first("ipv6"), None);
+    }
+
+    #[test]
+    fn test_translate_offline() {
+        let event = request(&[
+            ("hostname", "home.example.org"),
+            ("myip", "1.2.3.4"),
+            ("offline", "YES"),
+        ]);
+        let parking_ips = ["203.0.113.1".parse().unwrap()];
+        let translated = translate_parameters(
+            &event,
+            &event.query_string_parameters(),
+            false,
+            &parking_ips,
+        );
+        assert_eq!(translated.first("ip"), Some("203.0.113.1"));
+        assert_eq!(translated.first("offline"), None);
+        assert_eq!(translated.first("action"), None);
+
+        let translated = translate_parameters(&event, &event.query_string_parameters(), false, &[]);
+        assert_eq!(translated.first("action"), Some("delete"));
+        assert_eq!(translated.first("ip"), None);
+
+        // Back online, the records are restored with the regular update
+        let event = request(&[
+            ("hostname", "home.example.org"),
+            ("myip", "1.2.3.4"),
+            ("offline", "NO"),
+        ]);
+        let translated = translate_parameters(
+            &event,
+            &event.query_string_parameters(),
+            false,
+            &parking_ips,
+        );
+        assert_eq!(translated.first("ip"), Some("1.2.3.4"));
+        assert_eq!(translated.first("action"), None);
+    }
+
+    #[test]
+    fn test_translate_without_myip_uses_source_ip() {
+        let mut event = request(&[("hostname", "home.example.org")]);
+        event
+            .headers_mut()
+            .insert("X-Forwarded-For", "2001:db8::2".parse().unwrap());
+        let translated = translate_parameters(&event, &event.query_string_parameters(), false, &[]);
+        assert_eq!(translated.first("ip"), None);
+        assert_eq!(translated.first("ipv6"), Some("2001:db8::2"));
+    }
+
+    #[test]
+    fn test_translate_basic_auth_password_to_token() {
+        let mut event = request(&[("hostname", "home.example.org"), ("myip", "1.2.3.4")]);
+        event.headers_mut().insert(
+            AUTHORIZATION,
+            "Basic cG9ya0R5bjpzZWNyZXQ=".parse().unwrap(), // porkDyn:secret
+        );
+        let translated = translate_parameters(&event, &event.query_string_parameters(), true, &[]);
+        assert_eq!(translated.first("token"), Some("secret"));
+
+        let translated = translate_parameters(&event, &event.query_string_parameters(), false, &[]);
+        assert_eq!(translated.first("token"), None);
+    }
+}
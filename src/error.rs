@@ -1,22 +1,38 @@
+pub use porkdyn_core::error::{ApiError, DomainError, PorkbunError};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
-pub enum DomainError {
-    #[error("Domain validation error: {0}")]
-    DomainValidationError(String),
-}
+pub enum CredentialsError {
+    #[error("Failed to load secret from Secrets Manager: {0}")]
+    SecretsManager(String),
 
-#[derive(Error, Debug)]
-pub enum ApiError {
-    #[error("Failed to create DNS record: {0}")]
-    CreateRecordFailed(String),
+    #[error("Failed to load parameters from SSM Parameter Store: {0}")]
+    ParameterStore(String),
+
+    #[error("Failed to decrypt credentials with KMS: {0}")]
+    Kms(String),
+
+    #[error("Invalid credentials secret: {0}")]
+    #[cfg_attr(
+        not(any(
+            feature = "cli",
+            feature = "kms",
+            feature = "parameter-store",
+            feature = "secrets-manager"
+        )),
+        allow(dead_code)
+    )]
+    InvalidSecret(String),
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
 
-    #[error("Failed to update DNS record: {0}")]
-    UpdateRecordFailed(String),
+    #[error("Invalid token-to-domain mapping: {0}")]
+    InvalidTokenDomains(String),
 
-    #[error("Failed to retrieve DNS record: {0}")]
-    RetrieveRecordFailed(String),
+    #[error("Invalid acme-dns configuration: {0}")]
+    InvalidAcmeDns(String),
 
-    #[error("Network error: {0}")]
-    NetworkError(#[from] reqwest::Error),
+    #[error("Invalid DNS provider: {0}")]
+    InvalidProvider(String),
 }
@@ -0,0 +1,42 @@
+use crate::http_handler::function_handler;
+use crate::scheduled::{is_scheduled_event, scheduled_update};
+use crate::sqs::{is_sqs_event, sqs_handler};
+use crate::state::AppState;
+#[cfg(feature = "opentelemetry")]
+use crate::telemetry;
+use lambda_http::lambda_runtime::LambdaEvent;
+use lambda_http::request::LambdaRequest;
+use lambda_http::tower::Service;
+use lambda_http::tracing::info;
+use lambda_http::{service_fn, Adapter, Error};
+use serde_json::Value;
+
+/// Entry point of the Lambda function: HTTP requests (Function URL, API Gateway) are passed
+/// to the HTTP handler, events of an EventBridge schedule trigger a scheduled update and
+/// SQS messages are processed as queued update requests.
+pub(crate) async fn event_handler(
+    event: LambdaEvent<Value>,
+    state: &AppState,
+) -> Result<Value, Error> {
+    let result = handle_event(event, state).await;
+    // Lambda freezes the process after the response, so the spans are exported before
+    #[cfg(feature = "opentelemetry")]
+    telemetry::flush();
+    result
+}
+
+async fn handle_event(event: LambdaEvent<Value>, state: &AppState) -> Result<Value, Error> {
+    let LambdaEvent { payload, context } = event;
+    if is_scheduled_event(&payload) {
+        info!("Invoked by EventBridge schedule");
+        return scheduled_update(state).await;
+    }
+    if is_sqs_event(&payload) {
+        return sqs_handler(payload, state).await;
+    }
+    let request: LambdaRequest = serde_json::from_value(payload)?;
+    // The adapter `lambda_http::run` uses, which answers in the format of the event's origin
+    let mut adapter = Adapter::from(service_fn(|request| function_handler(request, state)));
+    let response = adapter.call(LambdaEvent::new(request, context)).await?;
+    Ok(serde_json::to_value(response)?)
+}
@@ -0,0 +1,210 @@
+use crate::metrics::Outcomes;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use lambda_http::tracing::{error, info};
+use lambda_http::Error;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// Environment variable with the address the Prometheus metrics are served on, e.g.
+/// `127.0.0.1:9464`, no metrics are served without
+pub const METRICS_ADDRESS_ENV: &str = "PORKDYN_METRICS_ADDRESS";
+const METRICS_PATH: &str = "/metrics";
+/// Provider calls take from tens of milliseconds up to the request timeout with retries
+const DURATION_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 40.0];
+
+/// The metrics of the process, registered once and shared by all requests.
+struct Collectors {
+    registry: Registry,
+    record_updates: IntCounterVec,
+    provider_duration: HistogramVec,
+    porkbun_call_duration: HistogramVec,
+    update_failures: IntCounterVec,
+    ip_lookup_failures: IntCounterVec,
+}
+
+impl Collectors {
+    fn new() -> Self {
+        let record_updates = IntCounterVec::new(
+            Opts::new(
+                "porkdyn_record_updates_total",
+                "Processed records by outcome",
+            ),
+            &["action"],
+        )
+        .expect("valid metric");
+        let provider_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "porkdyn_provider_duration_seconds",
+                "Time the DNS provider took to process a record, including retries",
+            )
+            .buckets(DURATION_BUCKETS.to_vec()),
+            &["provider"],
+        )
+        .expect("valid metric");
+        let porkbun_call_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "porkdyn_porkbun_call_duration_seconds",
+                "Time a single call to the Porkbun API took by endpoint",
+            )
+            .buckets(DURATION_BUCKETS.to_vec()),
+            &["endpoint"],
+        )
+        .expect("valid metric");
+        let update_failures = IntCounterVec::new(
+            Opts::new(
+                "porkdyn_update_failures_total",
+                "Failed update requests by reason",
+            ),
+            &["reason"],
+        )
+        .expect("valid metric");
+        let ip_lookup_failures = IntCounterVec::new(
+            Opts::new(
+                "porkdyn_ip_lookup_failures_total",
+                "Failed lookups of the public IP by source",
+            ),
+            &["source"],
+        )
+        .expect("valid metric");
+        let registry = Registry::new();
+        registry
+            .register(Box::new(record_updates.clone()))
+            .expect("unique metric");
+        registry
+            .register(Box::new(provider_duration.clone()))
+            .expect("unique metric");
+        registry
+            .register(Box::new(porkbun_call_duration.clone()))
+            .expect("unique metric");
+        registry
+            .register(Box::new(update_failures.clone()))
+            .expect("unique metric");
+        registry
+            .register(Box::new(ip_lookup_failures.clone()))
+            .expect("unique metric");
+        Collectors {
+            registry,
+            record_updates,
+            provider_duration,
+            porkbun_call_duration,
+            update_failures,
+            ip_lookup_failures,
+        }
+    }
+}
+
+static COLLECTORS: LazyLock<Collectors> = LazyLock::new(Collectors::new);
+
+/// Counts the outcomes of the records of a request.
+pub(crate) fn record_outcomes(outcomes: &Outcomes) {
+    let counts = [
+        ("updated", outcomes.updated),
+        ("created", outcomes.created),
+        ("unchanged", outcomes.unchanged),
+        ("deleted", outcomes.deleted),
+        ("failed", outcomes.failed),
+    ];
+    for (action, count) in counts {
+        COLLECTORS
+            .record_updates
+            .with_label_values(&[action])
+            .inc_by(count.into());
+    }
+}
+
+/// Records how long the provider took to process a record.
+pub(crate) fn observe_provider_duration(provider: &str, duration: Duration) {
+    COLLECTORS
+        .provider_duration
+        .with_label_values(&[provider])
+        .observe(duration.as_secs_f64());
+}
+
+/// Records how long a call to a Porkbun endpoint took.
+pub(crate) fn observe_porkbun_call(endpoint: &str, latency: Duration) {
+    COLLECTORS
+        .porkbun_call_duration
+        .with_label_values(&[endpoint])
+        .observe(latency.as_secs_f64());
+}
+
+/// Counts a failed update request.
+pub(crate) fn count_update_failure(reason: &str) {
+    COLLECTORS
+        .update_failures
+        .with_label_values(&[reason])
+        .inc();
+}
+
+/// Counts a source which failed to report the public IP.
+pub(crate) fn count_ip_lookup_failure(source: &str) {
+    COLLECTORS
+        .ip_lookup_failures
+        .with_label_values(&[source])
+        .inc();
+}
+
+/// The metrics in the Prometheus text format.
+fn render() -> String {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&COLLECTORS.registry.gather(), &mut buffer)
+        .expect("metrics are encodable");
+    String::from_utf8(buffer).expect("metrics are UTF-8")
+}
+
+/// Serves the metrics at `/metrics` on the address of `PORKDYN_METRICS_ADDRESS` in the background,
+/// if set. It's a separate listener, so the metrics aren't exposed with the update endpoints.
+pub async fn spawn_from_env() -> Result<(), Error> {
+    let Ok(address) = std::env::var(METRICS_ADDRESS_ENV) else {
+        return Ok(());
+    };
+    let listener = tokio::net::TcpListener::bind(&address).await?;
+    info!(address = %address, path = METRICS_PATH, "Serving Prometheus metrics");
+    let router = Router::new().route(METRICS_PATH, get(metrics_response));
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            error!(error = %e, "Prometheus metrics listener failed");
+        }
+    });
+    Ok(())
+}
+
+async fn metrics_response() -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, TextEncoder::new().format_type().to_string())],
+        render(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        record_outcomes(&Outcomes {
+            updated: 2,
+            ..Outcomes::default()
+        });
+        observe_provider_duration("porkbun", Duration::from_millis(300));
+        count_ip_lookup_failure("test-source");
+        observe_porkbun_call("retrieve", Duration::from_millis(80));
+        count_update_failure("test-reason");
+        let metrics = render();
+        assert!(metrics.contains("porkdyn_record_updates_total{action=\"updated\"}"));
+        assert!(metrics
+            .contains("porkdyn_provider_duration_seconds_bucket{provider=\"porkbun\",le=\"0.5\"}"));
+        assert!(metrics.contains("porkdyn_ip_lookup_failures_total{source=\"test-source\"} 1"));
+        assert!(metrics.contains("porkdyn_update_failures_total{reason=\"test-reason\"} 1"));
+        assert!(metrics.contains(
+            "porkdyn_porkbun_call_duration_seconds_bucket{endpoint=\"retrieve\",le=\"0.1\"}"
+        ));
+    }
+}
@@ -0,0 +1,102 @@
+use lambda_http::aws_lambda_events::query_map::QueryMap;
+use std::collections::HashMap;
+
+/// Path of the FRITZ!Box compatibility mode
+pub(crate) const FRITZBOX_PATH: &str = "/fritzbox";
+
+/// Query-parameters named after the FRITZ!Box update URL placeholders and the regular
+/// query-parameter each of them replaces
+const ALIASES: [(&str, &str); 6] = [
+    ("username", "apikey"),
+    ("passwd", "secretapikey"),
+    ("pass", "secretapikey"),
+    ("ipaddr", "ip"),
+    ("ip6addr", "ipv6"),
+    ("ip6lanprefix", "ipv6prefix"),
+];
+
+/// Translates the FRITZ!Box placeholder parameters into the regular query-parameters, e.g.
+/// `?domain=<domain>&ipaddr=<ipaddr>&ip6addr=<ip6addr>&username=<username>&passwd=<passwd>`.
+/// - The FRITZ!Box leaves a placeholder empty if it has no such address (e.g. no IPv6
+///   connectivity), so empty parameters are ignored instead of being rejected as invalid
+/// - With server-side credentials, the password is used as access token, since the
+///   FRITZ!Box can only be configured with a username and password
+/// - Regular query-parameters take precedence over the aliases
+pub(crate) fn translate_parameters(query_params: &QueryMap, server_credentials: bool) -> QueryMap {
+    let mut parameters: HashMap<String, String> = query_params
+        .iter()
+        .filter(|(_, value)| !value.trim().is_empty())
+        .map(|(name, value)| (name.to_string(), value.trim().to_string()))
+        .collect();
+
+    for (alias, name) in ALIASES {
+        let Some(value) = parameters.remove(alias) else {
+            continue;
+        };
+        let name = match name {
+            "secretapikey" if server_credentials => "token",
+            name => name,
+        };
+        parameters.entry(name.to_string()).or_insert(value);
+    }
+
+    QueryMap::from(parameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query_map(parameters: &[(&str, &str)]) -> QueryMap {
+        QueryMap::from(
+            parameters
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    #[test]
+    fn test_translate_dual_stack() {
+        let translated = translate_parameters(
+            &query_map(&[
+                ("domain", "home.example.org"),
+                ("ipaddr", "1.2.3.4"),
+                ("ip6addr", "2001:db8::1"),
+                ("username", "pk1_key"),
+                ("passwd", "sk1_secret"),
+            ]),
+            false,
+        );
+        assert_eq!(translated.first("domain"), Some("home.example.org"));
+        assert_eq!(translated.first("ip"), Some("1.2.3.4"));
+        assert_eq!(translated.first("ipv6"), Some("2001:db8::1"));
+        assert_eq!(translated.first("apikey"), Some("pk1_key"));
+        assert_eq!(translated.first("secretapikey"), Some("sk1_secret"));
+        assert_eq!(translated.first("ipaddr"), None);
+    }
+
+    #[test]
+    fn test_translate_empty_placeholders() {
+        let translated = translate_parameters(
+            &query_map(&[
+                ("domain", "home.example.org"),
+                ("ipaddr", "1.2.3.4"),
+                ("ip6addr", ""),
+            ]),
+            false,
+        );
+        assert_eq!(translated.first("ip"), Some("1.2.3.4"));
+        assert_eq!(translated.first("ipv6"), None);
+    }
+
+    #[test]
+    fn test_translate_password_to_token() {
+        let translated = translate_parameters(
+            &query_map(&[("username", "router"), ("passwd", "router-token")]),
+            true,
+        );
+        assert_eq!(translated.first("token"), Some("router-token"));
+        assert_eq!(translated.first("secretapikey"), None);
+    }
+}
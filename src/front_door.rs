@@ -0,0 +1,147 @@
+use lambda_http::aws_lambda_events::query_map::QueryMap;
+use lambda_http::request::RequestContext;
+use lambda_http::{Request, RequestExt};
+use std::net::IpAddr;
+
+/// The service a request reached the function through. Their events differ in where the
+/// caller's address, the stage and the query-parameters are found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FrontDoor {
+    /// API Gateway REST API (payload format 1.0)
+    RestApi,
+    /// API Gateway HTTP API (payload format 2.0)
+    HttpApi,
+    /// Lambda Function URL, which sends the payload format 2.0 as well
+    FunctionUrl,
+    /// Application Load Balancer with the function as target
+    Alb,
+    /// Not a Lambda HTTP event, e.g. the standalone server, the CLI or SQS messages
+    Direct,
+}
+
+impl FrontDoor {
+    pub(crate) fn of(event: &Request) -> Self {
+        match event.request_context_ref() {
+            Some(RequestContext::ApiGatewayV1(_)) => FrontDoor::RestApi,
+            Some(RequestContext::ApiGatewayV2(context))
+                if context
+                    .domain_name
+                    .as_deref()
+                    .is_some_and(|domain| domain.contains(".lambda-url.")) =>
+            {
+                FrontDoor::FunctionUrl
+            }
+            Some(RequestContext::ApiGatewayV2(_)) => FrontDoor::HttpApi,
+            Some(RequestContext::Alb(_)) => FrontDoor::Alb,
+            _ => FrontDoor::Direct,
+        }
+    }
+}
+
+/// The query-parameters of the request. The payload format 2.0 joins repeated parameters with
+/// commas and splits them apart again when decoded, which would also split a comma-separated
+/// `domain` list, so its raw query string is parsed instead.
+pub(crate) fn query_parameters(event: &Request) -> QueryMap {
+    match FrontDoor::of(event) {
+        FrontDoor::HttpApi | FrontDoor::FunctionUrl => event
+            .uri()
+            .query()
+            .and_then(|query| query.parse().ok())
+            .unwrap_or_else(|| event.query_string_parameters()),
+        FrontDoor::RestApi | FrontDoor::Alb | FrontDoor::Direct => event.query_string_parameters(),
+    }
+}
+
+/// The IP address of the caller. API Gateway and Function URLs report it in the request context,
+/// otherwise it is the last `X-Forwarded-For` entry, the one appended by the load balancer or the
+/// reverse proxy (earlier entries are sent by the caller and can't be trusted), or set by the
+/// standalone server itself. An entry which isn't an IP address is ignored.
+pub(crate) fn source_ip(event: &Request) -> Option<String> {
+    let from_context = match event.request_context_ref() {
+        Some(RequestContext::ApiGatewayV2(context)) => context.http.source_ip.clone(),
+        Some(RequestContext::ApiGatewayV1(context)) => context.identity.source_ip.clone(),
+        _ => None,
+    };
+    from_context.or_else(|| {
+        let forwarded_for = event
+            .headers()
+            .get("X-Forwarded-For")
+            .and_then(|value| value.to_str().ok())?;
+        let ip: IpAddr = forwarded_for.rsplit(',').next()?.trim().parse().ok()?;
+        Some(ip.to_string())
+    })
+}
+
+/// The API Gateway stage the request was sent to, `None` for the `$default` stage, which isn't
+/// part of the path.
+pub(crate) fn stage(event: &Request) -> Option<&str> {
+    let stage = match event.request_context_ref()? {
+        RequestContext::ApiGatewayV2(context) => context.stage.as_deref(),
+        RequestContext::ApiGatewayV1(context) => context.stage.as_deref(),
+        _ => None,
+    };
+    stage.filter(|stage| *stage != "$default")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Recorded events of the front doors, all for the same DynDNS2 update of two hosts
+    fn fixture(name: &str) -> Request {
+        let json = match name {
+            "apigw_rest" => include_str!("../tests/fixtures/apigw_rest.json"),
+            "apigw_http" => include_str!("../tests/fixtures/apigw_http.json"),
+            "function_url" => include_str!("../tests/fixtures/function_url.json"),
+            "alb" => include_str!("../tests/fixtures/alb.json"),
+            _ => unreachable!(),
+        };
+        lambda_http::request::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_front_doors() {
+        for (name, front_door, ip, expected_stage) in [
+            (
+                "apigw_rest",
+                FrontDoor::RestApi,
+                "198.51.100.1",
+                Some("prod"),
+            ),
+            ("apigw_http", FrontDoor::HttpApi, "198.51.100.2", None),
+            ("alb", FrontDoor::Alb, "198.51.100.3", None),
+            ("function_url", FrontDoor::FunctionUrl, "198.51.100.4", None),
+        ] {
+            let event = fixture(name);
+            assert_eq!(FrontDoor::of(&event), front_door, "{}", name);
+            assert_eq!(source_ip(&event).as_deref(), Some(ip), "{}", name);
+            assert_eq!(stage(&event), expected_stage, "{}", name);
+            let query_params = query_parameters(&event);
+            assert_eq!(
+                query_params.all("hostname"),
+                Some(vec!["home.example.org,nas.example.org"]),
+                "{}",
+                name
+            );
+            assert_eq!(query_params.first("myip"), Some("1.2.3.4"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_direct_requests() {
+        let mut request = Request::default();
+        request
+            .headers_mut()
+            .insert("X-Forwarded-For", "10.0.0.1, 203.0.113.7".parse().unwrap());
+        assert_eq!(FrontDoor::of(&request), FrontDoor::Direct);
+        assert_eq!(source_ip(&request), Some("203.0.113.7".to_string()));
+        assert_eq!(source_ip(&Request::default()), None);
+
+        let mut request = Request::default();
+        request
+            .headers_mut()
+            .insert("X-Forwarded-For", "10.0.0.1, <script>".parse().unwrap());
+        assert_eq!(source_ip(&request), None);
+        assert_eq!(stage(&request), None);
+    }
+}
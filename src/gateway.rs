@@ -0,0 +1,212 @@
+use lambda_http::Error;
+use reqwest::{Client, Url};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+const NAT_PMP_PORT: u16 = 5351;
+/// Version 0, opcode 0: external address request (RFC 6886)
+const NAT_PMP_REQUEST: [u8; 2] = [0, 0];
+const SSDP_ADDRESS: (Ipv4Addr, u16) = (Ipv4Addr::new(239, 255, 255, 250), 1900);
+const SSDP_SEARCH: &str = "M-SEARCH * HTTP/1.1\r\n\
+    HOST: 239.255.255.250:1900\r\n\
+    MAN: \"ssdp:discover\"\r\n\
+    MX: 2\r\n\
+    ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\r\n";
+/// Services of an Internet Gateway Device which know the WAN address
+const WAN_SERVICES: [&str; 2] = ["WANIPConnection", "WANPPPConnection"];
+/// Routers on the LAN answer quickly, a missing answer means there is no such router
+const GATEWAY_TIMEOUT: Duration = Duration::from_secs(3);
+/// NAT-PMP clients retry with doubling timeouts starting at 250ms
+const NAT_PMP_ATTEMPTS: u32 = 4;
+
+/// Asks the router for its WAN address with NAT-PMP, the default gateway if none is given.
+pub(crate) async fn nat_pmp_external_ip(gateway: Option<Ipv4Addr>) -> Result<Ipv4Addr, Error> {
+    let gateway = match gateway {
+        Some(gateway) => gateway,
+        None => default_gateway()?,
+    };
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket.connect((gateway, NAT_PMP_PORT)).await?;
+    let mut buffer = [0; 16];
+    let mut timeout = Duration::from_millis(250);
+    for _ in 0..NAT_PMP_ATTEMPTS {
+        socket.send(&NAT_PMP_REQUEST).await?;
+        if let Ok(received) = tokio::time::timeout(timeout, socket.recv(&mut buffer)).await {
+            return parse_nat_pmp_response(&buffer[..received?]);
+        }
+        timeout *= 2;
+    }
+    Err(format!("No NAT-PMP answer from {}", gateway).into())
+}
+
+fn parse_nat_pmp_response(response: &[u8]) -> Result<Ipv4Addr, Error> {
+    let [0, 128, result_high, result_low, _, _, _, _, a, b, c, d] = response else {
+        return Err(format!("Invalid NAT-PMP response: {:?}", response).into());
+    };
+    match u16::from_be_bytes([*result_high, *result_low]) {
+        0 => Ok(Ipv4Addr::new(*a, *b, *c, *d)),
+        code => Err(format!("NAT-PMP request failed with result code {}", code).into()),
+    }
+}
+
+/// The IPv4 default gateway from the kernel's routing table.
+fn default_gateway() -> Result<Ipv4Addr, Error> {
+    let routes = std::fs::read_to_string("/proc/net/route")
+        .map_err(|e| format!("Failed to read the routing table, pass the gateway: {}", e))?;
+    parse_default_gateway(&routes).ok_or_else(|| "No IPv4 default gateway found".into())
+}
+
+/// Finds the default route in `/proc/net/route`, whose addresses are hex in host byte order.
+fn parse_default_gateway(routes: &str) -> Option<Ipv4Addr> {
+    routes.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            [_, "00000000", gateway, ..] => u32::from_str_radix(gateway, 16)
+                .ok()
+                .map(|gateway| Ipv4Addr::from(gateway.to_ne_bytes()))
+                .filter(|gateway| !gateway.is_unspecified()),
+            _ => None,
+        }
+    })
+}
+
+/// Asks the router for its WAN address with UPnP IGD: the router is discovered with SSDP, its
+/// WAN connection service is looked up in its description and asked with a SOAP call.
+pub(crate) async fn upnp_external_ip() -> Result<Ipv4Addr, Error> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket
+        .send_to(SSDP_SEARCH.as_bytes(), SocketAddr::from(SSDP_ADDRESS))
+        .await?;
+    let mut buffer = [0; 2048];
+    let received = tokio::time::timeout(GATEWAY_TIMEOUT, socket.recv(&mut buffer))
+        .await
+        .map_err(|_| "No UPnP Internet Gateway Device answered")??;
+    let response = String::from_utf8_lossy(&buffer[..received]);
+    let location = parse_location(&response).ok_or("UPnP answer without a location")?;
+    let location = Url::parse(location)?;
+
+    let client = Client::builder().timeout(GATEWAY_TIMEOUT).build()?;
+    let description = client.get(location.clone()).send().await?.text().await?;
+    let (service_type, control_url) =
+        find_wan_service(&description).ok_or("The UPnP gateway has no WAN connection service")?;
+    let body = format!(
+        "<?xml version=\"1.0\"?>\
+        <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+        s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+        <s:Body><u:GetExternalIPAddress xmlns:u=\"{}\"></u:GetExternalIPAddress></s:Body>\
+        </s:Envelope>",
+        service_type
+    );
+    let response = client
+        .post(location.join(control_url)?)
+        .header("Content-Type", "text/xml; charset=\"utf-8\"")
+        .header(
+            "SOAPAction",
+            format!("\"{}#GetExternalIPAddress\"", service_type),
+        )
+        .body(body)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let ip = tag_text(&response, "NewExternalIPAddress")
+        .ok_or("The UPnP gateway didn't return its external address")?;
+    Ok(ip.trim().parse()?)
+}
+
+/// The `LOCATION` header of an SSDP answer.
+fn parse_location(response: &str) -> Option<&str> {
+    response.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("location")
+            .then(|| value.trim())
+    })
+}
+
+/// The type and control URL of the first WAN connection service in a device description.
+fn find_wan_service(description: &str) -> Option<(&str, &str)> {
+    description.split("<service>").skip(1).find_map(|service| {
+        let service_type = tag_text(service, "serviceType")?;
+        if !WAN_SERVICES
+            .iter()
+            .any(|wan_service| service_type.contains(wan_service))
+        {
+            return None;
+        }
+        Some((service_type.trim(), tag_text(service, "controlURL")?.trim()))
+    })
+}
+
+/// The text of the first element with the tag, without namespace handling.
+fn tag_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let length = xml[start..].find(&format!("</{}>", tag))?;
+    Some(&xml[start..start + length])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nat_pmp_response() {
+        let response = [0, 128, 0, 0, 0, 0, 0x1c, 0x20, 203, 0, 113, 7];
+        assert_eq!(
+            parse_nat_pmp_response(&response).unwrap(),
+            Ipv4Addr::new(203, 0, 113, 7)
+        );
+        // Result code 3: the router isn't connected to the Internet
+        let response = [0, 128, 0, 3, 0, 0, 0x1c, 0x20, 0, 0, 0, 0];
+        assert!(parse_nat_pmp_response(&response).is_err());
+        assert!(parse_nat_pmp_response(&response[..8]).is_err());
+    }
+
+    #[test]
+    fn test_parse_default_gateway() {
+        let routes = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\n\
+            eth0\t0001A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\n\
+            eth0\t00000000\t0101A8C0\t0003\t0\t0\t0\t00000000\n";
+        let expected = if cfg!(target_endian = "little") {
+            Ipv4Addr::new(192, 168, 1, 1)
+        } else {
+            Ipv4Addr::new(1, 1, 168, 192)
+        };
+        assert_eq!(parse_default_gateway(routes), Some(expected));
+        assert_eq!(parse_default_gateway("Iface\tDestination\tGateway\n"), None);
+    }
+
+    #[test]
+    fn test_upnp_parsing() {
+        let answer = "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=120\r\n\
+            Location: http://192.168.1.1:49000/igddesc.xml\r\nST: upnp:rootdevice\r\n\r\n";
+        assert_eq!(
+            parse_location(answer),
+            Some("http://192.168.1.1:49000/igddesc.xml")
+        );
+
+        let description = r#"<root><device><serviceList>
+            <service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>
+            <controlURL>/igdupnp/control/layer3forwarding</controlURL></service>
+            <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>
+            <controlURL>/igdupnp/control/WANIPConn1</controlURL></service>
+            </serviceList></device></root>"#;
+        assert_eq!(
+            find_wan_service(description),
+            Some((
+                "urn:schemas-upnp-org:service:WANIPConnection:1",
+                "/igdupnp/control/WANIPConn1"
+            ))
+        );
+
+        let response = "<s:Envelope><s:Body><u:GetExternalIPAddressResponse>\
+            <NewExternalIPAddress>203.0.113.7</NewExternalIPAddress>\
+            </u:GetExternalIPAddressResponse></s:Body></s:Envelope>";
+        assert_eq!(
+            tag_text(response, "NewExternalIPAddress"),
+            Some("203.0.113.7")
+        );
+    }
+}
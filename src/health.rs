@@ -0,0 +1,175 @@
+use crate::api::ping;
+use crate::credentials::Credentials;
+use crate::error::{ApiError, PorkbunError};
+use crate::state::AppState;
+use lambda_http::tracing::{error, warn};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Path of the health check for uptime monitors and load balancers
+pub(crate) const HEALTH_PATH: &str = "/health";
+
+/// The outcome of a single dependency check.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CheckStatus {
+    Ok,
+    Failed,
+    /// The check couldn't run, e.g. no server-side credentials are configured
+    Skipped,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct Check {
+    name: &'static str,
+    status: CheckStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl Check {
+    fn new(name: &'static str, status: CheckStatus, message: Option<String>) -> Self {
+        Check {
+            name,
+            status,
+            message,
+        }
+    }
+}
+
+/// The response body of `/health`, `checks` is only present if the dependencies were checked.
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct HealthReport {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    checks: Vec<Check>,
+}
+
+impl HealthReport {
+    /// Healthy unless a check failed, skipped checks don't count.
+    pub(crate) fn from_checks(checks: Vec<Check>) -> Self {
+        let failed = checks
+            .iter()
+            .any(|check| check.status == CheckStatus::Failed);
+        HealthReport {
+            status: if failed { "unavailable" } else { "ok" },
+            checks,
+        }
+    }
+
+    /// 200 if healthy, 503 otherwise, so load balancers take the instance out of rotation.
+    pub(crate) fn status_code(&self) -> u16 {
+        if self.status == "ok" {
+            200
+        } else {
+            503
+        }
+    }
+}
+
+/// Checks that the Porkbun API is reachable and the server-side credentials are valid, with a
+/// single ping. Without server-side credentials the ping is sent without credentials, an
+/// answer rejecting them still proves Porkbun is reachable.
+pub(crate) async fn check_dependencies(state: &AppState) -> Vec<Check> {
+    let credentials = match &state.server_credentials {
+        Some(server_credentials) => match server_credentials.get().await {
+            Ok(credentials) => Ok(credentials),
+            Err(e) => {
+                error!(error = ?e, "Failed to load server-side credentials");
+                Err(e.to_string())
+            }
+        },
+        None => Err("No server-side credentials configured".to_string()),
+    };
+    let anonymous = Credentials::new(String::new(), String::new());
+    let result = ping(&state.porkbun, credentials.as_ref().unwrap_or(&anonymous)).await;
+    let (porkbun, credentials_check) = classify_ping(&result);
+    let credentials_check = match credentials {
+        Ok(_) => credentials_check,
+        // Missing credentials aren't a failure, callers may pass their own
+        Err(message) if state.server_credentials.is_none() => {
+            Check::new("credentials", CheckStatus::Skipped, Some(message))
+        }
+        Err(message) => Check::new("credentials", CheckStatus::Failed, Some(message)),
+    };
+    vec![porkbun, credentials_check]
+}
+
+/// Splits the result of a ping into the reachability of Porkbun and the validity of the
+/// credentials, which can't be judged if Porkbun didn't answer.
+fn classify_ping(result: &Result<String, ApiError>) -> (Check, Check) {
+    match result {
+        Ok(_) => (
+            Check::new("porkbun", CheckStatus::Ok, None),
+            Check::new("credentials", CheckStatus::Ok, None),
+        ),
+        Err(ApiError::PingFailed(PorkbunError::InvalidCredentials(message))) => (
+            Check::new("porkbun", CheckStatus::Ok, None),
+            Check::new(
+                "credentials",
+                CheckStatus::Failed,
+                Some(format!("Invalid Porkbun credentials: {}", message)),
+            ),
+        ),
+        Err(e) => {
+            warn!(error = %e, "Health check failed to reach Porkbun");
+            (
+                Check::new("porkbun", CheckStatus::Failed, Some(e.to_string())),
+                Check::new(
+                    "credentials",
+                    CheckStatus::Skipped,
+                    Some("Porkbun is unreachable".to_string()),
+                ),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_ping() {
+        let (porkbun, credentials) = classify_ping(&Ok("203.0.113.7".to_string()));
+        assert_eq!(porkbun.status, CheckStatus::Ok);
+        assert_eq!(credentials.status, CheckStatus::Ok);
+
+        let (porkbun, credentials) = classify_ping(&Err(ApiError::PingFailed(
+            PorkbunError::InvalidCredentials("Invalid API key.".to_string()),
+        )));
+        assert_eq!(porkbun.status, CheckStatus::Ok);
+        assert_eq!(credentials.status, CheckStatus::Failed);
+        let report = HealthReport::from_checks(vec![porkbun, credentials]);
+        assert_eq!(report.status_code(), 503);
+
+        let (porkbun, credentials) = classify_ping(&Err(ApiError::PingFailed(
+            PorkbunError::Unavailable("502 Bad Gateway".to_string()),
+        )));
+        assert_eq!(porkbun.status, CheckStatus::Failed);
+        assert_eq!(credentials.status, CheckStatus::Skipped);
+    }
+
+    #[test]
+    fn test_skipped_checks_are_healthy() {
+        let report = HealthReport::from_checks(vec![
+            Check::new("porkbun", CheckStatus::Ok, None),
+            Check::new("credentials", CheckStatus::Skipped, None),
+        ]);
+        assert_eq!(report.status_code(), 200);
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "status": "ok",
+                "checks": [
+                    { "name": "porkbun", "status": "ok" },
+                    { "name": "credentials", "status": "skipped" },
+                ],
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(HealthReport::from_checks(vec![])).unwrap(),
+            serde_json::json!({ "status": "ok" })
+        );
+    }
+}
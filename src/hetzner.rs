@@ -0,0 +1,318 @@
+use crate::api::{DnsRecord, DEFAULT_TTL};
+use crate::domain::Domain;
+use crate::error::{ApiError, PorkbunError};
+use crate::ip_utils::RecordType;
+use crate::provider::DnsProvider;
+use crate::record::{parse_rdata, RecordUpdate};
+use lambda_http::tracing::{error, info};
+use reqwest::{Client, RequestBuilder};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Environment variable with the Hetzner DNS API token
+pub const HETZNER_DNS_TOKEN_ENV: &str = "HETZNER_DNS_TOKEN";
+
+const API_BASE_URL: &str = "https://dns.hetzner.com/api/v1";
+/// Records are listed per zone, zones of a homelab fit into a single page
+const RECORDS_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+struct ZonesResponse {
+    #[serde(default)]
+    zones: Vec<Zone>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Zone {
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecordsResponse {
+    #[serde(default)]
+    records: Vec<HetznerRecord>,
+}
+
+/// A record as returned by Hetzner, the name is relative to the zone (`@` for the apex).
+#[derive(Debug, Deserialize)]
+struct HetznerRecord {
+    id: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    name: String,
+    value: String,
+    ttl: Option<u64>,
+}
+
+/// The response of a created record, only its id is needed.
+#[derive(Debug, Deserialize)]
+struct CreatedRecordResponse {
+    record: CreatedRecord,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreatedRecord {
+    id: String,
+}
+
+#[derive(Serialize)]
+struct HetznerRecordRequest<'a> {
+    zone_id: &'a str,
+    #[serde(rename = "type")]
+    record_type: &'static str,
+    name: &'a str,
+    value: String,
+    ttl: u64,
+}
+
+/// The body of a Hetzner error response, which comes in two shapes.
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: Option<ErrorMessage>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorMessage {
+    message: String,
+}
+
+/// The Hetzner DNS API, authenticated with an API token.
+pub(crate) struct Hetzner<'a> {
+    client: &'a Client,
+    token: &'a str,
+    /// Zone ids by domain name, so a request updating several records looks each zone up once.
+    zones: Mutex<HashMap<String, String>>,
+}
+
+impl<'a> Hetzner<'a> {
+    pub fn new(client: &'a Client, token: &'a str) -> Self {
+        Self {
+            client,
+            token,
+            zones: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sends a request, returning the body of a successful response.
+    async fn send_raw(&self, request: RequestBuilder) -> Result<String, PorkbunError> {
+        let response = request.header("Auth-API-Token", self.token).send().await?;
+        let http_status = response.status();
+        let text = response.text().await?;
+        if http_status.is_success() {
+            return Ok(text);
+        }
+        let message = serde_json::from_str::<ErrorResponse>(&text)
+            .ok()
+            .and_then(|response| response.error.map(|e| e.message).or(response.message))
+            .unwrap_or_else(|| format!("HTTP {}", http_status.as_u16()));
+        Err(PorkbunError::from_response(http_status.as_u16(), message))
+    }
+
+    /// Sends a request and decodes the response, turning error responses into typed errors.
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, PorkbunError> {
+        let text = self.send_raw(request).await?;
+        serde_json::from_str(&text).map_err(|e| {
+            error!(error = %e, "Malformed Hetzner response");
+            PorkbunError::MalformedResponse(e.to_string())
+        })
+    }
+
+    /// Looks up the id of the zone the domain belongs to.
+    async fn zone_id(&self, domain: &Domain) -> Result<String, PorkbunError> {
+        let domain_name = domain.domain_name();
+        if let Some(zone_id) = self.zones.lock().unwrap().get(domain_name) {
+            return Ok(zone_id.clone());
+        }
+        let url = format!("{}/zones?name={}", API_BASE_URL, domain_name);
+        info!(url = %url, "Look up Hetzner zone");
+        let response: ZonesResponse = match self.send(self.client.get(&url)).await {
+            Ok(response) => response,
+            // Hetzner answers `404 Not Found` if no zone has the name
+            Err(PorkbunError::DomainNotFound(_)) => ZonesResponse { zones: Vec::new() },
+            Err(e) => return Err(e),
+        };
+        let zone_id = response
+            .zones
+            .into_iter()
+            .find(|zone| zone.name.eq_ignore_ascii_case(domain_name))
+            .map(|zone| zone.id)
+            .ok_or_else(|| {
+                PorkbunError::DomainNotFound(format!("No Hetzner zone for {}", domain_name))
+            })?;
+        self.zones
+            .lock()
+            .unwrap()
+            .insert(domain_name.to_string(), zone_id.clone());
+        Ok(zone_id)
+    }
+
+    async fn records(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<Vec<DnsRecord>, PorkbunError> {
+        let zone_id = self.zone_id(domain).await?;
+        let url = format!(
+            "{}/records?zone_id={}&per_page={}",
+            API_BASE_URL, zone_id, RECORDS_PAGE_SIZE
+        );
+        info!(url = %url, "Get existing records");
+        let response: RecordsResponse = self.send(self.client.get(&url)).await?;
+        let name = record_name(domain);
+        Ok(response
+            .records
+            .into_iter()
+            .filter(|record| {
+                record.record_type == record_type.as_str() && record.name.eq_ignore_ascii_case(name)
+            })
+            .map(|record| {
+                let (content, prio) = parse_rdata(record_type, &record.value);
+                let mut dns_record =
+                    DnsRecord::new(record.id, domain.qualified_name(), record_type, content);
+                dns_record.prio = prio;
+                dns_record.ttl = record.ttl.map(|ttl| ttl.to_string());
+                dns_record
+            })
+            .collect())
+    }
+}
+
+/// Builds the request body, the record data is sent in zone file notation.
+fn record_request<'a>(
+    zone_id: &'a str,
+    domain: &'a Domain,
+    record: &RecordUpdate,
+) -> Result<HetznerRecordRequest<'a>, PorkbunError> {
+    if record.record_type == RecordType::ALIAS {
+        return Err(PorkbunError::Unsupported(
+            "ALIAS records are not supported by Hetzner".to_string(),
+        ));
+    }
+    Ok(HetznerRecordRequest {
+        zone_id,
+        record_type: record.record_type.as_str(),
+        name: record_name(domain),
+        value: record.rdata(),
+        ttl: record.ttl.unwrap_or(DEFAULT_TTL),
+    })
+}
+
+/// The record name relative to the zone, `@` for the apex.
+fn record_name(domain: &Domain) -> &str {
+    if domain.is_apex() {
+        "@"
+    } else {
+        domain.subdomain()
+    }
+}
+
+impl DnsProvider for Hetzner<'_> {
+    async fn get_records(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<Vec<DnsRecord>, ApiError> {
+        self.records(domain, record_type)
+            .await
+            .inspect_err(|e| error!(error = %e, "Failed to retrieve DNS records"))
+            .map_err(ApiError::RetrieveRecordFailed)
+    }
+
+    async fn create_record(
+        &self,
+        domain: &Domain,
+        record: &RecordUpdate,
+    ) -> Result<Option<String>, ApiError> {
+        async {
+            let zone_id = self.zone_id(domain).await?;
+            let request_body = record_request(&zone_id, domain, record)?;
+            let url = format!("{}/records", API_BASE_URL);
+            info!(url = %url, domain = domain.qualified_name(), "Create DNS record");
+            let created: CreatedRecordResponse = self
+                .send(self.client.post(&url).json(&request_body))
+                .await?;
+            info!(record_id = %created.record.id, "Created DNS record");
+            Ok::<_, PorkbunError>(Some(created.record.id))
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to create DNS record"))
+        .map_err(ApiError::CreateRecordFailed)
+    }
+
+    async fn update_record(
+        &self,
+        domain: &Domain,
+        record_id: &str,
+        record: &RecordUpdate,
+    ) -> Result<(), ApiError> {
+        async {
+            let zone_id = self.zone_id(domain).await?;
+            let request_body = record_request(&zone_id, domain, record)?;
+            let url = format!("{}/records/{}", API_BASE_URL, record_id);
+            info!(url = %url, "Update DNS record");
+            self.send::<IgnoredAny>(self.client.put(&url).json(&request_body))
+                .await?;
+            info!(record_id, "Updated DNS record");
+            Ok::<_, PorkbunError>(())
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to update DNS record"))
+        .map_err(ApiError::UpdateRecordFailed)
+    }
+
+    async fn delete_record(&self, _domain: &Domain, record_id: &str) -> Result<(), ApiError> {
+        let url = format!("{}/records/{}", API_BASE_URL, record_id);
+        info!(url = %url, "Delete DNS record");
+        // Successful deletions have an empty body
+        self.send_raw(self.client.delete(&url))
+            .await
+            .inspect_err(|e| error!(error = %e, "Failed to delete DNS record"))
+            .map_err(ApiError::DeleteRecordFailed)?;
+        info!(record_id, "Deleted DNS record");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_deserialization() {
+        let response: RecordsResponse = serde_json::from_value(serde_json::json!({
+            "records": [
+                {"id": "5f0a6a4ab9bb0b2ef4ae21f8", "type": "MX", "name": "@", "value": "10 mail.example.org.", "zone_id": "HBgLK3P9vZcQbM2jV5t4Rd", "created": "2024-01-01 00:00:00.000 +0000 UTC", "modified": "2024-01-01 00:00:00.000 +0000 UTC"}
+            ]
+        }))
+        .unwrap();
+        let record = &response.records[0];
+        assert_eq!(record.id, "5f0a6a4ab9bb0b2ef4ae21f8");
+        assert_eq!(record.ttl, None);
+        assert_eq!(
+            parse_rdata(&RecordType::MX, &record.value),
+            ("mail.example.org".to_string(), Some("10".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_created_record_deserialization() {
+        let response: CreatedRecordResponse = serde_json::from_value(serde_json::json!({
+            "record": {"id": "5f0a6a4ab9bb0b2ef4ae21f8", "type": "A", "name": "home", "value": "203.0.113.7", "ttl": 600, "zone_id": "HBgLK3P9vZcQbM2jV5t4Rd"}
+        }))
+        .unwrap();
+        assert_eq!(response.record.id, "5f0a6a4ab9bb0b2ef4ae21f8");
+    }
+
+    #[test]
+    fn test_record_name() {
+        assert_eq!(record_name(&Domain::new("example.org").unwrap()), "@");
+        assert_eq!(
+            record_name(&Domain::new("home.example.org").unwrap()),
+            "home"
+        );
+    }
+}
@@ -1,259 +1,3566 @@
-use crate::api::{create_dns_record, get_existing_dns_record, update_dns_record};
+use crate::acme_dns::{self, AcmeDnsRequest, UpdateRequest};
+use crate::api::{DnsRecord, Latencies, PorkbunClient};
+#[cfg(feature = "dynamodb")]
+use crate::audit::{self, Change, ChangeLog};
+use crate::auth::{authenticate, log_auth_failure, Access};
+#[cfg(feature = "cloudflare")]
+use crate::cloudflare::Cloudflare;
+use crate::cors;
 use crate::credentials::Credentials;
+use crate::deadline::Deadline;
+use crate::desec::Desec;
 use crate::domain::Domain;
-use crate::error::ApiError;
-use crate::ip_utils::{validate_and_classify_ip, IpType, RecordType};
-use lambda_http::tracing::{error, info};
+use crate::dyndns;
+use crate::error::{ApiError, PorkbunError};
+#[cfg(all(feature = "prometheus", any(feature = "cli", feature = "server")))]
+use crate::exporter;
+use crate::fritzbox;
+use crate::front_door::{self, source_ip};
+use crate::health::{self, HealthReport};
+use crate::hetzner::Hetzner;
+use crate::ip_utils::{
+    canonical_ip, compose_ipv6, is_public_ip, parse_ipv6_prefix, validate_and_classify_ip, IpType,
+    RecordType,
+};
+use crate::metrics::{self, FailureReason, Outcomes};
+use crate::openapi::{self, ApiDoc};
+use crate::propagation::{self, Verification};
+use crate::provider::{DnsProvider, Porkbun, Provider, ProviderKind};
+use crate::record::RecordUpdate;
+use crate::request_id;
+#[cfg(feature = "route53")]
+use crate::route53::Route53;
+use crate::state::AppState;
+#[cfg(feature = "opentelemetry")]
+use crate::telemetry;
+use crate::version::{self, BuildInfo};
+use crate::webhook;
+use futures::future::join_all;
+use lambda_http::aws_lambda_events::query_map::QueryMap;
+use lambda_http::http::{
+    header::{ACCEPT, ALLOW, AUTHORIZATION, CACHE_CONTROL, ORIGIN, X_CONTENT_TYPE_OPTIONS},
+    HeaderMap, HeaderValue, Method,
+};
+use lambda_http::tracing::field::Empty;
+use lambda_http::tracing::{error, info, info_span, warn, Instrument};
 use lambda_http::{Body, Error, Request, RequestExt, Response};
-use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Instant;
+use utoipa::{OpenApi, ToSchema};
 
-#[derive(Debug)]
-struct IpUpdate {
-    address: String,
-    record_type: RecordType,
+/// Marks requests built by porkDyn itself (e.g. scheduled updates), which skip the token check.
+/// Only extensions set in-process carry it, callers can't add it to their requests.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct InternalRequest;
+
+/// What happened to a single record while processing a request.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum UpdateAction {
+    Unchanged,
+    Updated,
+    Created,
+    Deleted,
+    Failed,
+}
+
+impl UpdateAction {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            UpdateAction::Unchanged => "unchanged",
+            UpdateAction::Updated => "updated",
+            UpdateAction::Created => "created",
+            UpdateAction::Deleted => "deleted",
+            UpdateAction::Failed => "failed",
+        }
+    }
+}
+
+/// The outcome of a single record of a request, the response body of single-record requests and
+/// reported per item for multi-record requests.
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct ItemResult {
+    domain: String,
+    #[serde(rename = "type")]
+    record_type: &'static str,
+    #[serde(rename = "status")]
+    status_code: u16,
+    action: UpdateAction,
+    message: String,
+    /// The requested record content (e.g. the IP address), reported by the DynDNS2 return codes
+    #[serde(skip)]
+    content: String,
+    /// The provider which served the record, only set if a fallback provider is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provider: Option<&'static str>,
+    /// The id of the record at the provider, if it was looked up or reported on creation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    record_id: Option<String>,
+    /// The address an A or AAAA record pointed to before
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_ip: Option<String>,
+    /// The address an A or AAAA record points to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_ip: Option<String>,
+    /// Whether the name servers asked by `verify` already return the new address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    visible: Option<bool>,
+}
+
+/// What processing a single record did, with the id of the record (also of a created one) and its
+/// previous content if it existed.
+struct RecordOutcome {
+    action: UpdateAction,
+    message: String,
+    record_id: Option<String>,
+    old_content: Option<String>,
+}
+
+/// The JSON body of responses without per-record results.
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct MessageBody<'a> {
+    message: &'a str,
+}
+
+/// An existing record reported by `action=check` and `/records`.
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct ExistingRecord {
+    domain: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    id: String,
+    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ttl: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prio: Option<u64>,
 }
 
-impl IpUpdate {
-    fn new(address: String, ip_type: IpType) -> Self {
+impl ExistingRecord {
+    fn new(domain: String, record: DnsRecord) -> Self {
         Self {
-            address,
-            record_type: RecordType::from(ip_type),
+            domain,
+            ttl: record.ttl.as_deref().and_then(|ttl| ttl.parse().ok()),
+            prio: record.prio.as_deref().and_then(|prio| prio.parse().ok()),
+            record_type: record.record_type,
+            id: record.id,
+            content: record.content,
+        }
+    }
+}
+
+/// The JSON body of `action=check` and `/records` responses.
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct RecordsBody<'a> {
+    message: &'a str,
+    records: &'a [ExistingRecord],
+}
+
+/// The JSON body of multi-record responses, a summary and the result of every record.
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct ResultsBody<'a> {
+    message: &'a str,
+    results: &'a [ItemResult],
+    /// The results of the mirror providers, which don't affect the status code
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    mirrors: &'a [ItemResult],
+}
+
+/// The JSON body of single-record responses, the result with the mirrors' results next to it.
+#[derive(Debug, Serialize)]
+struct ResultBody<'a> {
+    #[serde(flatten)]
+    result: &'a ItemResult,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    mirrors: &'a [ItemResult],
+}
+
+/// The endpoint of a request, determined by its path (see `ROUTES`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Protocol {
+    /// The regular query-parameters or JSON body (`/` or `/update`)
+    Native,
+    /// DynDNS2 (`/nic/update`) as spoken by ddclient and many routers
+    DynDns2,
+    /// FRITZ!Box update URL placeholders (`/fritzbox`)
+    FritzBox,
+    /// DynDNS-style public IP check (`/checkip`), e.g. used by inadyn
+    CheckIp,
+    /// IP change push notifications with a JSON payload (`/webhook`)
+    Webhook,
+    /// Read-only listing of a zone's records (`GET /records`)
+    Records,
+    /// Liveness and optional dependency checks (`/health`) for uptime monitors
+    Health,
+    /// Build information of the deployed binary (`/version`)
+    Version,
+    /// OpenAPI document of the endpoints (`/openapi.json`)
+    OpenApi,
+    /// acme-dns API (`/register` and `/update`) for ACME DNS-01 challenges
+    AcmeDns(AcmeDnsRequest),
+}
+
+impl Protocol {
+    /// Selects the endpoint of the request, `None` if nothing is served at its path.
+    fn route(event: &Request) -> Option<Self> {
+        let mut path = request_path(event).trim_end_matches('/');
+        // HTTP APIs prefix the path with the stage unless it is `$default`
+        if let Some(stage) = front_door::stage(event) {
+            path = path
+                .strip_prefix('/')
+                .and_then(|path| path.strip_prefix(stage))
+                .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+                .unwrap_or(path);
+        }
+        if path.is_empty() {
+            return Some(Protocol::Native);
+        }
+        let (_, protocol) = ROUTES.iter().find(|(route, _)| path == *route)?;
+        // acme-dns clients send their updates to `/update` as well
+        if *protocol == Protocol::Native && event.headers().contains_key(acme_dns::API_USER_HEADER)
+        {
+            return Some(Protocol::AcmeDns(AcmeDnsRequest::Update));
+        }
+        Some(*protocol)
+    }
+
+    /// The methods the endpoint accepts, others get `405` with an `Allow` header.
+    fn allowed_methods(self) -> &'static [Method] {
+        match self {
+            Protocol::Native
+            | Protocol::DynDns2
+            | Protocol::FritzBox
+            | Protocol::Webhook
+            | Protocol::AcmeDns(_) => &GET_OR_POST,
+            Protocol::CheckIp | Protocol::Health | Protocol::Version | Protocol::OpenApi => {
+                &GET_OR_HEAD
+            }
+            Protocol::Records => &GET_ONLY,
+        }
+    }
+
+    /// Alternative parameter names of the endpoint and the parameter each of them stands for.
+    /// The compatibility modes translate their own names (e.g. DynDNS2's `myip` may carry an
+    /// IPv6 address), so only the regular update takes the aliases.
+    fn parameter_aliases(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Protocol::Native => &PARAMETER_ALIASES,
+            _ => &[],
+        }
+    }
+
+    /// Whether unknown parameters are rejected in strict mode. Push notifications carry arbitrary
+    /// payloads and DynDNS2 clients send vendor-specific options, so only the endpoints whose
+    /// URLs are typed by hand are checked.
+    fn checks_parameters(self) -> bool {
+        matches!(self, Protocol::Native | Protocol::FritzBox)
+    }
+
+    fn response_format(self) -> ResponseFormat {
+        match self {
+            Protocol::DynDns2 | Protocol::CheckIp => ResponseFormat::DynDns,
+            Protocol::Native
+            | Protocol::FritzBox
+            | Protocol::Webhook
+            | Protocol::Records
+            | Protocol::Health
+            | Protocol::Version
+            | Protocol::OpenApi
+            | Protocol::AcmeDns(_) => ResponseFormat::Json,
+        }
+    }
+}
+
+/// How the outcome of a request is reported to the caller.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ResponseFormat {
+    /// JSON with a `message` (and per-item `results` for multi-record requests)
+    Json,
+    /// DynDNS2 return codes (`good <ip>`, `nochg <ip>`, `badauth`, ...) as plain text, the
+    /// default of `/nic/update` and chosen by `Accept: text/plain` or `format=dyndns`
+    DynDns,
+    /// Just the resulting IP address (or record content) or a one-word status (`format=plain`),
+    /// easy to consume from scripts like MikroTik's RouterOS `fetch`
+    Plain,
+}
+
+impl ResponseFormat {
+    /// The format requested by the `format` parameter (`json`, `dyndns` or `plain`), otherwise
+    /// by the `Accept` header (`application/json` or `text/plain` for DynDNS2 return codes),
+    /// otherwise the default of the protocol. Unknown formats fall back to the next source.
+    fn negotiate(format: Option<&str>, accept: Option<&str>, default: ResponseFormat) -> Self {
+        format
+            .and_then(|format| match format.to_ascii_lowercase().as_str() {
+                "json" => Some(ResponseFormat::Json),
+                "dyndns" | "text" => Some(ResponseFormat::DynDns),
+                "plain" => Some(ResponseFormat::Plain),
+                _ => None,
+            })
+            .or_else(|| accept.and_then(Self::from_accept))
+            .unwrap_or(default)
+    }
+
+    /// The supported media type with the highest quality in an `Accept` header, the first one
+    /// on a tie. Wildcards (e.g. `*/*` of browsers and curl) leave the choice to the protocol.
+    fn from_accept(accept: &str) -> Option<Self> {
+        let mut best: Option<(f32, ResponseFormat)> = None;
+        for media_range in accept.split(',') {
+            let mut parts = media_range.split(';').map(str::trim);
+            let format = match parts
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "application/json" => ResponseFormat::Json,
+                "text/plain" => ResponseFormat::DynDns,
+                _ => continue,
+            };
+            let quality = parts
+                .find_map(|parameter| parameter.strip_prefix("q="))
+                .and_then(|quality| quality.parse().ok())
+                .unwrap_or(1.0);
+            if quality > best.map_or(0.0, |(best_quality, _)| best_quality) {
+                best = Some((quality, format));
+            }
         }
+        best.map(|(_, format)| format)
     }
 }
 
+/// The JSON body of a POST request.
+enum RequestBody {
+    /// A JSON array of records to update
+    Batch(Vec<BatchEntry>),
+    /// A JSON object with the same parameters as the query-parameters (including credentials)
+    Parameters(HashMap<String, String>),
+}
+
+/// A single entry of a batch update sent as JSON array in the request body.
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct BatchEntry {
+    domain: String,
+    ip: String,
+    #[serde(rename = "type")]
+    record_type: Option<String>,
+    ttl: Option<u64>,
+}
+
+/// Updates read their parameters from the query or a POST body
+static GET_OR_POST: [Method; 2] = [Method::GET, Method::POST];
+/// Read-only endpoints polled by monitors, which often send HEAD requests
+static GET_OR_HEAD: [Method; 2] = [Method::GET, Method::HEAD];
+static GET_ONLY: [Method; 1] = [Method::GET];
+
+/// Parameter names used by router firmwares and other DDNS services, with the parameter each of
+/// them stands for. `token` isn't an alias of `apikey`, it is the access token.
+const PARAMETER_ALIASES: [(&str, &str); 9] = [
+    ("hostname", "domain"),
+    ("host", "domain"),
+    ("myip", "ip"),
+    ("ipv4", "ip"),
+    ("myipv6", "ipv6"),
+    ("ip6", "ipv6"),
+    ("api_key", "apikey"),
+    ("secret_api_key", "secretapikey"),
+    ("secretkey", "secretapikey"),
+];
+/// The parameters of the regular update, anything else is rejected in strict mode
+const KNOWN_PARAMETERS: [&str; 31] = [
+    "token",
+    "apikey",
+    "secretapikey",
+    "credentials",
+    "provider",
+    "domain",
+    "hostname",
+    "ip",
+    "ipv6",
+    "ipv6suffix",
+    "ipv6prefix",
+    "mx",
+    "prio",
+    "srvtarget",
+    "srvport",
+    "srvprio",
+    "srvweight",
+    "caa",
+    "caatag",
+    "caaflags",
+    "alias",
+    "txt",
+    "type",
+    "subdomain",
+    "action",
+    "checkdomain",
+    "fast",
+    "force",
+    "verify",
+    "format",
+    "strict",
+];
+/// Path of the regular update, which is also served at the root path
+const UPDATE_PATH: &str = "/update";
+/// Path of the read-only listing of a zone's records
+const RECORDS_PATH: &str = "/records";
+/// The endpoints by path, matched exactly after the API Gateway stage (`/prod/nic/update`) is
+/// stripped.
+const ROUTES: [(&str, Protocol); 10] = [
+    (dyndns::NIC_UPDATE_PATH, Protocol::DynDns2),
+    (fritzbox::FRITZBOX_PATH, Protocol::FritzBox),
+    (dyndns::CHECKIP_PATH, Protocol::CheckIp),
+    (webhook::WEBHOOK_PATH, Protocol::Webhook),
+    (RECORDS_PATH, Protocol::Records),
+    (health::HEALTH_PATH, Protocol::Health),
+    (version::VERSION_PATH, Protocol::Version),
+    (openapi::OPENAPI_PATH, Protocol::OpenApi),
+    (
+        acme_dns::REGISTER_PATH,
+        Protocol::AcmeDns(AcmeDnsRequest::Register),
+    ),
+    (UPDATE_PATH, Protocol::Native),
+];
+const API_KEY_HEADER: &str = "X-Api-Key";
+const SECRET_API_KEY_HEADER: &str = "X-Secret-Api-Key";
+const DEFAULT_MX_PRIO: u16 = 10;
+const DEFAULT_SRV_PRIO: u16 = 0;
+const DEFAULT_SRV_WEIGHT: u16 = 0;
+const DEFAULT_CAA_TAG: &str = "issue";
+const CAA_TAGS: [&str; 3] = ["issue", "issuewild", "iodef"];
+
 /// This function is the entry point for the Lambda function.
 /// It receives a request with query parameters and updates the DNS record for the given domain and subdomain.
 /// If the record does not exist, it creates a new one.
 ///
-/// Following query-parameters are required:
-/// - apikey: The API key for the porkbun API
-/// - secretapikey: The secret API key for the porkbun API
-/// - domain: The domain for which the DNS record should be updated
+/// Following query-parameters are required (credentials only if they are not configured on the server side):
+/// - token: The shared access token, only if one is configured on the server side
+/// - apikey: The API key for the porkbun API (alternatively the Basic Auth username or `X-Api-Key` header)
+/// - secretapikey: The secret API key for the porkbun API (alternatively the Basic Auth password
+///   or `X-Secret-Api-Key` header)
+/// - domain: The domain for which the DNS record should be updated, multiple domains can be comma-separated
+///   (`hostname` is accepted as alias, see `PARAMETER_ALIASES` for all aliases; parameter names
+///   are case-insensitive)
 /// - ip: The IPv4 address to which the DNS A record should be updated
 /// - ipv6: The IPv6 address to which the DNS AAAA record should be updated (optional)
-pub(crate) async fn function_handler(event: Request) -> Result<Response<Body>, Error> {
+/// - ipv6suffix: The interface identifier of a host behind the router, e.g. `::5`; the AAAA
+///   record is set to the prefix of `ipv6prefix` (or the /64 of `ipv6`) followed by it (optional)
+/// - ipv6prefix: The delegated prefix the suffix is appended to, e.g. `2001:db8:1200::/56`, an
+///   address without length counts as /64 (optional)
+/// - mx: The mail server to which the DNS MX record should point (optional)
+/// - prio: The priority of the MX record (optional, defaults to 10)
+/// - srvtarget: The target host of the DNS SRV record (optional)
+/// - srvport: The port of the SRV record (required if srvtarget is set)
+/// - srvprio: The priority of the SRV record (optional, defaults to 0)
+/// - srvweight: The weight of the SRV record (optional, defaults to 0)
+/// - caa: The value of the DNS CAA record, e.g. the authorized certificate authority (optional)
+/// - caatag: The tag of the CAA record (optional, one of issue, issuewild or iodef, defaults to issue)
+/// - caaflags: The flags of the CAA record (optional, defaults to 0)
+/// - alias: The target host of the DNS ALIAS record, typically used on the apex domain (optional)
+/// - checkdomain: If `true`, verify that the domain belongs to the Porkbun account first (optional)
+/// - provider: The DNS provider of the domain, `porkbun`, `cloudflare`, `route53`, `desec` or
+///   `hetzner` (optional, defaults to the provider configured on the server side)
+/// - credentials: The base64 encoded KMS ciphertext of `{"apikey": "...", "secretapikey": "..."}`,
+///   replaces apikey and secretapikey if a KMS key is configured on the server side (optional)
+///
+/// Alternatively a POST request with a JSON array body updates multiple records at once,
+/// each entry has the form `{"domain": "api.example.com", "ip": "1.2.3.4", "type": "A", "ttl": 600}`
+/// (`type` and `ttl` are optional). The credentials are still passed as query-parameters or headers.
+///
+/// A POST request with a JSON object body is treated like the query-parameters, e.g.
+/// `{"apikey": "...", "secretapikey": "...", "domain": "api.example.com", "ip": "1.2.3.4"}`,
+/// so secrets never have to be part of the URL.
+///
+/// With `format=plain` the response only contains the resulting IP address (or record content)
+/// per line or a one-word status like `unauthorized` or `invalid`.
+///
+/// Requests to `/nic/update` follow the DynDNS2 protocol (`hostname`, `myip` and Basic Auth),
+/// so off-the-shelf clients can be pointed at this function directly.
+/// `/nic/update` also accepts `username` and `password` query-parameters instead of Basic Auth
+/// and `/checkip` reports the caller's IP address, as expected by inadyn's `custom` provider.
+///
+/// Requests to `/fritzbox` accept the FRITZ!Box placeholder names (`username`, `passwd`,
+/// `ipaddr`, `ip6addr` and `ip6lanprefix`) as aliases, empty placeholders are ignored.
+///
+/// Requests to `/webhook` accept IP change notifications with any JSON payload, the new address
+/// is taken from fields like `new_ip` and the domains default to the configured webhook domains.
+///
+/// Every request is logged within a span carrying its request id (see `request_id.rs`), which is
+/// echoed as `X-Request-Id` header and `request_id` field of JSON responses.
+///
+/// Within Lambda, Porkbun calls and retries stop shortly before the invocation times out (see
+/// `deadline.rs`) and a request still running then is answered with `504 Gateway Timeout`.
+///
+/// Browsers may call from the origins configured in `cors`, which get the CORS headers on every
+/// response and their `OPTIONS` preflights answered (see `cors.rs`).
+///
+/// Responses contain operational details (addresses, record ids), so they are never cached.
+pub(crate) async fn function_handler(
+    event: Request,
+    state: &AppState,
+) -> Result<Response<Body>, Error> {
+    let started = Instant::now();
+    let request_id = request_id::request_id(&event);
+    let span = info_span!("request", request_id = %request_id);
+    #[cfg(feature = "opentelemetry")]
+    telemetry::continue_xray_trace(&span, &event);
+    let deadline = Deadline::from_event(&event);
+    // The latency of every Porkbun call of the request ends up in its metrics
+    let latencies = Latencies::default();
+    let porkbun = state.porkbun.clone().with_latencies(latencies.clone());
+    // Porkbun calls stop before the Lambda timeout instead of being killed mid-update
+    let porkbun = match deadline {
+        Some(deadline) => porkbun.with_deadline(deadline.calls()),
+        None => porkbun,
+    };
+    let allowed_origin = event
+        .headers()
+        .get(ORIGIN)
+        .and_then(|origin| cors::allowed_origin(&state.cors, origin));
+    // Changes are recorded as they happen, so an aborted request still audits the ones it made
+    #[cfg(feature = "dynamodb")]
+    let (change_log, source_ip) = (ChangeLog::default(), source_ip(&event));
+    #[cfg(feature = "dynamodb")]
+    let event = {
+        let mut event = event;
+        change_log.attach(&mut event);
+        event
+    };
+    let response = async {
+        let Some(deadline) = deadline else {
+            return handle_request(event, state, &porkbun).await;
+        };
+        let response_deadline = tokio::time::Instant::from_std(deadline.response());
+        let request = handle_request(event, state, &porkbun);
+        match tokio::time::timeout_at(response_deadline, request).await {
+            Ok(response) => response,
+            Err(_) => {
+                error!(
+                    remaining = ?deadline.remaining(),
+                    "Aborting the request before the Lambda timeout"
+                );
+                let response = json_response(
+                    504,
+                    "Aborted before the Lambda timeout, records may be partially updated",
+                );
+                Ok(metrics::attach_failure(response, FailureReason::Upstream))
+            }
+        }
+    }
+    .instrument(span)
+    .await?;
+    metrics::emit(
+        &state.metrics,
+        &response,
+        started.elapsed(),
+        &latencies.calls(),
+        &request_id,
+    );
+    // Written before responding, the Lambda may be frozen right after
+    #[cfg(feature = "dynamodb")]
+    if let Some(audit_log) = &state.audit_log {
+        let changes = change_log.changes();
+        let write = audit_log.write(&changes, source_ip.as_deref(), &request_id);
+        match deadline {
+            Some(deadline) => {
+                let audit_deadline = tokio::time::Instant::from_std(deadline.audit());
+                if tokio::time::timeout_at(audit_deadline, write)
+                    .await
+                    .is_err()
+                {
+                    error!(
+                        changes = changes.len(),
+                        "No time left to write the audit log before the Lambda timeout"
+                    );
+                }
+            }
+            None => write.await,
+        }
+    }
+    let mut response = request_id::attach(response, &request_id);
+    add_security_headers(response.headers_mut());
+    Ok(match allowed_origin {
+        Some(allowed_origin) => cors::attach(response, allowed_origin),
+        None => response,
+    })
+}
+
+async fn handle_request(
+    event: Request,
+    state: &AppState,
+    porkbun: &PorkbunClient,
+) -> Result<Response<Body>, Error> {
     // Extract query parameters
     info!("Validating request");
-    let query_params = event.query_string_parameters();
-    let api_key = match query_params.first("apikey") {
-        Some(query_param) => query_param,
-        None => return Ok(json_response(400, "Missing query-parameter 'apikey'")),
+    // Compatibility modes (e.g. DynDNS2 clients calling `/nic/update`) use their own parameters
+    let Some(protocol) = Protocol::route(&event) else {
+        let path = request_path(&event);
+        error!(path, "No endpoint at the path");
+        return Ok(json_response(404, &format!("Unknown path '{}'", path)));
     };
-    let secret_key = match query_params.first("secretapikey") {
-        Some(query_param) => query_param,
-        None => return Ok(json_response(400, "Missing query-parameter 'secretapikey'")),
+    if cors::is_preflight(&state.cors, &event) {
+        return Ok(preflight_response(
+            &event,
+            state,
+            protocol.allowed_methods(),
+        ));
+    }
+    if !protocol.allowed_methods().contains(event.method()) {
+        error!(method = %event.method(), path = request_path(&event), "Method is not allowed");
+        return Ok(method_not_allowed_response(protocol.allowed_methods()));
+    }
+    match protocol {
+        Protocol::CheckIp => return Ok(checkip_response(&event)),
+        Protocol::Health => return Ok(health_response(&event, state).await),
+        Protocol::Version => return Ok(version_response(&event, state)),
+        Protocol::OpenApi => return Ok(openapi_response()),
+        Protocol::AcmeDns(request) => {
+            return Ok(acme_dns_response(&event, state, porkbun, request).await)
+        }
+        _ => {}
+    }
+    let aliases = protocol.parameter_aliases();
+    let query_params = normalize_parameters(&front_door::query_parameters(&event), aliases);
+    let format = ResponseFormat::negotiate(
+        query_params.first("format"),
+        event
+            .headers()
+            .get(ACCEPT)
+            .and_then(|value| value.to_str().ok()),
+        protocol.response_format(),
+    );
+    let request_body = match parse_request_body(&event) {
+        Ok(request_body) => request_body,
+        Err(message) => {
+            error!(error = %message, "Invalid request body");
+            return Ok(error_response(format, 400, &message));
+        }
+    };
+    // Parameters of a JSON object body take precedence over the query-parameters
+    let (query_params, batch_entries) = match request_body {
+        Some(RequestBody::Batch(entries)) => (query_params, Some(entries)),
+        Some(RequestBody::Parameters(parameters)) => (
+            normalize_parameters(&merge_parameters(&query_params, parameters), aliases),
+            None,
+        ),
+        None => (query_params, None),
+    };
+    let server_credentials = state.server_credentials.is_some();
+    let query_params = match protocol {
+        Protocol::DynDns2 => dyndns::translate_parameters(
+            &event,
+            &query_params,
+            server_credentials,
+            &state.parking_ips,
+        ),
+        Protocol::FritzBox => fritzbox::translate_parameters(&query_params, server_credentials),
+        Protocol::Webhook => webhook::translate_parameters(&query_params, &state.webhook_domains),
+        Protocol::Native
+        | Protocol::CheckIp
+        | Protocol::Records
+        | Protocol::Health
+        | Protocol::Version
+        | Protocol::OpenApi
+        | Protocol::AcmeDns(_) => query_params,
+    };
+    // Typos like `secertapikey` would otherwise end up as a missing parameter
+    let strict = state.strict_parameters || query_params.first("strict") == Some("true");
+    if strict && protocol.checks_parameters() {
+        if let Some(message) = unknown_parameters_message(&query_params) {
+            error!(error = %message, "Invalid request");
+            return Ok(error_response(format, 400, &message));
+        }
+    }
+    // If configured, callers have to authenticate to the Lambda with a token
+    let access = if event.extensions().get::<InternalRequest>().is_some() {
+        Some(Access::All)
+    } else {
+        authenticate(state, query_params.first("token"))
+    };
+    let access = match access {
+        Some(access) => access.within(&state.allowed_domains),
+        None => {
+            error!("Invalid or missing access token provided");
+            log_auth_failure(
+                "invalid_token",
+                source_ip(&event).as_deref(),
+                query_params
+                    .first("domain")
+                    .or_else(|| query_params.first("hostname")),
+            );
+            return Ok(error_response(
+                format,
+                401,
+                "Invalid or missing query-parameter 'token'",
+            ));
+        }
+    };
+    // DynDNS2 clients expect a dedicated return code for invalid host names
+    if format == ResponseFormat::DynDns && !dyndns::has_valid_hostnames(&query_params) {
+        error!("Invalid or missing hostname in DynDNS2 request");
+        let response = text_response(200, "notfqdn");
+        return Ok(metrics::attach_failure(response, FailureReason::Validation));
+    }
+
+    // Households with domains at several providers select one per request
+    let provider_kind = match query_params.first("provider") {
+        Some(provider) => match provider.parse::<ProviderKind>() {
+            Ok(provider_kind) => provider_kind,
+            Err(message) => {
+                error!(error = %message, "Invalid request");
+                return Ok(error_response(format, 400, &message));
+            }
+        },
+        None => state.provider,
+    };
+    // The fallback provider takes over records the provider failed to update
+    let fallback_kind = state
+        .fallback_provider
+        .filter(|fallback_kind| *fallback_kind != provider_kind);
+    // Mirrors receive the same record changes as the provider
+    let mirror_kinds: Vec<ProviderKind> = state
+        .mirror_providers
+        .iter()
+        .copied()
+        .filter(|mirror_kind| *mirror_kind != provider_kind)
+        .collect();
+    // Porkbun credentials are only required if Porkbun may be used
+    let credentials = if provider_kind == ProviderKind::Porkbun
+        || fallback_kind == Some(ProviderKind::Porkbun)
+        || mirror_kinds.contains(&ProviderKind::Porkbun)
+    {
+        match load_credentials(&event, state, &query_params).await {
+            Ok(credentials) => Some(credentials),
+            Err((status_code, message)) => {
+                error!(error = %message, "Invalid credentials provided");
+                return Ok(error_response(format, status_code, &message));
+            }
+        }
+    } else {
+        None
+    };
+    let provider = match create_provider(provider_kind, state, porkbun, credentials.as_ref()) {
+        Ok(provider) => provider,
+        Err(message) => {
+            error!(error = %message, "Invalid request");
+            return Ok(error_response(format, 400, message));
+        }
+    };
+    let fallback = fallback_kind.and_then(|fallback_kind| {
+        create_provider(fallback_kind, state, porkbun, credentials.as_ref())
+            .inspect_err(|message| error!(error = %message, "Fallback provider is not available"))
+            .ok()
+    });
+    let mirrors: Vec<Provider> = mirror_kinds
+        .into_iter()
+        .filter_map(|mirror_kind| {
+            create_provider(mirror_kind, state, porkbun, credentials.as_ref())
+                .inspect_err(|message| error!(error = %message, "Mirror provider is not available"))
+                .ok()
+        })
+        .collect();
+    if protocol == Protocol::Records {
+        return Ok(match &provider {
+            Provider::Porkbun(porkbun) => {
+                records_response(porkbun, &event, &query_params, format, &access).await
+            }
+            _ => error_response(format, 400, "/records is only supported for Porkbun"),
+        });
+    }
+    match query_params.first("action") {
+        Some("ping") => {
+            return Ok(match &provider {
+                Provider::Porkbun(porkbun) => ping_response(porkbun, format).await,
+                _ => error_response(format, 400, "action=ping is only supported for Porkbun"),
+            })
+        }
+        Some("delete") => {
+            return Ok(delete_response(&provider, &event, &query_params, format, &access).await)
+        }
+        Some("check") => {
+            return Ok(check_response(&provider, &event, &query_params, format, &access).await)
+        }
+        // certbot `--manual-auth-hook`/`--manual-cleanup-hook` scripts set and clear ACME challenges
+        Some(action) => {
+            return Ok(acme_action_response(
+                &provider,
+                &event,
+                &query_params,
+                format,
+                &access,
+                action,
+            )
+            .await)
+        }
+        None => {}
+    }
+    let updates = match batch_entries {
+        Some(entries) => parse_batch_updates(entries),
+        None => parse_query_updates(&query_params),
+    };
+    let mut updates = match updates {
+        Ok(updates) => updates,
+        Err(message) => {
+            error!(error = %message, "Invalid request");
+            return Ok(error_response(format, 400, &message));
+        }
+    };
+    // Records without an explicit TTL get the configured default, if any
+    for (_, record_update) in &mut updates {
+        record_update.ttl = record_update.ttl.or(state.default_ttl);
+    }
+
+    // Tokens and the server may be restricted to certain domains
+    if let Some((domain, _)) = updates.iter().find(|(domain, _)| !access.allows(domain)) {
+        return Ok(domain_not_allowed_response(&event, format, &access, domain));
+    }
+
+    // A router would otherwise publish its LAN address, e.g. 192.168.1.1
+    if !state.allow_private {
+        if let Some((domain, record_update)) = updates
+            .iter()
+            .find(|(_, record_update)| is_non_public_ip(record_update, &state.parking_ips))
+        {
+            error!(
+                content = %record_update.content,
+                domain = domain.qualified_name(),
+                "Refusing to publish a non-public address"
+            );
+            return Ok(error_response(
+                format,
+                400,
+                &format!(
+                    "IP address '{}' of '{}' is private or reserved, set allow_private to publish it",
+                    record_update.content,
+                    domain.qualified_name()
+                ),
+            ));
+        }
+    }
+
+    info!(
+        records = updates.len(),
+        "Valid request received for updating DNS records"
+    );
+
+    // Optionally verify that all domains belong to the account before touching any record
+    if query_params.first("checkdomain") == Some("true") {
+        let Provider::Porkbun(porkbun) = &provider else {
+            return Ok(error_response(
+                format,
+                400,
+                "checkdomain is only supported for Porkbun",
+            ));
+        };
+        match porkbun.list_domains().await {
+            Ok(account_domains) => {
+                if let Some(missing) = find_missing_domain(&updates, &account_domains) {
+                    error!(domain = %missing, "Domain is not part of the Porkbun account");
+                    return Ok(error_response(
+                        format,
+                        404,
+                        &format!("Domain '{}' is not part of this Porkbun account", missing),
+                    ));
+                }
+            }
+            Err(e) => {
+                error!(error = ?e, "Failed to verify domain ownership");
+                return Ok(error_response(
+                    format,
+                    e.status_code(),
+                    &format!("Upstream DNS service error: {}", e),
+                ));
+            }
+        }
+    }
+
+    // The fast path updates records with a single call, but reports unchanged records as updated
+    let fast = query_params.first("fast") == Some("true");
+    // Rewrites records which are already up to date, e.g. after manual edits at the provider
+    let force = query_params.first("force") == Some("true");
+    let verification = match query_params.first("verify") {
+        None | Some("false") => None,
+        Some(verify) => match verify.parse::<Verification>() {
+            Ok(Verification::Authoritative) if provider_kind != ProviderKind::Porkbun => {
+                return Ok(error_response(
+                    format,
+                    400,
+                    "verify=authoritative is only supported for Porkbun",
+                ));
+            }
+            Ok(verification) => Some(verification),
+            Err(message) => {
+                error!(error = %message, "Invalid request");
+                return Ok(error_response(format, 400, &message));
+            }
+        },
+    };
+    let mut results: Vec<ItemResult> = Vec::new();
+
+    // Which provider served a record is only reported if there is a choice
+    let report_provider = fallback.is_some() || !mirrors.is_empty();
+    let mut mirror_results: Vec<ItemResult> = Vec::new();
+    #[cfg(feature = "dynamodb")]
+    let caller = audit::caller(&event, query_params.first("token"));
+
+    // Process each record, a failing record does not stop the remaining ones
+    for (domain, record_update) in &updates {
+        let primary = async {
+            let result = process_dns_record(&provider, domain, record_update, fast, force).await;
+            // Only unreachable providers are failed over, e.g. not invalid credentials
+            let transient = result
+                .as_ref()
+                .err()
+                .and_then(|e| e.downcast_ref::<ApiError>())
+                .is_some_and(|e| e.porkbun_error().is_transient());
+            let (result, served_by) = match &fallback {
+                Some(fallback) if transient => {
+                    warn!(
+                        provider = provider_kind.as_str(),
+                        domain = domain.qualified_name(),
+                        fallback = fallback.kind().as_str(),
+                        "Provider failed to update the record, falling back"
+                    );
+                    (
+                        process_dns_record(fallback, domain, record_update, fast, force).await,
+                        fallback.kind(),
+                    )
+                }
+                _ => (result, provider_kind),
+            };
+            record_result(
+                domain,
+                record_update,
+                result,
+                report_provider.then_some(served_by.as_str()),
+            )
+        };
+        // Mirrors are updated in parallel to the provider
+        let mirrored = join_all(mirrors.iter().map(|mirror| async move {
+            record_result(
+                domain,
+                record_update,
+                process_dns_record(mirror, domain, record_update, fast, force).await,
+                Some(mirror.kind().as_str()),
+            )
+        }));
+        let (item_result, mirrored) = tokio::join!(primary, mirrored);
+        // Mirrored changes are audited in every format
+        #[cfg(feature = "dynamodb")]
+        audit::record(
+            &event,
+            [
+                audit_changes(std::slice::from_ref(&item_result), provider_kind, &caller),
+                audit_changes(&mirrored, provider_kind, &caller),
+            ]
+            .concat(),
+        );
+        results.push(item_result);
+        mirror_results.extend(mirrored);
+    }
+
+    // Successfully updated addresses are looked up at the name servers, reported in JSON only
+    if let Some(verification) = verification.filter(|_| format == ResponseFormat::Json) {
+        let name_servers = verification.name_servers().await;
+        let checks = updates
+            .iter()
+            .zip(&results)
+            .map(|((domain, record_update), result)| {
+                let name_servers = &name_servers;
+                async move {
+                    if result.status_code != 200 {
+                        return None;
+                    }
+                    propagation::is_visible(name_servers, domain, record_update).await
+                }
+            });
+        let visible = join_all(checks).await;
+        for (result, visible) in results.iter_mut().zip(visible) {
+            result.visible = visible;
+        }
+    }
+
+    Ok(formatted_results_response(format, results, mirror_results))
+}
+
+/// The path of the request without the API Gateway stage, falling back to the URI path.
+fn request_path(event: &Request) -> &str {
+    match event.raw_http_path() {
+        "" => event.uri().path(),
+        path => path,
+    }
+}
+
+/// Returns the first registrable domain of the updates which is not part of the given account domains.
+fn find_missing_domain<'a>(
+    updates: &'a [(Domain, RecordUpdate)],
+    account_domains: &[String],
+) -> Option<&'a str> {
+    updates
+        .iter()
+        .map(|(domain, _)| domain.domain_name())
+        .find(|domain_name| {
+            !account_domains
+                .iter()
+                .any(|account_domain| account_domain.eq_ignore_ascii_case(domain_name))
+        })
+}
+
+/// The records created, updated or deleted by `caller`, for the audit log. Results only report
+/// their provider if there is a choice, `provider` served the others.
+#[cfg(feature = "dynamodb")]
+fn audit_changes(results: &[ItemResult], provider: ProviderKind, caller: &str) -> Vec<Change> {
+    results
+        .iter()
+        .filter(|result| {
+            matches!(
+                result.action,
+                UpdateAction::Updated | UpdateAction::Created | UpdateAction::Deleted
+            )
+        })
+        .map(|result| Change {
+            domain: result.domain.clone(),
+            record_type: result.record_type,
+            action: result.action,
+            provider: result.provider.unwrap_or(provider.as_str()),
+            caller: caller.to_string(),
+            old_ip: result.old_ip.clone(),
+            new_ip: result.new_ip.clone(),
+        })
+        .collect()
+}
+
+/// Reports the processed records in the response format, with their outcomes attached for the
+/// metrics. Only the selected provider's results decide the status code, outcomes and failure
+/// reason, the mirrors' results are reported next to them in JSON. DynDNS2 and plain text clients
+/// expect a line per record, so they don't get the mirrors.
+fn formatted_results_response(
+    format: ResponseFormat,
+    results: Vec<ItemResult>,
+    mirrors: Vec<ItemResult>,
+) -> Response<Body> {
+    let outcomes = Outcomes::count(results.iter().map(|result| result.action));
+    // A single failed record fails the update, e.g. when Porkbun rejects the API key
+    let failure = results
+        .iter()
+        .find(|result| result.action == UpdateAction::Failed)
+        .map(|result| {
+            FailureReason::from_status(result.status_code).unwrap_or(FailureReason::Upstream)
+        });
+    let mut response = match format {
+        ResponseFormat::Json => results_response(results, mirrors),
+        ResponseFormat::DynDns => dyndns_response(&results),
+        ResponseFormat::Plain => plain_response(&results),
+    };
+    response.extensions_mut().insert(outcomes);
+    match failure {
+        Some(reason) => metrics::attach_failure(response, reason),
+        None => response,
+    }
+}
+
+/// Builds the response for the processed records.
+/// A single record is reported as its result (`message`, `action`, `new_ip`, ...), multiple
+/// records as a summary with the per-item results and a 207 (Multi-Status) if only some failed.
+/// The results of the mirror providers are added as `mirrors`.
+fn results_response(results: Vec<ItemResult>, mirrors: Vec<ItemResult>) -> Response<Body> {
+    let failed = results
+        .iter()
+        .filter(|result| result.status_code != 200)
+        .count();
+    let status_code = results_status_code(&results);
+
+    if let [result] = results.as_slice() {
+        return json_body_response(
+            result.status_code,
+            &ResultBody {
+                result,
+                mirrors: &mirrors,
+            },
+        );
+    }
+
+    let message = if failed == 0 {
+        results
+            .iter()
+            .map(|result| result.message.as_str())
+            .collect::<Vec<_>>()
+            .join("; ")
+    } else {
+        format!("{} of {} record update(s) failed", failed, results.len())
     };
-    let qualified_domain_name = match query_params.first("domain") {
+    json_body_response(
+        status_code,
+        &ResultsBody {
+            message: &message,
+            results: &results,
+            mirrors: &mirrors,
+        },
+    )
+}
+
+/// The status code of the processed records: 200 if all succeeded, 207 (Multi-Status) if only
+/// some of them failed and the status code of the first failure if all failed.
+fn results_status_code(results: &[ItemResult]) -> u16 {
+    let failed = results
+        .iter()
+        .filter(|result| result.status_code != 200)
+        .count();
+    match results.iter().find(|result| result.status_code != 200) {
+        None => 200,
+        Some(_) if failed < results.len() => 207,
+        Some(first_failure) => first_failure.status_code,
+    }
+}
+
+/// Builds the plain text response with one line per record, the record content (e.g. the IP
+/// address) if it succeeded or a one-word status if it failed.
+fn plain_response(results: &[ItemResult]) -> Response<Body> {
+    let body = results
+        .iter()
+        .map(|result| match result.action {
+            UpdateAction::Failed => plain_status(result.status_code).to_string(),
+            UpdateAction::Deleted => "deleted".to_string(),
+            _ => result.content.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    text_response(results_status_code(results), &body)
+}
+
+/// The one-word status of a failed request or record in plain text responses.
+fn plain_status(status_code: u16) -> &'static str {
+    match status_code {
+        400 => "invalid",
+        401 => "unauthorized",
+        403 => "forbidden",
+        404 => "notfound",
+        _ => "error",
+    }
+}
+
+/// Creates the provider of the kind, returning the error message if it isn't configured.
+fn create_provider<'a>(
+    kind: ProviderKind,
+    state: &'a AppState,
+    porkbun: &'a PorkbunClient,
+    credentials: Option<&'a Credentials>,
+) -> Result<Provider<'a>, &'static str> {
+    let client = porkbun.http_client();
+    match kind {
+        ProviderKind::Porkbun => credentials
+            .map(|credentials| Provider::Porkbun(Porkbun::new(porkbun, credentials)))
+            .ok_or("Porkbun credentials are missing"),
+        #[cfg(feature = "cloudflare")]
+        ProviderKind::Cloudflare => state
+            .cloudflare_token
+            .as_deref()
+            .map(|token| Provider::Cloudflare(Cloudflare::new(client, token)))
+            .ok_or("Cloudflare is not configured on this server"),
+        #[cfg(not(feature = "cloudflare"))]
+        ProviderKind::Cloudflare => Err("Cloudflare is not included in this build"),
+        #[cfg(feature = "route53")]
+        ProviderKind::Route53 => state
+            .route53
+            .as_ref()
+            .map(|route53| Provider::Route53(Route53::new(route53)))
+            .ok_or("Route 53 is not enabled on this server"),
+        #[cfg(not(feature = "route53"))]
+        ProviderKind::Route53 => Err("Route 53 is not included in this build"),
+        ProviderKind::Desec => state
+            .desec_token
+            .as_deref()
+            .map(|token| Provider::Desec(Desec::new(client, token)))
+            .ok_or("deSEC is not configured on this server"),
+        ProviderKind::Hetzner => state
+            .hetzner_token
+            .as_deref()
+            .map(|token| Provider::Hetzner(Hetzner::new(client, token)))
+            .ok_or("Hetzner is not configured on this server"),
+    }
+}
+
+/// Loads the Porkbun credentials for the request, returning the status code and message on error.
+async fn load_credentials(
+    event: &Request,
+    state: &AppState,
+    query_params: &QueryMap,
+) -> Result<Credentials, (u16, String)> {
+    // Server-side credentials take precedence, callers then only authenticate to the Lambda itself
+    match &state.server_credentials {
+        Some(server_credentials) => server_credentials.get().await.map_err(|e| {
+            error!(error = ?e, "Failed to load server-side credentials");
+            (500, "Failed to load server-side credentials".to_string())
+        }),
+        // Callers may send their credentials KMS-encrypted instead of in plain text
+        #[cfg(feature = "kms")]
+        None => match (query_params.first("credentials"), &state.kms_decryptor) {
+            (Some(ciphertext), Some(kms_decryptor)) => kms_decryptor
+                .decrypt_credentials(ciphertext)
+                .await
+                .map_err(|e| {
+                    error!(error = ?e, "Failed to decrypt credentials");
+                    (
+                        401,
+                        "Failed to decrypt query-parameter 'credentials'".to_string(),
+                    )
+                }),
+            (Some(_), None) => Err((
+                400,
+                "Encrypted credentials are not enabled on this server".to_string(),
+            )),
+            (None, _) => extract_credentials(event, query_params),
+        },
+        #[cfg(not(feature = "kms"))]
+        None => match query_params.first("credentials") {
+            Some(_) => Err((
+                400,
+                "Encrypted credentials are not enabled on this server".to_string(),
+            )),
+            None => extract_credentials(event, query_params),
+        },
+    }
+}
+
+/// Extracts the Porkbun credentials of the request, returning the status code and message on error.
+/// Credentials are taken from (in this order) a Basic Auth header, the `X-Api-Key`/`X-Secret-Api-Key`
+/// headers or the `apikey`/`secretapikey` query-parameters.
+fn extract_credentials(
+    event: &Request,
+    query_params: &QueryMap,
+) -> Result<Credentials, (u16, String)> {
+    let headers = event.headers();
+    if let Some(header_value) = headers.get(AUTHORIZATION) {
+        return header_value
+            .to_str()
+            .ok()
+            .and_then(Credentials::from_basic_auth)
+            .ok_or_else(|| {
+                (
+                    401,
+                    "Invalid 'Authorization' header, expected Basic Auth with API key as username and secret API key as password".to_string(),
+                )
+            });
+    }
+
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    match (header(API_KEY_HEADER), header(SECRET_API_KEY_HEADER)) {
+        (Some(api_key), Some(secret_key)) => {
+            return Ok(Credentials::new(
+                api_key.to_string(),
+                secret_key.to_string(),
+            ))
+        }
+        (Some(_), None) => {
+            return Err((400, format!("Missing header '{}'", SECRET_API_KEY_HEADER)))
+        }
+        (None, Some(_)) => return Err((400, format!("Missing header '{}'", API_KEY_HEADER))),
+        (None, None) => {}
+    }
+
+    let api_key = query_params
+        .first("apikey")
+        .ok_or_else(|| (400, "Missing query-parameter 'apikey'".to_string()))?;
+    let secret_key = query_params
+        .first("secretapikey")
+        .ok_or_else(|| (400, "Missing query-parameter 'secretapikey'".to_string()))?;
+    Ok(Credentials::new(
+        api_key.to_string(),
+        secret_key.to_string(),
+    ))
+}
+
+/// Parses the updates of a regular query-parameter request.
+/// All given records are applied to all given (comma-separated) domains.
+fn parse_query_updates(query_params: &QueryMap) -> Result<Vec<(Domain, RecordUpdate)>, String> {
+    // Multiple hostnames can be passed comma-separated, `hostname` is accepted as DynDNS-style alias
+    let qualified_domain_names = match query_params
+        .first("domain")
+        .or_else(|| query_params.first("hostname"))
+    {
         Some(query_param) => query_param,
-        None => return Ok(json_response(400, "Missing query-parameter 'domain'")),
+        None => return Err("Missing query-parameter 'domain'".to_string()),
     };
     // Process IPv4 address (required)
-    let ipv4: Option<IpUpdate> = match query_params.first("ip") {
+    let ipv4: Option<RecordUpdate> = match query_params.first("ip") {
         Some(ip_str) => match validate_and_classify_ip(ip_str) {
-            Ok(IpType::V4) => Some(IpUpdate::new(ip_str.to_string(), IpType::V4)),
+            Ok(IpType::V4) => Some(RecordUpdate::ip(ip_str.to_string(), IpType::V4)),
             Ok(IpType::V6) => {
                 error!("IPv6 address provided in 'ip' parameter, use 'ipv6' parameter instead");
-                return Ok(json_response(
-                    400,
-                    "IPv6 address provided in 'ip' parameter, use 'ipv6' parameter instead",
-                ));
+                return Err(
+                    "IPv6 address provided in 'ip' parameter, use 'ipv6' parameter instead"
+                        .to_string(),
+                );
             }
             Err(e) => {
-                error!("Invalid IPv4 address provided: {:?}", e);
-                return Ok(json_response(400, &format!("Invalid IPv4 address: {}", e)));
+                error!(error = ?e, "Invalid IPv4 address provided");
+                return Err(format!("Invalid IPv4 address: {}", e));
             }
         },
         None => None,
     };
 
     // Process IPv6 address (optional)
-    let ipv6: Option<IpUpdate> = match query_params.first("ipv6") {
+    let ipv6: Option<RecordUpdate> = match query_params.first("ipv6") {
         Some(ip_str) => match validate_and_classify_ip(ip_str) {
-            Ok(IpType::V6) => Some(IpUpdate::new(ip_str.to_string(), IpType::V6)),
+            Ok(IpType::V6) => Some(RecordUpdate::ip(ip_str.to_string(), IpType::V6)),
             Ok(IpType::V4) => {
                 error!("IPv4 address provided in 'ipv6' parameter, use 'ip' parameter instead");
-                return Ok(json_response(
-                    400,
-                    "IPv4 address provided in 'ipv6' parameter, use 'ip' parameter instead",
-                ));
+                return Err(
+                    "IPv4 address provided in 'ipv6' parameter, use 'ip' parameter instead"
+                        .to_string(),
+                );
             }
             Err(e) => {
-                error!("Invalid IPv6 address provided: {:?}", e);
-                return Ok(json_response(400, &format!("Invalid IPv6 address: {}", e)));
+                error!(error = ?e, "Invalid IPv6 address provided");
+                return Err(format!("Invalid IPv6 address: {}", e));
             }
         },
         None => None,
     };
 
-    // Ensure at least one IP address is provided
-    if ipv4.is_none() && ipv6.is_none() {
-        return Ok(json_response(
-            400,
-            "At least one IP address must be provided (ip or ipv6)",
-        ));
-    }
-
-    let ip_updates = [ipv4, ipv6].into_iter().flatten().collect::<Vec<_>>();
+    // Compose the IPv6 address from the prefix and the host suffix (optional)
+    let ipv6 = match query_params.first("ipv6suffix") {
+        Some(suffix) => {
+            let prefix = query_params
+                .first("ipv6prefix")
+                .or_else(|| ipv6.as_ref().map(|ipv6| ipv6.content.as_str()))
+                .ok_or("Query-parameter 'ipv6suffix' requires 'ipv6prefix' or 'ipv6'")?;
+            let (prefix, prefix_len) = parse_ipv6_prefix(prefix)?;
+            let suffix = suffix
+                .parse()
+                .map_err(|_| format!("Invalid IPv6 suffix: {}", suffix))?;
+            let address = compose_ipv6(prefix, prefix_len, suffix).to_string();
+            Some(RecordUpdate::ip(address, IpType::V6))
+        }
+        None if query_params.first("ipv6prefix").is_some() => {
+            return Err("Query-parameter 'ipv6prefix' requires 'ipv6suffix'".to_string())
+        }
+        None => ipv6,
+    };
 
-    info!(
-        "Valid request received for updating DNS entries for domain: '{:?}' with {} IP address(es)",
-        qualified_domain_name,
-        ip_updates.len()
-    );
+    // Process MX record (optional)
+    let mx: Option<RecordUpdate> = match query_params.first("mx") {
+        Some(mail_server) => {
+            if !is_valid_target(mail_server) {
+                error!(mail_server, "Invalid mail server provided");
+                return Err(format!("Invalid mail server: {}", mail_server));
+            }
+            let prio = match parse_u16_param(query_params, "prio", DEFAULT_MX_PRIO) {
+                Ok(prio) => prio,
+                Err(prio_str) => {
+                    error!(prio = %prio_str, "Invalid MX priority provided");
+                    return Err(format!("Invalid MX priority: {}", prio_str));
+                }
+            };
+            Some(RecordUpdate::mx(mail_server.to_string(), prio))
+        }
+        None => None,
+    };
 
-    // Extract domain
-    let domain: Domain = match Domain::new(qualified_domain_name) {
-        Ok(domain) => {
-            info!("Domain: {:?}", domain);
-            domain
+    // Process SRV record (optional)
+    let srv: Option<RecordUpdate> = match query_params.first("srvtarget") {
+        Some(target) => {
+            if !is_valid_target(target) {
+                error!(target = %target, "Invalid SRV target provided");
+                return Err(format!("Invalid SRV target: {}", target));
+            }
+            let port = match query_params.first("srvport").map(str::parse::<u16>) {
+                Some(Ok(port)) => port,
+                Some(Err(_)) | None => {
+                    error!("Missing or invalid SRV port provided");
+                    return Err("Missing or invalid query-parameter 'srvport'".to_string());
+                }
+            };
+            let prio = match parse_u16_param(query_params, "srvprio", DEFAULT_SRV_PRIO) {
+                Ok(prio) => prio,
+                Err(prio_str) => {
+                    error!(prio = %prio_str, "Invalid SRV priority provided");
+                    return Err(format!("Invalid SRV priority: {}", prio_str));
+                }
+            };
+            let weight = match parse_u16_param(query_params, "srvweight", DEFAULT_SRV_WEIGHT) {
+                Ok(weight) => weight,
+                Err(weight_str) => {
+                    error!(weight = %weight_str, "Invalid SRV weight provided");
+                    return Err(format!("Invalid SRV weight: {}", weight_str));
+                }
+            };
+            Some(RecordUpdate::srv(prio, weight, port, target))
         }
-        Err(e) => {
-            error!("Invalid subdomain format: {:?}", e);
-            return Ok(json_response(400, "Invalid subdomain format"));
+        None => None,
+    };
+
+    // Process CAA record (optional)
+    let caa: Option<RecordUpdate> = match query_params.first("caa") {
+        Some(value) => {
+            if value.is_empty() || value.contains('"') {
+                error!(value, "Invalid CAA value provided");
+                return Err(format!("Invalid CAA value: {}", value));
+            }
+            let tag = query_params.first("caatag").unwrap_or(DEFAULT_CAA_TAG);
+            if !CAA_TAGS.contains(&tag) {
+                error!(tag, "Invalid CAA tag provided");
+                return Err(format!(
+                    "Invalid CAA tag: {} (expected one of {})",
+                    tag,
+                    CAA_TAGS.join(", ")
+                ));
+            }
+            let flags = match parse_u16_param(query_params, "caaflags", 0) {
+                Ok(flags) if flags <= u8::MAX as u16 => flags as u8,
+                Ok(flags) => {
+                    error!(flags, "Invalid CAA flags provided");
+                    return Err(format!("Invalid CAA flags: {}", flags));
+                }
+                Err(flags_str) => {
+                    error!(flags = %flags_str, "Invalid CAA flags provided");
+                    return Err(format!("Invalid CAA flags: {}", flags_str));
+                }
+            };
+            Some(RecordUpdate::caa(flags, tag, value))
         }
+        None => None,
     };
 
-    let credentials = Credentials::new(api_key.to_string(), secret_key.to_string());
-    let client = Client::new();
+    // Process ALIAS record (optional)
+    let alias: Option<RecordUpdate> = match query_params.first("alias") {
+        Some(target) => {
+            if !is_valid_target(target) {
+                error!(target = %target, "Invalid ALIAS target provided");
+                return Err(format!("Invalid ALIAS target: {}", target));
+            }
+            Some(RecordUpdate::alias(target.to_string()))
+        }
+        None => None,
+    };
 
-    let mut results = Vec::new();
+    // Ensure at least one record is provided
+    if ipv4.is_none()
+        && ipv6.is_none()
+        && mx.is_none()
+        && srv.is_none()
+        && caa.is_none()
+        && alias.is_none()
+    {
+        return Err(
+            "At least one record value must be provided (ip, ipv6, mx, srvtarget, caa or alias)"
+                .to_string(),
+        );
+    }
 
-    // Process each IP address
-    for ip_update in ip_updates {
-        let result = process_dns_record(
-            &client,
-            &credentials,
-            &domain,
-            &ip_update.address,
-            &ip_update.record_type,
-        )
-        .await;
+    let record_updates = [ipv4, ipv6, mx, srv, caa, alias]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
 
-        match result {
-            Ok(message) => {
-                results.push(message);
+    // Extract domains
+    let mut domains: Vec<Domain> = Vec::new();
+    for qualified_domain_name in qualified_domain_names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        match Domain::new(qualified_domain_name) {
+            Ok(domain) => {
+                info!(domain = domain.qualified_name(), "Domain to update");
+                if domain.is_wildcard() {
+                    info!(
+                        domain = domain.qualified_name(),
+                        "Domain is a wildcard, records will match all names below it"
+                    );
+                }
+                domains.push(domain);
             }
             Err(e) => {
-                error!(
-                    "Failed to process {} record: {:?}",
-                    ip_update.record_type.as_str(),
-                    e
-                );
-                // Determine status code based on error type
-                let (status_code, error_message) =
-                    if let Some(api_error) = e.downcast_ref::<ApiError>() {
-                        // API errors (Porkbun failures) return 502 Bad Gateway
-                        (502, format!("Upstream DNS service error: {}", api_error))
-                    } else {
-                        // Other errors return 500 Internal Server Error
-                        (
-                            500,
-                            format!(
-                                "Failed to process {} record",
-                                ip_update.record_type.as_str()
-                            ),
-                        )
-                    };
-                return Ok(json_response(status_code, &error_message));
+                error!(error = ?e, "Invalid subdomain format");
+                return Err(format!(
+                    "Invalid subdomain format: {}",
+                    qualified_domain_name
+                ));
             }
         }
     }
+    if domains.is_empty() {
+        return Err("Missing query-parameter 'domain'".to_string());
+    }
 
-    let success_message = results.join("; ");
-    Ok(json_response(200, &success_message))
+    // Every record is applied to every domain
+    Ok(domains
+        .into_iter()
+        .flat_map(|domain| {
+            record_updates
+                .iter()
+                .map(move |record_update| (domain.clone(), record_update.clone()))
+        })
+        .collect())
 }
 
-async fn process_dns_record(
-    client: &Client,
-    credentials: &Credentials,
-    domain: &Domain,
-    ip: &str,
-    record_type: &RecordType,
-) -> Result<String, Box<dyn std::error::Error>> {
-    // Check if the record exists
-    match get_existing_dns_record(client, credentials, domain, record_type).await {
-        // If the record exists and the IP is the same, do nothing and return a success message
-        Ok(Some(record)) if record.content == ip => {
-            info!(
-                "Skip updating, {} record with id {:?} is already up to date.",
-                record_type.as_str(),
-                record.id
-            );
-            Ok(format!(
-                "{} record {:?} is already up to date",
-                record_type.as_str(),
-                record.name
-            ))
+/// Parses the JSON body of a POST request, requests without a body are regular query-parameter requests.
+fn parse_request_body(event: &Request) -> Result<Option<RequestBody>, String> {
+    if event.method() != Method::POST || event.body().is_empty() {
+        return Ok(None);
+    }
+    let value: serde_json::Value =
+        serde_json::from_slice(event.body()).map_err(|e| format!("Invalid request body: {}", e))?;
+    match value {
+        serde_json::Value::Array(_) => serde_json::from_value(value)
+            .map(|entries| Some(RequestBody::Batch(entries)))
+            .map_err(|e| format!("Invalid batch request body: {}", e)),
+        serde_json::Value::Object(object) => Ok(Some(RequestBody::Parameters(
+            object
+                .into_iter()
+                .map(|(name, value)| match value {
+                    serde_json::Value::String(value) => (name, value),
+                    value => (name, value.to_string()),
+                })
+                .collect(),
+        ))),
+        _ => Err("Invalid request body: expected a JSON array or object".to_string()),
+    }
+}
+
+/// Lower-cases the parameter names and renames aliases (e.g. `hostname`) to the parameter they
+/// stand for, since router firmwares differ in both. A parameter sent under its own name takes
+/// precedence over its aliases.
+fn normalize_parameters(query_params: &QueryMap, aliases: &[(&str, &str)]) -> QueryMap {
+    let mut parameters: HashMap<String, Vec<String>> = HashMap::new();
+    let mut aliased = Vec::new();
+    for (name, value) in query_params.iter() {
+        let name = name.to_ascii_lowercase();
+        match aliases.iter().find(|(alias, _)| *alias == name) {
+            Some((_, target)) => aliased.push((*target, value)),
+            None => parameters.entry(name).or_default().push(value.to_string()),
         }
-        // If the record exists and the IP is different, update the record
-        Ok(Some(record)) => {
-            info!(
-                "Updating {} DNS record {:?} for domain {:?} with subdomain {:?} to IP {:?}",
-                record_type.as_str(),
-                record,
-                domain.domain_name(),
-                domain.subdomain(),
-                ip
-            );
-            update_dns_record(client, credentials, domain, &record.id, ip, record_type).await?;
-            Ok(format!(
-                "{} record '{:?}' updated successfully",
-                record_type.as_str(),
-                record.name
-            ))
+    }
+    let explicit: Vec<String> = parameters.keys().cloned().collect();
+    for (target, value) in aliased {
+        if !explicit.iter().any(|name| name == target) {
+            parameters
+                .entry(target.to_string())
+                .or_default()
+                .push(value.to_string());
         }
-        // If the record does not exist, create a new one
-        Ok(None) => {
-            info!(
-                "Creating new {} DNS record for domain {:?} with subdomain {:?} and IP {:?}",
-                record_type.as_str(),
-                domain.domain_name(),
-                domain.subdomain(),
-                ip
-            );
-            create_dns_record(client, credentials, domain, ip, record_type).await?;
-            Ok(format!(
-                "{} record for subdomain '{:?}' successfully created",
-                record_type.as_str(),
-                domain.subdomain()
-            ))
+    }
+    QueryMap::from(parameters)
+}
+
+/// Lists the parameters which aren't part of `KNOWN_PARAMETERS`, `None` if there are none.
+fn unknown_parameters_message(query_params: &QueryMap) -> Option<String> {
+    let mut unknown: Vec<&str> = query_params
+        .iter()
+        .map(|(name, _)| name)
+        .filter(|name| !KNOWN_PARAMETERS.contains(name))
+        .collect();
+    unknown.sort_unstable();
+    unknown.dedup();
+    match unknown.as_slice() {
+        [] => None,
+        [name] => Some(format!("Unknown query-parameter '{}'", name)),
+        names => Some(format!("Unknown query-parameters '{}'", names.join("', '"))),
+    }
+}
+
+/// Combines the query-parameters with the parameters of a JSON body, the latter take precedence.
+fn merge_parameters(query_params: &QueryMap, parameters: HashMap<String, String>) -> QueryMap {
+    let mut merged: HashMap<String, String> = query_params
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    merged.extend(parameters);
+    QueryMap::from(merged)
+}
+
+/// Parses the updates of a batch request, where each entry describes a single record.
+fn parse_batch_updates(entries: Vec<BatchEntry>) -> Result<Vec<(Domain, RecordUpdate)>, String> {
+    if entries.is_empty() {
+        return Err("Batch request body must contain at least one entry".to_string());
+    }
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let domain = Domain::new(&entry.domain)
+                .map_err(|_| format!("Invalid subdomain format: {}", entry.domain))?;
+            let ip_type = validate_and_classify_ip(&entry.ip)?;
+            let record_update = RecordUpdate::ip(entry.ip, ip_type).with_ttl(entry.ttl);
+            if let Some(record_type) = entry.record_type {
+                if !record_type.eq_ignore_ascii_case(record_update.record_type.as_str()) {
+                    return Err(format!(
+                        "Record type {} does not match IP address {} of domain {}",
+                        record_type, record_update.content, entry.domain
+                    ));
+                }
+            }
+            Ok((domain, record_update))
+        })
+        .collect()
+}
+
+/// Builds the outcome of a single record, API errors are reported with their status code.
+fn record_result(
+    domain: &Domain,
+    record_update: &RecordUpdate,
+    result: Result<RecordOutcome, Box<dyn std::error::Error + Send + Sync>>,
+    provider: Option<&'static str>,
+) -> ItemResult {
+    // Only addresses are reported as IPs, not e.g. the mail server of an MX record
+    let is_ip = matches!(record_update.record_type, RecordType::A | RecordType::AAAA);
+    let new_ip = is_ip.then(|| record_update.content.clone());
+    match result {
+        Ok(outcome) => ItemResult {
+            domain: domain.display_name().to_string(),
+            record_type: record_update.record_type.as_str(),
+            status_code: 200,
+            action: outcome.action,
+            message: outcome.message,
+            content: record_update.content.clone(),
+            provider,
+            record_id: outcome.record_id,
+            old_ip: outcome.old_content.filter(|_| is_ip),
+            new_ip,
+            visible: None,
+        },
+        Err(e) => {
+            error!(
+                record_type = record_update.record_type.as_str(),
+                domain = domain.qualified_name(),
+                error = ?e,
+                "Failed to process record"
+            );
+            // Determine status code based on error type
+            let (status_code, error_message) = if let Some(api_error) = e.downcast_ref::<ApiError>()
+            {
+                // API errors are mapped by their cause, e.g. 401 for invalid credentials
+                (
+                    api_error.status_code(),
+                    format!("Upstream DNS service error: {}", api_error),
+                )
+            } else {
+                // Other errors return 500 Internal Server Error
+                (
+                    500,
+                    format!(
+                        "Failed to process {} record for {}",
+                        record_update.record_type.as_str(),
+                        domain.display_name()
+                    ),
+                )
+            };
+            ItemResult {
+                domain: domain.display_name().to_string(),
+                record_type: record_update.record_type.as_str(),
+                status_code,
+                action: UpdateAction::Failed,
+                message: error_message,
+                content: record_update.content.clone(),
+                provider,
+                record_id: None,
+                old_ip: None,
+                new_ip,
+                visible: None,
+            }
+        }
+    }
+}
+
+/// Processes a record at the provider in a span of its own, which reports the action taken, and
+/// times the provider for the metrics.
+async fn process_dns_record(
+    provider: &Provider<'_>,
+    domain: &Domain,
+    record_update: &RecordUpdate,
+    fast: bool,
+    force: bool,
+) -> Result<RecordOutcome, Box<dyn std::error::Error + Send + Sync>> {
+    let span = info_span!(
+        "record",
+        domain = domain.qualified_name(),
+        record_type = record_update.record_type.as_str(),
+        provider = provider.kind().as_str(),
+        action = Empty,
+    );
+    #[cfg(all(feature = "prometheus", any(feature = "cli", feature = "server")))]
+    let started = Instant::now();
+    let result = change_dns_record(provider, domain, record_update, fast, force)
+        .instrument(span.clone())
+        .await;
+    let action = match &result {
+        Ok(outcome) => outcome.action,
+        Err(_) => UpdateAction::Failed,
+    };
+    span.record("action", action.as_str());
+    #[cfg(all(feature = "prometheus", any(feature = "cli", feature = "server")))]
+    exporter::observe_provider_duration(provider.kind().as_str(), started.elapsed());
+    result
+}
+
+async fn change_dns_record(
+    provider: &impl DnsProvider,
+    domain: &Domain,
+    record_update: &RecordUpdate,
+    fast: bool,
+    force: bool,
+) -> Result<RecordOutcome, Box<dyn std::error::Error + Send + Sync>> {
+    let content = record_update.content.as_str();
+    let record_type = &record_update.record_type;
+    // Without an existing record the fast path fails, the record is then created below
+    if fast {
+        match provider.update_records(domain, record_update).await {
+            Ok(()) => {
+                return Ok(RecordOutcome {
+                    action: UpdateAction::Updated,
+                    message: format!(
+                        "{} record '{:?}' updated successfully",
+                        record_type.as_str(),
+                        domain.display_name()
+                    ),
+                    record_id: None,
+                    old_content: None,
+                })
+            }
+            Err(ApiError::UpdateRecordFailed(
+                PorkbunError::Failed(message) | PorkbunError::Unsupported(message),
+            )) => info!(
+                domain = domain.qualified_name(),
+                error = %message,
+                "Fast update failed, falling back to lookup"
+            ),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    // Check if the record exists
+    match provider.get_record(domain, record_type).await {
+        // If the record exists and the content is the same, do nothing and return a success message
+        Ok(Some(record)) if !force && record_matches(&record, record_update) => {
+            info!(
+                record_type = record_type.as_str(),
+                record_id = %record.id,
+                "Skip updating, record is already up to date"
+            );
+            Ok(RecordOutcome {
+                action: UpdateAction::Unchanged,
+                message: format!(
+                    "{} record {:?} is already up to date",
+                    record_type.as_str(),
+                    domain.display_name()
+                ),
+                record_id: Some(record.id),
+                old_content: Some(record.content),
+            })
+        }
+        // If the record exists and the content is different, update the record
+        Ok(Some(record)) => {
+            info!(
+                record_type = record_type.as_str(),
+                record_id = %record.id,
+                domain = domain.domain_name(),
+                subdomain = domain.subdomain(),
+                old_content = %record.content,
+                content,
+                "Updating DNS record"
+            );
+            provider
+                .update_record(domain, &record.id, record_update)
+                .await?;
+            Ok(RecordOutcome {
+                action: UpdateAction::Updated,
+                message: format!(
+                    "{} record '{:?}' updated successfully",
+                    record_type.as_str(),
+                    domain.display_name()
+                ),
+                record_id: Some(record.id),
+                old_content: Some(record.content),
+            })
+        }
+        // If the record does not exist, create a new one
+        Ok(None) => {
+            info!(
+                record_type = record_type.as_str(),
+                domain = domain.domain_name(),
+                subdomain = domain.subdomain(),
+                content,
+                "Creating new DNS record"
+            );
+            let record_id = provider.create_record(domain, record_update).await?;
+            Ok(RecordOutcome {
+                action: UpdateAction::Created,
+                message: format!(
+                    "{} record for '{:?}' successfully created",
+                    record_type.as_str(),
+                    domain.display_name()
+                ),
+                record_id,
+                old_content: None,
+            })
         }
         // If there is an error, propagate it
         Err(e) => {
             error!(
-                "Failed to retrieve {} records for domain {:?}: {:?}",
-                record_type.as_str(),
-                domain.domain_name(),
-                e
+                record_type = record_type.as_str(),
+                domain = domain.domain_name(),
+                error = ?e,
+                "Failed to retrieve records"
             );
             Err(Box::new(e))
         }
     }
-}
+}
+
+/// Whether the A or AAAA record would point to an address which isn't reachable from the Internet.
+/// The configured parking addresses are intended, e.g. `0.0.0.0` for offline hosts.
+fn is_non_public_ip(record_update: &RecordUpdate, parking_ips: &[IpAddr]) -> bool {
+    matches!(record_update.record_type, RecordType::A | RecordType::AAAA)
+        && record_update
+            .content
+            .parse()
+            .is_ok_and(|ip| !is_public_ip(&ip) && !parking_ips.contains(&ip))
+}
+
+/// Parses an optional numeric query parameter, returning the raw value as error if it is not a valid number.
+fn parse_u16_param(query_params: &QueryMap, name: &str, default: u16) -> Result<u16, String> {
+    match query_params.first(name) {
+        Some(value) => value.parse::<u16>().map_err(|_| value.to_string()),
+        None => Ok(default),
+    }
+}
+
+/// Checks whether a target host name (e.g. of an MX or SRV record) is usable as record content.
+fn is_valid_target(target: &str) -> bool {
+    !target.is_empty() && !target.contains(char::is_whitespace)
+}
+
+/// Checks whether an existing record already matches the requested update.
+/// Priority and TTL are only compared when they are part of the update.
+fn record_matches(record: &DnsRecord, record_update: &RecordUpdate) -> bool {
+    let number_matches = |existing: &Option<String>, requested: Option<u64>| match requested {
+        Some(requested) => {
+            existing
+                .as_deref()
+                .and_then(|existing| existing.parse::<u64>().ok())
+                == Some(requested)
+        }
+        None => true,
+    };
+    // Addresses are compared in canonical form, `2001:db8:0:0::1` is the same as `2001:db8::1`
+    let content_matches = match record_update.record_type {
+        RecordType::A | RecordType::AAAA => {
+            canonical_ip(&record.content) == canonical_ip(&record_update.content)
+        }
+        _ => record.content == record_update.content,
+    };
+    content_matches
+        && number_matches(&record.prio, record_update.prio.map(u64::from))
+        && number_matches(&record.ttl, record_update.ttl)
+}
+
+/// Builds the DynDNS2 response with one return code per record, e.g. `good 1.2.3.4`.
+fn dyndns_response(results: &[ItemResult]) -> Response<Body> {
+    let body = results
+        .iter()
+        .map(|result| dyndns::return_code(result.action, result.status_code, &result.content))
+        .collect::<Vec<_>>()
+        .join("\n");
+    text_response(200, &body)
+}
+
+/// Reports the public IP address the request was sent from, no authentication is required.
+fn checkip_response(event: &Request) -> Response<Body> {
+    match source_ip(event) {
+        Some(ip) => Response::builder()
+            .status(200)
+            .header("Content-Type", "text/html")
+            .body(Body::Text(dyndns::checkip_body(&ip)))
+            .unwrap(),
+        None => {
+            error!("Unable to determine the IP address of the request");
+            text_response(500, "911")
+        }
+    }
+}
+
+/// Reports that porkDyn is alive, no authentication is required. With `checks=true` it also
+/// checks that Porkbun is reachable and the server-side credentials are valid (503 otherwise),
+/// which requires the access token if one is configured.
+async fn health_response(event: &Request, state: &AppState) -> Response<Body> {
+    let query_params = front_door::query_parameters(event);
+    let checks = match query_params.first("checks").map(str::parse::<bool>) {
+        None => false,
+        Some(Ok(checks)) => checks,
+        Some(Err(_)) => {
+            return json_response(
+                400,
+                "Invalid query-parameter 'checks', expected true or false",
+            )
+        }
+    };
+    if !checks {
+        return json_body_response(200, &HealthReport::from_checks(Vec::new()));
+    }
+    // The checks call Porkbun, so unauthenticated callers can't use them to exhaust the rate limit
+    if authenticate(state, query_params.first("token")).is_none() {
+        error!("Invalid or missing access token provided");
+        log_auth_failure("invalid_token", source_ip(event).as_deref(), None);
+        return json_response(401, "Invalid or missing query-parameter 'token'");
+    }
+    let report = HealthReport::from_checks(health::check_dependencies(state).await);
+    if report.status_code() != 200 {
+        warn!(report = ?report, "Health check failed");
+    }
+    json_body_response(report.status_code(), &report)
+}
+
+/// Reports the version, commit, build time and features of the binary, to tell deployments
+/// apart. Requires the access token if one is configured.
+fn version_response(event: &Request, state: &AppState) -> Response<Body> {
+    let query_params = front_door::query_parameters(event);
+    if authenticate(state, query_params.first("token")).is_none() {
+        error!("Invalid or missing access token provided");
+        log_auth_failure("invalid_token", source_ip(event).as_deref(), None);
+        return json_response(401, "Invalid or missing query-parameter 'token'");
+    }
+    json_body_response(200, &BuildInfo::current())
+}
+
+/// Serves the OpenAPI document of the endpoints, without authentication like `/health`. Its own
+/// media type keeps the request id out of the document.
+fn openapi_response() -> Response<Body> {
+    Response::builder()
+        .status(200)
+        .header("Content-Type", openapi::OPENAPI_CONTENT_TYPE)
+        .body(Body::Text(
+            ApiDoc::openapi()
+                .to_json()
+                .expect("the OpenAPI document is serializable"),
+        ))
+        .unwrap()
+}
+
+/// Handles the acme-dns compatible API, which requires server-side credentials.
+async fn acme_dns_response(
+    event: &Request,
+    state: &AppState,
+    porkbun: &PorkbunClient,
+    request: AcmeDnsRequest,
+) -> Response<Body> {
+    let (Some(config), Some(server_credentials)) = (&state.acme_dns, &state.server_credentials)
+    else {
+        error!("acme-dns request received but the acme-dns API is not enabled");
+        return error_response(ResponseFormat::Json, 404, "The acme-dns API is not enabled");
+    };
+    let update = match request {
+        AcmeDnsRequest::Register => {
+            // Registering is restricted to the global access token, if one is configured
+            let query_params = front_door::query_parameters(event);
+            if authenticate(state, query_params.first("token")) != Some(Access::All) {
+                error!("Invalid or missing access token provided");
+                log_auth_failure("invalid_token", source_ip(event).as_deref(), None);
+                return error_response(
+                    ResponseFormat::Json,
+                    401,
+                    "Invalid or missing query-parameter 'token'",
+                );
+            }
+            return json_body_response(201, &config.register());
+        }
+        AcmeDnsRequest::Update => match serde_json::from_slice::<UpdateRequest>(event.body()) {
+            Ok(update) => update,
+            Err(e) => {
+                error!(error = %e, "Invalid acme-dns update body");
+                return acme_dns_error(400, "malformed_json_payload");
+            }
+        },
+    };
+    let header = |name: &str| {
+        event
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let domain = match config.authorize_update(
+        header(acme_dns::API_USER_HEADER),
+        header("X-Api-Key"),
+        &update,
+    ) {
+        Ok(domain) => domain,
+        Err((status_code, error)) => {
+            error!(error = %error, "Rejected acme-dns update");
+            if status_code == 401 {
+                log_auth_failure(
+                    "invalid_acme_dns_key",
+                    source_ip(event).as_deref(),
+                    Some(&update.subdomain),
+                );
+            }
+            return acme_dns_error(status_code, error);
+        }
+    };
+    let credentials = match server_credentials.get().await {
+        Ok(credentials) => credentials,
+        Err(e) => {
+            error!(error = ?e, "Failed to load server-side credentials");
+            return error_response(
+                ResponseFormat::Json,
+                500,
+                "Failed to load server-side credentials",
+            );
+        }
+    };
+    let porkbun = Porkbun::new(porkbun, &credentials);
+    match acme_dns::set_challenge(&porkbun, &domain, &update.txt).await {
+        Ok((created, deleted)) => {
+            info!(
+                domain = domain.qualified_name(),
+                created, deleted, "Set acme-dns challenge"
+            );
+            // The outdated challenges deleted to make room for the new one are changes as well
+            #[cfg(feature = "dynamodb")]
+            audit::record(
+                event,
+                std::iter::repeat_n(UpdateAction::Created, usize::from(created))
+                    .chain(std::iter::repeat_n(UpdateAction::Deleted, deleted))
+                    .map(|action| Change {
+                        domain: domain.display_name().to_string(),
+                        record_type: RecordType::TXT.as_str(),
+                        action,
+                        provider: ProviderKind::Porkbun.as_str(),
+                        caller: format!(
+                            "acme-dns {}",
+                            header(acme_dns::API_USER_HEADER).unwrap_or_default()
+                        ),
+                        old_ip: None,
+                        new_ip: None,
+                    }),
+            );
+            json_body_response(200, &serde_json::json!({ "txt": update.txt }))
+        }
+        Err(e) => {
+            error!(error = ?e, "Failed to set acme-dns challenge");
+            error_response(
+                ResponseFormat::Json,
+                e.status_code(),
+                &format!("Upstream DNS service error: {}", e),
+            )
+        }
+    }
+}
+
+/// Handles `action=ping`, which reports whether the credentials are valid and the public IP
+/// address Porkbun sees, e.g. to check the setup of a new device.
+async fn ping_response(porkbun: &Porkbun<'_>, format: ResponseFormat) -> Response<Body> {
+    match porkbun.ping().await {
+        Ok(ip) => match format {
+            ResponseFormat::Json => json_body_response(
+                200,
+                &serde_json::json!({ "message": "Credentials are valid", "ip": ip }),
+            ),
+            ResponseFormat::DynDns => text_response(200, &format!("good {}", ip)),
+            ResponseFormat::Plain => text_response(200, &ip),
+        },
+        Err(ApiError::PingFailed(PorkbunError::InvalidCredentials(message))) => error_response(
+            format,
+            401,
+            &format!("Invalid Porkbun credentials: {}", message),
+        ),
+        Err(e) => {
+            error!(error = ?e, "Failed to validate credentials");
+            error_response(
+                format,
+                e.status_code(),
+                &format!("Upstream DNS service error: {}", e),
+            )
+        }
+    }
+}
+
+/// Handles `GET /records`, which lists the records of the zone of `domain`, optionally filtered
+/// by `type` (comma-separated) and `subdomain` (`@` for the apex). Only records the caller may
+/// update are listed, e.g. a token restricted to `*.lab.example.org` only sees those.
+async fn records_response(
+    porkbun: &Porkbun<'_>,
+    event: &Request,
+    query_params: &QueryMap,
+    format: ResponseFormat,
+    access: &Access<'_>,
+) -> Response<Body> {
+    let Some(name) = query_params.first("domain") else {
+        return error_response(format, 400, "Missing query-parameter 'domain'");
+    };
+    let zone = match Domain::new(name) {
+        Ok(domain) => domain.domain_name().to_string(),
+        Err(e) => {
+            error!(error = ?e, "Invalid domain format");
+            return error_response(format, 400, &format!("Invalid domain format: {}", name));
+        }
+    };
+    let record_types: Option<Vec<&str>> = query_params.first("type").map(|types| {
+        types
+            .split(',')
+            .map(str::trim)
+            .filter(|record_type| !record_type.is_empty())
+            .collect()
+    });
+    let qualified_name = query_params
+        .first("subdomain")
+        .map(|subdomain| match subdomain.trim() {
+            "" | "@" => zone.clone(),
+            subdomain => format!("{}.{}", subdomain, zone),
+        });
+
+    let records = match porkbun.list_records(&zone).await {
+        Ok(records) => records,
+        Err(e) => {
+            error!(zone = %zone, error = ?e, "Failed to list the records");
+            return error_response(
+                format,
+                e.status_code(),
+                &format!("Upstream DNS service error: {}", e),
+            );
+        }
+    };
+    let records: Vec<ExistingRecord> = records
+        .into_iter()
+        .filter(|record| {
+            record_types.as_ref().is_none_or(|record_types| {
+                record_types
+                    .iter()
+                    .any(|record_type| record_type.eq_ignore_ascii_case(&record.record_type))
+            })
+        })
+        .filter(|record| {
+            qualified_name
+                .as_ref()
+                .is_none_or(|name| name.eq_ignore_ascii_case(&record.name))
+        })
+        .filter_map(|record| {
+            let domain = Domain::new(&record.name).ok()?;
+            access
+                .allows(&domain)
+                .then(|| ExistingRecord::new(domain.display_name().to_string(), record))
+        })
+        .collect();
+    info!(records = records.len(), zone = %zone, source_ip = ?source_ip(event), "Listing records");
+    json_body_response(
+        200,
+        &RecordsBody {
+            message: &format!("{} record(s) of '{}'", records.len(), zone),
+            records: &records,
+        },
+    )
+}
+
+/// Handles `action=delete`, which removes all records of `domain` with the given `type`s
+/// (comma-separated, `A,AAAA` by default).
+async fn delete_response(
+    provider: &Provider<'_>,
+    event: &Request,
+    query_params: &QueryMap,
+    format: ResponseFormat,
+    access: &Access<'_>,
+) -> Response<Body> {
+    let (domain, record_types) = match action_target(event, query_params, format, access) {
+        Ok(target) => target,
+        Err(response) => return *response,
+    };
+
+    let mut results: Vec<ItemResult> = Vec::new();
+    for record_type in &record_types {
+        let (status_code, action, message) =
+            match provider.delete_records(&domain, record_type).await {
+                Ok(()) => (
+                    200,
+                    UpdateAction::Deleted,
+                    format!(
+                        "{} record {:?} deleted successfully",
+                        record_type.as_str(),
+                        domain.display_name()
+                    ),
+                ),
+                Err(e) => {
+                    error!(
+                        record_type = record_type.as_str(),
+                        domain = domain.qualified_name(),
+                        error = ?e,
+                        "Failed to delete record"
+                    );
+                    (
+                        e.status_code(),
+                        UpdateAction::Failed,
+                        format!("Upstream DNS service error: {}", e),
+                    )
+                }
+            };
+        results.push(ItemResult {
+            domain: domain.display_name().to_string(),
+            record_type: record_type.as_str(),
+            status_code,
+            action,
+            message,
+            content: String::new(),
+            provider: None,
+            record_id: None,
+            old_ip: None,
+            new_ip: None,
+            visible: None,
+        });
+    }
+
+    #[cfg(feature = "dynamodb")]
+    audit::record(
+        event,
+        audit_changes(
+            &results,
+            provider.kind(),
+            &audit::caller(event, query_params.first("token")),
+        ),
+    );
+    formatted_results_response(format, results, Vec::new())
+}
+
+/// Handles `action=check`, which reports the existing records of `domain` with the given `type`s
+/// (comma-separated, `A,AAAA` by default) without changing anything, e.g. for monitoring.
+/// Fails with 404 if none of them exists.
+async fn check_response(
+    provider: &impl DnsProvider,
+    event: &Request,
+    query_params: &QueryMap,
+    format: ResponseFormat,
+    access: &Access<'_>,
+) -> Response<Body> {
+    let (domain, record_types) = match action_target(event, query_params, format, access) {
+        Ok(target) => target,
+        Err(response) => return *response,
+    };
+
+    let mut records: Vec<ExistingRecord> = Vec::new();
+    for record_type in &record_types {
+        match provider.get_record(&domain, record_type).await {
+            // Not every provider reports the type of its records
+            Ok(Some(record)) => records.push(ExistingRecord {
+                record_type: record_type.as_str().to_string(),
+                ..ExistingRecord::new(domain.display_name().to_string(), record)
+            }),
+            Ok(None) => {}
+            Err(e) => {
+                error!(
+                    record_type = record_type.as_str(),
+                    domain = domain.qualified_name(),
+                    error = ?e,
+                    "Failed to retrieve record"
+                );
+                return error_response(
+                    format,
+                    e.status_code(),
+                    &format!("Upstream DNS service error: {}", e),
+                );
+            }
+        }
+    }
+    let record_types = record_types
+        .iter()
+        .map(RecordType::as_str)
+        .collect::<Vec<_>>()
+        .join("/");
+    if records.is_empty() {
+        return error_response(
+            format,
+            404,
+            &format!(
+                "No {} record found for '{}'",
+                record_types,
+                domain.display_name()
+            ),
+        );
+    }
+
+    match format {
+        ResponseFormat::Json => json_body_response(
+            200,
+            &RecordsBody {
+                message: &format!(
+                    "{} {} record(s) found for '{}'",
+                    records.len(),
+                    record_types,
+                    domain.display_name()
+                ),
+                records: &records,
+            },
+        ),
+        // Nothing was changed, just like an update with the current content
+        ResponseFormat::DynDns => text_response(
+            200,
+            &records
+                .iter()
+                .map(|record| format!("nochg {}", record.content))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        ResponseFormat::Plain => text_response(
+            200,
+            &records
+                .iter()
+                .map(|record| record.content.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+    }
+}
+
+/// The domain (`domain` or `hostname`) and record types (`type`, comma-separated, `A,AAAA` by
+/// default) an action applies to, or the (boxed) error response if they are invalid or not allowed.
+fn action_target(
+    event: &Request,
+    query_params: &QueryMap,
+    format: ResponseFormat,
+    access: &Access<'_>,
+) -> Result<(Domain, Vec<RecordType>), Box<Response<Body>>> {
+    let Some(name) = query_params
+        .first("domain")
+        .or_else(|| query_params.first("hostname"))
+    else {
+        return Err(Box::new(error_response(
+            format,
+            400,
+            "Missing query-parameter 'domain'",
+        )));
+    };
+    let domain = match Domain::new(name) {
+        Ok(domain) if access.allows(&domain) => domain,
+        Ok(domain) => {
+            return Err(Box::new(domain_not_allowed_response(
+                event, format, access, &domain,
+            )))
+        }
+        Err(e) => {
+            error!(error = ?e, "Invalid subdomain format");
+            return Err(Box::new(error_response(
+                format,
+                400,
+                &format!("Invalid subdomain format: {}", name),
+            )));
+        }
+    };
+    let record_types = query_params
+        .first("type")
+        .unwrap_or("A,AAAA")
+        .split(',')
+        .map(|record_type| record_type.trim().parse::<RecordType>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|message| {
+            error!(error = %message, "Invalid record type provided");
+            Box::new(error_response(format, 400, &message))
+        })?;
+    Ok((domain, record_types))
+}
+
+/// Rejects a request for a domain the server doesn't manage or the caller's token may not update.
+fn domain_not_allowed_response(
+    event: &Request,
+    format: ResponseFormat,
+    access: &Access<'_>,
+    domain: &Domain,
+) -> Response<Body> {
+    if access.server_denies(domain) {
+        error!(
+            domain = domain.qualified_name(),
+            "Domain is not in the allowed domains"
+        );
+        log_auth_failure(
+            "domain_not_configured",
+            source_ip(event).as_deref(),
+            Some(domain.qualified_name()),
+        );
+        return error_response(
+            format,
+            403,
+            &format!(
+                "Domain '{}' is not allowed on this server",
+                domain.display_name()
+            ),
+        );
+    }
+    error!(
+        domain = domain.qualified_name(),
+        "Token is not allowed to update the domain"
+    );
+    log_auth_failure(
+        "domain_not_allowed",
+        source_ip(event).as_deref(),
+        Some(domain.qualified_name()),
+    );
+    error_response(
+        format,
+        403,
+        &format!(
+            "Token is not allowed to update domain '{}'",
+            domain.display_name()
+        ),
+    )
+}
+
+/// Handles `action=acme-set` and `action=acme-clear`, which create or delete the ACME DNS-01
+/// challenge TXT record (`txt`) of `_acme-challenge.<domain>`.
+async fn acme_action_response(
+    provider: &Provider<'_>,
+    event: &Request,
+    query_params: &QueryMap,
+    format: ResponseFormat,
+    access: &Access<'_>,
+    action: &str,
+) -> Response<Body> {
+    let set = match action {
+        "acme-set" => true,
+        "acme-clear" => false,
+        _ => {
+            error!(action, "Invalid action provided");
+            return error_response(
+                format,
+                400,
+                &format!("Invalid query-parameter 'action': {}", action),
+            );
+        }
+    };
+    let Some(name) = query_params
+        .first("domain")
+        .or_else(|| query_params.first("hostname"))
+    else {
+        return error_response(format, 400, "Missing query-parameter 'domain'");
+    };
+    let domain = match Domain::new(name).and_then(|domain| {
+        let challenge = acme_dns::challenge_domain(&domain)?;
+        Ok((domain, challenge))
+    }) {
+        Ok((domain, challenge)) if access.allows(&domain) => challenge,
+        Ok((domain, _)) => return domain_not_allowed_response(event, format, access, &domain),
+        Err(e) => {
+            error!(error = ?e, "Invalid subdomain format");
+            return error_response(format, 400, &format!("Invalid subdomain format: {}", name));
+        }
+    };
+    let txt = query_params.first("txt");
+    if let Some(txt) = txt.filter(|txt| !acme_dns::is_valid_challenge(txt)) {
+        error!(txt, "Invalid ACME challenge provided");
+        return error_response(format, 400, &format!("Invalid ACME challenge: {}", txt));
+    }
+
+    // The number of TXT records created or deleted
+    let result = match (set, txt) {
+        (true, Some(txt)) => acme_dns::add_challenge(provider, &domain, txt)
+            .await
+            .map(usize::from),
+        (true, None) => return error_response(format, 400, "Missing query-parameter 'txt'"),
+        (false, txt) => acme_dns::clear_challenge(provider, &domain, txt).await,
+    };
+    let changed = match result {
+        Ok(changed) => changed,
+        Err(e) => {
+            error!(action, error = ?e, "Failed to change ACME challenge");
+            return error_response(
+                format,
+                e.status_code(),
+                &format!("Upstream DNS service error: {}", e),
+            );
+        }
+    };
+    let message = match (set, changed) {
+        (true, 0) => format!(
+            "TXT record {:?} is already up to date",
+            domain.display_name()
+        ),
+        (true, _) => format!(
+            "TXT record {:?} created successfully",
+            domain.display_name()
+        ),
+        (false, deleted) => format!(
+            "{} TXT record(s) of {:?} deleted successfully",
+            deleted,
+            domain.display_name()
+        ),
+    };
+    let response = match format {
+        ResponseFormat::Json => json_response(200, &message),
+        ResponseFormat::DynDns | ResponseFormat::Plain => text_response(200, "ok"),
+    };
+    #[cfg(feature = "dynamodb")]
+    audit::record(
+        event,
+        (0..changed).map(|_| Change {
+            domain: domain.display_name().to_string(),
+            record_type: RecordType::TXT.as_str(),
+            action: if set {
+                UpdateAction::Created
+            } else {
+                UpdateAction::Deleted
+            },
+            provider: provider.kind().as_str(),
+            caller: audit::caller(event, query_params.first("token")),
+            old_ip: None,
+            new_ip: None,
+        }),
+    );
+    response
+}
+
+/// acme-dns reports errors as `{"error": "<code>"}`.
+fn acme_dns_error(status_code: u16, error: &str) -> Response<Body> {
+    let response = json_body_response(status_code, &serde_json::json!({ "error": error }));
+    match FailureReason::from_status(status_code) {
+        Some(reason) => metrics::attach_failure(response, reason),
+        None => response,
+    }
+}
+
+/// Builds the response of a request which failed as a whole in the requested format.
+fn error_response(format: ResponseFormat, status_code: u16, message: &str) -> Response<Body> {
+    let response = match format {
+        ResponseFormat::Json => json_response(status_code, message),
+        // DynDNS2 clients read the return code from the body, only authentication failures keep their status
+        ResponseFormat::DynDns => match dyndns::error_code(status_code) {
+            "badauth" => text_response(401, "badauth"),
+            code => text_response(200, code),
+        },
+        ResponseFormat::Plain => text_response(status_code, plain_status(status_code)),
+    };
+    match FailureReason::from_status(status_code) {
+        Some(reason) => metrics::attach_failure(response, reason),
+        None => response,
+    }
+}
+
+/// Forbids caching the response and content sniffing by browsers.
+pub(crate) fn add_security_headers(headers: &mut HeaderMap) {
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+}
+
+/// Answers a CORS preflight, rejecting origins which aren't allowed to call.
+fn preflight_response(event: &Request, state: &AppState, methods: &[Method]) -> Response<Body> {
+    let origin = event.headers().get(ORIGIN);
+    if origin
+        .and_then(|origin| cors::allowed_origin(&state.cors, origin))
+        .is_some()
+    {
+        return cors::preflight(&state.cors, methods);
+    }
+    error!(origin = ?origin, "Preflight from an origin which isn't allowed");
+    json_response(403, "Origin not allowed")
+}
+
+/// Rejects a request with a method the endpoint doesn't accept, listing the allowed ones.
+fn method_not_allowed_response(allowed: &[Method]) -> Response<Body> {
+    let allowed = allowed
+        .iter()
+        .map(Method::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut response = json_response(405, &format!("Method not allowed, use {}", allowed));
+    response.headers_mut().insert(
+        ALLOW,
+        allowed
+            .parse()
+            .expect("method names are valid header values"),
+    );
+    response
+}
+
+fn json_response(status_code: u16, message: &str) -> Response<Body> {
+    json_body_response(status_code, &MessageBody { message })
+}
+
+fn json_body_response(status_code: u16, response_body: &impl Serialize) -> Response<Body> {
+    Response::builder()
+        .status(status_code)
+        .header("Content-Type", "application/json")
+        .body(Body::Text(
+            serde_json::to_string(response_body).expect("response bodies are serializable"),
+        ))
+        .unwrap()
+}
+
+fn text_response(status_code: u16, body: &str) -> Response<Body> {
+    Response::builder()
+        .status(status_code)
+        .header("Content-Type", "text/plain; charset=utf-8")
+        .body(Body::Text(body.to_string()))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acme_dns::AcmeDnsConfig;
+    use crate::config::Cors;
+    use crate::ip_utils::RecordType;
+    use crate::secrets::ServerCredentials;
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayV2httpRequestContext;
+    use lambda_http::request::RequestContext;
+    use lambda_http::{Request, RequestExt};
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_without_query_strings() {
+        let request = Request::default();
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+        assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+
+        let body_bytes = response.body().to_vec();
+        let body_string = String::from_utf8(body_bytes).unwrap();
+        let body_json = serde_json::from_str::<serde_json::Value>(&body_string).unwrap();
+
+        assert_eq!(
+            body_json["message"].as_str().unwrap(),
+            "Missing query-parameter 'apikey'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_missing_secret_api_key() {
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("apikey".into(), "porkDyn".into());
+
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let body_bytes = response.body().to_vec();
+        let body_string = String::from_utf8(body_bytes).unwrap();
+        let body_json = serde_json::from_str::<serde_json::Value>(&body_string).unwrap();
+
+        assert_eq!(
+            body_json["message"].as_str().unwrap(),
+            "Missing query-parameter 'secretapikey'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_invalid_basic_auth() {
+        let mut request = Request::default();
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, "Bearer token".parse().unwrap());
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+    }
+
+    #[tokio::test]
+    async fn test_with_basic_auth_and_missing_domain() {
+        let mut request = Request::default();
+        request.headers_mut().insert(
+            AUTHORIZATION,
+            "Basic cG9ya0R5bjpzZWNyZXQ=".parse().unwrap(), // porkDyn:secret
+        );
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let body_bytes = response.body().to_vec();
+        let body_string = String::from_utf8(body_bytes).unwrap();
+        let body_json = serde_json::from_str::<serde_json::Value>(&body_string).unwrap();
+
+        assert_eq!(
+            body_json["message"].as_str().unwrap(),
+            "Missing query-parameter 'domain'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_incomplete_credential_headers() {
+        let mut request = Request::default();
+        request
+            .headers_mut()
+            .insert(API_KEY_HEADER, "porkDyn".parse().unwrap());
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let body_bytes = response.body().to_vec();
+        let body_string = String::from_utf8(body_bytes).unwrap();
+        let body_json = serde_json::from_str::<serde_json::Value>(&body_string).unwrap();
+
+        assert_eq!(
+            body_json["message"].as_str().unwrap(),
+            "Missing header 'X-Secret-Api-Key'"
+        );
+    }
+
+    #[test]
+    fn test_credential_headers_preferred_over_query_parameters() {
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("apikey".into(), "query-key".into());
+        query_string_parameters.insert("secretapikey".into(), "query-secret".into());
+        let mut request = Request::default().with_query_string_parameters(query_string_parameters);
+        request
+            .headers_mut()
+            .insert(API_KEY_HEADER, "header-key".parse().unwrap());
+        request
+            .headers_mut()
+            .insert(SECRET_API_KEY_HEADER, "header-secret".parse().unwrap());
+
+        let credentials =
+            extract_credentials(&request, &request.query_string_parameters()).unwrap();
+        assert_eq!(credentials.api_key(), "header-key");
+        assert_eq!(credentials.secret_key(), "header-secret");
+    }
+
+    #[tokio::test]
+    async fn test_with_server_credentials_and_missing_domain() {
+        let state = AppState {
+            server_credentials: Some(ServerCredentials::Static(Credentials::new(
+                "porkDyn".into(),
+                "secret".into(),
+            ))),
+            ..AppState::default()
+        };
+
+        let response = function_handler(Request::default(), &state).await.unwrap();
+        assert_eq!(response.status(), 400);
+
+        let body_bytes = response.body().to_vec();
+        let body_string = String::from_utf8(body_bytes).unwrap();
+        let body_json = serde_json::from_str::<serde_json::Value>(&body_string).unwrap();
+
+        assert_eq!(
+            body_json["message"].as_str().unwrap(),
+            "Missing query-parameter 'domain'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_invalid_access_token() {
+        let state = AppState {
+            server_credentials: Some(ServerCredentials::Static(Credentials::new(
+                "porkDyn".into(),
+                "secret".into(),
+            ))),
+            access_token: Some("router-token".into()),
+            ..AppState::default()
+        };
+
+        for token in [None, Some("wrong-token")] {
+            let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+            query_string_parameters.insert("domain".into(), "me.example.org".into());
+            if let Some(token) = token {
+                query_string_parameters.insert("token".into(), token.into());
+            }
+            let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+            let response = function_handler(request, &state).await.unwrap();
+            assert_eq!(response.status(), 401);
+        }
+
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("token".into(), "router-token".into());
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 400);
+
+        // Scheduled updates are built in-process and don't carry a token
+        let mut request = Request::default();
+        request.extensions_mut().insert(InternalRequest);
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn test_with_token_not_allowed_for_domain() {
+        let mut token_domains = HashMap::new();
+        token_domains.insert(
+            "router-token".to_string(),
+            vec!["home.example.org".to_string()],
+        );
+        let state = AppState {
+            server_credentials: Some(ServerCredentials::Static(Credentials::new(
+                "porkDyn".into(),
+                "secret".into(),
+            ))),
+            token_domains,
+            ..AppState::default()
+        };
+
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("token".into(), "router-token".into());
+        query_string_parameters.insert("domain".into(), "home.example.org,vpn.example.org".into());
+        query_string_parameters.insert("ip".into(), "1.2.3.4".into());
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 403);
+        assert_eq!(
+            response_json(&response)["message"],
+            "Token is not allowed to update domain 'vpn.example.org'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_domain_not_in_allowed_domains() {
+        let state = AppState {
+            server_credentials: Some(ServerCredentials::Static(Credentials::new(
+                "porkDyn".into(),
+                "secret".into(),
+            ))),
+            allowed_domains: vec!["*.example.com".to_string()],
+            ..AppState::default()
+        };
+
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("domain".into(), "home.example.org".into());
+        query_string_parameters.insert("ip".into(), "1.2.3.4".into());
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 403);
+        assert_eq!(
+            response_json(&response)["message"],
+            "Domain 'home.example.org' is not allowed on this server"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_encrypted_credentials_not_enabled() {
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("credentials".into(), "AQICAHg=".into());
+        query_string_parameters.insert("domain".into(), "me.example.org".into());
+        query_string_parameters.insert("ip".into(), "1.2.3.4".into());
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(
+            response_json(&response)["message"],
+            "Encrypted credentials are not enabled on this server"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_nic_update_with_missing_hostname() {
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("myip".into(), "1.2.3.4".into());
+        let mut request = Request::default()
+            .with_query_string_parameters(query_string_parameters)
+            .with_raw_http_path("/nic/update");
+        request.headers_mut().insert(
+            AUTHORIZATION,
+            "Basic cG9ya0R5bjpzZWNyZXQ=".parse().unwrap(), // porkDyn:secret
+        );
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body().to_vec(), b"notfqdn");
+    }
+
+    #[tokio::test]
+    async fn test_nic_update_with_invalid_token() {
+        let state = AppState {
+            server_credentials: Some(ServerCredentials::Static(Credentials::new(
+                "porkDyn".into(),
+                "secret".into(),
+            ))),
+            access_token: Some("router-token".into()),
+            ..AppState::default()
+        };
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("hostname".into(), "home.example.org".into());
+        let mut request = Request::default()
+            .with_query_string_parameters(query_string_parameters)
+            .with_raw_http_path("/nic/update");
+        request.headers_mut().insert(
+            AUTHORIZATION,
+            "Basic cG9ya0R5bjpzZWNyZXQ=".parse().unwrap(), // porkDyn:secret
+        );
+
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 401);
+        assert_eq!(response.body().to_vec(), b"badauth");
+    }
+
+    #[test]
+    fn test_route() {
+        let route = |path: &str| Protocol::route(&Request::default().with_raw_http_path(path));
+        let staged = |path: &str| {
+            let mut context = ApiGatewayV2httpRequestContext::default();
+            context.stage = Some("prod".to_string());
+            Protocol::route(
+                &Request::default()
+                    .with_raw_http_path(path)
+                    .with_request_context(RequestContext::ApiGatewayV2(context)),
+            )
+        };
+        assert_eq!(route(""), Some(Protocol::Native));
+        assert_eq!(route("/update/"), Some(Protocol::Native));
+        assert_eq!(route("/nic/update"), Some(Protocol::DynDns2));
+        assert_eq!(staged("/prod/nic/update/"), Some(Protocol::DynDns2));
+        assert_eq!(staged("/prod/fritzbox/"), Some(Protocol::FritzBox));
+        assert_eq!(route("/checkip"), Some(Protocol::CheckIp));
+        assert_eq!(staged("/prod/webhook/"), Some(Protocol::Webhook));
+        assert_eq!(route("/records"), Some(Protocol::Records));
+        assert_eq!(staged("/prod/health/"), Some(Protocol::Health));
+        assert_eq!(staged("/records"), Some(Protocol::Records));
+        assert_eq!(staged("/prod/"), Some(Protocol::Native));
+        assert_eq!(route("/version"), Some(Protocol::Version));
+        assert_eq!(route("/openapi.json"), Some(Protocol::OpenApi));
+        assert_eq!(
+            route("/register"),
+            Some(Protocol::AcmeDns(AcmeDnsRequest::Register))
+        );
+        assert_eq!(route("/nic"), None);
+        assert_eq!(route("/favicon.ico"), None);
+        assert_eq!(route("/prod"), None);
+        assert_eq!(route("/x/records"), None);
+        assert_eq!(route("/prod/nic/update"), None);
+        assert_eq!(staged("/production/records"), None);
+        assert_eq!(staged("/prod/x/records"), None);
+
+        let mut request = Request::default().with_raw_http_path("/update/");
+        request
+            .headers_mut()
+            .insert(acme_dns::API_USER_HEADER, "acme-user".parse().unwrap());
+        assert_eq!(
+            Protocol::route(&request),
+            Some(Protocol::AcmeDns(AcmeDnsRequest::Update))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_method_not_allowed() {
+        for method in [Method::PUT, Method::DELETE, Method::HEAD] {
+            let mut request = batch_request("[]");
+            *request.method_mut() = method;
+            let response = function_handler(request, &AppState::default())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 405);
+            assert_eq!(response.headers()[ALLOW], "GET, POST");
+            assert_eq!(
+                response_json(&response)["message"],
+                "Method not allowed, use GET, POST"
+            );
+        }
+
+        let mut request = Request::default().with_raw_http_path("/health");
+        *request.method_mut() = Method::HEAD;
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_cors() {
+        let preflight = |origin: &str| {
+            let mut request = Request::default().with_raw_http_path("/records");
+            *request.method_mut() = Method::OPTIONS;
+            let headers = request.headers_mut();
+            headers.insert(ORIGIN, origin.parse().unwrap());
+            headers.insert("Access-Control-Request-Method", "GET".parse().unwrap());
+            request
+        };
+        // Without configured origins preflights are rejected like any other OPTIONS request
+        let response =
+            function_handler(preflight("https://dash.example.com"), &AppState::default())
+                .await
+                .unwrap();
+        assert_eq!(response.status(), 405);
+        assert!(!response
+            .headers()
+            .contains_key("Access-Control-Allow-Origin"));
+
+        let state = AppState {
+            cors: Cors {
+                origins: vec!["https://dash.example.com".to_string()],
+                methods: vec![],
+            },
+            ..AppState::default()
+        };
+        let response = function_handler(preflight("https://dash.example.com"), &state)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 204);
+        assert_eq!(
+            response.headers()["Access-Control-Allow-Origin"],
+            "https://dash.example.com"
+        );
+        assert_eq!(response.headers()["Access-Control-Allow-Methods"], "GET");
+
+        let response = function_handler(preflight("https://evil.example.net"), &state)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+        assert!(!response
+            .headers()
+            .contains_key("Access-Control-Allow-Origin"));
+
+        // Actual requests carry the headers as well, also if they fail
+        let mut request = Request::default();
+        request
+            .headers_mut()
+            .insert(ORIGIN, "https://dash.example.com".parse().unwrap());
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(
+            response.headers()["Access-Control-Allow-Origin"],
+            "https://dash.example.com"
+        );
+        assert_eq!(
+            response.headers()["Access-Control-Expose-Headers"],
+            "X-Request-Id"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_path() {
+        let request = Request::default().with_raw_http_path("/wp-login.php");
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+        assert_eq!(
+            response_json(&response)["message"],
+            "Unknown path '/wp-login.php'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checkip() {
+        let mut request = Request::default().with_raw_http_path("/checkip");
+        request
+            .headers_mut()
+            .insert("X-Forwarded-For", "203.0.113.7".parse().unwrap());
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body_string = String::from_utf8(response.body().to_vec()).unwrap();
+        assert!(body_string.contains("Current IP Address: 203.0.113.7"));
+
+        let request = Request::default().with_raw_http_path("/checkip");
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 500);
+
+        let mut request = Request::default().with_raw_http_path("/checkip");
+        request
+            .headers_mut()
+            .insert("X-Forwarded-For", "<b>not-an-ip</b>".parse().unwrap());
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 500);
+        assert_eq!(response.body().to_vec(), b"911");
+    }
+
+    #[tokio::test]
+    async fn test_health() {
+        let request = Request::default().with_raw_http_path("/health");
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response_json(&response)["status"], "ok");
+
+        let state = AppState {
+            access_token: Some("secret-token".into()),
+            ..AppState::default()
+        };
+        let request = Request::default()
+            .with_query_string_parameters(HashMap::from([(
+                "checks".to_string(),
+                "true".to_string(),
+            )]))
+            .with_raw_http_path("/health");
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 401);
+
+        let request = Request::default()
+            .with_query_string_parameters(HashMap::from([(
+                "checks".to_string(),
+                "porkbun".to_string(),
+            )]))
+            .with_raw_http_path("/health");
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 400);
+
+        let request = batch_request("[]").with_raw_http_path("/health");
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 405);
+    }
+
+    #[tokio::test]
+    async fn test_version() {
+        let request = Request::default().with_raw_http_path("/version");
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response_json(&response)["version"],
+            env!("CARGO_PKG_VERSION")
+        );
+
+        let state = AppState {
+            access_token: Some("secret-token".into()),
+            ..AppState::default()
+        };
+        let request = Request::default().with_raw_http_path("/version");
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 401);
+    }
+
+    #[tokio::test]
+    async fn test_openapi() {
+        // The document is public and isn't tagged with the request id
+        let state = AppState {
+            access_token: Some("secret-token".into()),
+            ..AppState::default()
+        };
+        let request = Request::default().with_raw_http_path("/openapi.json");
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()["Content-Type"],
+            openapi::OPENAPI_CONTENT_TYPE
+        );
+        let document = response_json(&response);
+        assert!(document["paths"]["/nic/update"].is_object());
+        assert!(document.get("request_id").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_acme_dns() {
+        let request = Request::default().with_raw_http_path("/register");
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        let state = AppState {
+            server_credentials: Some(ServerCredentials::Static(Credentials::new(
+                "porkDyn".into(),
+                "secret".into(),
+            ))),
+            access_token: Some("router-token".into()),
+            acme_dns: Some(AcmeDnsConfig::new("acme.example.org", "acme-secret")),
+            ..AppState::default()
+        };
+        let request = Request::default().with_raw_http_path("/register");
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 401);
+
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("token".into(), "router-token".into());
+        let request = Request::default()
+            .with_query_string_parameters(query_string_parameters)
+            .with_raw_http_path("/register");
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 201);
+        let account = response_json(&response);
+
+        let mut request = Request::new(Body::Text(format!(
+            r#"{{"subdomain": "{}", "txt": "short"}}"#,
+            account["subdomain"].as_str().unwrap()
+        )))
+        .with_raw_http_path("/update");
+        let headers = request.headers_mut();
+        headers.insert(
+            "X-Api-User",
+            account["username"].as_str().unwrap().parse().unwrap(),
+        );
+        headers.insert("X-Api-Key", "wrong-key".parse().unwrap());
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 401);
+        assert_eq!(response_json(&response)["error"], "forbidden");
+    }
+
+    #[tokio::test]
+    async fn test_acme_action_validation() {
+        let state = AppState {
+            server_credentials: Some(ServerCredentials::Static(Credentials::new(
+                "porkDyn".into(),
+                "secret".into(),
+            ))),
+            ..AppState::default()
+        };
+        for (action, txt, message) in [
+            (
+                "acme-renew",
+                None,
+                "Invalid query-parameter 'action': acme-renew",
+            ),
+            ("acme-set", None, "Missing query-parameter 'txt'"),
+            ("acme-set", Some("short"), "Invalid ACME challenge: short"),
+        ] {
+            let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+            query_string_parameters.insert("domain".into(), "example.org".into());
+            query_string_parameters.insert("action".into(), action.into());
+            if let Some(txt) = txt {
+                query_string_parameters.insert("txt".into(), txt.into());
+            }
+            let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+            let response = function_handler(request, &state).await.unwrap();
+            assert_eq!(response.status(), 400);
+            assert_eq!(response_json(&response)["message"], message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_and_check_validation() {
+        let state = AppState {
+            server_credentials: Some(ServerCredentials::Static(Credentials::new(
+                "porkDyn".into(),
+                "secret".into(),
+            ))),
+            token_domains: HashMap::from([(
+                "lab-token".to_string(),
+                vec!["*.lab.example.org".to_string()],
+            )]),
+            ..AppState::default()
+        };
+        for (domain, record_type, status, message) in [
+            (
+                "me.lab.example.org",
+                "A,PTR",
+                400,
+                "Invalid record type: PTR",
+            ),
+            (
+                "home.example.org",
+                "A",
+                403,
+                "Token is not allowed to update domain 'home.example.org'",
+            ),
+        ] {
+            for action in ["delete", "check"] {
+                let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+                query_string_parameters.insert("token".into(), "lab-token".into());
+                query_string_parameters.insert("action".into(), action.into());
+                query_string_parameters.insert("domain".into(), domain.into());
+                query_string_parameters.insert("type".into(), record_type.into());
+                let request =
+                    Request::default().with_query_string_parameters(query_string_parameters);
+
+                let response = function_handler(request, &state).await.unwrap();
+                assert_eq!(response.status(), status);
+                assert_eq!(response_json(&response)["message"], message);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provider_selection() {
+        let state = AppState {
+            server_credentials: Some(ServerCredentials::Static(Credentials::new(
+                "porkDyn".into(),
+                "secret".into(),
+            ))),
+            ..AppState::default()
+        };
+        for (provider, status, message) in [
+            ("gandi", 400, "Invalid provider: gandi"),
+            (
+                "route53",
+                400,
+                if cfg!(feature = "route53") {
+                    "Route 53 is not enabled on this server"
+                } else {
+                    "Route 53 is not included in this build"
+                },
+            ),
+            ("desec", 400, "deSEC is not configured on this server"),
+            ("hetzner", 400, "Hetzner is not configured on this server"),
+            (
+                "cloudflare",
+                400,
+                if cfg!(feature = "cloudflare") {
+                    "Cloudflare is not configured on this server"
+                } else {
+                    "Cloudflare is not included in this build"
+                },
+            ),
+        ] {
+            let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+            query_string_parameters.insert("provider".into(), provider.into());
+            query_string_parameters.insert("domain".into(), "home.example.org".into());
+            query_string_parameters.insert("ip".into(), "1.2.3.4".into());
+            let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+            let response = function_handler(request, &state).await.unwrap();
+            assert_eq!(response.status(), status);
+            assert_eq!(response_json(&response)["message"], message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lambda_deadline() {
+        let state = AppState {
+            server_credentials: Some(ServerCredentials::Static(Credentials::new(
+                "porkDyn".into(),
+                "secret".into(),
+            ))),
+            ..AppState::default()
+        };
+        // Too little time left to call Porkbun, so the record fails without a call being sent
+        let deadline = std::time::SystemTime::now() + std::time::Duration::from_millis(500);
+        let mut context = lambda_http::Context::default();
+        context.deadline = deadline
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("domain".into(), "home.example.org".into());
+        query_string_parameters.insert("ip".into(), "1.2.3.4".into());
+        let request = Request::default()
+            .with_query_string_parameters(query_string_parameters)
+            .with_lambda_context(context);
+
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 504);
+        assert!(response_json(&response)["message"]
+            .as_str()
+            .unwrap()
+            .contains("Deadline exceeded"));
+    }
+
+    #[tokio::test]
+    async fn test_fritzbox_without_ipv6() {
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("username".into(), "porkDyn".into());
+        query_string_parameters.insert("domain".into(), "home.example.org".into());
+        query_string_parameters.insert("ipaddr".into(), "".into());
+        query_string_parameters.insert("ip6addr".into(), "".into());
+        let request = Request::default()
+            .with_query_string_parameters(query_string_parameters)
+            .with_raw_http_path("/fritzbox");
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(
+            response_json(&response)["message"],
+            "Missing query-parameter 'secretapikey'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webhook_without_domains() {
+        let request = batch_request(
+            r#"{"action": "ip_changed", "old_ip": "198.51.100.1", "new_ip": "203.0.113.7"}"#,
+        )
+        .with_raw_http_path("/webhook");
+
+        // The payload's `action` is not mistaken for a parameter, the domains are missing
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(
+            response_json(&response)["message"],
+            "Missing query-parameter 'domain'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_records_validation() {
+        let request = batch_request("[]").with_raw_http_path("/records");
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 405);
+        assert_eq!(response.headers()[ALLOW], "GET");
+
+        let query_string_parameters = HashMap::from([
+            ("apikey".to_string(), "porkDyn".to_string()),
+            ("secretapikey".to_string(), "secret".to_string()),
+        ]);
+        let request = Request::default()
+            .with_query_string_parameters(query_string_parameters.clone())
+            .with_raw_http_path("/records");
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(
+            response_json(&response)["message"],
+            "Missing query-parameter 'domain'"
+        );
+
+        let mut query_string_parameters = query_string_parameters;
+        query_string_parameters.insert("domain".into(), "example.org".into());
+        query_string_parameters.insert("provider".into(), "desec".into());
+        let request = Request::default()
+            .with_query_string_parameters(query_string_parameters)
+            .with_raw_http_path("/records");
+        let state = AppState {
+            desec_token: Some("desec-token".into()),
+            ..AppState::default()
+        };
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(
+            response_json(&response)["message"],
+            "/records is only supported for Porkbun"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_missing_domain() {
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("apikey".into(), "porkDyn".into());
+        query_string_parameters.insert("secretapikey".into(), "secret".into());
+
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let body_bytes = response.body().to_vec();
+        let body_string = String::from_utf8(body_bytes).unwrap();
+        let body_json = serde_json::from_str::<serde_json::Value>(&body_string).unwrap();
+
+        assert_eq!(
+            body_json["message"].as_str().unwrap(),
+            "Missing query-parameter 'domain'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_missing_ip() {
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("apikey".into(), "porkDyn".into());
+        query_string_parameters.insert("secretapikey".into(), "secret".into());
+        query_string_parameters.insert("domain".into(), "me.example.org".into());
+
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let body_bytes = response.body().to_vec();
+        let body_string = String::from_utf8(body_bytes).unwrap();
+        let body_json = serde_json::from_str::<serde_json::Value>(&body_string).unwrap();
+
+        assert_eq!(
+            body_json["message"].as_str().unwrap(),
+            "At least one record value must be provided (ip, ipv6, mx, srvtarget, caa or alias)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_invalid_ipv4() {
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("apikey".into(), "porkDyn".into());
+        query_string_parameters.insert("secretapikey".into(), "secret".into());
+        query_string_parameters.insert("domain".into(), "me.example.org".into());
+        query_string_parameters.insert("ip".into(), "invalid_ip".into());
+
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let body_bytes = response.body().to_vec();
+        let body_string = String::from_utf8(body_bytes).unwrap();
+        let body_json = serde_json::from_str::<serde_json::Value>(&body_string).unwrap();
+
+        assert!(body_json["message"]
+            .as_str()
+            .unwrap()
+            .contains("Invalid IPv4 address"));
+    }
+
+    #[tokio::test]
+    async fn test_with_invalid_ipv6() {
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("apikey".into(), "porkDyn".into());
+        query_string_parameters.insert("secretapikey".into(), "secret".into());
+        query_string_parameters.insert("domain".into(), "me.example.org".into());
+        query_string_parameters.insert("ipv6".into(), "invalid_ipv6".into());
+
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let body_bytes = response.body().to_vec();
+        let body_string = String::from_utf8(body_bytes).unwrap();
+        let body_json = serde_json::from_str::<serde_json::Value>(&body_string).unwrap();
+
+        assert!(body_json["message"]
+            .as_str()
+            .unwrap()
+            .contains("Invalid IPv6 address"));
+    }
 
-fn json_response(status_code: u16, message: &str) -> Response<Body> {
-    let response_body = serde_json::json!({
-        "message": message
-    });
-    Response::builder()
-        .status(status_code)
-        .header("Content-Type", "application/json")
-        .body(Body::Text(response_body.to_string()))
-        .unwrap()
-}
+    #[test]
+    fn test_ipv6_suffix() {
+        let updates = |parameters: &[(&str, &str)]| {
+            let mut query_params: HashMap<String, String> =
+                HashMap::from([("domain".to_string(), "nas.example.org".to_string())]);
+            for (name, value) in parameters {
+                query_params.insert(name.to_string(), value.to_string());
+            }
+            parse_query_updates(&QueryMap::from(query_params))
+        };
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use lambda_http::{Request, RequestExt};
-    use std::collections::HashMap;
+        let result = updates(&[("ipv6", "2001:db8:1:2::abc"), ("ipv6suffix", "::5")]).unwrap();
+        assert_eq!(result[0].1.content, "2001:db8:1:2::5");
+        assert_eq!(result[0].1.record_type, RecordType::AAAA);
+        let result = updates(&[
+            ("ipv6", "2001:db8:1:2::abc"),
+            ("ipv6prefix", "2001:db8:1200::/56"),
+            ("ipv6suffix", "::34:0:0:0:5"),
+        ])
+        .unwrap();
+        assert_eq!(result[0].1.content, "2001:db8:1200:34::5");
+        assert!(updates(&[("ipv6suffix", "::5")]).is_err());
+        assert!(updates(&[("ipv6", "2001:db8::1"), ("ipv6prefix", "2001:db8::/56")]).is_err());
+        assert!(updates(&[("ipv6", "2001:db8::1"), ("ipv6suffix", "5")]).is_err());
+    }
 
     #[tokio::test]
-    async fn test_without_query_strings() {
-        let request = Request::default();
+    async fn test_with_ipv6_in_ip_parameter() {
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("apikey".into(), "porkDyn".into());
+        query_string_parameters.insert("secretapikey".into(), "secret".into());
+        query_string_parameters.insert("domain".into(), "me.example.org".into());
+        query_string_parameters.insert("ip".into(), "2001:db8::1".into());
+
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
 
-        let response = function_handler(request).await.unwrap();
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
         assert_eq!(response.status(), 400);
 
         let body_bytes = response.body().to_vec();
@@ -262,18 +3569,23 @@ mod tests {
 
         assert_eq!(
             body_json["message"].as_str().unwrap(),
-            "Missing query-parameter 'apikey'"
+            "IPv6 address provided in 'ip' parameter, use 'ipv6' parameter instead"
         );
     }
 
     #[tokio::test]
-    async fn test_with_missing_secret_api_key() {
+    async fn test_with_ipv4_in_ipv6_parameter() {
         let mut query_string_parameters: HashMap<String, String> = HashMap::new();
         query_string_parameters.insert("apikey".into(), "porkDyn".into());
+        query_string_parameters.insert("secretapikey".into(), "secret".into());
+        query_string_parameters.insert("domain".into(), "me.example.org".into());
+        query_string_parameters.insert("ipv6".into(), "192.168.1.1".into());
 
         let request = Request::default().with_query_string_parameters(query_string_parameters);
 
-        let response = function_handler(request).await.unwrap();
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
         assert_eq!(response.status(), 400);
 
         let body_bytes = response.body().to_vec();
@@ -282,19 +3594,23 @@ mod tests {
 
         assert_eq!(
             body_json["message"].as_str().unwrap(),
-            "Missing query-parameter 'secretapikey'"
+            "IPv4 address provided in 'ipv6' parameter, use 'ip' parameter instead"
         );
     }
 
     #[tokio::test]
-    async fn test_with_missing_domain() {
+    async fn test_with_private_ip() {
         let mut query_string_parameters: HashMap<String, String> = HashMap::new();
         query_string_parameters.insert("apikey".into(), "porkDyn".into());
         query_string_parameters.insert("secretapikey".into(), "secret".into());
+        query_string_parameters.insert("domain".into(), "me.example.org".into());
+        query_string_parameters.insert("ip".into(), "192.168.1.1".into());
 
         let request = Request::default().with_query_string_parameters(query_string_parameters);
 
-        let response = function_handler(request).await.unwrap();
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
         assert_eq!(response.status(), 400);
 
         let body_bytes = response.body().to_vec();
@@ -302,21 +3618,32 @@ mod tests {
         let body_json = serde_json::from_str::<serde_json::Value>(&body_string).unwrap();
 
         assert_eq!(
-            body_json["message"].as_str().unwrap(),
-            "Missing query-parameter 'domain'"
+            body_json["message"],
+            "IP address '192.168.1.1' of 'me.example.org' is private or reserved, set allow_private to publish it"
         );
+        assert!(!is_non_public_ip(
+            &RecordUpdate::mx("mail.example.org".to_string(), 10),
+            &[]
+        ));
+        let parked = RecordUpdate::ip("0.0.0.0".to_string(), IpType::V4);
+        assert!(is_non_public_ip(&parked, &[]));
+        assert!(!is_non_public_ip(&parked, &["0.0.0.0".parse().unwrap()]));
     }
 
     #[tokio::test]
-    async fn test_with_missing_ip() {
+    async fn test_with_invalid_mx_prio() {
         let mut query_string_parameters: HashMap<String, String> = HashMap::new();
         query_string_parameters.insert("apikey".into(), "porkDyn".into());
         query_string_parameters.insert("secretapikey".into(), "secret".into());
         query_string_parameters.insert("domain".into(), "me.example.org".into());
+        query_string_parameters.insert("mx".into(), "mail.example.org".into());
+        query_string_parameters.insert("prio".into(), "high".into());
 
         let request = Request::default().with_query_string_parameters(query_string_parameters);
 
-        let response = function_handler(request).await.unwrap();
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
         assert_eq!(response.status(), 400);
 
         let body_bytes = response.body().to_vec();
@@ -325,67 +3652,77 @@ mod tests {
 
         assert_eq!(
             body_json["message"].as_str().unwrap(),
-            "At least one IP address must be provided (ip or ipv6)"
+            "Invalid MX priority: high"
         );
     }
 
     #[tokio::test]
-    async fn test_with_invalid_ipv4() {
+    async fn test_with_srv_target_without_port() {
         let mut query_string_parameters: HashMap<String, String> = HashMap::new();
         query_string_parameters.insert("apikey".into(), "porkDyn".into());
         query_string_parameters.insert("secretapikey".into(), "secret".into());
-        query_string_parameters.insert("domain".into(), "me.example.org".into());
-        query_string_parameters.insert("ip".into(), "invalid_ip".into());
+        query_string_parameters.insert("domain".into(), "_sip._tcp.example.org".into());
+        query_string_parameters.insert("srvtarget".into(), "sip.example.org".into());
 
         let request = Request::default().with_query_string_parameters(query_string_parameters);
 
-        let response = function_handler(request).await.unwrap();
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
         assert_eq!(response.status(), 400);
 
         let body_bytes = response.body().to_vec();
         let body_string = String::from_utf8(body_bytes).unwrap();
         let body_json = serde_json::from_str::<serde_json::Value>(&body_string).unwrap();
 
-        assert!(body_json["message"]
-            .as_str()
-            .unwrap()
-            .contains("Invalid IPv4 address"));
+        assert_eq!(
+            body_json["message"].as_str().unwrap(),
+            "Missing or invalid query-parameter 'srvport'"
+        );
     }
 
     #[tokio::test]
-    async fn test_with_invalid_ipv6() {
+    async fn test_with_invalid_domain_in_list() {
         let mut query_string_parameters: HashMap<String, String> = HashMap::new();
         query_string_parameters.insert("apikey".into(), "porkDyn".into());
         query_string_parameters.insert("secretapikey".into(), "secret".into());
-        query_string_parameters.insert("domain".into(), "me.example.org".into());
-        query_string_parameters.insert("ipv6".into(), "invalid_ipv6".into());
+        query_string_parameters.insert(
+            "hostname".into(),
+            "me.example.org, invalid@example.org".into(),
+        );
+        query_string_parameters.insert("ip".into(), "1.2.3.4".into());
 
         let request = Request::default().with_query_string_parameters(query_string_parameters);
 
-        let response = function_handler(request).await.unwrap();
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
         assert_eq!(response.status(), 400);
 
         let body_bytes = response.body().to_vec();
         let body_string = String::from_utf8(body_bytes).unwrap();
         let body_json = serde_json::from_str::<serde_json::Value>(&body_string).unwrap();
 
-        assert!(body_json["message"]
-            .as_str()
-            .unwrap()
-            .contains("Invalid IPv6 address"));
+        assert_eq!(
+            body_json["message"].as_str().unwrap(),
+            "Invalid subdomain format: invalid@example.org"
+        );
     }
 
     #[tokio::test]
-    async fn test_with_ipv6_in_ip_parameter() {
+    async fn test_with_invalid_caa_tag() {
         let mut query_string_parameters: HashMap<String, String> = HashMap::new();
         query_string_parameters.insert("apikey".into(), "porkDyn".into());
         query_string_parameters.insert("secretapikey".into(), "secret".into());
         query_string_parameters.insert("domain".into(), "me.example.org".into());
-        query_string_parameters.insert("ip".into(), "2001:db8::1".into());
+        query_string_parameters.insert("caa".into(), "letsencrypt.org".into());
+        query_string_parameters.insert("caatag".into(), "issuer".into());
 
         let request = Request::default().with_query_string_parameters(query_string_parameters);
 
-        let response = function_handler(request).await.unwrap();
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
         assert_eq!(response.status(), 400);
 
         let body_bytes = response.body().to_vec();
@@ -394,21 +3731,99 @@ mod tests {
 
         assert_eq!(
             body_json["message"].as_str().unwrap(),
-            "IPv6 address provided in 'ip' parameter, use 'ipv6' parameter instead"
+            "Invalid CAA tag: issuer (expected one of issue, issuewild, iodef)"
         );
     }
 
-    #[tokio::test]
-    async fn test_with_ipv4_in_ipv6_parameter() {
+    #[test]
+    fn test_record_matches() {
+        let record: DnsRecord = serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "name": "me.example.org",
+            "type": "MX",
+            "content": "mail.example.org",
+            "prio": "10",
+            "ttl": "600"
+        }))
+        .unwrap();
+        assert!(record_matches(
+            &record,
+            &RecordUpdate::mx("mail.example.org".into(), 10)
+        ));
+        assert!(!record_matches(
+            &record,
+            &RecordUpdate::mx("mail.example.org".into(), 20)
+        ));
+        assert!(!record_matches(
+            &record,
+            &RecordUpdate::mx("mx.example.org".into(), 10)
+        ));
+        assert!(record_matches(
+            &record,
+            &RecordUpdate::mx("mail.example.org".into(), 10).with_ttl(Some(600))
+        ));
+        assert!(!record_matches(
+            &record,
+            &RecordUpdate::mx("mail.example.org".into(), 10).with_ttl(Some(3600))
+        ));
+
+        let record: DnsRecord = serde_json::from_value(serde_json::json!({
+            "id": "2",
+            "name": "me.example.org",
+            "type": "AAAA",
+            "content": "2001:0db8:0:0::1",
+            "ttl": "600"
+        }))
+        .unwrap();
+        let update = |ip: &str| RecordUpdate {
+            content: ip.to_string(),
+            ..RecordUpdate::ip(String::new(), IpType::V6)
+        };
+        assert!(record_matches(&record, &update("2001:db8::1")));
+        assert!(record_matches(&record, &update("2001:DB8::0:1")));
+        assert!(!record_matches(&record, &update("2001:db8::2")));
+    }
+
+    fn batch_request(body: &str) -> Request {
         let mut query_string_parameters: HashMap<String, String> = HashMap::new();
         query_string_parameters.insert("apikey".into(), "porkDyn".into());
         query_string_parameters.insert("secretapikey".into(), "secret".into());
-        query_string_parameters.insert("domain".into(), "me.example.org".into());
-        query_string_parameters.insert("ipv6".into(), "192.168.1.1".into());
 
-        let request = Request::default().with_query_string_parameters(query_string_parameters);
+        let mut request =
+            Request::new(Body::from(body)).with_query_string_parameters(query_string_parameters);
+        *request.method_mut() = Method::POST;
+        request
+    }
+
+    #[tokio::test]
+    async fn test_batch_with_invalid_body() {
+        let response = function_handler(
+            batch_request("[{\"domain\": \"me.example.org\"}]"),
+            &AppState::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), 400);
 
-        let response = function_handler(request).await.unwrap();
+        let body_bytes = response.body().to_vec();
+        let body_string = String::from_utf8(body_bytes).unwrap();
+        let body_json = serde_json::from_str::<serde_json::Value>(&body_string).unwrap();
+
+        assert!(body_json["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid batch request body"));
+    }
+
+    #[tokio::test]
+    async fn test_batch_with_mismatching_type() {
+        let body = r#"[
+            {"domain": "me.example.org", "ip": "1.2.3.4", "type": "A", "ttl": 600},
+            {"domain": "me.example.org", "ip": "1.2.3.4", "type": "AAAA"}
+        ]"#;
+        let response = function_handler(batch_request(body), &AppState::default())
+            .await
+            .unwrap();
         assert_eq!(response.status(), 400);
 
         let body_bytes = response.body().to_vec();
@@ -417,8 +3832,556 @@ mod tests {
 
         assert_eq!(
             body_json["message"].as_str().unwrap(),
-            "IPv4 address provided in 'ipv6' parameter, use 'ip' parameter instead"
+            "Record type AAAA does not match IP address 1.2.3.4 of domain me.example.org"
+        );
+    }
+
+    #[test]
+    fn test_find_missing_domain() {
+        let updates = vec![
+            (
+                Domain::new("a.example.org").unwrap(),
+                RecordUpdate::ip("1.2.3.4".into(), IpType::V4),
+            ),
+            (
+                Domain::new("b.example.co.uk").unwrap(),
+                RecordUpdate::ip("1.2.3.4".into(), IpType::V4),
+            ),
+        ];
+        assert_eq!(
+            find_missing_domain(&updates, &["example.org".into()]),
+            Some("example.co.uk")
+        );
+        assert_eq!(
+            find_missing_domain(&updates, &["EXAMPLE.org".into(), "example.co.uk".into()]),
+            None
+        );
+    }
+
+    fn json_body_request(body: &str) -> Request {
+        let mut request = Request::new(Body::from(body));
+        *request.method_mut() = Method::POST;
+        request
+    }
+
+    #[tokio::test]
+    async fn test_json_body_with_missing_secret_api_key() {
+        let response = function_handler(
+            json_body_request(r#"{"apikey": "porkDyn"}"#),
+            &AppState::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(
+            response_json(&response)["message"],
+            "Missing query-parameter 'secretapikey'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_json_body_with_invalid_ip() {
+        let body = r#"{"apikey": "porkDyn", "secretapikey": "secret", "domain": "me.example.org", "ip": "invalid_ip", "prio": 10}"#;
+        let response = function_handler(json_body_request(body), &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert!(response_json(&response)["message"]
+            .as_str()
+            .unwrap()
+            .contains("Invalid IPv4 address"));
+    }
+
+    #[tokio::test]
+    async fn test_with_invalid_json_body() {
+        let response = function_handler(json_body_request("\"text\""), &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(
+            response_json(&response)["message"],
+            "Invalid request body: expected a JSON array or object"
+        );
+    }
+
+    #[test]
+    fn test_merge_parameters() {
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("domain".into(), "query.example.org".into());
+        query_string_parameters.insert("ip".into(), "1.2.3.4".into());
+        let mut body_parameters: HashMap<String, String> = HashMap::new();
+        body_parameters.insert("domain".into(), "body.example.org".into());
+
+        let merged = merge_parameters(&QueryMap::from(query_string_parameters), body_parameters);
+        assert_eq!(merged.first("domain"), Some("body.example.org"));
+        assert_eq!(merged.first("ip"), Some("1.2.3.4"));
+    }
+
+    #[test]
+    fn test_normalize_parameters() {
+        let query_string_parameters = HashMap::from([
+            ("Hostname".to_string(), "home.example.org".to_string()),
+            ("MYIP".to_string(), "1.2.3.4".to_string()),
+            ("ApiKey".to_string(), "pk1_key".to_string()),
+            ("secret_api_key".to_string(), "sk1_secret".to_string()),
+            ("token".to_string(), "router-token".to_string()),
+        ]);
+        let normalized =
+            normalize_parameters(&QueryMap::from(query_string_parameters), &PARAMETER_ALIASES);
+        assert_eq!(normalized.first("domain"), Some("home.example.org"));
+        assert_eq!(normalized.first("ip"), Some("1.2.3.4"));
+        assert_eq!(normalized.first("apikey"), Some("pk1_key"));
+        assert_eq!(normalized.first("secretapikey"), Some("sk1_secret"));
+        assert_eq!(normalized.first("token"), Some("router-token"));
+        assert_eq!(normalized.first("hostname"), None);
+
+        // The parameter's own name wins over an alias, compatibility modes keep their names
+        let query_string_parameters = HashMap::from([
+            ("domain".to_string(), "nas.example.org".to_string()),
+            ("hostname".to_string(), "home.example.org".to_string()),
+        ]);
+        let query_params = QueryMap::from(query_string_parameters);
+        let normalized = normalize_parameters(&query_params, &PARAMETER_ALIASES);
+        assert_eq!(normalized.first("domain"), Some("nas.example.org"));
+        let normalized = normalize_parameters(&query_params, Protocol::DynDns2.parameter_aliases());
+        assert_eq!(normalized.first("hostname"), Some("home.example.org"));
+    }
+
+    #[tokio::test]
+    async fn test_strict_parameters() {
+        let query_string_parameters = HashMap::from([
+            ("apikey".to_string(), "porkDyn".to_string()),
+            ("secertapikey".to_string(), "secret".to_string()),
+            ("domain".to_string(), "home.example.org".to_string()),
+            ("ip".to_string(), "1.2.3.4".to_string()),
+            ("tll".to_string(), "600".to_string()),
+        ]);
+        let state = AppState {
+            strict_parameters: true,
+            ..AppState::default()
+        };
+        let request =
+            Request::default().with_query_string_parameters(query_string_parameters.clone());
+        let response = function_handler(request, &state).await.unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(
+            response_json(&response)["message"],
+            "Unknown query-parameters 'secertapikey', 'tll'"
+        );
+
+        // Without strict mode the typo surfaces as a missing parameter
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(
+            response_json(&response)["message"],
+            "Missing query-parameter 'secretapikey'"
+        );
+
+        let query_params = QueryMap::from(HashMap::from([
+            ("strict".to_string(), "true".to_string()),
+            ("hostname".to_string(), "home.example.org".to_string()),
+        ]));
+        assert_eq!(unknown_parameters_message(&query_params), None);
+    }
+
+    #[tokio::test]
+    async fn test_with_aliased_parameters() {
+        let query_string_parameters = HashMap::from([
+            ("APIKEY".to_string(), "porkDyn".to_string()),
+            ("secretkey".to_string(), "secret".to_string()),
+            ("host".to_string(), "home.example.org".to_string()),
+            ("myip".to_string(), "not-an-ip".to_string()),
+        ]);
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert!(response_json(&response)["message"]
+            .as_str()
+            .unwrap()
+            .contains("Invalid IPv4 address"));
+    }
+
+    fn item_result(domain: &str, status_code: u16, action: UpdateAction) -> ItemResult {
+        ItemResult {
+            domain: domain.into(),
+            record_type: "A",
+            status_code,
+            action,
+            message: format!("{} {:?}", domain, action),
+            content: "1.2.3.4".into(),
+            provider: None,
+            record_id: None,
+            old_ip: None,
+            new_ip: Some("1.2.3.4".into()),
+            visible: None,
+        }
+    }
+
+    fn response_json(response: &Response<Body>) -> serde_json::Value {
+        let body_string = String::from_utf8(response.body().to_vec()).unwrap();
+        serde_json::from_str::<serde_json::Value>(&body_string).unwrap()
+    }
+
+    #[test]
+    fn test_results_response_single_item() {
+        let response = results_response(
+            vec![item_result("a.example.org", 502, UpdateAction::Failed)],
+            Vec::new(),
+        );
+        assert_eq!(response.status(), 502);
+        let body_json = response_json(&response);
+        assert_eq!(body_json["message"], "a.example.org Failed");
+        assert_eq!(body_json["action"], "failed");
+        assert!(body_json.get("results").is_none());
+
+        let mut result = item_result("a.example.org", 200, UpdateAction::Updated);
+        result.record_id = Some("106926659".into());
+        result.old_ip = Some("5.6.7.8".into());
+        let body_json = response_json(&results_response(vec![result], Vec::new()));
+        assert_eq!(body_json["domain"], "a.example.org");
+        assert_eq!(body_json["action"], "updated");
+        assert_eq!(body_json["record_id"], "106926659");
+        assert_eq!(body_json["old_ip"], "5.6.7.8");
+        assert_eq!(body_json["new_ip"], "1.2.3.4");
+    }
+
+    #[test]
+    fn test_json_response_escapes_message() {
+        let response = json_response(400, "Invalid domain \"a\\b\"\n");
+        assert_eq!(
+            response_json(&response)["message"],
+            "Invalid domain \"a\\b\"\n"
+        );
+    }
+
+    #[test]
+    fn test_results_response_with_provider() {
+        let mut result = item_result("home.example.org", 200, UpdateAction::Updated);
+        result.provider = Some("cloudflare");
+        let response = results_response(vec![result], Vec::new());
+        assert_eq!(response.status(), 200);
+        assert_eq!(response_json(&response)["provider"], "cloudflare");
+
+        let response = results_response(
+            vec![item_result("home.example.org", 200, UpdateAction::Updated)],
+            Vec::new(),
+        );
+        assert!(response_json(&response).get("provider").is_none());
+    }
+
+    #[test]
+    fn test_results_response_with_mirrors() {
+        let mirror = || ItemResult {
+            provider: Some("desec"),
+            ..item_result("home.example.org", 502, UpdateAction::Failed)
+        };
+        let response = results_response(
+            vec![item_result("home.example.org", 200, UpdateAction::Updated)],
+            vec![mirror()],
+        );
+        assert_eq!(response.status(), 200);
+        let body_json = response_json(&response);
+        assert_eq!(body_json["action"], "updated");
+        assert_eq!(body_json["mirrors"][0]["provider"], "desec");
+        assert_eq!(body_json["mirrors"][0]["status"], 502);
+
+        let response = results_response(
+            vec![
+                item_result("a.example.org", 200, UpdateAction::Updated),
+                item_result("b.example.org", 200, UpdateAction::Unchanged),
+            ],
+            vec![mirror()],
+        );
+        assert_eq!(response.status(), 200);
+        let body_json = response_json(&response);
+        assert_eq!(body_json["results"].as_array().unwrap().len(), 2);
+        assert_eq!(body_json["mirrors"].as_array().unwrap().len(), 1);
+
+        // A failing mirror fails neither the update nor the metric in any format
+        for format in [
+            ResponseFormat::Json,
+            ResponseFormat::DynDns,
+            ResponseFormat::Plain,
+        ] {
+            let response = formatted_results_response(
+                format,
+                vec![item_result("home.example.org", 200, UpdateAction::Updated)],
+                vec![mirror()],
+            );
+            assert_eq!(response.status(), 200);
+            assert!(response.extensions().get::<FailureReason>().is_none());
+            let outcomes = response.extensions().get::<Outcomes>().unwrap();
+            assert_eq!((outcomes.updated, outcomes.failed), (1, 0));
+        }
+    }
+
+    #[test]
+    fn test_results_response_multi_status() {
+        let response = results_response(
+            vec![
+                item_result("a.example.org", 200, UpdateAction::Created),
+                item_result("b.example.org", 502, UpdateAction::Failed),
+            ],
+            Vec::new(),
         );
+        assert_eq!(response.status(), 207);
+        let body_json = response_json(&response);
+        assert_eq!(body_json["message"], "1 of 2 record update(s) failed");
+        assert_eq!(body_json["results"][0]["domain"], "a.example.org");
+        assert_eq!(body_json["results"][0]["action"], "created");
+        assert_eq!(body_json["results"][0]["status"], 200);
+        assert_eq!(body_json["results"][1]["action"], "failed");
+        assert_eq!(body_json["results"][1]["status"], 502);
+    }
+
+    #[test]
+    fn test_results_response_all_succeeded_or_failed() {
+        let response = results_response(
+            vec![
+                item_result("a.example.org", 200, UpdateAction::Unchanged),
+                item_result("b.example.org", 200, UpdateAction::Updated),
+            ],
+            Vec::new(),
+        );
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response_json(&response)["message"],
+            "a.example.org Unchanged; b.example.org Updated"
+        );
+
+        let response = results_response(
+            vec![
+                item_result("a.example.org", 500, UpdateAction::Failed),
+                item_result("b.example.org", 502, UpdateAction::Failed),
+            ],
+            Vec::new(),
+        );
+        assert_eq!(response.status(), 500);
+    }
+
+    #[test]
+    fn test_dyndns_response() {
+        let response = dyndns_response(&[
+            item_result("a.example.org", 200, UpdateAction::Created),
+            item_result("b.example.org", 200, UpdateAction::Unchanged),
+            item_result("c.example.org", 502, UpdateAction::Failed),
+        ]);
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.body().to_vec(),
+            b"good 1.2.3.4\nnochg 1.2.3.4\n911"
+        );
+    }
+
+    #[test]
+    fn test_plain_response() {
+        let response = plain_response(&[
+            item_result("a.example.org", 200, UpdateAction::Updated),
+            item_result("b.example.org", 403, UpdateAction::Failed),
+        ]);
+        assert_eq!(response.status(), 207);
+        assert_eq!(response.body().to_vec(), b"1.2.3.4\nforbidden");
+    }
+
+    #[test]
+    fn test_outcomes_attached() {
+        let response = formatted_results_response(
+            ResponseFormat::Plain,
+            vec![
+                item_result("a.example.org", 200, UpdateAction::Updated),
+                item_result("b.example.org", 502, UpdateAction::Failed),
+            ],
+            Vec::new(),
+        );
+        let outcomes = response.extensions().get::<Outcomes>().unwrap();
+        assert_eq!((outcomes.updated, outcomes.failed), (1, 1));
+        assert_eq!(
+            response.extensions().get::<FailureReason>(),
+            Some(&FailureReason::Upstream)
+        );
+        assert!(json_response(400, "Invalid IP address")
+            .extensions()
+            .get::<Outcomes>()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failure_reason_attached() {
+        let response = function_handler(Request::default(), &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(
+            response.extensions().get::<FailureReason>(),
+            Some(&FailureReason::Validation)
+        );
+        // DynDNS2 clients get most errors with `200`, the metric still counts them
+        let response = error_response(ResponseFormat::DynDns, 502, "Porkbun is unavailable");
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.extensions().get::<FailureReason>(),
+            Some(&FailureReason::Upstream)
+        );
+        let response = error_response(ResponseFormat::DynDns, 401, "Invalid credentials");
+        assert_eq!(
+            response.extensions().get::<FailureReason>(),
+            Some(&FailureReason::Auth)
+        );
+        let response = formatted_results_response(
+            ResponseFormat::Json,
+            vec![item_result("a.example.org", 200, UpdateAction::Updated)],
+            Vec::new(),
+        );
+        assert!(response.extensions().get::<FailureReason>().is_none());
+    }
+
+    #[cfg(feature = "dynamodb")]
+    #[test]
+    fn test_audit_changes() {
+        let mirrored = ItemResult {
+            provider: Some("cloudflare"),
+            old_ip: Some("5.6.7.8".into()),
+            ..item_result("c.example.org", 200, UpdateAction::Created)
+        };
+        let results = vec![
+            item_result("a.example.org", 200, UpdateAction::Updated),
+            item_result("b.example.org", 200, UpdateAction::Unchanged),
+            item_result("b.example.org", 502, UpdateAction::Failed),
+            mirrored,
+        ];
+        let changes = audit_changes(&results, ProviderKind::Porkbun, "anonymous");
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    domain: "a.example.org".into(),
+                    record_type: "A",
+                    action: UpdateAction::Updated,
+                    provider: "porkbun",
+                    caller: "anonymous".into(),
+                    old_ip: None,
+                    new_ip: Some("1.2.3.4".into()),
+                },
+                Change {
+                    domain: "c.example.org".into(),
+                    record_type: "A",
+                    action: UpdateAction::Created,
+                    provider: "cloudflare",
+                    caller: "anonymous".into(),
+                    old_ip: Some("5.6.7.8".into()),
+                    new_ip: Some("1.2.3.4".into()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_negotiate_response_format() {
+        let negotiate =
+            |format, accept| ResponseFormat::negotiate(format, accept, ResponseFormat::Json);
+        assert_eq!(negotiate(None, None), ResponseFormat::Json);
+        assert_eq!(negotiate(Some("plain"), None), ResponseFormat::Plain);
+        assert_eq!(
+            negotiate(Some("DynDNS"), Some("application/json")),
+            ResponseFormat::DynDns
+        );
+        assert_eq!(negotiate(None, Some("text/plain")), ResponseFormat::DynDns);
+        assert_eq!(
+            negotiate(Some("xml"), Some("text/plain")),
+            ResponseFormat::DynDns
+        );
+        assert_eq!(
+            negotiate(None, Some("text/plain;q=0.5, application/json")),
+            ResponseFormat::Json
+        );
+        assert_eq!(
+            negotiate(None, Some("application/json;q=0, text/plain;q=0.1")),
+            ResponseFormat::DynDns
+        );
+        // Browsers and curl accept anything, the protocol decides
+        assert_eq!(negotiate(None, Some("*/*")), ResponseFormat::Json);
+        assert_eq!(
+            ResponseFormat::negotiate(
+                Some("json"),
+                Some("*/*"),
+                Protocol::DynDns2.response_format()
+            ),
+            ResponseFormat::Json
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_verify() {
+        for (verify, provider, message) in [
+            (
+                "dnssec",
+                "porkbun",
+                "Invalid query-parameter 'verify': dnssec",
+            ),
+            (
+                "authoritative",
+                "desec",
+                "verify=authoritative is only supported for Porkbun",
+            ),
+        ] {
+            let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+            query_string_parameters.insert("apikey".into(), "porkDyn".into());
+            query_string_parameters.insert("secretapikey".into(), "secret".into());
+            query_string_parameters.insert("domain".into(), "me.example.org".into());
+            query_string_parameters.insert("ip".into(), "1.2.3.4".into());
+            query_string_parameters.insert("provider".into(), provider.into());
+            query_string_parameters.insert("verify".into(), verify.into());
+            let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+            let state = AppState {
+                desec_token: Some("desec-token".into()),
+                ..AppState::default()
+            };
+            let response = function_handler(request, &state).await.unwrap();
+            assert_eq!(response.status(), 400);
+            assert_eq!(response_json(&response)["message"], message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plain_format_with_invalid_ip() {
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("apikey".into(), "porkDyn".into());
+        query_string_parameters.insert("secretapikey".into(), "secret".into());
+        query_string_parameters.insert("domain".into(), "me.example.org".into());
+        query_string_parameters.insert("ip".into(), "invalid_ip".into());
+        query_string_parameters.insert("format".into(), "plain".into());
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+        let response = function_handler(request, &AppState::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(response.body().to_vec(), b"invalid");
+    }
+
+    #[test]
+    fn test_parse_batch_updates() {
+        let entries: Vec<BatchEntry> = serde_json::from_str(
+            r#"[
+                {"domain": "a.example.org", "ip": "1.2.3.4", "ttl": 3600},
+                {"domain": "b.example.org", "ip": "2001:db8::1", "type": "aaaa"}
+            ]"#,
+        )
+        .unwrap();
+        let updates = parse_batch_updates(entries).unwrap();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].0.qualified_name(), "a.example.org");
+        assert_eq!(updates[0].1.record_type, RecordType::A);
+        assert_eq!(updates[0].1.ttl, Some(3600));
+        assert_eq!(updates[1].0.qualified_name(), "b.example.org");
+        assert_eq!(updates[1].1.record_type, RecordType::AAAA);
+        assert_eq!(updates[1].1.ttl, None);
     }
 
     // Note: Tests for valid IPv4/IPv6 API calls would require mocking the HTTP client
@@ -0,0 +1,103 @@
+use lambda_http::Error;
+use std::net::Ipv6Addr;
+
+/// The kernel's list of IPv6 addresses with their interface, scope and flags
+const IF_INET6_PATH: &str = "/proc/net/if_inet6";
+const SCOPE_GLOBAL: u32 = 0x00;
+/// Privacy extension address (RFC 8981), rotated every few hours
+const IFA_F_TEMPORARY: u32 = 0x01;
+const IFA_F_DADFAILED: u32 = 0x08;
+/// Preferred lifetime expired, kept only for existing connections
+const IFA_F_DEPRECATED: u32 = 0x20;
+const IFA_F_TENTATIVE: u32 = 0x40;
+
+/// An IPv6 address assigned to an interface.
+#[derive(Debug)]
+struct InterfaceAddress {
+    address: Ipv6Addr,
+    interface: String,
+    scope: u32,
+    flags: u32,
+}
+
+/// The global IPv6 address of the interface, read from the kernel instead of asking an external
+/// service. Stable addresses are preferred over temporary privacy addresses, which change every
+/// few hours, and deprecated ones are only used as a last resort.
+pub(crate) fn interface_ipv6(interface: &str) -> Result<Ipv6Addr, Error> {
+    let addresses = std::fs::read_to_string(IF_INET6_PATH)
+        .map_err(|e| format!("Failed to read the interface addresses: {}", e))?;
+    select_address(&parse_if_inet6(&addresses), interface)
+        .ok_or_else(|| format!("Interface {} has no global IPv6 address", interface).into())
+}
+
+/// Parses lines like `20010db8000000000000000000000001 02 40 00 80 eth0`: address, interface
+/// index, prefix length, scope, flags and name, all numbers in hex.
+fn parse_if_inet6(addresses: &str) -> Vec<InterfaceAddress> {
+    addresses
+        .lines()
+        .filter_map(|line| {
+            let [address, _, _, scope, flags, interface] =
+                line.split_whitespace().collect::<Vec<_>>()[..]
+            else {
+                return None;
+            };
+            Some(InterfaceAddress {
+                address: Ipv6Addr::from(u128::from_str_radix(address, 16).ok()?),
+                interface: interface.to_string(),
+                scope: u32::from_str_radix(scope, 16).ok()?,
+                flags: u32::from_str_radix(flags, 16).ok()?,
+            })
+        })
+        .collect()
+}
+
+/// The most suitable global address of the interface, the first one of the best kind wins.
+fn select_address(addresses: &[InterfaceAddress], interface: &str) -> Option<Ipv6Addr> {
+    addresses
+        .iter()
+        .filter(|address| {
+            address.interface == interface
+                && address.scope == SCOPE_GLOBAL
+                && address.flags & (IFA_F_TENTATIVE | IFA_F_DADFAILED) == 0
+                // Unique local addresses have global scope but aren't reachable from the Internet
+                && address.address.segments()[0] & 0xfe00 != 0xfc00
+        })
+        .min_by_key(|address| {
+            (
+                address.flags & IFA_F_DEPRECATED != 0,
+                address.flags & IFA_F_TEMPORARY != 0,
+            )
+        })
+        .map(|address| address.address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_address() {
+        let addresses = parse_if_inet6(
+            "fe80000000000000021122fffe334455 02 40 20 80     eth0\n\
+             20010db800000000a1b2c3d4e5f60718 02 40 00 01     eth0\n\
+             fd000000000000000000000000000001 02 40 00 80     eth0\n\
+             20010db8000000000000000000000002 02 40 00 a0     eth0\n\
+             20010db800000000021122fffe334455 02 40 00 00     eth0\n\
+             20010db80000000000000000000000ff 03 40 00 80     wlan0\n",
+        );
+        assert_eq!(
+            select_address(&addresses, "eth0"),
+            Some("2001:db8::211:22ff:fe33:4455".parse().unwrap())
+        );
+        // A temporary address is still better than a deprecated one
+        assert_eq!(
+            select_address(&addresses[..4], "eth0"),
+            Some("2001:db8::a1b2:c3d4:e5f6:718".parse().unwrap())
+        );
+        assert_eq!(
+            select_address(&addresses, "wlan0"),
+            Some("2001:db8::ff".parse().unwrap())
+        );
+        assert_eq!(select_address(&addresses, "eth1"), None);
+    }
+}
@@ -0,0 +1,342 @@
+use crate::api::{ping, IPV4_API_BASE_URL};
+#[cfg(all(feature = "prometheus", any(feature = "cli", feature = "server")))]
+use crate::exporter;
+use crate::gateway::{nat_pmp_external_ip, upnp_external_ip};
+use crate::interface::interface_ipv6;
+use crate::ip_utils::IpType;
+use crate::resolver::Resolver;
+use crate::state::AppState;
+use crate::stun::{stun_public_ip, DEFAULT_STUN_PORT};
+use lambda_http::tracing::{info, warn};
+use lambda_http::Error;
+use reqwest::Client;
+use serde::Deserialize;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Environment variable with the comma-separated sources the public IP is looked up with, in order,
+/// e.g. `porkbun,https://api64.ipify.org`
+pub const IP_SOURCES_ENV: &str = "PORKDYN_IP_SOURCES";
+
+/// Sources used if none are configured, Porkbun is skipped without server-side credentials
+const DEFAULT_IP_SOURCES: [&str; 3] = [
+    "porkbun",
+    "https://api64.ipify.org",
+    "https://icanhazip.com",
+];
+/// Lookups are quick, a source which takes longer is skipped
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A way to discover the public address of this host.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum IpSource {
+    /// The address Porkbun sees requests from, requires server-side credentials. IPv4 is looked up
+    /// at `api-ipv4.porkbun.com`, which dual-stack hosts can't reach over IPv6.
+    Porkbun,
+    /// A service answering with the caller's address in plain text, e.g. `https://icanhazip.com`
+    Http(String),
+    /// A STUN server as `host:port`, e.g. `stun:stun.l.google.com:19302`
+    Stun(String),
+    /// A name server answering with the caller's address, e.g. `dns:opendns`
+    Dns(Resolver),
+    /// The WAN address of the router via NAT-PMP, of the default gateway if none is given
+    NatPmp(Option<Ipv4Addr>),
+    /// The WAN address of the router via UPnP IGD
+    Upnp,
+    /// The global IPv6 address of a local network interface, e.g. `interface:eth0`
+    Interface(String),
+}
+
+impl FromStr for IpSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("porkbun") {
+            Ok(IpSource::Porkbun)
+        } else if s.eq_ignore_ascii_case("upnp") {
+            Ok(IpSource::Upnp)
+        } else if s.eq_ignore_ascii_case("natpmp") {
+            Ok(IpSource::NatPmp(None))
+        } else if let Some(gateway) = s.strip_prefix("natpmp:") {
+            let gateway = gateway
+                .parse()
+                .map_err(|_| format!("Invalid NAT-PMP gateway: {}", gateway))?;
+            Ok(IpSource::NatPmp(Some(gateway)))
+        } else if let Some(interface) = s
+            .strip_prefix("interface:")
+            .filter(|interface| !interface.is_empty())
+        {
+            Ok(IpSource::Interface(interface.to_string()))
+        } else if s.starts_with("https://") || s.starts_with("http://") {
+            Ok(IpSource::Http(s.to_string()))
+        } else if let Some(resolver) = s.strip_prefix("dns:") {
+            Ok(IpSource::Dns(resolver.parse()?))
+        } else if let Some(server) = s.strip_prefix("stun:").filter(|server| !server.is_empty()) {
+            // The port follows the host, IPv6 addresses are enclosed in brackets
+            let has_port = server
+                .rsplit_once(']')
+                .map_or(server, |(_, rest)| rest)
+                .contains(':');
+            Ok(IpSource::Stun(if has_port {
+                server.to_string()
+            } else {
+                format!("{}:{}", server, DEFAULT_STUN_PORT)
+            }))
+        } else {
+            Err(format!("Invalid IP source: {}", s))
+        }
+    }
+}
+
+impl TryFrom<String> for IpSource {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for IpSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpSource::Porkbun => write!(f, "porkbun"),
+            IpSource::Http(url) => write!(f, "{}", url),
+            IpSource::Stun(server) => write!(f, "stun:{}", server),
+            IpSource::Dns(resolver) => write!(f, "dns:{}", resolver),
+            IpSource::NatPmp(None) => write!(f, "natpmp"),
+            IpSource::NatPmp(Some(gateway)) => write!(f, "natpmp:{}", gateway),
+            IpSource::Upnp => write!(f, "upnp"),
+            IpSource::Interface(interface) => write!(f, "interface:{}", interface),
+        }
+    }
+}
+
+impl IpSource {
+    async fn lookup(&self, state: &AppState, ip_type: &IpType) -> Result<IpAddr, Error> {
+        let ip = match self {
+            IpSource::Porkbun => porkbun_public_ip(state, ip_type).await?,
+            IpSource::Http(url) => http_public_ip(url, ip_type).await?,
+            IpSource::Stun(server) => stun_public_ip(server, *ip_type == IpType::V6)
+                .await?
+                .to_string(),
+            IpSource::Dns(resolver) => resolver
+                .public_ip(*ip_type == IpType::V6)
+                .await?
+                .to_string(),
+            // Routers only translate IPv4, IPv6 addresses aren't translated
+            IpSource::NatPmp(_) | IpSource::Upnp if *ip_type == IpType::V6 => {
+                return Err(format!("{} only knows the IPv4 address", self).into())
+            }
+            IpSource::NatPmp(gateway) => nat_pmp_external_ip(*gateway).await?.to_string(),
+            IpSource::Upnp => upnp_external_ip().await?.to_string(),
+            // IPv4 addresses of the host are usually private and translated by the router
+            IpSource::Interface(_) if *ip_type == IpType::V4 => {
+                return Err(format!("{} only knows the IPv6 address", self).into())
+            }
+            IpSource::Interface(interface) => interface_ipv6(interface)?.to_string(),
+        };
+        let ip: IpAddr = ip
+            .trim()
+            .parse()
+            .map_err(|_| format!("{} answered with an invalid address: {:?}", self, ip))?;
+        if !matches!(
+            (ip, ip_type),
+            (IpAddr::V4(_), IpType::V4) | (IpAddr::V6(_), IpType::V6)
+        ) {
+            return Err(format!(
+                "{} answered with {}, not an {} address",
+                self,
+                ip,
+                version(ip_type)
+            )
+            .into());
+        }
+        Ok(ip)
+    }
+}
+
+/// Looks up the public address of this host with the configured sources, in order.
+/// An address is accepted once two sources agree on it, or if only one source answered at all,
+/// so a single misbehaving service can't point the records somewhere else.
+pub(crate) async fn public_ip(state: &AppState, ip_type: IpType) -> Result<String, Error> {
+    let default_sources: [IpSource; 3];
+    let sources = if state.ip_sources.is_empty() {
+        default_sources = DEFAULT_IP_SOURCES.map(|source| source.parse().unwrap());
+        &default_sources[..]
+    } else {
+        &state.ip_sources[..]
+    };
+    let mut answers: Vec<IpAddr> = Vec::new();
+    for source in sources {
+        if *source == IpSource::Porkbun && state.server_credentials.is_none() {
+            continue;
+        }
+        match source.lookup(state, &ip_type).await {
+            Ok(ip) if answers.contains(&ip) => {
+                info!(
+                    version = version(&ip_type),
+                    ip = %ip,
+                    source = %source,
+                    "Public address confirmed"
+                );
+                return Ok(ip.to_string());
+            }
+            Ok(ip) => {
+                info!(source = %source, ip = %ip, "Source reports public address");
+                answers.push(ip);
+            }
+            Err(e) => {
+                warn!(source = %source, error = %e, "Failed to look up the public IP");
+                #[cfg(all(feature = "prometheus", any(feature = "cli", feature = "server")))]
+                exporter::count_ip_lookup_failure(&source.to_string());
+            }
+        }
+    }
+    match answers.as_slice() {
+        [ip] => Ok(ip.to_string()),
+        [] => Err(format!("No source found a public {} address", version(&ip_type)).into()),
+        _ => Err(format!(
+            "The IP sources disagree on the public address: {:?}",
+            answers
+        )
+        .into()),
+    }
+}
+
+fn version(ip_type: &IpType) -> &'static str {
+    match ip_type {
+        IpType::V4 => "IPv4",
+        IpType::V6 => "IPv6",
+    }
+}
+
+/// Asks Porkbun for the IP it sees the request from.
+async fn porkbun_public_ip(state: &AppState, ip_type: &IpType) -> Result<String, Error> {
+    let credentials = match &state.server_credentials {
+        Some(server_credentials) => server_credentials.get().await?,
+        None => return Err("Porkbun credentials are required to look up the public IP".into()),
+    };
+    // The IPv4-only endpoint can't be reached over IPv6, the regular one prefers IPv6
+    let client = match ip_type {
+        IpType::V4 => state.porkbun.clone().with_base_url(IPV4_API_BASE_URL),
+        IpType::V6 => state.porkbun.clone(),
+    };
+    Ok(ping(&client, &credentials).await?)
+}
+
+/// Fetches the address from a plain text service, connecting over the requested IP version so
+/// dual-stack services answer with the address of that version.
+async fn http_public_ip(url: &str, ip_type: &IpType) -> Result<String, Error> {
+    let local_address = match ip_type {
+        IpType::V4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpType::V6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let client = Client::builder()
+        .local_address(local_address)
+        .timeout(LOOKUP_TIMEOUT)
+        .build()?;
+    Ok(client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?)
+}
+
+/// Resolves a host name (e.g. a router's own DynDNS name) to its first IPv4 and IPv6 address.
+pub(crate) async fn resolve_hostname(
+    hostname: &str,
+) -> Result<(Option<String>, Option<String>), Error> {
+    let addresses: Vec<IpAddr> = tokio::net::lookup_host((hostname, 0))
+        .await?
+        .map(|address| address.ip())
+        .collect();
+    info!(hostname, addresses = ?addresses, "Resolved host name");
+    let ipv4 = addresses
+        .iter()
+        .find(|ip| ip.is_ipv4())
+        .map(IpAddr::to_string);
+    let ipv6 = addresses
+        .iter()
+        .find(|ip| ip.is_ipv6())
+        .map(IpAddr::to_string);
+    if ipv4.is_none() && ipv6.is_none() {
+        return Err(format!("{} has no addresses", hostname).into());
+    }
+    Ok((ipv4, ipv6))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_parse_ip_source() {
+        assert_eq!("Porkbun".parse(), Ok(IpSource::Porkbun));
+        assert_eq!(
+            "https://api64.ipify.org".parse(),
+            Ok(IpSource::Http("https://api64.ipify.org".to_string()))
+        );
+        assert_eq!(
+            "stun:stun.l.google.com:19302".parse(),
+            Ok(IpSource::Stun("stun.l.google.com:19302".to_string()))
+        );
+        assert_eq!(
+            "stun:[2001:db8::1]".parse(),
+            Ok(IpSource::Stun("[2001:db8::1]:3478".to_string()))
+        );
+        assert_eq!("dns:OpenDNS".parse(), Ok(IpSource::Dns(Resolver::OpenDns)));
+        assert!("dns:quad9".parse::<IpSource>().is_err());
+        assert_eq!(
+            "natpmp:192.168.1.1".parse(),
+            Ok(IpSource::NatPmp(Some(Ipv4Addr::new(192, 168, 1, 1))))
+        );
+        assert_eq!("UPnP".parse(), Ok(IpSource::Upnp));
+        assert_eq!(
+            "interface:eth0".parse(),
+            Ok(IpSource::Interface("eth0".to_string()))
+        );
+        assert!("interface:".parse::<IpSource>().is_err());
+        assert!("ipify".parse::<IpSource>().is_err());
+        assert!("stun:".parse::<IpSource>().is_err());
+        for source in DEFAULT_IP_SOURCES {
+            assert!(source.parse::<IpSource>().is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_public_ip_cross_check() {
+        let server = MockServer::start().await;
+        for (path, ip) in [
+            ("/a", "203.0.113.7\n"),
+            ("/b", "203.0.113.7"),
+            ("/c", "198.51.100.1"),
+        ] {
+            Mock::given(method("GET"))
+                .and(wiremock::matchers::path(path))
+                .respond_with(ResponseTemplate::new(200).set_body_string(ip))
+                .mount(&server)
+                .await;
+        }
+        let state = |paths: &[&str]| AppState {
+            ip_sources: paths
+                .iter()
+                .map(|path| IpSource::Http(format!("{}{}", server.uri(), path)))
+                .collect(),
+            ..AppState::default()
+        };
+
+        let ip = public_ip(&state(&["/a", "/c", "/b"]), IpType::V4).await;
+        assert_eq!(ip.unwrap(), "203.0.113.7");
+        let ip = public_ip(&state(&["/c"]), IpType::V4).await;
+        assert_eq!(ip.unwrap(), "198.51.100.1");
+        assert!(public_ip(&state(&["/a", "/c"]), IpType::V4).await.is_err());
+        assert!(public_ip(&state(&["/a"]), IpType::V6).await.is_err());
+    }
+}
@@ -0,0 +1,328 @@
+#[cfg(feature = "opentelemetry")]
+use crate::telemetry;
+use lambda_http::tracing::field::{Field, Visit};
+use lambda_http::tracing::{Event, Subscriber};
+use lambda_http::Error;
+use serde_json::{json, Map, Value};
+use std::fmt;
+use tracing_subscriber::field::{MakeVisitor, VisitFmt, VisitOutput};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::{DefaultFields, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+/// Field names containing one of these (ignoring case and separators) hold credentials, e.g.
+/// `apikey`, `secret_api_key` or `access_token`
+const SECRET_FIELD_NAMES: [&str; 6] = [
+    "apikey",
+    "secret",
+    "password",
+    "passwd",
+    "token",
+    "authorization",
+];
+/// Logged instead of the value of a secret field
+const REDACTED: &str = "[redacted]";
+
+/// Sets up logging like the Lambda runtime's default subscriber (level from `AWS_LAMBDA_LOG_LEVEL`
+/// or `RUST_LOG`, JSON if `AWS_LAMBDA_LOG_FORMAT` is `JSON`), with secret fields redacted.
+pub fn init() -> Result<(), Error> {
+    let level = std::env::var("AWS_LAMBDA_LOG_LEVEL")
+        .or_else(|_| std::env::var("RUST_LOG"))
+        .ok()
+        .and_then(|level| level.parse::<LevelFilter>().ok())
+        .unwrap_or(LevelFilter::INFO);
+    // CloudWatch shows ANSI colors as escape codes
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_target(false)
+        .with_ansi(false)
+        .without_time()
+        .fmt_fields(RedactedFields::default());
+    let fmt = match std::env::var("AWS_LAMBDA_LOG_FORMAT") {
+        Ok(format) if format.eq_ignore_ascii_case("json") => fmt.event_format(JsonFormat).boxed(),
+        _ => fmt.boxed(),
+    };
+    let registry = tracing_subscriber::registry().with(level).with(fmt);
+    #[cfg(feature = "opentelemetry")]
+    let registry = registry.with(telemetry::layer()?);
+    registry.try_init()?;
+    Ok(())
+}
+
+/// Whether the values of the field are credentials, which are never logged.
+pub(crate) fn is_secret(name: &str) -> bool {
+    let name = name
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect::<String>();
+    SECRET_FIELD_NAMES
+        .iter()
+        .any(|secret| name.contains(secret))
+}
+
+/// Formats the fields of events and spans like `M`, with the values of secret fields redacted.
+/// Credentials can only end up in the log as field values, so every value logged is a field.
+#[derive(Debug)]
+pub(crate) struct RedactedFields<M = DefaultFields>(M);
+
+impl Default for RedactedFields {
+    fn default() -> Self {
+        RedactedFields(DefaultFields::new())
+    }
+}
+
+impl<T, M: MakeVisitor<T>> MakeVisitor<T> for RedactedFields<M> {
+    type Visitor = RedactingVisitor<M::Visitor>;
+
+    fn make_visitor(&self, target: T) -> Self::Visitor {
+        RedactingVisitor(self.0.make_visitor(target))
+    }
+}
+
+/// Passes the fields on to the inner visitor, secret ones as `[redacted]`.
+pub(crate) struct RedactingVisitor<V>(V);
+
+impl<V: Visit> Visit for RedactingVisitor<V> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if is_secret(field.name()) {
+            self.0.record_str(field, REDACTED);
+        } else {
+            self.0.record_debug(field, value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if is_secret(field.name()) {
+            self.0.record_str(field, REDACTED);
+        } else {
+            self.0.record_str(field, value);
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if is_secret(field.name()) {
+            self.0.record_str(field, REDACTED);
+        } else {
+            self.0.record_i64(field, value);
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if is_secret(field.name()) {
+            self.0.record_str(field, REDACTED);
+        } else {
+            self.0.record_u64(field, value);
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if is_secret(field.name()) {
+            self.0.record_str(field, REDACTED);
+        } else {
+            self.0.record_bool(field, value);
+        }
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        if is_secret(field.name()) {
+            self.0.record_str(field, REDACTED);
+        } else {
+            self.0.record_error(field, value);
+        }
+    }
+}
+
+impl<V: VisitOutput<fmt::Result>> VisitOutput<fmt::Result> for RedactingVisitor<V> {
+    fn finish(self) -> fmt::Result {
+        self.0.finish()
+    }
+}
+
+impl<V: VisitFmt> VisitFmt for RedactingVisitor<V> {
+    fn writer(&mut self) -> &mut dyn fmt::Write {
+        self.0.writer()
+    }
+}
+
+/// Writes an event as a JSON object for Lambda's JSON log format: its level, fields and the
+/// spans it happened in, with secret fields redacted.
+struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+        let mut object = Map::new();
+        object.insert("level".into(), event.metadata().level().as_str().into());
+        object.insert("fields".into(), Value::Object(fields.0));
+        if let Some(scope) = ctx.event_scope() {
+            let spans = scope
+                .from_root()
+                .map(|span| {
+                    let extensions = span.extensions();
+                    let fields = extensions
+                        .get::<FormattedFields<N>>()
+                        .map(|formatted| formatted.fields.as_str())
+                        .unwrap_or_default();
+                    json!({ "name": span.name(), "fields": fields })
+                })
+                .collect::<Vec<_>>();
+            object.insert("spans".into(), spans.into());
+        }
+        writeln!(writer, "{}", Value::Object(object))
+    }
+}
+
+/// Collects the fields of an event as JSON values, secret ones as `[redacted]`.
+#[derive(Default)]
+struct JsonFields(Map<String, Value>);
+
+impl JsonFields {
+    fn insert(&mut self, field: &Field, value: Value) {
+        let value = if is_secret(field.name()) {
+            REDACTED.into()
+        } else {
+            value
+        };
+        self.0.insert(field.name().to_string(), value);
+    }
+}
+
+impl Visit for JsonFields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, format!("{:?}", value).into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lambda_http::tracing::{info, info_span};
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+
+    /// Collects the log output of a test
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Output {
+        type Writer = Output;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    impl Output {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    fn log_credentials() {
+        let span = info_span!("request", access_token = "router-token");
+        let _entered = span.enter();
+        info!(
+            domain = "home.example.com",
+            apikey = "pk1_key",
+            secret_api_key = ?"sk1_secret",
+            attempt = 2,
+            "Updating records"
+        );
+    }
+
+    #[test]
+    fn test_is_secret() {
+        for name in [
+            "apikey",
+            "secretapikey",
+            "secret_api_key",
+            "X-Api-Key",
+            "access_token",
+        ] {
+            assert!(is_secret(name), "{}", name);
+        }
+        for name in ["message", "domain", "record_type", "api"] {
+            assert!(!is_secret(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_redacted_text() {
+        let output = Output::default();
+        let subscriber = tracing_subscriber::fmt()
+            .fmt_fields(RedactedFields::default())
+            .with_ansi(false)
+            .with_writer(output.clone())
+            .finish();
+        let _guard = subscriber.set_default();
+        log_credentials();
+        let text = output.text();
+        assert!(text.contains("Updating records"), "{}", text);
+        assert!(text.contains("domain=\"home.example.com\""), "{}", text);
+        assert!(text.contains("attempt=2"), "{}", text);
+        for secret in ["pk1_key", "sk1_secret", "router-token"] {
+            assert!(!text.contains(secret), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_redacted_json() {
+        let output = Output::default();
+        let subscriber = tracing_subscriber::fmt()
+            .fmt_fields(RedactedFields::default())
+            .event_format(JsonFormat)
+            .with_writer(output.clone())
+            .finish();
+        let _guard = subscriber.set_default();
+        log_credentials();
+        let event: Value = serde_json::from_str(output.text().trim()).unwrap();
+        assert_eq!(event["level"], "INFO");
+        assert_eq!(event["fields"]["message"], "Updating records");
+        assert_eq!(event["fields"]["domain"], "home.example.com");
+        assert_eq!(event["fields"]["apikey"], REDACTED);
+        assert_eq!(event["fields"]["secret_api_key"], REDACTED);
+        assert_eq!(event["spans"][0]["name"], "request");
+        assert!(!output.text().contains("router-token"));
+    }
+}
@@ -1,16 +1,80 @@
-use lambda_http::{run, service_fn, tracing, Error};
-mod api;
-mod credentials;
-mod domain;
+use lambda_http::{lambda_runtime, service_fn, Error};
+mod acme_dns;
+#[cfg(feature = "dynamodb")]
+mod audit;
+mod auth;
+#[cfg(feature = "cli")]
+mod check;
+#[cfg(feature = "cli")]
+mod cli;
+#[cfg(feature = "cloudflare")]
+mod cloudflare;
+mod config;
+mod cors;
+mod deadline;
+mod desec;
+mod dyndns;
 mod error;
+mod event_handler;
+#[cfg(all(feature = "prometheus", any(feature = "cli", feature = "server")))]
+mod exporter;
+mod fritzbox;
+mod front_door;
+mod gateway;
+mod health;
+mod hetzner;
 mod http_handler;
-mod ip_utils;
+mod interface;
+mod ip_source;
+mod logging;
+mod metrics;
+mod openapi;
+mod propagation;
+mod provider;
+#[cfg(any(feature = "cli", feature = "server"))]
+mod reload;
+mod request_id;
+mod resolver;
+#[cfg(feature = "route53")]
+mod route53;
+mod scheduled;
+mod secrets;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(any(feature = "cli", feature = "server"))]
+mod shutdown;
+mod sqs;
+mod state;
+mod stun;
+#[cfg(feature = "cli")]
+mod systemd;
+#[cfg(feature = "opentelemetry")]
+mod telemetry;
+mod version;
+mod webhook;
 
-use http_handler::function_handler;
+use porkdyn_core::{api, credentials, domain, ip_utils, record};
+
+use event_handler::event_handler;
+use state::AppState;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    tracing::init_default_subscriber();
+    logging::init()?;
+
+    #[cfg(feature = "cli")]
+    if cli::is_cli_invocation() {
+        return cli::run().await;
+    }
+    let state = AppState::from_env().await?;
+    // The CLI runs locally, only the Lambda and the server are reachable by others
+    auth::check_server_credentials(&state, auth::allow_unauthenticated())?;
+    // `--server` serves the same handler over plain HTTP instead of the Lambda runtime API
+    #[cfg(feature = "server")]
+    if std::env::args().any(|arg| arg == "--server") {
+        return server::serve(state).await;
+    }
+    let state = &state;
 
-    run(service_fn(function_handler)).await
+    lambda_runtime::run(service_fn(move |event| event_handler(event, state))).await
 }
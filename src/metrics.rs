@@ -0,0 +1,341 @@
+use crate::api::Endpoint;
+use crate::config::Metrics;
+#[cfg(all(feature = "prometheus", any(feature = "cli", feature = "server")))]
+use crate::exporter;
+use crate::http_handler::UpdateAction;
+use lambda_http::{Body, Response};
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The outcomes of the records of a request, attached to its response for the metrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Outcomes {
+    pub(crate) updated: u32,
+    pub(crate) created: u32,
+    pub(crate) unchanged: u32,
+    pub(crate) deleted: u32,
+    /// Records the DNS provider failed to change
+    pub(crate) failed: u32,
+}
+
+impl Outcomes {
+    pub(crate) fn count(actions: impl IntoIterator<Item = UpdateAction>) -> Self {
+        let mut outcomes = Outcomes::default();
+        for action in actions {
+            let counter = match action {
+                UpdateAction::Updated => &mut outcomes.updated,
+                UpdateAction::Created => &mut outcomes.created,
+                UpdateAction::Unchanged => &mut outcomes.unchanged,
+                UpdateAction::Deleted => &mut outcomes.deleted,
+                UpdateAction::Failed => &mut outcomes.failed,
+            };
+            *counter += 1;
+        }
+        outcomes
+    }
+}
+
+/// Why an update request failed, the dimension of the `UpdateFailed` metric alarms are set on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FailureReason {
+    /// Missing or invalid token or credentials, also when Porkbun rejects the API key
+    Auth,
+    /// Porkbun (or the selected provider) failed, was unreachable or too slow
+    Upstream,
+    /// Invalid parameters, unknown domains or records which aren't allowed
+    Validation,
+}
+
+impl FailureReason {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            FailureReason::Auth => "auth",
+            FailureReason::Upstream => "upstream",
+            FailureReason::Validation => "validation",
+        }
+    }
+
+    /// The reason of an error by its HTTP status code, `None` for successes.
+    pub(crate) fn from_status(status_code: u16) -> Option<Self> {
+        match status_code {
+            401 | 403 => Some(FailureReason::Auth),
+            // Porkbun rate limited us
+            429 => Some(FailureReason::Upstream),
+            400..=499 => Some(FailureReason::Validation),
+            500.. => Some(FailureReason::Upstream),
+            _ => None,
+        }
+    }
+}
+
+/// Marks the response of a failed update for the `UpdateFailed` metric. The status code alone
+/// doesn't tell, DynDNS2 clients get most errors with `200`.
+pub(crate) fn attach_failure(
+    mut response: Response<Body>,
+    reason: FailureReason,
+) -> Response<Body> {
+    response.extensions_mut().insert(reason);
+    response
+}
+
+/// Records the metrics of a request: the outcomes if it processed records, the reason if it failed
+/// and the latency of its Porkbun calls. With a namespace configured they are printed in the
+/// Embedded Metric Format, which CloudWatch extracts from the function's log.
+pub(crate) fn emit(
+    config: &Metrics,
+    response: &Response<Body>,
+    latency: Duration,
+    calls: &[(Endpoint, Duration)],
+    request_id: &str,
+) {
+    let outcomes = response.extensions().get::<Outcomes>();
+    let failure = response.extensions().get::<FailureReason>();
+    #[cfg(all(feature = "prometheus", any(feature = "cli", feature = "server")))]
+    {
+        if let Some(outcomes) = outcomes {
+            exporter::record_outcomes(outcomes);
+        }
+        if let Some(reason) = failure {
+            exporter::count_update_failure(reason.as_str());
+        }
+        for (endpoint, call_latency) in calls {
+            exporter::observe_porkbun_call(endpoint.as_str(), *call_latency);
+        }
+    }
+    let Some(namespace) = config.namespace.as_deref() else {
+        return;
+    };
+    let now = SystemTime::now();
+    if let Some(outcomes) = outcomes {
+        println!(
+            "{}",
+            emf_document(namespace, outcomes, latency, request_id, now)
+        );
+    }
+    if let Some(reason) = failure {
+        println!("{}", failure_document(namespace, *reason, request_id, now));
+    }
+    for document in call_latency_documents(namespace, calls, request_id, now) {
+        println!("{}", document);
+    }
+}
+
+/// Milliseconds since the epoch, the EMF timestamp.
+fn timestamp(now: SystemTime) -> u64 {
+    now.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// The EMF log event: the metric definitions in `_aws` and their values as top-level fields.
+fn emf_document(
+    namespace: &str,
+    outcomes: &Outcomes,
+    latency: Duration,
+    request_id: &str,
+    now: SystemTime,
+) -> Value {
+    json!({
+        "_aws": {
+            "Timestamp": timestamp(now),
+            "CloudWatchMetrics": [{
+                "Namespace": namespace,
+                "Dimensions": [[]],
+                "Metrics": [
+                    { "Name": "Updated", "Unit": "Count" },
+                    { "Name": "Created", "Unit": "Count" },
+                    { "Name": "Unchanged", "Unit": "Count" },
+                    { "Name": "Deleted", "Unit": "Count" },
+                    { "Name": "ProviderErrors", "Unit": "Count" },
+                    { "Name": "Latency", "Unit": "Milliseconds" },
+                ],
+            }],
+        },
+        "Updated": outcomes.updated,
+        "Created": outcomes.created,
+        "Unchanged": outcomes.unchanged,
+        "Deleted": outcomes.deleted,
+        "ProviderErrors": outcomes.failed,
+        "Latency": latency.as_secs_f64() * 1000.0,
+        // Not a metric, but searchable in the log to find the request
+        "request_id": request_id,
+    })
+}
+
+/// The EMF log event of a failed update: `UpdateFailed` with the `Reason` dimension, so an alarm
+/// can watch all reasons or just some.
+fn failure_document(
+    namespace: &str,
+    reason: FailureReason,
+    request_id: &str,
+    now: SystemTime,
+) -> Value {
+    json!({
+        "_aws": {
+            "Timestamp": timestamp(now),
+            "CloudWatchMetrics": [{
+                "Namespace": namespace,
+                // The empty set also publishes the total over all reasons
+                "Dimensions": [["Reason"], []],
+                "Metrics": [{ "Name": "UpdateFailed", "Unit": "Count" }],
+            }],
+        },
+        "Reason": reason.as_str(),
+        "UpdateFailed": 1,
+        "request_id": request_id,
+    })
+}
+
+/// One EMF log event per Porkbun endpoint called, with the `Endpoint` dimension and the latency
+/// of each call. An event can only carry one value per dimension, hence one per endpoint.
+fn call_latency_documents(
+    namespace: &str,
+    calls: &[(Endpoint, Duration)],
+    request_id: &str,
+    now: SystemTime,
+) -> Vec<Value> {
+    let mut endpoints: Vec<Endpoint> = Vec::new();
+    for (endpoint, _) in calls {
+        if !endpoints.contains(endpoint) {
+            endpoints.push(*endpoint);
+        }
+    }
+    endpoints
+        .into_iter()
+        .map(|endpoint| {
+            let latencies: Vec<f64> = calls
+                .iter()
+                .filter(|(called, _)| *called == endpoint)
+                .map(|(_, latency)| latency.as_secs_f64() * 1000.0)
+                .collect();
+            json!({
+                "_aws": {
+                    "Timestamp": timestamp(now),
+                    "CloudWatchMetrics": [{
+                        "Namespace": namespace,
+                        "Dimensions": [["Endpoint"]],
+                        "Metrics": [{ "Name": "PorkbunLatency", "Unit": "Milliseconds" }],
+                    }],
+                },
+                "Endpoint": endpoint.as_str(),
+                // Multiple calls to the endpoint are values of the same metric
+                "PorkbunLatency": latencies,
+                "request_id": request_id,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcomes() {
+        let outcomes = Outcomes::count([
+            UpdateAction::Updated,
+            UpdateAction::Unchanged,
+            UpdateAction::Unchanged,
+            UpdateAction::Failed,
+        ]);
+        assert_eq!(
+            outcomes,
+            Outcomes {
+                updated: 1,
+                unchanged: 2,
+                failed: 1,
+                ..Outcomes::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_emf_document() {
+        let outcomes = Outcomes {
+            created: 1,
+            failed: 1,
+            ..Outcomes::default()
+        };
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let document = emf_document(
+            "porkDyn",
+            &outcomes,
+            Duration::from_millis(250),
+            "abc-123",
+            now,
+        );
+        let directive = &document["_aws"]["CloudWatchMetrics"][0];
+        assert_eq!(document["_aws"]["Timestamp"], 1_700_000_000_000u64);
+        assert_eq!(directive["Namespace"], "porkDyn");
+        // Every metric defined in the directive needs a value
+        for metric in directive["Metrics"].as_array().unwrap() {
+            assert!(document[metric["Name"].as_str().unwrap()].is_number());
+        }
+        assert_eq!(document["Created"], 1);
+        assert_eq!(document["Updated"], 0);
+        assert_eq!(document["ProviderErrors"], 1);
+        assert_eq!(document["Latency"], 250.0);
+        assert_eq!(document["request_id"], "abc-123");
+    }
+
+    #[test]
+    fn test_failure_reason() {
+        assert_eq!(FailureReason::from_status(200), None);
+        assert_eq!(FailureReason::from_status(207), None);
+        assert_eq!(
+            FailureReason::from_status(400),
+            Some(FailureReason::Validation)
+        );
+        assert_eq!(FailureReason::from_status(401), Some(FailureReason::Auth));
+        assert_eq!(FailureReason::from_status(403), Some(FailureReason::Auth));
+        assert_eq!(
+            FailureReason::from_status(404),
+            Some(FailureReason::Validation)
+        );
+        assert_eq!(
+            FailureReason::from_status(429),
+            Some(FailureReason::Upstream)
+        );
+        assert_eq!(
+            FailureReason::from_status(500),
+            Some(FailureReason::Upstream)
+        );
+        assert_eq!(
+            FailureReason::from_status(502),
+            Some(FailureReason::Upstream)
+        );
+        assert_eq!(
+            FailureReason::from_status(504),
+            Some(FailureReason::Upstream)
+        );
+    }
+
+    #[test]
+    fn test_failure_document() {
+        let document = failure_document("porkDyn", FailureReason::Auth, "abc-123", UNIX_EPOCH);
+        let directive = &document["_aws"]["CloudWatchMetrics"][0];
+        assert_eq!(directive["Dimensions"], json!([["Reason"], []]));
+        assert_eq!(directive["Metrics"][0]["Name"], "UpdateFailed");
+        assert_eq!(document["Reason"], "auth");
+        assert_eq!(document["UpdateFailed"], 1);
+    }
+
+    #[test]
+    fn test_call_latency_documents() {
+        let calls = [
+            (Endpoint::Retrieve, Duration::from_millis(120)),
+            (Endpoint::Edit, Duration::from_millis(300)),
+            (Endpoint::Retrieve, Duration::from_millis(80)),
+        ];
+        let documents = call_latency_documents("porkDyn", &calls, "abc-123", UNIX_EPOCH);
+        assert_eq!(documents.len(), 2);
+        let directive = &documents[0]["_aws"]["CloudWatchMetrics"][0];
+        assert_eq!(directive["Dimensions"], json!([["Endpoint"]]));
+        assert_eq!(directive["Metrics"][0]["Name"], "PorkbunLatency");
+        assert_eq!(documents[0]["Endpoint"], "retrieve");
+        assert_eq!(documents[0]["PorkbunLatency"], json!([120.0, 80.0]));
+        assert_eq!(documents[1]["Endpoint"], "edit");
+        assert_eq!(documents[1]["PorkbunLatency"], json!([300.0]));
+        assert!(call_latency_documents("porkDyn", &[], "abc-123", UNIX_EPOCH).is_empty());
+    }
+}
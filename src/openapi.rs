@@ -0,0 +1,378 @@
+use crate::health::{Check, CheckStatus, HealthReport};
+use crate::http_handler::{
+    BatchEntry, ExistingRecord, ItemResult, MessageBody, RecordsBody, ResultsBody, UpdateAction,
+};
+use crate::version::BuildInfo;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+/// Path of the OpenAPI document describing the endpoints
+pub(crate) const OPENAPI_PATH: &str = "/openapi.json";
+/// The media type of OpenAPI documents, also keeps `request_id` out of the document
+pub(crate) const OPENAPI_CONTENT_TYPE: &str = "application/vnd.oai.openapi+json";
+
+/// The OpenAPI 3 document of the HTTP API. The schemas are derived from the request and response
+/// types, the operations are declared in `paths`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "porkDyn",
+        description = "Dynamic DNS updates for Porkbun and other DNS providers"
+    ),
+    paths(
+        paths::update,
+        paths::update_with_body,
+        paths::nic_update,
+        paths::fritzbox,
+        paths::webhook,
+        paths::checkip,
+        paths::records,
+        paths::health,
+        paths::version,
+        paths::openapi
+    ),
+    components(schemas(
+        UpdateAction,
+        ItemResult,
+        ResultsBody,
+        MessageBody,
+        BatchEntry,
+        ExistingRecord,
+        RecordsBody,
+        HealthReport,
+        Check,
+        CheckStatus,
+        BuildInfo
+    )),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "updates", description = "Record updates, natively and for router firmwares"),
+        (name = "monitoring", description = "Health, build information and the caller's address")
+    )
+)]
+pub(crate) struct ApiDoc;
+
+/// The Porkbun credentials can be sent as Basic Auth or in headers instead of parameters.
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "basic",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Basic).build()),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Api-Key"))),
+        );
+        components.add_security_scheme(
+            "secret_api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Secret-Api-Key"))),
+        );
+    }
+}
+
+/// The operations of the document. The handler reads its parameters from a `QueryMap` and
+/// dispatches by `Protocol`, so these functions and parameter types only describe them.
+#[allow(dead_code)]
+mod paths {
+    use super::*;
+    use serde::Deserialize;
+    use utoipa::{IntoParams, ToSchema};
+
+    /// The parameters of the regular update, as query-parameters or JSON object body
+    #[derive(Deserialize, IntoParams, ToSchema)]
+    #[into_params(parameter_in = Query)]
+    pub(super) struct UpdateParameters {
+        /// Shared access token, required if one is configured on the server side
+        token: Option<String>,
+        /// Porkbun API key, unless sent as header, Basic Auth or configured on the server side
+        apikey: Option<String>,
+        /// Porkbun secret API key
+        secretapikey: Option<String>,
+        /// Base64 KMS ciphertext of the credentials, replaces `apikey` and `secretapikey`
+        credentials: Option<String>,
+        /// Fully qualified domain name(s), comma-separated (alias `hostname`)
+        domain: String,
+        /// IPv4 address of the A record (alias `myip`)
+        ip: Option<String>,
+        /// IPv6 address of the AAAA record (alias `myipv6`)
+        ipv6: Option<String>,
+        /// Interface identifier appended to the prefix, e.g. `::5`
+        ipv6suffix: Option<String>,
+        /// Delegated prefix `ipv6suffix` is appended to, e.g. `2001:db8:1200::/56`
+        ipv6prefix: Option<String>,
+        /// Mail server of the MX record
+        mx: Option<String>,
+        /// Priority of the MX record (default 10)
+        prio: Option<u16>,
+        /// Target host of the SRV record
+        srvtarget: Option<String>,
+        /// Port of the SRV record, required with `srvtarget`
+        srvport: Option<u16>,
+        /// Priority of the SRV record (default 0)
+        srvprio: Option<u16>,
+        /// Weight of the SRV record (default 0)
+        srvweight: Option<u16>,
+        /// Value of the CAA record, e.g. `letsencrypt.org`
+        caa: Option<String>,
+        /// Tag of the CAA record: `issue`, `issuewild` or `iodef` (default `issue`)
+        caatag: Option<String>,
+        /// Flags of the CAA record (default 0)
+        caaflags: Option<u8>,
+        /// Target host of the ALIAS record
+        alias: Option<String>,
+        /// `ping`, `delete`, `check`, `acme-set` or `acme-clear`
+        action: Option<String>,
+        /// ACME DNS-01 challenge of `acme-set` and `acme-clear`
+        txt: Option<String>,
+        /// Comma-separated record types of `delete` and `check` (default `A,AAAA`)
+        #[serde(rename = "type")]
+        record_type: Option<String>,
+        /// `porkbun`, `cloudflare`, `route53`, `desec` or `hetzner`
+        provider: Option<String>,
+        /// `json`, `dyndns` or `plain`, takes precedence over the `Accept` header
+        format: Option<String>,
+        /// `true` or `authoritative` to report whether the new address is visible yet
+        verify: Option<String>,
+        /// Rewrite records even if they are up to date
+        force: Option<bool>,
+        /// Reject unknown parameters
+        strict: Option<bool>,
+        /// Update with a single call without looking the records up first
+        fast: Option<bool>,
+        /// Verify that the domain belongs to the Porkbun account first
+        checkdomain: Option<bool>,
+    }
+
+    /// A JSON array updates every entry, a JSON object is read like the query-parameters
+    #[derive(Deserialize, ToSchema)]
+    #[serde(untagged)]
+    pub(super) enum UpdateBody {
+        Batch(Vec<BatchEntry>),
+        Parameters(Box<UpdateParameters>),
+    }
+
+    /// DynDNS2 parameters, the credentials are sent as Basic Auth
+    #[derive(IntoParams)]
+    #[into_params(parameter_in = Query)]
+    pub(super) struct NicUpdateParameters {
+        /// Comma-separated host names
+        hostname: String,
+        /// IPv4 and/or IPv6 address (comma-separated), `delete` removes the records; defaults to
+        /// the caller's address
+        myip: Option<String>,
+        /// `YES` points the records at the parking addresses or removes them
+        offline: Option<String>,
+        /// Username instead of Basic Auth
+        username: Option<String>,
+        /// Password instead of Basic Auth
+        password: Option<String>,
+    }
+
+    /// The placeholders of the FRITZ!Box update URL, empty ones are ignored
+    #[derive(IntoParams)]
+    #[into_params(parameter_in = Query)]
+    pub(super) struct FritzBoxParameters {
+        /// Comma-separated domain names (`<domain>`)
+        domain: String,
+        /// `<ipaddr>`
+        ipaddr: Option<String>,
+        /// `<ip6addr>`
+        ip6addr: Option<String>,
+        /// `<ip6lanprefix>`
+        ip6lanprefix: Option<String>,
+        /// `<username>`, the Porkbun API key
+        username: Option<String>,
+        /// `<passwd>`, the Porkbun secret API key or the access token with server-side credentials
+        passwd: Option<String>,
+    }
+
+    /// Selects the records to list
+    #[derive(Deserialize, IntoParams)]
+    #[into_params(parameter_in = Query)]
+    pub(super) struct RecordsParameters {
+        /// Shared access token, required if one is configured on the server side
+        token: Option<String>,
+        /// The zone (registrable domain) to list
+        domain: String,
+        /// Only records of these comma-separated types
+        #[serde(rename = "type")]
+        record_type: Option<String>,
+        /// Only records of this subdomain
+        subdomain: Option<String>,
+    }
+
+    /// Whether to check the dependencies as well
+    #[derive(IntoParams)]
+    #[into_params(parameter_in = Query)]
+    pub(super) struct HealthParameters {
+        /// Also check that Porkbun is reachable and the credentials are valid
+        checks: Option<bool>,
+        /// Shared access token, required for `checks` if one is configured
+        token: Option<String>,
+    }
+
+    /// Updates records
+    ///
+    /// Also served at `/update`. Single records are answered with their result, multiple
+    /// records with a summary and a result per record.
+    #[utoipa::path(
+        get,
+        path = "/",
+        tag = "updates",
+        params(UpdateParameters),
+        responses(
+            (status = 200, description = "All records are up to date", body = ItemResult),
+            (status = 207, description = "Only some records were updated", body = ResultsBody),
+            (status = 400, description = "Invalid or missing parameters", body = MessageBody),
+            (status = 401, description = "Invalid token or Porkbun credentials", body = MessageBody),
+            (status = 403, description = "The domain may not be updated", body = MessageBody),
+            (status = 502, description = "The DNS provider failed", body = ItemResult),
+            (status = 504, description = "The DNS provider or the Lambda timed out", body = ItemResult)
+        ),
+        security((), ("basic" = []), ("api_key" = [], "secret_api_key" = []))
+    )]
+    fn update() {}
+
+    /// Updates records with a JSON body
+    #[utoipa::path(
+        post,
+        path = "/",
+        tag = "updates",
+        request_body = UpdateBody,
+        responses(
+            (status = 200, description = "All records are up to date", body = ResultsBody),
+            (status = 207, description = "Only some records were updated", body = ResultsBody),
+            (status = 400, description = "Invalid body", body = MessageBody),
+            (status = 401, description = "Invalid token or Porkbun credentials", body = MessageBody)
+        ),
+        security((), ("basic" = []), ("api_key" = [], "secret_api_key" = []))
+    )]
+    fn update_with_body() {}
+
+    /// DynDNS2 update
+    ///
+    /// Answers with DynDNS2 return codes like `good 1.2.3.4` or `badauth`, one line per host.
+    #[utoipa::path(
+        get,
+        path = "/nic/update",
+        tag = "updates",
+        params(NicUpdateParameters),
+        responses(
+            (status = 200, description = "DynDNS2 return codes", body = String, content_type = "text/plain")
+        ),
+        security(("basic" = []))
+    )]
+    fn nic_update() {}
+
+    /// FRITZ!Box update
+    #[utoipa::path(
+        get,
+        path = "/fritzbox",
+        tag = "updates",
+        params(FritzBoxParameters),
+        responses(
+            (status = 200, description = "All records are up to date", body = ResultsBody),
+            (status = 400, description = "Invalid or missing parameters", body = MessageBody)
+        )
+    )]
+    fn fritzbox() {}
+
+    /// IP change push notification
+    ///
+    /// Takes the address from fields like `new_ip` of any JSON payload, the domains default to
+    /// the configured webhook domains.
+    #[utoipa::path(
+        post,
+        path = "/webhook",
+        tag = "updates",
+        request_body(content = Object, description = "The notification, e.g. `{\"new_ip\": \"203.0.113.7\"}`"),
+        responses(
+            (status = 200, description = "All records are up to date", body = ResultsBody),
+            (status = 400, description = "No address or domain", body = MessageBody)
+        )
+    )]
+    fn webhook() {}
+
+    /// The caller's public IP address
+    #[utoipa::path(
+        get,
+        path = "/checkip",
+        tag = "monitoring",
+        responses(
+            (status = 200, description = "`Current IP Address: <ip>`", body = String, content_type = "text/html")
+        )
+    )]
+    fn checkip() {}
+
+    /// Lists the records of a zone
+    #[utoipa::path(
+        get,
+        path = "/records",
+        tag = "updates",
+        params(RecordsParameters),
+        responses(
+            (status = 200, description = "The records", body = RecordsBody),
+            (status = 403, description = "The domain may not be listed", body = MessageBody)
+        ),
+        security((), ("basic" = []), ("api_key" = [], "secret_api_key" = []))
+    )]
+    fn records() {}
+
+    /// Health check
+    #[utoipa::path(
+        get,
+        path = "/health",
+        tag = "monitoring",
+        params(HealthParameters),
+        responses(
+            (status = 200, description = "Healthy", body = HealthReport),
+            (status = 503, description = "A dependency check failed", body = HealthReport)
+        )
+    )]
+    fn health() {}
+
+    /// Build information
+    #[utoipa::path(
+        get,
+        path = "/version",
+        tag = "monitoring",
+        params(("token" = Option<String>, Query, description = "Shared access token, if configured")),
+        responses((status = 200, description = "Version, commit and features", body = BuildInfo))
+    )]
+    fn version() {}
+
+    /// This document
+    #[utoipa::path(
+        get,
+        path = "/openapi.json",
+        tag = "monitoring",
+        responses(
+            (status = 200, description = "The OpenAPI document", content_type = "application/vnd.oai.openapi+json")
+        )
+    )]
+    fn openapi() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document() {
+        let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        assert!(document["openapi"].as_str().unwrap().starts_with("3."));
+        for path in ["/", "/nic/update", "/records", "/health", OPENAPI_PATH] {
+            assert!(document["paths"][path].is_object(), "{}", path);
+        }
+        assert!(document["paths"]["/"]["get"].is_object());
+        assert!(document["paths"]["/"]["post"].is_object());
+        // The schemas follow the serde attributes of the response types
+        let item_result = &document["components"]["schemas"]["ItemResult"]["properties"];
+        assert!(item_result["status"].is_object());
+        assert!(item_result["type"].is_object());
+        assert!(item_result["content"].is_null());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::domain::Domain;
+use crate::ip_utils::RecordType;
+use crate::record::RecordUpdate;
+use crate::resolver;
+use futures::future::join_all;
+use lambda_http::tracing::{info, warn};
+use std::net::{IpAddr, Ipv4Addr};
+use std::str::FromStr;
+
+/// Cloudflare's and Google's public resolvers, what most clients will see
+const PUBLIC_RESOLVERS: [IpAddr; 2] = [
+    IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)),
+    IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
+];
+/// Porkbun's authoritative name servers, which see changes before any cache expires
+const PORKBUN_NAME_SERVERS: [&str; 4] = [
+    "curitiba.ns.porkbun.com",
+    "fortaleza.ns.porkbun.com",
+    "maceio.ns.porkbun.com",
+    "salvador.ns.porkbun.com",
+];
+
+/// Where updated records are looked up to verify they are visible (`verify` parameter).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Verification {
+    /// Public resolvers (`verify=true`), which may still return the old value until its TTL expires
+    Public,
+    /// Porkbun's name servers (`verify=authoritative`)
+    Authoritative,
+}
+
+impl FromStr for Verification {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "true" | "public" => Ok(Verification::Public),
+            "authoritative" => Ok(Verification::Authoritative),
+            _ => Err(format!("Invalid query-parameter 'verify': {}", s)),
+        }
+    }
+}
+
+impl Verification {
+    /// The addresses of the name servers to ask, name servers which can't be resolved are skipped.
+    pub(crate) async fn name_servers(self) -> Vec<IpAddr> {
+        match self {
+            Verification::Public => PUBLIC_RESOLVERS.to_vec(),
+            Verification::Authoritative => {
+                let lookups = PORKBUN_NAME_SERVERS
+                    .iter()
+                    .map(|name_server| tokio::net::lookup_host((*name_server, 53)));
+                join_all(lookups)
+                    .await
+                    .into_iter()
+                    .zip(PORKBUN_NAME_SERVERS)
+                    .filter_map(|(addresses, name_server)| {
+                        let mut addresses = addresses
+                            .inspect_err(|e| {
+                                warn!(
+                                    name_server = %name_server,
+                                    error = %e,
+                                    "Failed to resolve name server"
+                                )
+                            })
+                            .ok()?;
+                        addresses.find(|address| address.is_ipv4())
+                    })
+                    .map(|address| address.ip())
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Whether all name servers already return the new content of an A or AAAA record, `None` for
+/// other record types. Name servers which don't answer count as not visible.
+pub(crate) async fn is_visible(
+    name_servers: &[IpAddr],
+    domain: &Domain,
+    record_update: &RecordUpdate,
+) -> Option<bool> {
+    let ipv6 = match record_update.record_type {
+        RecordType::A => false,
+        RecordType::AAAA => true,
+        _ => return None,
+    };
+    let expected: IpAddr = record_update.content.parse().ok()?;
+    let answers = join_all(
+        name_servers
+            .iter()
+            .map(|name_server| resolver::lookup(*name_server, domain.qualified_name(), ipv6)),
+    )
+    .await;
+    let visible = name_servers
+        .iter()
+        .zip(answers)
+        .filter(|(name_server, answer)| match answer {
+            Ok(addresses) => addresses.contains(&expected),
+            Err(e) => {
+                warn!(name_server = %name_server, error = %e, "Failed to verify the record");
+                false
+            }
+        })
+        .count();
+    info!(
+        visible,
+        name_servers = name_servers.len(),
+        expected = %expected,
+        domain = domain.qualified_name(),
+        "Checked the name servers for the new address"
+    );
+    Some(!name_servers.is_empty() && visible == name_servers.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_verification() {
+        assert_eq!("true".parse(), Ok(Verification::Public));
+        assert_eq!("Authoritative".parse(), Ok(Verification::Authoritative));
+        assert_eq!(
+            "dnssec".parse::<Verification>(),
+            Err("Invalid query-parameter 'verify': dnssec".to_string())
+        );
+    }
+}
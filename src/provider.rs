@@ -0,0 +1,376 @@
+use crate::api::{
+    create_dns_record, delete_dns_record, delete_dns_records_by_name_type, get_all_dns_records,
+    get_dns_records, list_domains, ping, update_dns_record, update_dns_record_by_name_type,
+    DnsRecord, PorkbunClient,
+};
+#[cfg(feature = "cloudflare")]
+use crate::cloudflare::Cloudflare;
+use crate::credentials::Credentials;
+use crate::desec::Desec;
+use crate::domain::Domain;
+use crate::error::{ApiError, PorkbunError};
+use crate::hetzner::Hetzner;
+use crate::ip_utils::RecordType;
+use crate::record::RecordUpdate;
+#[cfg(feature = "route53")]
+use crate::route53::Route53;
+use lambda_http::tracing::info;
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Environment variable with the provider used if a request doesn't select one
+pub const PROVIDER_ENV: &str = "PORKDYN_PROVIDER";
+
+/// The record types of providers which manage whole record sets, ALIAS records are Porkbun-specific
+pub(crate) const RECORD_SET_TYPES: [RecordType; 6] = [
+    RecordType::A,
+    RecordType::AAAA,
+    RecordType::TXT,
+    RecordType::MX,
+    RecordType::SRV,
+    RecordType::CAA,
+];
+
+/// Environment variable with the provider records are updated at if the selected one is unreachable
+pub const FALLBACK_PROVIDER_ENV: &str = "PORKDYN_FALLBACK_PROVIDER";
+
+/// Environment variable with the comma-separated providers every record change is mirrored to
+pub const MIRROR_PROVIDERS_ENV: &str = "PORKDYN_MIRROR_PROVIDERS";
+
+/// The supported DNS hosting backends, selected with the `provider` parameter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ProviderKind {
+    #[default]
+    Porkbun,
+    Cloudflare,
+    Route53,
+    Desec,
+    Hetzner,
+}
+
+impl ProviderKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProviderKind::Porkbun => "porkbun",
+            ProviderKind::Cloudflare => "cloudflare",
+            ProviderKind::Route53 => "route53",
+            ProviderKind::Desec => "desec",
+            ProviderKind::Hetzner => "hetzner",
+        }
+    }
+
+    /// Whether support for the provider is compiled into this build, see the cargo features.
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            ProviderKind::Cloudflare => cfg!(feature = "cloudflare"),
+            ProviderKind::Route53 => cfg!(feature = "route53"),
+            ProviderKind::Porkbun | ProviderKind::Desec | ProviderKind::Hetzner => true,
+        }
+    }
+}
+
+impl FromStr for ProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "porkbun" => Ok(ProviderKind::Porkbun),
+            "cloudflare" => Ok(ProviderKind::Cloudflare),
+            "route53" => Ok(ProviderKind::Route53),
+            "desec" => Ok(ProviderKind::Desec),
+            "hetzner" => Ok(ProviderKind::Hetzner),
+            _ => Err(format!("Invalid provider: {}", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for ProviderKind {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// A DNS hosting backend the records are managed with.
+/// Providers are created per request, as the credentials may be sent by the caller.
+pub(crate) trait DnsProvider {
+    /// Retrieves all records of the type for the domain.
+    async fn get_records(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<Vec<DnsRecord>, ApiError>;
+
+    /// Creates a record, returns its id if the provider reports one.
+    async fn create_record(
+        &self,
+        domain: &Domain,
+        record: &RecordUpdate,
+    ) -> Result<Option<String>, ApiError>;
+
+    async fn update_record(
+        &self,
+        domain: &Domain,
+        record_id: &str,
+        record: &RecordUpdate,
+    ) -> Result<(), ApiError>;
+
+    async fn delete_record(&self, domain: &Domain, record_id: &str) -> Result<(), ApiError>;
+
+    /// Retrieves the first record of the type for the domain.
+    async fn get_record(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<Option<DnsRecord>, ApiError> {
+        let record = self
+            .get_records(domain, record_type)
+            .await?
+            .into_iter()
+            .next();
+        match &record {
+            Some(record) => {
+                info!(domain = domain.qualified_name(), record = ?record, "Found matching record")
+            }
+            None => info!("No existing record found."),
+        }
+        Ok(record)
+    }
+
+    /// Updates all records of the name and type without looking them up first.
+    /// Providers without such an operation fail, callers then fall back to a lookup.
+    async fn update_records(
+        &self,
+        _domain: &Domain,
+        _record: &RecordUpdate,
+    ) -> Result<(), ApiError> {
+        Err(ApiError::UpdateRecordFailed(PorkbunError::Unsupported(
+            "Updating records by name and type".to_string(),
+        )))
+    }
+
+    /// Deletes all records of the name and type.
+    async fn delete_records(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<(), ApiError> {
+        for record in self.get_records(domain, record_type).await? {
+            self.delete_record(domain, &record.id).await?;
+        }
+        Ok(())
+    }
+}
+
+/// The Porkbun API, authenticated with the given credentials.
+pub(crate) struct Porkbun<'a> {
+    client: &'a PorkbunClient,
+    credentials: &'a Credentials,
+}
+
+impl<'a> Porkbun<'a> {
+    pub fn new(client: &'a PorkbunClient, credentials: &'a Credentials) -> Self {
+        Self {
+            client,
+            credentials,
+        }
+    }
+
+    /// Validates the credentials, returning the public IP address Porkbun sees.
+    pub async fn ping(&self) -> Result<String, ApiError> {
+        ping(self.client, self.credentials).await
+    }
+
+    /// Lists all domains of the account, only Porkbun supports this.
+    pub async fn list_domains(&self) -> Result<Vec<String>, ApiError> {
+        list_domains(self.client, self.credentials).await
+    }
+
+    /// Lists all records of the domain's zone, only Porkbun supports this.
+    pub async fn list_records(&self, domain_name: &str) -> Result<Vec<DnsRecord>, ApiError> {
+        get_all_dns_records(self.client, self.credentials, domain_name).await
+    }
+}
+
+impl DnsProvider for Porkbun<'_> {
+    async fn get_records(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<Vec<DnsRecord>, ApiError> {
+        get_dns_records(self.client, self.credentials, domain, record_type).await
+    }
+
+    async fn create_record(
+        &self,
+        domain: &Domain,
+        record: &RecordUpdate,
+    ) -> Result<Option<String>, ApiError> {
+        create_dns_record(self.client, self.credentials, domain, record).await
+    }
+
+    async fn update_record(
+        &self,
+        domain: &Domain,
+        record_id: &str,
+        record: &RecordUpdate,
+    ) -> Result<(), ApiError> {
+        update_dns_record(self.client, self.credentials, domain, record_id, record).await
+    }
+
+    async fn delete_record(&self, domain: &Domain, record_id: &str) -> Result<(), ApiError> {
+        delete_dns_record(self.client, self.credentials, domain, record_id).await
+    }
+
+    async fn update_records(&self, domain: &Domain, record: &RecordUpdate) -> Result<(), ApiError> {
+        update_dns_record_by_name_type(self.client, self.credentials, domain, record).await
+    }
+
+    async fn delete_records(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<(), ApiError> {
+        delete_dns_records_by_name_type(self.client, self.credentials, domain, record_type).await
+    }
+}
+
+/// The provider selected for a request.
+pub(crate) enum Provider<'a> {
+    Porkbun(Porkbun<'a>),
+    #[cfg(feature = "cloudflare")]
+    Cloudflare(Cloudflare<'a>),
+    #[cfg(feature = "route53")]
+    Route53(Route53<'a>),
+    Desec(Desec<'a>),
+    Hetzner(Hetzner<'a>),
+}
+
+impl Provider<'_> {
+    pub fn kind(&self) -> ProviderKind {
+        match self {
+            Provider::Porkbun(_) => ProviderKind::Porkbun,
+            #[cfg(feature = "cloudflare")]
+            Provider::Cloudflare(_) => ProviderKind::Cloudflare,
+            #[cfg(feature = "route53")]
+            Provider::Route53(_) => ProviderKind::Route53,
+            Provider::Desec(_) => ProviderKind::Desec,
+            Provider::Hetzner(_) => ProviderKind::Hetzner,
+        }
+    }
+}
+
+impl DnsProvider for Provider<'_> {
+    async fn get_records(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<Vec<DnsRecord>, ApiError> {
+        match self {
+            Provider::Porkbun(provider) => provider.get_records(domain, record_type).await,
+            #[cfg(feature = "cloudflare")]
+            Provider::Cloudflare(provider) => provider.get_records(domain, record_type).await,
+            #[cfg(feature = "route53")]
+            Provider::Route53(provider) => provider.get_records(domain, record_type).await,
+            Provider::Desec(provider) => provider.get_records(domain, record_type).await,
+            Provider::Hetzner(provider) => provider.get_records(domain, record_type).await,
+        }
+    }
+
+    async fn create_record(
+        &self,
+        domain: &Domain,
+        record: &RecordUpdate,
+    ) -> Result<Option<String>, ApiError> {
+        match self {
+            Provider::Porkbun(provider) => provider.create_record(domain, record).await,
+            #[cfg(feature = "cloudflare")]
+            Provider::Cloudflare(provider) => provider.create_record(domain, record).await,
+            #[cfg(feature = "route53")]
+            Provider::Route53(provider) => provider.create_record(domain, record).await,
+            Provider::Desec(provider) => provider.create_record(domain, record).await,
+            Provider::Hetzner(provider) => provider.create_record(domain, record).await,
+        }
+    }
+
+    async fn update_record(
+        &self,
+        domain: &Domain,
+        record_id: &str,
+        record: &RecordUpdate,
+    ) -> Result<(), ApiError> {
+        match self {
+            Provider::Porkbun(provider) => provider.update_record(domain, record_id, record).await,
+            #[cfg(feature = "cloudflare")]
+            Provider::Cloudflare(provider) => {
+                provider.update_record(domain, record_id, record).await
+            }
+            #[cfg(feature = "route53")]
+            Provider::Route53(provider) => provider.update_record(domain, record_id, record).await,
+            Provider::Desec(provider) => provider.update_record(domain, record_id, record).await,
+            Provider::Hetzner(provider) => provider.update_record(domain, record_id, record).await,
+        }
+    }
+
+    async fn delete_record(&self, domain: &Domain, record_id: &str) -> Result<(), ApiError> {
+        match self {
+            Provider::Porkbun(provider) => provider.delete_record(domain, record_id).await,
+            #[cfg(feature = "cloudflare")]
+            Provider::Cloudflare(provider) => provider.delete_record(domain, record_id).await,
+            #[cfg(feature = "route53")]
+            Provider::Route53(provider) => provider.delete_record(domain, record_id).await,
+            Provider::Desec(provider) => provider.delete_record(domain, record_id).await,
+            Provider::Hetzner(provider) => provider.delete_record(domain, record_id).await,
+        }
+    }
+
+    async fn update_records(&self, domain: &Domain, record: &RecordUpdate) -> Result<(), ApiError> {
+        match self {
+            Provider::Porkbun(provider) => provider.update_records(domain, record).await,
+            #[cfg(feature = "cloudflare")]
+            Provider::Cloudflare(provider) => provider.update_records(domain, record).await,
+            #[cfg(feature = "route53")]
+            Provider::Route53(provider) => provider.update_records(domain, record).await,
+            Provider::Desec(provider) => provider.update_records(domain, record).await,
+            Provider::Hetzner(provider) => provider.update_records(domain, record).await,
+        }
+    }
+
+    async fn delete_records(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<(), ApiError> {
+        match self {
+            Provider::Porkbun(provider) => provider.delete_records(domain, record_type).await,
+            #[cfg(feature = "cloudflare")]
+            Provider::Cloudflare(provider) => provider.delete_records(domain, record_type).await,
+            #[cfg(feature = "route53")]
+            Provider::Route53(provider) => provider.delete_records(domain, record_type).await,
+            Provider::Desec(provider) => provider.delete_records(domain, record_type).await,
+            Provider::Hetzner(provider) => provider.delete_records(domain, record_type).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_kind_from_str() {
+        assert_eq!("porkbun".parse(), Ok(ProviderKind::Porkbun));
+        assert_eq!("Cloudflare".parse(), Ok(ProviderKind::Cloudflare));
+        assert_eq!("route53".parse(), Ok(ProviderKind::Route53));
+        assert_eq!("deSEC".parse(), Ok(ProviderKind::Desec));
+        assert_eq!("hetzner".parse(), Ok(ProviderKind::Hetzner));
+        assert!("gandi".parse::<ProviderKind>().is_err());
+        assert_eq!(
+            ProviderKind::Cloudflare.is_enabled(),
+            cfg!(feature = "cloudflare")
+        );
+        assert!(ProviderKind::Porkbun.is_enabled());
+    }
+}
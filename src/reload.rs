@@ -0,0 +1,209 @@
+use crate::config::{Config, CONFIG_FILE_ENV};
+use crate::credentials::redact;
+use crate::error::CredentialsError;
+use crate::state::AppState;
+use lambda_http::tracing::{error, info};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Environment variable with the seconds between two checks of the configuration file for changes
+/// in server mode
+#[cfg(feature = "server")]
+pub const CONFIG_RELOAD_ENV: &str = "PORKDYN_CONFIG_RELOAD_SECONDS";
+/// Seconds between two checks of the configuration file, if not configured
+#[cfg(feature = "server")]
+pub const DEFAULT_CONFIG_RELOAD_SECONDS: u64 = 30;
+
+/// Checks the configuration file for changes, so a long-running server or daemon picks up new
+/// domains and tokens without a restart.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    config: Config,
+}
+
+impl ConfigWatcher {
+    /// Watches the file named by `PORKDYN_CONFIG_FILE`, `None` if no file is configured.
+    pub fn from_env() -> Result<Option<Self>, CredentialsError> {
+        let Ok(path) = std::env::var(CONFIG_FILE_ENV) else {
+            return Ok(None);
+        };
+        let path = PathBuf::from(path);
+        Ok(Some(Self {
+            modified: modified(&path),
+            config: Config::load()?,
+            path,
+        }))
+    }
+
+    /// Returns the state built from the new configuration if the file changed.
+    /// An invalid file is logged and the current configuration kept.
+    pub async fn reload(&mut self) -> Option<AppState> {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        let config = match Config::load() {
+            Ok(config) => config,
+            Err(e) => {
+                error!(error = %e, "Keeping the current configuration, failed to reload");
+                return None;
+            }
+        };
+        let changes = diff(&self.config, &config);
+        if changes.is_empty() {
+            info!("Configuration file changed, but no setting did");
+            return None;
+        }
+        match AppState::from_config(config.clone()).await {
+            Ok(state) => {
+                info!(changes = %changes.join(", "), "Reloaded configuration");
+                self.config = config;
+                Some(state)
+            }
+            Err(e) => {
+                error!(error = %e, "Keeping the current configuration, failed to reload");
+                None
+            }
+        }
+    }
+}
+
+/// The modification time of the file, `None` if it can't be read.
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Describes the settings which differ between the configurations, caller tokens are masked.
+fn diff(old: &Config, new: &Config) -> Vec<String> {
+    let mut changes = Vec::new();
+    change(&mut changes, "provider", &old.provider, &new.provider);
+    change(
+        &mut changes,
+        "fallback_provider",
+        &old.fallback_provider,
+        &new.fallback_provider,
+    );
+    change(
+        &mut changes,
+        "mirror_providers",
+        &old.mirror_providers,
+        &new.mirror_providers,
+    );
+    change(
+        &mut changes,
+        "default_ttl",
+        &old.default_ttl,
+        &new.default_ttl,
+    );
+    change(
+        &mut changes,
+        "allowed_domains",
+        &old.allowed_domains,
+        &new.allowed_domains,
+    );
+    change(
+        &mut changes,
+        "allow_private",
+        &old.allow_private,
+        &new.allow_private,
+    );
+    change(
+        &mut changes,
+        "webhook_domains",
+        &old.webhook_domains,
+        &new.webhook_domains,
+    );
+    change(
+        &mut changes,
+        "parking_ips",
+        &old.parking_ips,
+        &new.parking_ips,
+    );
+    change(
+        &mut changes,
+        "strict_parameters",
+        &old.strict_parameters,
+        &new.strict_parameters,
+    );
+    change(&mut changes, "ip_sources", &old.ip_sources, &new.ip_sources);
+    if old.access_token != new.access_token {
+        changes.push("access_token changed".to_string());
+    }
+    for (token, domains) in &new.token_domains {
+        match old.token_domains.get(token) {
+            None => changes.push(format!("token {} added: {:?}", redact(token), domains)),
+            Some(previous) if previous != domains => changes.push(format!(
+                "token {}: {:?} -> {:?}",
+                redact(token),
+                previous,
+                domains
+            )),
+            Some(_) => {}
+        }
+    }
+    for token in old.token_domains.keys() {
+        if !new.token_domains.contains_key(token) {
+            changes.push(format!("token {} removed", redact(token)));
+        }
+    }
+    change(&mut changes, "timeouts", &old.timeouts, &new.timeouts);
+    change(&mut changes, "retry", &old.retry, &new.retry);
+    change(&mut changes, "cors", &old.cors, &new.cors);
+    change(&mut changes, "metrics", &old.metrics, &new.metrics);
+    changes
+}
+
+fn change<T: PartialEq + fmt::Debug>(changes: &mut Vec<String>, name: &str, old: &T, new: &T) {
+    if old != new {
+        changes.push(format!("{}: {:?} -> {:?}", name, old, new));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let old = Config::parse(
+            r#"
+            default_ttl = 300
+            access_token = "admin-token"
+
+            [token_domains]
+            router-token = ["home.example.com"]
+            lab-token = ["*.lab.example.com"]
+            "#,
+        )
+        .unwrap();
+        let new = Config::parse(
+            r#"
+            provider = "hetzner"
+            default_ttl = 300
+            access_token = "new-admin-token"
+
+            [token_domains]
+            router-token = ["home.example.com", "vpn.example.com"]
+            "#,
+        )
+        .unwrap();
+        let mut changes = diff(&old, &new);
+        changes.sort();
+        assert_eq!(
+            changes,
+            vec![
+                "access_token changed",
+                "provider: Porkbun -> Hetzner",
+                "token lab-*** removed",
+                "token rout***: [\"home.example.com\"] -> [\"home.example.com\", \"vpn.example.com\"]",
+            ]
+        );
+        assert!(diff(&new, &new.clone()).is_empty());
+        assert!(!format!("{:?}", new).contains("router-token"));
+    }
+}
@@ -0,0 +1,110 @@
+use lambda_http::{Body, Request, RequestExt, Response};
+
+/// Header a caller (or a reverse proxy) can pass its own request id in, echoed in the response
+pub(crate) const REQUEST_ID_HEADER: &str = "X-Request-Id";
+/// Longer ids, or ids with other characters, are replaced instead of being logged
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// The id the request is logged and answered with: the caller's `X-Request-Id`, the Lambda
+/// invocation id (so the logs match the CloudWatch request id) or a new UUID.
+pub(crate) fn request_id(event: &Request) -> String {
+    event
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| is_valid(id))
+        .map(str::to_string)
+        .or_else(|| {
+            event
+                .lambda_context_ref()
+                .map(|context| context.request_id.clone())
+                .filter(|id| is_valid(id))
+        })
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// Request ids end up in logs, headers and JSON bodies, so only plain characters are accepted.
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+/// Echoes the request id as `X-Request-Id` header and as `request_id` field of JSON objects.
+pub(crate) fn attach(mut response: Response<Body>, request_id: &str) -> Response<Body> {
+    let is_json = response
+        .headers()
+        .get("Content-Type")
+        .is_some_and(|content_type| content_type == "application/json");
+    if is_json {
+        if let Body::Text(text) = response.body() {
+            // The id only has plain characters, so it can be spliced in without re-serializing,
+            // which keeps the order of the fields
+            let text = match text.strip_prefix('{') {
+                Some("}") => Some(format!("{{\"request_id\":\"{}\"}}", request_id)),
+                Some(fields) => Some(format!("{{\"request_id\":\"{}\",{}", request_id, fields)),
+                None => None,
+            };
+            if let Some(text) = text {
+                *response.body_mut() = Body::Text(text);
+            }
+        }
+    }
+    if let Ok(value) = request_id.parse() {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_id() {
+        let mut event = Request::default();
+        event
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER, "edge-4f2a:17".parse().unwrap());
+        assert_eq!(request_id(&event), "edge-4f2a:17");
+
+        event
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER, "id with spaces".parse().unwrap());
+        let generated = request_id(&event);
+        assert!(uuid::Uuid::parse_str(&generated).is_ok());
+        assert!(!is_valid(&"a".repeat(MAX_REQUEST_ID_LENGTH + 1)));
+    }
+
+    #[test]
+    fn test_attach() {
+        let json = |text: &str| {
+            Response::builder()
+                .header("Content-Type", "application/json")
+                .body(Body::Text(text.to_string()))
+                .unwrap()
+        };
+        let response = attach(json(r#"{"message":"ok"}"#), "abc-123");
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "abc-123");
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(response.body()).unwrap(),
+            serde_json::json!({ "request_id": "abc-123", "message": "ok" })
+        );
+        let response = attach(json("{}"), "abc-123");
+        assert_eq!(
+            response.body().to_vec(),
+            br#"{"request_id":"abc-123"}"#.to_vec()
+        );
+
+        let text = Response::builder()
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(Body::Text("good 1.2.3.4".to_string()))
+            .unwrap();
+        let response = attach(text, "abc-123");
+        assert_eq!(response.body().to_vec(), b"good 1.2.3.4".to_vec());
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "abc-123");
+    }
+}
@@ -0,0 +1,327 @@
+use lambda_http::Error;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+const TYPE_A: u16 = 1;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+const CLASS_CHAOS: u16 = 3;
+const RCODE_NO_ERROR: u8 = 0;
+const RCODE_NXDOMAIN: u8 = 3;
+/// UDP may lose the query or the answer, so it is sent a few times
+const ATTEMPTS: u32 = 3;
+const ATTEMPT_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Name servers answering with the address a query arrives from, a single UDP round trip
+/// instead of an HTTPS request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolver {
+    /// `myip.opendns.com` A/AAAA at the OpenDNS resolvers
+    OpenDns,
+    /// `o-o.myaddr.l.google.com` TXT at Google's name servers
+    Google,
+    /// `whoami.cloudflare` CHAOS TXT at Cloudflare's resolvers
+    Cloudflare,
+}
+
+impl FromStr for Resolver {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "opendns" => Ok(Resolver::OpenDns),
+            "google" => Ok(Resolver::Google),
+            "cloudflare" => Ok(Resolver::Cloudflare),
+            _ => Err(format!("Invalid resolver: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Resolver::OpenDns => "opendns",
+            Resolver::Google => "google",
+            Resolver::Cloudflare => "cloudflare",
+        })
+    }
+}
+
+/// A question to a name server, e.g. one whose answer contains the address of the asking host.
+struct Question<'a> {
+    server: IpAddr,
+    name: &'a str,
+    record_type: u16,
+    class: u16,
+}
+
+impl Resolver {
+    fn question(&self, ipv6: bool) -> Question<'static> {
+        let (v4, v6, name, record_type, class) = match self {
+            Resolver::OpenDns => (
+                Ipv4Addr::new(208, 67, 222, 222),
+                Ipv6Addr::new(0x2620, 0x119, 0x35, 0, 0, 0, 0, 0x35),
+                "myip.opendns.com",
+                if ipv6 { TYPE_AAAA } else { TYPE_A },
+                CLASS_IN,
+            ),
+            Resolver::Google => (
+                Ipv4Addr::new(216, 239, 32, 10),
+                Ipv6Addr::new(0x2001, 0x4860, 0x4802, 0x32, 0, 0, 0, 0xa),
+                "o-o.myaddr.l.google.com",
+                TYPE_TXT,
+                CLASS_IN,
+            ),
+            Resolver::Cloudflare => (
+                Ipv4Addr::new(1, 1, 1, 1),
+                Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111),
+                "whoami.cloudflare",
+                TYPE_TXT,
+                CLASS_CHAOS,
+            ),
+        };
+        Question {
+            server: if ipv6 { IpAddr::V6(v6) } else { IpAddr::V4(v4) },
+            name,
+            record_type,
+            class,
+        }
+    }
+
+    /// Asks the resolver for the address of this host, over the IP version of `ipv6`.
+    pub(crate) async fn public_ip(&self, ipv6: bool) -> Result<IpAddr, Error> {
+        ask(&self.question(ipv6), |response, id| {
+            parse_answer(response, id, ipv6)
+        })
+        .await?
+        .ok_or_else(|| format!("No valid answer from the {} resolver", self).into())
+    }
+}
+
+/// The addresses of the A (or AAAA if `ipv6`) records of the name at the name server, empty if
+/// the name or the record doesn't exist.
+pub(crate) async fn lookup(server: IpAddr, name: &str, ipv6: bool) -> Result<Vec<IpAddr>, Error> {
+    let question = Question {
+        server,
+        name,
+        record_type: if ipv6 { TYPE_AAAA } else { TYPE_A },
+        class: CLASS_IN,
+    };
+    let addresses = ask(&question, parse_answers)
+        .await?
+        .ok_or_else(|| format!("No valid answer from {}", server))?;
+    Ok(addresses
+        .into_iter()
+        .filter(|address| address.is_ipv6() == ipv6)
+        .collect())
+}
+
+/// Sends the question until `parse`, which gets the response and the id of the query, accepts
+/// a response. `None` if no valid response arrived.
+async fn ask<T>(
+    question: &Question<'_>,
+    parse: impl Fn(&[u8], u16) -> Option<T>,
+) -> Result<Option<T>, Error> {
+    let local: SocketAddr = if question.server.is_ipv6() {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect((question.server, 53)).await?;
+    let id = u16::from_be_bytes(uuid::Uuid::new_v4().as_bytes()[..2].try_into()?);
+    let query = encode_query(id, question);
+    let mut buffer = [0; 512];
+    for _ in 0..ATTEMPTS {
+        socket.send(&query).await?;
+        let Ok(received) = tokio::time::timeout(ATTEMPT_TIMEOUT, socket.recv(&mut buffer)).await
+        else {
+            continue;
+        };
+        if let Some(answer) = parse(&buffer[..received?], id) {
+            return Ok(Some(answer));
+        }
+    }
+    Ok(None)
+}
+
+fn encode_query(id: u16, question: &Question) -> Vec<u8> {
+    let mut query = Vec::with_capacity(64);
+    query.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, one question
+    query.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]);
+    for label in question.name.split('.') {
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&question.record_type.to_be_bytes());
+    query.extend_from_slice(&question.class.to_be_bytes());
+    query
+}
+
+/// The first address of the requested version in the answer section, from A/AAAA records or
+/// TXT records containing an address.
+fn parse_answer(response: &[u8], id: u16, ipv6: bool) -> Option<IpAddr> {
+    parse_answers(response, id)?
+        .into_iter()
+        .find(|address| address.is_ipv6() == ipv6)
+}
+
+/// The addresses in the answer section of a response to the query, from A/AAAA records or TXT
+/// records containing an address. A response for a non-existent name (NXDOMAIN) has none,
+/// `None` for other errors and malformed responses.
+fn parse_answers(response: &[u8], id: u16) -> Option<Vec<IpAddr>> {
+    let header = response.get(..12)?;
+    let is_response = header[2] & 0x80 != 0;
+    let rcode = header[3] & 0x0f;
+    if header[..2] != id.to_be_bytes()
+        || !is_response
+        || !matches!(rcode, RCODE_NO_ERROR | RCODE_NXDOMAIN)
+    {
+        return None;
+    }
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+    let answers = u16::from_be_bytes([header[6], header[7]]);
+    let mut position = 12;
+    for _ in 0..questions {
+        position = skip_name(response, position)? + 4;
+    }
+    let mut addresses = Vec::new();
+    for _ in 0..answers {
+        position = skip_name(response, position)?;
+        let fixed = response.get(position..position + 10)?;
+        let record_type = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let length = usize::from(u16::from_be_bytes([fixed[8], fixed[9]]));
+        position += 10;
+        let data = response.get(position..position + length)?;
+        position += length;
+        let address = match record_type {
+            TYPE_A => <[u8; 4]>::try_from(data).ok().map(IpAddr::from),
+            TYPE_AAAA => <[u8; 16]>::try_from(data).ok().map(IpAddr::from),
+            TYPE_TXT => txt_strings(data).find_map(|text| text.trim().parse().ok()),
+            _ => None,
+        };
+        addresses.extend(address);
+    }
+    Some(addresses)
+}
+
+/// The position after the (possibly compressed) name starting at `position`.
+fn skip_name(message: &[u8], mut position: usize) -> Option<usize> {
+    loop {
+        let length = *message.get(position)?;
+        match length {
+            0 => return Some(position + 1),
+            // A pointer to an earlier name ends the name
+            length if length & 0xc0 == 0xc0 => return Some(position + 2),
+            length => position += 1 + usize::from(length),
+        }
+    }
+}
+
+/// The character strings of TXT record data.
+fn txt_strings(mut data: &[u8]) -> impl Iterator<Item = &str> {
+    std::iter::from_fn(move || {
+        let (&length, rest) = data.split_first()?;
+        let text = rest.get(..usize::from(length))?;
+        data = &rest[text.len()..];
+        Some(std::str::from_utf8(text).unwrap_or_default())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response to the query with the given answer records, names compressed to the question.
+    fn response(id: u16, question: &Question, answers: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut response = encode_query(id, question);
+        response[2] |= 0x80;
+        response[7] = answers.len() as u8;
+        for (record_type, data) in answers {
+            response.extend_from_slice(&[0xc0, 12]);
+            response.extend_from_slice(&record_type.to_be_bytes());
+            response.extend_from_slice(&question.class.to_be_bytes());
+            response.extend_from_slice(&[0, 0, 0, 0]);
+            response.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            response.extend_from_slice(data);
+        }
+        response
+    }
+
+    #[test]
+    fn test_encode_query() {
+        let query = encode_query(0x1234, &Resolver::OpenDns.question(false));
+        assert_eq!(&query[..4], &[0x12, 0x34, 0x01, 0x00]);
+        assert_eq!(&query[12..17], b"\x04myip");
+        assert_eq!(&query[query.len() - 5..], &[0, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_parse_answer() {
+        let question = Resolver::OpenDns.question(false);
+        let a = response(7, &question, &[(TYPE_A, vec![203, 0, 113, 7])]);
+        assert_eq!(
+            parse_answer(&a, 7, false),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        assert_eq!(parse_answer(&a, 8, false), None);
+        assert_eq!(parse_answer(&a, 7, true), None);
+
+        let question = Resolver::Google.question(true);
+        let txt = |text: &str| [&[text.len() as u8][..], text.as_bytes()].concat();
+        let answer = response(
+            7,
+            &question,
+            &[
+                (TYPE_TXT, txt("edns0-client-subnet 2001:db8::/56")),
+                (TYPE_TXT, txt("2001:db8::1")),
+            ],
+        );
+        assert_eq!(
+            parse_answer(&answer, 7, true),
+            Some("2001:db8::1".parse().unwrap())
+        );
+
+        let mut refused = response(7, &question, &[]);
+        refused[3] |= 5;
+        assert_eq!(parse_answer(&refused, 7, true), None);
+    }
+
+    #[test]
+    fn test_parse_answers() {
+        let question = Question {
+            server: Ipv4Addr::new(1, 1, 1, 1).into(),
+            name: "home.example.org",
+            record_type: TYPE_A,
+            class: CLASS_IN,
+        };
+        let answer = response(
+            7,
+            &question,
+            &[
+                (TYPE_A, vec![203, 0, 113, 7]),
+                (TYPE_A, vec![203, 0, 113, 8]),
+            ],
+        );
+        assert_eq!(
+            parse_answers(&answer, 7),
+            Some(vec![
+                "203.0.113.7".parse().unwrap(),
+                "203.0.113.8".parse().unwrap()
+            ])
+        );
+
+        let mut nxdomain = response(7, &question, &[]);
+        nxdomain[3] |= RCODE_NXDOMAIN;
+        assert_eq!(parse_answers(&nxdomain, 7), Some(vec![]));
+        let mut servfail = response(7, &question, &[]);
+        servfail[3] |= 2;
+        assert_eq!(parse_answers(&servfail, 7), None);
+    }
+}
@@ -0,0 +1,392 @@
+use crate::api::{DnsRecord, DEFAULT_TTL};
+use crate::domain::Domain;
+use crate::error::{ApiError, PorkbunError};
+use crate::ip_utils::RecordType;
+use crate::provider::{DnsProvider, RECORD_SET_TYPES};
+use crate::record::{parse_rdata, RecordUpdate};
+use aws_sdk_route53::error::{ProvideErrorMetadata, SdkError};
+use aws_sdk_route53::types::{
+    Change, ChangeAction, ChangeBatch, ResourceRecord, ResourceRecordSet, RrType,
+};
+use lambda_http::tracing::{error, info};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Environment variable enabling Route 53 (`true`), implied if it is the default provider.
+/// The Lambda's role needs `route53:ListHostedZonesByName`, `route53:ListResourceRecordSets`
+/// and `route53:ChangeResourceRecordSets`
+pub const ROUTE53_ENABLED_ENV: &str = "PORKDYN_ROUTE53_ENABLED";
+
+/// Creates the Route 53 client with the Lambda's IAM role.
+pub async fn client() -> aws_sdk_route53::Client {
+    let config = aws_config::load_from_env().await;
+    aws_sdk_route53::Client::new(&config)
+}
+
+/// AWS Route 53, authenticated with the Lambda's IAM role.
+/// Route 53 manages record sets without ids, so a record is identified by its value.
+pub(crate) struct Route53<'a> {
+    client: &'a aws_sdk_route53::Client,
+    /// Hosted zone ids by domain name, so a request updating several records looks each zone up once.
+    zones: Mutex<HashMap<String, String>>,
+}
+
+impl<'a> Route53<'a> {
+    pub fn new(client: &'a aws_sdk_route53::Client) -> Self {
+        Self {
+            client,
+            zones: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Looks up the id of the public hosted zone the domain belongs to.
+    async fn zone_id(&self, domain: &Domain) -> Result<String, PorkbunError> {
+        let domain_name = domain.domain_name();
+        if let Some(zone_id) = self.zones.lock().unwrap().get(domain_name) {
+            return Ok(zone_id.clone());
+        }
+        info!(domain = domain_name, "Look up Route 53 hosted zone");
+        let response = self
+            .client
+            .list_hosted_zones_by_name()
+            .dns_name(domain_name)
+            .send()
+            .await
+            .map_err(sdk_error)?;
+        let zone_name = format!("{}.", domain_name);
+        let zone_id = response
+            .hosted_zones()
+            .iter()
+            .find(|zone| {
+                zone.name() == zone_name
+                    && !zone.config().is_some_and(|config| config.private_zone())
+            })
+            .map(|zone| zone.id().trim_start_matches("/hostedzone/").to_string())
+            .ok_or_else(|| {
+                PorkbunError::DomainNotFound(format!("No Route 53 hosted zone for {}", domain_name))
+            })?;
+        self.zones
+            .lock()
+            .unwrap()
+            .insert(domain_name.to_string(), zone_id.clone());
+        Ok(zone_id)
+    }
+
+    /// Retrieves the record set of the name and type, alias record sets are ignored.
+    async fn record_set(
+        &self,
+        zone_id: &str,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<Option<ResourceRecordSet>, PorkbunError> {
+        let rr_type = rr_type(record_type)?;
+        let response = self
+            .client
+            .list_resource_record_sets()
+            .hosted_zone_id(zone_id)
+            .start_record_name(domain.qualified_name())
+            .start_record_type(rr_type.clone())
+            .max_items(1)
+            .send()
+            .await
+            .map_err(sdk_error)?;
+        Ok(response
+            .resource_record_sets()
+            .iter()
+            .find(|set| {
+                set.r#type() == &rr_type
+                    && record_name(set.name()).eq_ignore_ascii_case(domain.qualified_name())
+                    && set.alias_target().is_none()
+            })
+            .cloned())
+    }
+
+    /// Applies a single change to the record set of the name and type.
+    async fn change(
+        &self,
+        zone_id: &str,
+        action: ChangeAction,
+        record_set: ResourceRecordSet,
+    ) -> Result<(), PorkbunError> {
+        info!(
+            record_set = record_set.name(),
+            action = action.as_str(),
+            zone_id,
+            "Change Route 53 record set"
+        );
+        let change = Change::builder()
+            .action(action)
+            .resource_record_set(record_set)
+            .build()
+            .map_err(build_error)?;
+        self.client
+            .change_resource_record_sets()
+            .hosted_zone_id(zone_id)
+            .change_batch(
+                ChangeBatch::builder()
+                    .changes(change)
+                    .build()
+                    .map_err(build_error)?,
+            )
+            .send()
+            .await
+            .map_err(sdk_error)?;
+        Ok(())
+    }
+
+    /// Replaces the record set of the name and type with the given values.
+    async fn upsert(
+        &self,
+        zone_id: &str,
+        domain: &Domain,
+        record_type: &RecordType,
+        ttl: u64,
+        values: Vec<String>,
+    ) -> Result<(), PorkbunError> {
+        let records = values
+            .into_iter()
+            .map(|value| ResourceRecord::builder().value(value).build())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(build_error)?;
+        let record_set = ResourceRecordSet::builder()
+            .name(domain.qualified_name())
+            .r#type(rr_type(record_type)?)
+            .ttl(ttl as i64)
+            .set_resource_records(Some(records))
+            .build()
+            .map_err(build_error)?;
+        self.change(zone_id, ChangeAction::Upsert, record_set).await
+    }
+}
+
+/// Route 53 returns fully qualified names with a trailing dot and escapes `*` as `\052`.
+fn record_name(name: &str) -> String {
+    name.trim_end_matches('.').replace("\\052", "*")
+}
+
+fn rr_type(record_type: &RecordType) -> Result<RrType, PorkbunError> {
+    match record_type {
+        RecordType::ALIAS => Err(PorkbunError::Unsupported(
+            "ALIAS records are not supported by Route 53".to_string(),
+        )),
+        record_type => Ok(RrType::from(record_type.as_str())),
+    }
+}
+
+fn record_values(record_set: &ResourceRecordSet) -> Vec<String> {
+    record_set
+        .resource_records()
+        .iter()
+        .map(|record| record.value().to_string())
+        .collect()
+}
+
+/// Classifies a failed Route 53 call like a failed Porkbun call.
+fn sdk_error<E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static, R>(
+    error: SdkError<E, R>,
+) -> PorkbunError {
+    let message = error
+        .message()
+        .map(str::to_string)
+        .unwrap_or_else(|| error.to_string());
+    match (&error, error.code()) {
+        (SdkError::TimeoutError(_) | SdkError::DispatchFailure(_), _) => {
+            PorkbunError::Unavailable(message)
+        }
+        (_, Some("Throttling" | "ThrottlingException" | "PriorRequestNotComplete")) => {
+            PorkbunError::RateLimited(message)
+        }
+        (_, Some("InvalidClientTokenId" | "ExpiredToken" | "UnrecognizedClientException")) => {
+            PorkbunError::InvalidCredentials(message)
+        }
+        (_, Some("AccessDenied" | "AccessDeniedException")) => {
+            PorkbunError::ApiAccessDisabled(message)
+        }
+        (_, Some("NoSuchHostedZone")) => PorkbunError::DomainNotFound(message),
+        (_, Some("ServiceUnavailable")) => PorkbunError::Unavailable(message),
+        _ => PorkbunError::Failed(message),
+    }
+}
+
+fn build_error(error: aws_sdk_route53::error::BuildError) -> PorkbunError {
+    PorkbunError::Failed(error.to_string())
+}
+
+impl DnsProvider for Route53<'_> {
+    async fn get_records(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<Vec<DnsRecord>, ApiError> {
+        async {
+            let zone_id = self.zone_id(domain).await?;
+            let Some(record_set) = self.record_set(&zone_id, domain, record_type).await? else {
+                return Ok(Vec::new());
+            };
+            let ttl = record_set.ttl().map(|ttl| ttl.to_string());
+            Ok::<_, PorkbunError>(
+                record_values(&record_set)
+                    .into_iter()
+                    .map(|value| {
+                        let (content, prio) = parse_rdata(record_type, &value);
+                        let mut record =
+                            DnsRecord::new(value, domain.qualified_name(), record_type, content);
+                        record.prio = prio;
+                        record.ttl = ttl.clone();
+                        record
+                    })
+                    .collect(),
+            )
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to retrieve DNS records"))
+        .map_err(ApiError::RetrieveRecordFailed)
+    }
+
+    /// Adds the value to the record set, which is created if it doesn't exist. The value is also
+    /// the id of the record.
+    async fn create_record(
+        &self,
+        domain: &Domain,
+        record: &RecordUpdate,
+    ) -> Result<Option<String>, ApiError> {
+        async {
+            let zone_id = self.zone_id(domain).await?;
+            let record_set = self
+                .record_set(&zone_id, domain, &record.record_type)
+                .await?;
+            let mut values = record_set.as_ref().map(record_values).unwrap_or_default();
+            values.push(record.rdata());
+            let ttl = record
+                .ttl
+                .or_else(|| record_set.and_then(|set| set.ttl()).map(|ttl| ttl as u64))
+                .unwrap_or(DEFAULT_TTL);
+            self.upsert(&zone_id, domain, &record.record_type, ttl, values)
+                .await
+                .map(|_| Some(record.rdata()))
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to create DNS record"))
+        .map_err(ApiError::CreateRecordFailed)
+    }
+
+    /// Replaces the value `record_id` of the record set.
+    async fn update_record(
+        &self,
+        domain: &Domain,
+        record_id: &str,
+        record: &RecordUpdate,
+    ) -> Result<(), ApiError> {
+        async {
+            let zone_id = self.zone_id(domain).await?;
+            let record_set = self
+                .record_set(&zone_id, domain, &record.record_type)
+                .await?;
+            let mut values = record_set.as_ref().map(record_values).unwrap_or_default();
+            values.retain(|value| value != record_id);
+            values.push(record.rdata());
+            self.upsert(
+                &zone_id,
+                domain,
+                &record.record_type,
+                record.ttl.unwrap_or(DEFAULT_TTL),
+                values,
+            )
+            .await
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to update DNS record"))
+        .map_err(ApiError::UpdateRecordFailed)
+    }
+
+    /// Removes the value `record_id` from the record sets of the domain, deleting a record set
+    /// once its last value is removed.
+    async fn delete_record(&self, domain: &Domain, record_id: &str) -> Result<(), ApiError> {
+        async {
+            let zone_id = self.zone_id(domain).await?;
+            // Record ids don't carry the type, so all types of the name are searched
+            for record_type in RECORD_SET_TYPES {
+                let Some(record_set) = self.record_set(&zone_id, domain, &record_type).await?
+                else {
+                    continue;
+                };
+                let mut values = record_values(&record_set);
+                if !values.iter().any(|value| value == record_id) {
+                    continue;
+                }
+                values.retain(|value| value != record_id);
+                return if values.is_empty() {
+                    self.change(&zone_id, ChangeAction::Delete, record_set)
+                        .await
+                } else {
+                    let ttl = record_set.ttl().unwrap_or(DEFAULT_TTL as i64) as u64;
+                    self.upsert(&zone_id, domain, &record_type, ttl, values)
+                        .await
+                };
+            }
+            Err(PorkbunError::Failed(format!(
+                "Record {} of {} not found",
+                record_id,
+                domain.qualified_name()
+            )))
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to delete DNS record"))
+        .map_err(ApiError::DeleteRecordFailed)
+    }
+
+    /// Route 53 replaces a whole record set with a single `UPSERT` change.
+    async fn update_records(&self, domain: &Domain, record: &RecordUpdate) -> Result<(), ApiError> {
+        async {
+            let zone_id = self.zone_id(domain).await?;
+            self.upsert(
+                &zone_id,
+                domain,
+                &record.record_type,
+                record.ttl.unwrap_or(DEFAULT_TTL),
+                vec![record.rdata()],
+            )
+            .await
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to update DNS record"))
+        .map_err(ApiError::UpdateRecordFailed)
+    }
+
+    async fn delete_records(
+        &self,
+        domain: &Domain,
+        record_type: &RecordType,
+    ) -> Result<(), ApiError> {
+        async {
+            let zone_id = self.zone_id(domain).await?;
+            match self.record_set(&zone_id, domain, record_type).await? {
+                Some(record_set) => {
+                    self.change(&zone_id, ChangeAction::Delete, record_set)
+                        .await
+                }
+                None => Ok(()),
+            }
+        }
+        .await
+        .inspect_err(|e| error!(error = %e, "Failed to delete DNS records"))
+        .map_err(ApiError::DeleteRecordFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rr_type() {
+        assert_eq!(rr_type(&RecordType::AAAA).unwrap(), RrType::Aaaa);
+        assert!(rr_type(&RecordType::ALIAS).is_err());
+    }
+
+    #[test]
+    fn test_record_name() {
+        assert_eq!(record_name("home.example.org."), "home.example.org");
+        assert_eq!(record_name("\\052.example.org."), "*.example.org");
+    }
+}
@@ -0,0 +1,108 @@
+use crate::http_handler::{function_handler, InternalRequest};
+use crate::ip_source::{public_ip, resolve_hostname};
+use crate::ip_utils::IpType;
+use crate::state::AppState;
+use lambda_http::tracing::info;
+use lambda_http::{Error, Request, RequestExt};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Environment variable with the comma-separated domains updated on every scheduled invocation
+pub const SCHEDULED_DOMAINS_ENV: &str = "PORKDYN_SCHEDULED_DOMAINS";
+/// Environment variable with a host name whose addresses the domains are pointed at,
+/// e.g. the router's own DynDNS name
+pub const SCHEDULED_IP_HOSTNAME_ENV: &str = "PORKDYN_SCHEDULED_IP_HOSTNAME";
+
+/// Updates triggered by an EventBridge schedule, so no client has to call the function.
+#[derive(Debug)]
+pub struct ScheduledConfig {
+    domains: String,
+    ip_hostname: Option<String>,
+}
+
+impl ScheduledConfig {
+    pub fn from_env() -> Option<Self> {
+        let domains = std::env::var(SCHEDULED_DOMAINS_ENV)
+            .ok()
+            .filter(|domains| !domains.trim().is_empty())?;
+        Some(Self {
+            domains,
+            ip_hostname: std::env::var(SCHEDULED_IP_HOSTNAME_ENV)
+                .ok()
+                .filter(|hostname| !hostname.is_empty()),
+        })
+    }
+}
+
+/// Whether the event was sent by an EventBridge schedule rule.
+pub(crate) fn is_scheduled_event(event: &Value) -> bool {
+    event.get("source").and_then(Value::as_str) == Some("aws.events")
+        && event.get("detail-type").and_then(Value::as_str) == Some("Scheduled Event")
+}
+
+/// Points the configured domains at the current IP, records which are up to date are skipped.
+/// Returns the response body of the update, a failed update fails the invocation.
+pub(crate) async fn scheduled_update(state: &AppState) -> Result<Value, Error> {
+    let Some(config) = &state.scheduled else {
+        return Err(format!(
+            "Scheduled updates are not configured, set {}",
+            SCHEDULED_DOMAINS_ENV
+        )
+        .into());
+    };
+    let (ip, ipv6) = match &config.ip_hostname {
+        Some(hostname) => resolve_hostname(hostname).await?,
+        // The function's own public IPv4 address, e.g. of the NAT gateway of its VPC
+        None => (Some(public_ip(state, IpType::V4).await?), None),
+    };
+    info!(domains = %config.domains, ip = ?ip, ipv6 = ?ipv6, "Scheduled update");
+    let mut parameters = HashMap::from([("domain".to_string(), config.domains.clone())]);
+    if let Some(ip) = ip {
+        parameters.insert("ip".to_string(), ip);
+    }
+    if let Some(ipv6) = ipv6 {
+        parameters.insert("ipv6".to_string(), ipv6);
+    }
+    let mut event = Request::default().with_query_string_parameters(parameters);
+    event.extensions_mut().insert(InternalRequest);
+
+    let response = function_handler(event, state).await?;
+    let body = serde_json::from_slice(response.body())
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(response.body()).into()));
+    if !response.status().is_success() {
+        return Err(format!(
+            "Scheduled update failed with status {}: {}",
+            response.status(),
+            body
+        )
+        .into());
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_scheduled_event() {
+        let event = serde_json::json!({
+            "version": "0",
+            "id": "53dc4d37-cffa-4f76-80c9-8b7d4a4d2eaa",
+            "detail-type": "Scheduled Event",
+            "source": "aws.events",
+            "account": "123456789012",
+            "time": "2024-01-01T00:00:00Z",
+            "region": "us-east-1",
+            "resources": ["arn:aws:events:us-east-1:123456789012:rule/porkdyn"],
+            "detail": {}
+        });
+        assert!(is_scheduled_event(&event));
+        let http_event = serde_json::json!({
+            "version": "2.0",
+            "rawPath": "/",
+            "requestContext": {"http": {"method": "GET"}}
+        });
+        assert!(!is_scheduled_event(&http_event));
+    }
+}
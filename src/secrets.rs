@@ -0,0 +1,294 @@
+use crate::credentials::Credentials;
+use crate::error::CredentialsError;
+#[cfg(feature = "kms")]
+use aws_sdk_kms::primitives::Blob;
+#[cfg(feature = "kms")]
+use base64::{engine::general_purpose::STANDARD, Engine};
+#[cfg(feature = "parameter-store")]
+use lambda_http::tracing::error;
+#[cfg(any(
+    feature = "cli",
+    feature = "kms",
+    feature = "parameter-store",
+    feature = "secrets-manager"
+))]
+use lambda_http::tracing::info;
+use serde::Deserialize;
+#[cfg(feature = "parameter-store")]
+use std::sync::Mutex;
+#[cfg(feature = "parameter-store")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "kms")]
+use zeroize::Zeroize;
+
+/// Environment variable with the name or ARN of the Secrets Manager secret holding the Porkbun credentials
+pub const SECRET_ID_ENV: &str = "PORKBUN_CREDENTIALS_SECRET_ID";
+/// Environment variable with the SSM Parameter Store path below which the `apikey` and
+/// `secretapikey` (SecureString) parameters are stored, e.g. `/porkdyn`
+pub const PARAMETER_PATH_ENV: &str = "PORKBUN_CREDENTIALS_PARAMETER_PATH";
+/// Environment variable with the number of seconds after which parameters are loaded again
+#[cfg(feature = "parameter-store")]
+pub const PARAMETER_REFRESH_ENV: &str = "PORKBUN_CREDENTIALS_REFRESH_SECONDS";
+#[cfg(feature = "parameter-store")]
+const DEFAULT_PARAMETER_REFRESH: Duration = Duration::from_secs(300);
+/// Environment variables with the Porkbun credentials configured directly on the Lambda
+pub const API_KEY_ENV: &str = "PORKBUN_API_KEY";
+pub const SECRET_KEY_ENV: &str = "PORKBUN_SECRET_KEY";
+/// Environment variable with the ID or ARN of the KMS key callers encrypt their credentials with
+pub const KMS_KEY_ID_ENV: &str = "PORKDYN_KMS_KEY_ID";
+
+/// Porkbun credentials configured on the server side, callers don't have to send them.
+#[derive(Debug)]
+pub enum ServerCredentials {
+    /// Credentials loaded once at cold start (e.g. from Secrets Manager)
+    Static(Credentials),
+    /// Credentials loaded from SSM Parameter Store, refreshed once the TTL expired
+    #[cfg(feature = "parameter-store")]
+    ParameterStore(ParameterStoreCredentials),
+}
+
+impl ServerCredentials {
+    pub async fn get(&self) -> Result<Credentials, CredentialsError> {
+        match self {
+            ServerCredentials::Static(credentials) => Ok(credentials.clone()),
+            #[cfg(feature = "parameter-store")]
+            ServerCredentials::ParameterStore(parameters) => parameters.get().await,
+        }
+    }
+}
+
+#[cfg(feature = "parameter-store")]
+#[derive(Debug)]
+pub struct ParameterStoreCredentials {
+    client: aws_sdk_ssm::Client,
+    path: String,
+    ttl: Duration,
+    cache: Mutex<Option<(Credentials, Instant)>>,
+}
+
+#[cfg(feature = "parameter-store")]
+impl ParameterStoreCredentials {
+    pub async fn new(path: &str, ttl: Option<Duration>) -> Self {
+        let config = aws_config::load_from_env().await;
+        Self {
+            client: aws_sdk_ssm::Client::new(&config),
+            path: path.trim_end_matches('/').to_string(),
+            ttl: ttl.unwrap_or(DEFAULT_PARAMETER_REFRESH),
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Returns the cached credentials, loading them again if the TTL expired.
+    /// If refreshing fails, the previously loaded credentials are used until the next attempt.
+    async fn get(&self) -> Result<Credentials, CredentialsError> {
+        let cached = self.cache.lock().unwrap().clone();
+        if let Some((credentials, loaded_at)) = &cached {
+            if loaded_at.elapsed() < self.ttl {
+                return Ok(credentials.clone());
+            }
+        }
+
+        match self.load().await {
+            Ok(credentials) => {
+                *self.cache.lock().unwrap() = Some((credentials.clone(), Instant::now()));
+                Ok(credentials)
+            }
+            Err(e) => match cached {
+                Some((credentials, _)) => {
+                    error!(error = %e, "Failed to refresh credentials, using cached ones");
+                    Ok(credentials)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    async fn load(&self) -> Result<Credentials, CredentialsError> {
+        let api_key_name = format!("{}/apikey", self.path);
+        let secret_key_name = format!("{}/secretapikey", self.path);
+        info!(path = %self.path, "Loading Porkbun credentials from parameters");
+        let response = self
+            .client
+            .get_parameters()
+            .names(&api_key_name)
+            .names(&secret_key_name)
+            .with_decryption(true)
+            .send()
+            .await
+            .map_err(|e| CredentialsError::ParameterStore(e.to_string()))?;
+        let value = |name: &str| {
+            response
+                .parameters()
+                .iter()
+                .find(|parameter| parameter.name() == Some(name))
+                .and_then(|parameter| parameter.value())
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    CredentialsError::InvalidSecret(format!("Parameter {} not found", name))
+                })
+        };
+        Ok(Credentials::new(
+            value(&api_key_name)?,
+            value(&secret_key_name)?,
+        ))
+    }
+}
+
+/// Decrypts caller credentials sent as KMS ciphertext, so credentials stored in a router
+/// configuration are useless without the Lambda's `kms:Decrypt` permission.
+#[cfg(feature = "kms")]
+#[derive(Debug)]
+pub struct KmsDecryptor {
+    client: aws_sdk_kms::Client,
+    key_id: String,
+}
+
+#[cfg(feature = "kms")]
+impl KmsDecryptor {
+    pub async fn new(key_id: &str) -> Self {
+        let config = aws_config::load_from_env().await;
+        Self {
+            client: aws_sdk_kms::Client::new(&config),
+            key_id: key_id.to_string(),
+        }
+    }
+
+    /// Decrypts a base64 encoded ciphertext blob whose plaintext has the same JSON structure as the
+    /// Secrets Manager secret, i.e. `{"apikey": "...", "secretapikey": "..."}`.
+    pub async fn decrypt_credentials(
+        &self,
+        ciphertext: &str,
+    ) -> Result<Credentials, CredentialsError> {
+        let ciphertext = STANDARD
+            .decode(ciphertext.trim())
+            .map_err(|e| CredentialsError::Kms(format!("Ciphertext is not valid base64: {}", e)))?;
+        info!(key_id = ?self.key_id, "Decrypting caller credentials with KMS");
+        let response = self
+            .client
+            .decrypt()
+            .key_id(&self.key_id)
+            .ciphertext_blob(Blob::new(ciphertext))
+            .send()
+            .await
+            .map_err(|e| CredentialsError::Kms(e.to_string()))?;
+        let mut plaintext = response
+            .plaintext
+            .ok_or_else(|| CredentialsError::Kms("Decryption returned no plaintext".to_string()))?
+            .into_inner();
+        let credentials = std::str::from_utf8(&plaintext)
+            .map_err(|e| CredentialsError::InvalidSecret(e.to_string()))
+            .and_then(parse_secret);
+        plaintext.zeroize();
+        credentials
+    }
+}
+
+/// The expected JSON structure of the secret string
+#[cfg_attr(
+    not(any(feature = "cli", feature = "kms", feature = "secrets-manager")),
+    allow(dead_code)
+)]
+#[derive(Deserialize)]
+struct SecretCredentials {
+    apikey: String,
+    secretapikey: String,
+}
+
+/// Loads the Porkbun credentials below the parameter path, refreshed after
+/// `PORKBUN_CREDENTIALS_REFRESH_SECONDS` (5 minutes by default).
+#[cfg(feature = "parameter-store")]
+pub(crate) async fn load_from_parameter_store(
+    path: &str,
+) -> Result<ServerCredentials, CredentialsError> {
+    let ttl = std::env::var(PARAMETER_REFRESH_ENV)
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .map(Duration::from_secs);
+    Ok(ServerCredentials::ParameterStore(
+        ParameterStoreCredentials::new(path, ttl).await,
+    ))
+}
+
+/// Parameter Store support is left out of the build, so a configured path is an error.
+#[cfg(not(feature = "parameter-store"))]
+pub(crate) async fn load_from_parameter_store(
+    _path: &str,
+) -> Result<ServerCredentials, CredentialsError> {
+    Err(CredentialsError::ParameterStore(
+        "not included in this build, enable the `parameter-store` feature".to_string(),
+    ))
+}
+
+/// Fetches the Porkbun credentials from AWS Secrets Manager using the Lambda's IAM role.
+#[cfg(feature = "secrets-manager")]
+pub(crate) async fn load_from_secrets_manager(
+    secret_id: &str,
+) -> Result<Credentials, CredentialsError> {
+    info!(
+        id = secret_id,
+        "Loading Porkbun credentials from Secrets Manager"
+    );
+    let config = aws_config::load_from_env().await;
+    let client = aws_sdk_secretsmanager::Client::new(&config);
+    let secret = client
+        .get_secret_value()
+        .secret_id(secret_id)
+        .send()
+        .await
+        .map_err(|e| CredentialsError::SecretsManager(e.to_string()))?;
+    let secret_string = secret
+        .secret_string()
+        .ok_or_else(|| CredentialsError::InvalidSecret("Secret has no string value".to_string()))?;
+    parse_secret(secret_string)
+}
+
+/// Secrets Manager support is left out of the build, so a configured secret is an error.
+#[cfg(not(feature = "secrets-manager"))]
+pub(crate) async fn load_from_secrets_manager(
+    _secret_id: &str,
+) -> Result<Credentials, CredentialsError> {
+    Err(CredentialsError::SecretsManager(
+        "not included in this build, enable the `secrets-manager` feature".to_string(),
+    ))
+}
+
+/// Reads the Porkbun credentials from a JSON file with the same structure as the secret.
+#[cfg(feature = "cli")]
+pub(crate) fn load_from_file(path: &std::path::Path) -> Result<Credentials, CredentialsError> {
+    info!(path = %path.display(), "Loading Porkbun credentials from file");
+    let secret_string = std::fs::read_to_string(path).map_err(|e| {
+        CredentialsError::InvalidSecret(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+    parse_secret(&secret_string)
+}
+
+/// Parses a secret string of the form `{"apikey": "...", "secretapikey": "..."}`.
+/// Only builds which load secrets (files, KMS, Secrets Manager) use it.
+#[cfg_attr(
+    not(any(feature = "cli", feature = "kms", feature = "secrets-manager")),
+    allow(dead_code)
+)]
+fn parse_secret(secret_string: &str) -> Result<Credentials, CredentialsError> {
+    let secret: SecretCredentials = serde_json::from_str(secret_string)
+        .map_err(|e| CredentialsError::InvalidSecret(e.to_string()))?;
+    Ok(Credentials::new(secret.apikey, secret.secretapikey))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_secret() {
+        let credentials =
+            parse_secret(r#"{"apikey": "pk1_key", "secretapikey": "sk1_secret"}"#).unwrap();
+        assert_eq!(credentials.api_key(), "pk1_key");
+        assert_eq!(credentials.secret_key(), "sk1_secret");
+    }
+
+    #[test]
+    fn test_parse_invalid_secret() {
+        assert!(parse_secret("pk1_key:sk1_secret").is_err());
+        assert!(parse_secret(r#"{"apikey": "pk1_key"}"#).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use crate::auth::{allow_unauthenticated, check_server_credentials};
+#[cfg(feature = "prometheus")]
+use crate::exporter;
+use crate::http_handler::{add_security_headers, function_handler};
+use crate::reload::{ConfigWatcher, CONFIG_RELOAD_ENV, DEFAULT_CONFIG_RELOAD_SECONDS};
+use crate::shutdown::shutdown_signal;
+use crate::state::AppState;
+use axum::body::{to_bytes, Body as AxumBody};
+use axum::extract::{ConnectInfo, Query, Request as AxumRequest, State};
+use axum::http::header::STRICT_TRANSPORT_SECURITY;
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response as AxumResponse};
+use axum::Router;
+use lambda_http::tracing::{error, info};
+use lambda_http::{Body, Error, Request, RequestExt};
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Environment variable with the address the server listens on, e.g. `127.0.0.1:8080`
+pub const LISTEN_ADDRESS_ENV: &str = "PORKDYN_LISTEN_ADDRESS";
+/// Environment variable which makes the server trust the `X-Forwarded-For` header of a reverse proxy
+pub const TRUST_FORWARDED_FOR_ENV: &str = "PORKDYN_TRUST_FORWARDED_FOR";
+const DEFAULT_LISTEN_ADDRESS: &str = "0.0.0.0:8080";
+/// Request bodies are small JSON documents, larger ones are rejected
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+/// Browsers only call over HTTPS for a year once they saw a response over HTTPS, e.g. through a
+/// TLS-terminating reverse proxy; the header is ignored on plain HTTP
+const HSTS: &str = "max-age=31536000";
+
+struct ServerState {
+    /// Replaced when the configuration file changes, requests keep the state they started with
+    app: RwLock<Arc<AppState>>,
+    trust_forwarded_for: bool,
+}
+
+/// Serves the Lambda handler over plain HTTP, so porkDyn can run without AWS (e.g. on a VPS).
+pub async fn serve(app: AppState) -> Result<(), Error> {
+    let address =
+        std::env::var(LISTEN_ADDRESS_ENV).unwrap_or_else(|_| DEFAULT_LISTEN_ADDRESS.to_string());
+    let state = Arc::new(ServerState {
+        app: RwLock::new(Arc::new(app)),
+        trust_forwarded_for: std::env::var(TRUST_FORWARDED_FOR_ENV)
+            .is_ok_and(|trust| trust == "true"),
+    });
+    if let Some(watcher) = ConfigWatcher::from_env()? {
+        tokio::spawn(watch_config(watcher, state.clone()));
+    }
+    #[cfg(feature = "prometheus")]
+    exporter::spawn_from_env().await?;
+    let router = Router::new().fallback(handle).with_state(state);
+    let listener = tokio::net::TcpListener::bind(&address).await?;
+    info!(address = %address, "Listening");
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    // The server stops accepting connections and waits for in-flight requests to finish,
+    // so no Porkbun call is cut off halfway through a multi-record update
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
+    info!("All requests finished, shutting down");
+    std::io::stdout().flush()?;
+    Ok(())
+}
+
+/// Swaps in a new state whenever the configuration file changes. A configuration which would
+/// serve the server-side credentials without a token is refused, just like at startup.
+async fn watch_config(mut watcher: ConfigWatcher, state: Arc<ServerState>) {
+    let seconds = std::env::var(CONFIG_RELOAD_ENV)
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .filter(|seconds| *seconds > 0)
+        .unwrap_or(DEFAULT_CONFIG_RELOAD_SECONDS);
+    let mut interval = tokio::time::interval(Duration::from_secs(seconds));
+    loop {
+        interval.tick().await;
+        let Some(app) = watcher.reload().await else {
+            continue;
+        };
+        match check_server_credentials(&app, allow_unauthenticated()) {
+            Ok(()) => *state.app.write().unwrap() = Arc::new(app),
+            Err(e) => error!(error = %e, "Keeping the current configuration"),
+        }
+    }
+}
+
+/// Translates the request into a Lambda event and the handler's response back, adding the
+/// security headers to every response.
+async fn handle(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: AxumRequest,
+) -> AxumResponse {
+    let mut response = respond(state, peer, request).await;
+    let headers = response.headers_mut();
+    add_security_headers(headers);
+    headers.insert(STRICT_TRANSPORT_SECURITY, HeaderValue::from_static(HSTS));
+    response
+}
+
+async fn respond(state: Arc<ServerState>, peer: SocketAddr, request: AxumRequest) -> AxumResponse {
+    let event = match into_event(request, peer, state.trust_forwarded_for).await {
+        Ok(event) => event,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let app = state.app.read().unwrap().clone();
+    match function_handler(event, &app).await {
+        Ok(response) => {
+            let (parts, body) = response.into_parts();
+            AxumResponse::from_parts(parts, AxumBody::from(body.to_vec()))
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to handle request");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Builds the event the handler expects from Lambda: query parameters are parsed and the caller's
+/// address is passed as `X-Forwarded-For`, unless a trusted reverse proxy already set it.
+async fn into_event(
+    request: AxumRequest,
+    peer: SocketAddr,
+    trust_forwarded_for: bool,
+) -> Result<Request, Error> {
+    let (mut parts, body) = request.into_parts();
+    let Query(pairs) = Query::<Vec<(String, String)>>::try_from_uri(&parts.uri)?;
+    let mut query_string_parameters: HashMap<String, Vec<String>> = HashMap::new();
+    for (name, value) in pairs {
+        query_string_parameters.entry(name).or_default().push(value);
+    }
+    if !trust_forwarded_for || !parts.headers.contains_key("X-Forwarded-For") {
+        parts.headers.insert(
+            "X-Forwarded-For",
+            HeaderValue::from_str(&peer.ip().to_canonical().to_string())?,
+        );
+    }
+    let bytes = to_bytes(body, MAX_BODY_SIZE).await?;
+    let body = if bytes.is_empty() {
+        Body::Empty
+    } else {
+        Body::from(bytes.to_vec())
+    };
+    Ok(Request::from_parts(parts, body).with_query_string_parameters(query_string_parameters))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::front_door::source_ip;
+
+    #[tokio::test]
+    async fn test_into_event() {
+        let peer: SocketAddr = "198.51.100.2:51234".parse().unwrap();
+        let request = || {
+            AxumRequest::builder()
+                .uri("/nic/update?hostname=home.example.org&hostname=nas.example.org&myip=1.2.3.4")
+                .header("X-Forwarded-For", "10.0.0.1, 203.0.113.7")
+                .body(AxumBody::empty())
+                .unwrap()
+        };
+
+        let event = into_event(request(), peer, false).await.unwrap();
+        let parameters = event.query_string_parameters();
+        assert_eq!(
+            parameters.all("hostname"),
+            Some(vec!["home.example.org", "nas.example.org"])
+        );
+        assert_eq!(parameters.first("myip"), Some("1.2.3.4"));
+        assert_eq!(event.uri().path(), "/nic/update");
+        assert_eq!(source_ip(&event), Some("198.51.100.2".to_string()));
+        assert!(event.body().is_empty());
+
+        let event = into_event(request(), peer, true).await.unwrap();
+        assert_eq!(source_ip(&event), Some("203.0.113.7".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_security_headers() {
+        let state = Arc::new(ServerState {
+            app: RwLock::new(Arc::new(AppState::default())),
+            trust_forwarded_for: false,
+        });
+        let peer: SocketAddr = "198.51.100.2:51234".parse().unwrap();
+        let request = AxumRequest::builder()
+            .uri("/health")
+            .body(AxumBody::empty())
+            .unwrap();
+        let response = handle(State(state), ConnectInfo(peer), request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[STRICT_TRANSPORT_SECURITY], HSTS);
+        assert_eq!(response.headers()["Cache-Control"], "no-store");
+        assert_eq!(response.headers()["X-Content-Type-Options"], "nosniff");
+    }
+}
@@ -0,0 +1,119 @@
+use crate::http_handler::function_handler;
+use crate::request_id::REQUEST_ID_HEADER;
+use crate::state::AppState;
+use lambda_http::http::{HeaderValue, Method};
+use lambda_http::tracing::{error, info};
+use lambda_http::{Body, Error, Request};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// An SQS event, only the fields needed to process the messages are decoded.
+#[derive(Debug, Deserialize)]
+struct SqsEvent {
+    #[serde(rename = "Records")]
+    records: Vec<SqsMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SqsMessage {
+    message_id: String,
+    #[serde(default)]
+    body: String,
+}
+
+/// The partial batch response: failed messages return to the queue and are retried, until SQS
+/// moves them to the dead-letter queue after `maxReceiveCount` attempts.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BatchResponse {
+    batch_item_failures: Vec<BatchItemFailure>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BatchItemFailure {
+    item_identifier: String,
+}
+
+/// Whether the event was sent by an SQS event source mapping.
+pub(crate) fn is_sqs_event(event: &Value) -> bool {
+    event
+        .get("Records")
+        .and_then(Value::as_array)
+        .and_then(|records| records.first())
+        .and_then(|record| record.get("eventSource"))
+        .and_then(Value::as_str)
+        == Some("aws:sqs")
+}
+
+/// Processes queued update requests one after another, returning the failed ones to the queue.
+pub(crate) async fn sqs_handler(event: Value, state: &AppState) -> Result<Value, Error> {
+    let event: SqsEvent = serde_json::from_value(event)?;
+    let mut response = BatchResponse::default();
+    for message in event.records {
+        match process_message(&message, state).await {
+            Ok(()) => info!(message_id = %message.message_id, "Processed message"),
+            Err(e) => {
+                error!(message_id = %message.message_id, error = %e, "Failed to process message");
+                response.batch_item_failures.push(BatchItemFailure {
+                    item_identifier: message.message_id,
+                });
+            }
+        }
+    }
+    Ok(serde_json::to_value(response)?)
+}
+
+/// Processes a message like a POST request with the message as JSON body,
+/// it only succeeds if every record of it was updated.
+async fn process_message(message: &SqsMessage, state: &AppState) -> Result<(), Error> {
+    let mut request = Request::new(Body::from(message.body.clone()));
+    *request.method_mut() = Method::POST;
+    // The message id ties the logs of the update to the message
+    if let Ok(value) = HeaderValue::from_str(&message.message_id) {
+        request.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    let response = function_handler(request, state).await?;
+    if response.status() != 200 {
+        return Err(format!(
+            "Update failed with status {}: {}",
+            response.status(),
+            String::from_utf8_lossy(response.body())
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_failed_messages_are_reported() {
+        let event = serde_json::json!({
+            "Records": [
+                {
+                    "messageId": "059f36b4-87a3-44ab-83d2-661975830a7d",
+                    "receiptHandle": "AQEBwJnKyrHigUMZj6rYigCgxlaS3SLy0a",
+                    "body": "{\"ip\": \"1.2.3.4\"}",
+                    "attributes": {"ApproximateReceiveCount": "1"},
+                    "eventSource": "aws:sqs",
+                    "eventSourceARN": "arn:aws:sqs:us-east-1:123456789012:porkdyn",
+                    "awsRegion": "us-east-1"
+                }
+            ]
+        });
+        assert!(is_sqs_event(&event));
+        assert!(!is_sqs_event(&serde_json::json!({"rawPath": "/"})));
+
+        let response = sqs_handler(event, &AppState::default()).await.unwrap();
+        assert_eq!(
+            response,
+            serde_json::json!({
+                "batchItemFailures": [{"itemIdentifier": "059f36b4-87a3-44ab-83d2-661975830a7d"}]
+            })
+        );
+    }
+}
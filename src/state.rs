@@ -0,0 +1,256 @@
+use crate::acme_dns::AcmeDnsConfig;
+use crate::api::{PorkbunClient, RetryPolicy};
+#[cfg(feature = "dynamodb")]
+use crate::audit::AuditLog;
+use crate::auth::TokenDomains;
+#[cfg(feature = "cloudflare")]
+use crate::cloudflare::CLOUDFLARE_API_TOKEN_ENV;
+use crate::config::{Config, Cors, Metrics};
+use crate::credentials::{redact, Credentials};
+use crate::desec::DESEC_TOKEN_ENV;
+use crate::error::CredentialsError;
+use crate::hetzner::HETZNER_DNS_TOKEN_ENV;
+use crate::ip_source::IpSource;
+use crate::provider::ProviderKind;
+#[cfg(feature = "route53")]
+use crate::route53::ROUTE53_ENABLED_ENV;
+use crate::scheduled::ScheduledConfig;
+#[cfg(feature = "kms")]
+use crate::secrets::KmsDecryptor;
+use crate::secrets::{
+    load_from_parameter_store, load_from_secrets_manager, ServerCredentials, API_KEY_ENV,
+    KMS_KEY_ID_ENV, PARAMETER_PATH_ENV, SECRET_ID_ENV, SECRET_KEY_ENV,
+};
+use std::fmt;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// State shared across invocations, initialized once at cold start.
+#[derive(Default)]
+pub struct AppState {
+    /// Porkbun credentials configured on the server side, callers don't have to send them.
+    pub server_credentials: Option<ServerCredentials>,
+    /// Shared secret callers have to send as `token` parameter, if configured.
+    pub access_token: Option<String>,
+    /// Caller tokens which may only update the listed domains.
+    pub token_domains: TokenDomains,
+    /// Decrypts KMS-encrypted caller credentials, if a KMS key is configured.
+    #[cfg(feature = "kms")]
+    pub kms_decryptor: Option<KmsDecryptor>,
+    /// The acme-dns compatible API, if a challenge zone is configured.
+    pub acme_dns: Option<AcmeDnsConfig>,
+    /// Client for the Porkbun API, shared across invocations.
+    pub porkbun: PorkbunClient,
+    /// The provider used if a request doesn't select one with the `provider` parameter.
+    pub provider: ProviderKind,
+    /// The provider records are updated at if the selected one is unreachable, if configured.
+    pub fallback_provider: Option<ProviderKind>,
+    /// The providers every record change is mirrored to, e.g. a secondary zone.
+    pub mirror_providers: Vec<ProviderKind>,
+    /// API token for Cloudflare, the provider is only available if one is configured.
+    #[cfg(feature = "cloudflare")]
+    pub cloudflare_token: Option<String>,
+    /// Client for Route 53, the provider is only available if it is enabled.
+    #[cfg(feature = "route53")]
+    pub route53: Option<aws_sdk_route53::Client>,
+    /// API token for deSEC, the provider is only available if one is configured.
+    pub desec_token: Option<String>,
+    /// API token for Hetzner DNS, the provider is only available if one is configured.
+    pub hetzner_token: Option<String>,
+    /// The domains updated on EventBridge schedule invocations, if configured.
+    pub scheduled: Option<ScheduledConfig>,
+    /// The TTL of records a request doesn't set one for, if configured.
+    pub default_ttl: Option<u64>,
+    /// Domain patterns the server manages, all domains are allowed if empty.
+    pub allowed_domains: Vec<String>,
+    /// Whether private and reserved addresses may be published as record content.
+    pub allow_private: bool,
+    /// The domains push notifications to `/webhook` update if they don't name any.
+    pub webhook_domains: Vec<String>,
+    /// The addresses records point to while their DynDNS2 client is offline, deleted if empty.
+    pub parking_ips: Vec<IpAddr>,
+    /// Whether updates with unknown parameters are rejected instead of ignoring them.
+    pub strict_parameters: bool,
+    /// Sources the public IP is looked up with, the defaults are used if empty.
+    pub ip_sources: Vec<IpSource>,
+    /// The origins and methods browsers may call with, disabled if no origin is configured.
+    pub cors: Cors,
+    /// The CloudWatch namespace of the request metrics, disabled if none is configured.
+    pub metrics: Metrics,
+    /// The DynamoDB table every record change is written to, if configured.
+    #[cfg(feature = "dynamodb")]
+    pub audit_log: Option<AuditLog>,
+}
+
+/// Caller tokens are masked, so the state can be logged safely.
+impl fmt::Debug for AppState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("AppState");
+        debug
+            .field("server_credentials", &self.server_credentials)
+            .field("access_token", &self.access_token.as_deref().map(redact))
+            .field(
+                "token_domains",
+                &self
+                    .token_domains
+                    .iter()
+                    .map(|(token, domains)| (redact(token), domains))
+                    .collect::<Vec<_>>(),
+            )
+            .field("acme_dns", &self.acme_dns)
+            .field("porkbun", &self.porkbun)
+            .field("provider", &self.provider)
+            .field("fallback_provider", &self.fallback_provider)
+            .field("mirror_providers", &self.mirror_providers);
+        #[cfg(feature = "kms")]
+        debug.field("kms_decryptor", &self.kms_decryptor);
+        #[cfg(feature = "cloudflare")]
+        debug.field(
+            "cloudflare_token",
+            &self.cloudflare_token.as_deref().map(redact),
+        );
+        #[cfg(feature = "route53")]
+        debug.field("route53", &self.route53.is_some());
+        debug
+            .field("desec_token", &self.desec_token.as_deref().map(redact))
+            .field("hetzner_token", &self.hetzner_token.as_deref().map(redact))
+            .field("scheduled", &self.scheduled)
+            .field("default_ttl", &self.default_ttl)
+            .field("allowed_domains", &self.allowed_domains)
+            .field("allow_private", &self.allow_private)
+            .field("webhook_domains", &self.webhook_domains)
+            .field("parking_ips", &self.parking_ips)
+            .field("strict_parameters", &self.strict_parameters)
+            .field("ip_sources", &self.ip_sources)
+            .field("cors", &self.cors)
+            .field("metrics", &self.metrics);
+        #[cfg(feature = "dynamodb")]
+        debug.field("audit_log", &self.audit_log);
+        debug.finish()
+    }
+}
+
+/// Environment variable with the shared secret callers have to send as `token` parameter
+pub const ACCESS_TOKEN_ENV: &str = "PORKDYN_ACCESS_TOKEN";
+/// Environment variable with the DynamoDB table every record change is written to
+pub const AUDIT_TABLE_ENV: &str = "PORKDYN_AUDIT_TABLE";
+
+impl AppState {
+    pub async fn from_env() -> Result<Self, CredentialsError> {
+        Self::from_config(Config::load()?).await
+    }
+
+    /// Creates the state from the configuration, credentials are still read from the environment.
+    pub async fn from_config(config: Config) -> Result<Self, CredentialsError> {
+        let server_credentials = if let Ok(secret_id) = std::env::var(SECRET_ID_ENV) {
+            Some(ServerCredentials::Static(
+                load_from_secrets_manager(&secret_id).await?,
+            ))
+        } else if let Ok(path) = std::env::var(PARAMETER_PATH_ENV) {
+            Some(load_from_parameter_store(&path).await?)
+        } else if let (Ok(api_key), Ok(secret_key)) =
+            (std::env::var(API_KEY_ENV), std::env::var(SECRET_KEY_ENV))
+        {
+            Some(ServerCredentials::Static(Credentials::new(
+                api_key, secret_key,
+            )))
+        } else {
+            None
+        };
+        #[cfg(feature = "kms")]
+        let kms_decryptor = match std::env::var(KMS_KEY_ID_ENV) {
+            Ok(key_id) if !key_id.is_empty() => Some(KmsDecryptor::new(&key_id).await),
+            _ => None,
+        };
+        // Encrypted credentials would be rejected on every request instead
+        #[cfg(not(feature = "kms"))]
+        if std::env::var(KMS_KEY_ID_ENV).is_ok_and(|key_id| !key_id.is_empty()) {
+            return Err(CredentialsError::Kms(
+                "not included in this build, enable the `kms` feature".to_string(),
+            ));
+        }
+        let acme_dns = AcmeDnsConfig::from_env()?;
+        let provider = config.provider;
+        let fallback_provider = config.fallback_provider;
+        let mirror_providers = config.mirror_providers;
+        // Fail at cold start instead of on every request if a provider is missing from the build
+        if let Some(kind) = std::iter::once(provider)
+            .chain(fallback_provider)
+            .chain(mirror_providers.iter().copied())
+            .find(|kind| !kind.is_enabled())
+        {
+            return Err(CredentialsError::InvalidProvider(format!(
+                "{} is not included in this build, enable the `{}` feature",
+                kind.as_str(),
+                kind.as_str()
+            )));
+        }
+        #[cfg(feature = "cloudflare")]
+        let cloudflare_token = std::env::var(CLOUDFLARE_API_TOKEN_ENV)
+            .ok()
+            .filter(|token| !token.is_empty());
+        #[cfg(feature = "route53")]
+        let route53 = if provider == ProviderKind::Route53
+            || fallback_provider == Some(ProviderKind::Route53)
+            || mirror_providers.contains(&ProviderKind::Route53)
+            || std::env::var(ROUTE53_ENABLED_ENV).is_ok_and(|enabled| enabled == "true")
+        {
+            Some(crate::route53::client().await)
+        } else {
+            None
+        };
+        let desec_token = std::env::var(DESEC_TOKEN_ENV)
+            .ok()
+            .filter(|token| !token.is_empty());
+        let hetzner_token = std::env::var(HETZNER_DNS_TOKEN_ENV)
+            .ok()
+            .filter(|token| !token.is_empty());
+        #[cfg(feature = "dynamodb")]
+        let audit_log = AuditLog::from_env().await;
+        // Changes must not go unaudited because the audit log is missing from the build
+        #[cfg(not(feature = "dynamodb"))]
+        if std::env::var(AUDIT_TABLE_ENV).is_ok_and(|table| !table.is_empty()) {
+            return Err(CredentialsError::InvalidConfig(format!(
+                "{} is set, but the audit log is not included in this build, enable the `dynamodb` feature",
+                AUDIT_TABLE_ENV
+            )));
+        }
+        Ok(Self {
+            server_credentials,
+            access_token: config.access_token.filter(|token| !token.is_empty()),
+            token_domains: config.token_domains,
+            #[cfg(feature = "kms")]
+            kms_decryptor,
+            acme_dns,
+            porkbun: PorkbunClient::with_timeouts(
+                config.timeouts.connect_seconds.map(Duration::from_secs),
+                config.timeouts.request_seconds.map(Duration::from_secs),
+                RetryPolicy::new(
+                    config.retry.attempts,
+                    config.retry.base_delay_ms.map(Duration::from_millis),
+                ),
+            ),
+            provider,
+            fallback_provider,
+            mirror_providers,
+            #[cfg(feature = "cloudflare")]
+            cloudflare_token,
+            #[cfg(feature = "route53")]
+            route53,
+            desec_token,
+            hetzner_token,
+            scheduled: ScheduledConfig::from_env(),
+            default_ttl: config.default_ttl,
+            allowed_domains: config.allowed_domains,
+            allow_private: config.allow_private,
+            webhook_domains: config.webhook_domains,
+            parking_ips: config.parking_ips,
+            strict_parameters: config.strict_parameters,
+            ip_sources: config.ip_sources,
+            cors: config.cors,
+            metrics: config.metrics,
+            #[cfg(feature = "dynamodb")]
+            audit_log,
+        })
+    }
+}
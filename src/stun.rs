@@ -0,0 +1,154 @@
+use lambda_http::Error;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// Port of STUN servers if none is given
+pub const DEFAULT_STUN_PORT: u16 = 3478;
+
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const MAGIC_COOKIE: u32 = 0x2112_A442;
+const MAPPED_ADDRESS: u16 = 0x0001;
+const XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const HEADER_LENGTH: usize = 20;
+/// UDP may lose the request or the response, so it is sent a few times
+const ATTEMPTS: u32 = 3;
+const ATTEMPT_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Asks a STUN server (RFC 5389) for the address this host's requests arrive from, over the
+/// IP version of `ipv6`. Works without any HTTP service, e.g. with `stun.l.google.com:19302`.
+pub(crate) async fn stun_public_ip(server: &str, ipv6: bool) -> Result<IpAddr, Error> {
+    let address = tokio::net::lookup_host(server)
+        .await?
+        .find(|address| address.is_ipv6() == ipv6)
+        .ok_or_else(|| format!("{} has no address of the requested IP version", server))?;
+    let local: SocketAddr = if ipv6 {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(address).await?;
+    let transaction_id: [u8; 12] = uuid::Uuid::new_v4().as_bytes()[..12].try_into()?;
+    let request = binding_request(&transaction_id);
+    let mut buffer = [0; 512];
+    for _ in 0..ATTEMPTS {
+        socket.send(&request).await?;
+        let Ok(received) = tokio::time::timeout(ATTEMPT_TIMEOUT, socket.recv(&mut buffer)).await
+        else {
+            continue;
+        };
+        if let Some(address) = parse_binding_response(&buffer[..received?], &transaction_id) {
+            return Ok(address);
+        }
+    }
+    Err(format!("No valid answer from STUN server {}", server).into())
+}
+
+fn binding_request(transaction_id: &[u8; 12]) -> Vec<u8> {
+    let mut request = Vec::with_capacity(HEADER_LENGTH);
+    request.extend_from_slice(&BINDING_REQUEST.to_be_bytes());
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(transaction_id);
+    request
+}
+
+/// The mapped address of a successful binding response to the transaction, preferring the
+/// XOR-MAPPED-ADDRESS attribute which NATs rewriting addresses in payloads can't tamper with.
+fn parse_binding_response(response: &[u8], transaction_id: &[u8; 12]) -> Option<IpAddr> {
+    let header = response.get(..HEADER_LENGTH)?;
+    if u16::from_be_bytes([header[0], header[1]]) != BINDING_SUCCESS
+        || header[4..8] != MAGIC_COOKIE.to_be_bytes()
+        || &header[8..20] != transaction_id
+    {
+        return None;
+    }
+    let length = usize::from(u16::from_be_bytes([header[2], header[3]]));
+    let mut attributes = response.get(HEADER_LENGTH..HEADER_LENGTH + length)?;
+    let mut mapped = None;
+    while attributes.len() >= 4 {
+        let kind = u16::from_be_bytes([attributes[0], attributes[1]]);
+        let value_length = usize::from(u16::from_be_bytes([attributes[2], attributes[3]]));
+        let value = attributes.get(4..4 + value_length)?;
+        match kind {
+            XOR_MAPPED_ADDRESS => return parse_address(value, Some(transaction_id)),
+            MAPPED_ADDRESS => mapped = parse_address(value, None),
+            _ => {}
+        }
+        // Attribute values are padded to a multiple of four bytes
+        let padded = (4 + value_length).next_multiple_of(4);
+        attributes = attributes.get(padded..).unwrap_or_default();
+    }
+    mapped
+}
+
+/// Decodes an address attribute, XOR-ed with the magic cookie and transaction id if given.
+fn parse_address(value: &[u8], xor_transaction_id: Option<&[u8; 12]>) -> Option<IpAddr> {
+    let mut mask = [0; 16];
+    if let Some(transaction_id) = xor_transaction_id {
+        mask[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        mask[4..].copy_from_slice(transaction_id);
+    }
+    let unmask = |bytes: &[u8]| -> Vec<u8> { bytes.iter().zip(mask).map(|(b, m)| b ^ m).collect() };
+    match value.get(1)? {
+        0x01 => {
+            let octets: [u8; 4] = unmask(value.get(4..8)?).try_into().ok()?;
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        0x02 => {
+            let octets: [u8; 16] = unmask(value.get(4..20)?).try_into().ok()?;
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSACTION_ID: [u8; 12] = [
+        0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6, 0x86, 0xfa, 0x87, 0xdf, 0xae,
+    ];
+
+    fn response(attributes: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (kind, value) in attributes {
+            body.extend_from_slice(&kind.to_be_bytes());
+            body.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            body.extend_from_slice(value);
+            body.resize(body.len().next_multiple_of(4), 0);
+        }
+        let mut response = Vec::new();
+        response.extend_from_slice(&BINDING_SUCCESS.to_be_bytes());
+        response.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        response.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        response.extend_from_slice(&TRANSACTION_ID);
+        response.extend_from_slice(&body);
+        response
+    }
+
+    #[test]
+    fn test_parse_binding_response() {
+        // 192.0.2.1:32853 from the RFC 5769 sample IPv4 response, after a software attribute
+        let xor_mapped = vec![0x00, 0x01, 0xa1, 0x47, 0xe1, 0x12, 0xa6, 0x43];
+        let software = b"test vector".to_vec();
+        let ipv4 = response(&[(0x8022, software), (XOR_MAPPED_ADDRESS, xor_mapped)]);
+        assert_eq!(
+            parse_binding_response(&ipv4, &TRANSACTION_ID),
+            Some("192.0.2.1".parse().unwrap())
+        );
+        assert_eq!(parse_binding_response(&ipv4, &[0; 12]), None);
+
+        let mut value = vec![0x00, 0x02, 0x00, 0x00];
+        value.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        let ipv6 = response(&[(MAPPED_ADDRESS, value)]);
+        assert_eq!(
+            parse_binding_response(&ipv6, &TRANSACTION_ID),
+            Some("2001:db8::1".parse().unwrap())
+        );
+        assert_eq!(parse_binding_response(&ipv6[..24], &TRANSACTION_ID), None);
+    }
+}
@@ -0,0 +1,116 @@
+use lambda_http::tracing::warn;
+use std::ffi::OsStr;
+use std::io;
+use std::time::Duration;
+
+/// Environment variable systemd sets to the socket `Type=notify` services report their state to
+const NOTIFY_SOCKET_ENV: &str = "NOTIFY_SOCKET";
+/// Environment variable systemd sets to the watchdog timeout in microseconds (`WatchdogSec=`)
+const WATCHDOG_USEC_ENV: &str = "WATCHDOG_USEC";
+/// Environment variable with the process the watchdog timeout is meant for
+const WATCHDOG_PID_ENV: &str = "WATCHDOG_PID";
+
+/// The service finished starting up
+pub const READY: &str = "READY=1";
+/// The service is still alive, has to be sent within the watchdog timeout
+pub const WATCHDOG: &str = "WATCHDOG=1";
+/// The service is shutting down
+pub const STOPPING: &str = "STOPPING=1";
+
+/// Sends a state change to systemd (`sd_notify`), does nothing if not run as a `Type=notify`
+/// service.
+pub fn notify(state: &str) {
+    let Some(path) = std::env::var_os(NOTIFY_SOCKET_ENV) else {
+        return;
+    };
+    if let Err(e) = send(&path, state) {
+        warn!(error = %e, "Failed to notify systemd");
+    }
+}
+
+/// Shows the message in `systemctl status`.
+pub fn status(message: &str) {
+    notify(&format!("STATUS={}", message));
+}
+
+/// How often to send [`WATCHDOG`], half the timeout configured for this process.
+pub fn watchdog_interval() -> Option<Duration> {
+    parse_watchdog_interval(
+        std::env::var(WATCHDOG_USEC_ENV).ok().as_deref(),
+        std::env::var(WATCHDOG_PID_ENV).ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn parse_watchdog_interval(
+    usec: Option<&str>,
+    pid: Option<&str>,
+    own_pid: u32,
+) -> Option<Duration> {
+    if pid.is_some_and(|pid| pid.parse() != Ok(own_pid)) {
+        return None;
+    }
+    let usec: u64 = usec?.parse().ok().filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec) / 2)
+}
+
+/// Sends the datagram to the socket, a leading `@` denotes an abstract socket.
+#[cfg(target_os = "linux")]
+fn send(path: &OsStr, state: &str) -> io::Result<()> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let address = match path.as_bytes().strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(path)?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &address)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send(_path: &OsStr, _state: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "systemd notifications are only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_watchdog_interval() {
+        assert_eq!(
+            parse_watchdog_interval(Some("60000000"), None, 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_watchdog_interval(Some("60000000"), Some("42"), 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_watchdog_interval(Some("60000000"), Some("7"), 42),
+            None
+        );
+        assert_eq!(parse_watchdog_interval(Some("0"), None, 42), None);
+        assert_eq!(parse_watchdog_interval(None, None, 42), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_send() {
+        use std::os::unix::net::UnixDatagram;
+
+        let path = std::env::temp_dir().join(format!("porkdyn-notify-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        send(path.as_os_str(), READY).unwrap();
+        let mut buffer = [0; 64];
+        let length = socket.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..length], READY.as_bytes());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+use crate::logging;
+use lambda_http::tracing::{warn, Span, Subscriber};
+use lambda_http::{Error, Request, RequestExt};
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_aws::trace::{XrayIdGenerator, XrayPropagator};
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Standard OpenTelemetry variable with the collector's OTLP endpoint, spans are only exported
+/// if it is set, e.g. to `http://localhost:4318` of the ADOT collector layer
+const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+const SERVICE_NAME_ENV: &str = "OTEL_SERVICE_NAME";
+const DEFAULT_SERVICE_NAME: &str = "porkdyn";
+/// Header API Gateway and load balancers pass the X-Ray trace in
+const XRAY_TRACE_HEADER: &str = "x-amzn-trace-id";
+
+/// Flushed after every invocation, Lambda freezes the process until the next one
+static TRACER_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// The layer exporting the spans, if an OTLP endpoint is configured. Spans and events with
+/// secret fields aren't exported at all, the fields would end up as attributes unredacted.
+pub(crate) fn layer<S>() -> Result<Option<impl Layer<S>>, Error>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    if std::env::var(OTLP_ENDPOINT_ENV).is_err() {
+        return Ok(None);
+    }
+    let provider = tracer_provider()?;
+    let tracer = provider.tracer(DEFAULT_SERVICE_NAME);
+    let _ = TRACER_PROVIDER.set(provider);
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(filter_fn(|metadata| {
+            !metadata
+                .fields()
+                .iter()
+                .any(|field| logging::is_secret(field.name()))
+        }));
+    Ok(Some(layer))
+}
+
+/// Exports the spans in batches over OTLP/HTTP, configured by the `OTEL_EXPORTER_OTLP_*`
+/// variables.
+fn tracer_provider() -> Result<SdkTracerProvider, Error> {
+    let exporter = SpanExporter::builder().with_http().build()?;
+    let service_name =
+        std::env::var(SERVICE_NAME_ENV).unwrap_or_else(|_| DEFAULT_SERVICE_NAME.to_string());
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        // X-Ray only accepts trace ids which start with the time
+        .with_id_generator(XrayIdGenerator::default())
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .build())
+}
+
+/// Exports the spans of the invocation before Lambda freezes the process.
+pub(crate) fn flush() {
+    if let Some(provider) = TRACER_PROVIDER.get() {
+        if let Err(e) = provider.force_flush() {
+            warn!(error = %e, "Failed to export the spans");
+        }
+    }
+}
+
+/// Continues the X-Ray trace of the invocation (or of the request's `X-Amzn-Trace-Id` header),
+/// so the spans show up below the segment of Lambda or API Gateway.
+pub(crate) fn continue_xray_trace(span: &Span, event: &Request) {
+    let trace_header = event
+        .lambda_context_ref()
+        .and_then(|context| context.xray_trace_id.clone())
+        .or_else(|| {
+            event
+                .headers()
+                .get(XRAY_TRACE_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        });
+    let Some(trace_header) = trace_header else {
+        return;
+    };
+    let carrier = HashMap::from([(XRAY_TRACE_HEADER.to_string(), trace_header)]);
+    span.set_parent(XrayPropagator::default().extract(&carrier));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lambda_http::tracing::info_span;
+    use opentelemetry::trace::TraceContextExt;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    #[test]
+    fn test_continue_xray_trace() {
+        let tracer = SdkTracerProvider::builder().build().tracer("test");
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+        let _guard = subscriber.set_default();
+        let mut event = Request::default();
+        event.headers_mut().insert(
+            XRAY_TRACE_HEADER,
+            "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1"
+                .parse()
+                .unwrap(),
+        );
+        let span = info_span!("request");
+        continue_xray_trace(&span, &event);
+        let trace_id = span.context().span().span_context().trace_id();
+        assert_eq!(trace_id.to_string(), "5759e988bd862e3fe1be46a994272793");
+    }
+}
@@ -0,0 +1,57 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Path of the build information of the running binary
+pub(crate) const VERSION_PATH: &str = "/version";
+
+/// Optional features compiled into the binary, see `Cargo.toml`
+const FEATURES: [(&str, bool); 10] = [
+    ("cli", cfg!(feature = "cli")),
+    ("cloudflare", cfg!(feature = "cloudflare")),
+    ("dynamodb", cfg!(feature = "dynamodb")),
+    ("kms", cfg!(feature = "kms")),
+    ("opentelemetry", cfg!(feature = "opentelemetry")),
+    ("parameter-store", cfg!(feature = "parameter-store")),
+    ("prometheus", cfg!(feature = "prometheus")),
+    ("route53", cfg!(feature = "route53")),
+    ("secrets-manager", cfg!(feature = "secrets-manager")),
+    ("server", cfg!(feature = "server")),
+];
+
+/// Identifies a deployment, the commit and build time are embedded by `build.rs`.
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct BuildInfo {
+    version: &'static str,
+    git_sha: &'static str,
+    build_timestamp: &'static str,
+    features: Vec<&'static str>,
+}
+
+impl BuildInfo {
+    pub(crate) fn current() -> Self {
+        BuildInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            git_sha: env!("PORKDYN_GIT_SHA"),
+            build_timestamp: env!("PORKDYN_BUILD_TIMESTAMP"),
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(feature, _)| *feature)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let info = BuildInfo::current();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_sha.is_empty());
+        assert!(info.build_timestamp.ends_with('Z'));
+        assert_eq!(info.features.contains(&"server"), cfg!(feature = "server"));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::ip_utils::{validate_and_classify_ip, IpType};
+use lambda_http::aws_lambda_events::query_map::QueryMap;
+use std::collections::HashMap;
+
+/// Path of the push notification receiver
+pub(crate) const WEBHOOK_PATH: &str = "/webhook";
+
+/// Payload fields which may carry the new address, in order of preference. Fields with the
+/// previous address (e.g. `old_ip`) are ignored, unchanged records are skipped anyway.
+const ADDRESS_FIELDS: [&str; 12] = [
+    "new_ip",
+    "newip",
+    "new_ipv4",
+    "new_ipv6",
+    "new_address",
+    "current_ip",
+    "wan_ip",
+    "ip",
+    "ipv4",
+    "ipv6",
+    "ipaddr",
+    "address",
+];
+/// Parameters taken over from the payload or the query, anything else in a payload (e.g. an
+/// `action` or `type` field describing the event) must not be mistaken for a parameter
+const PASSED_PARAMETERS: [&str; 6] = [
+    "token",
+    "apikey",
+    "secretapikey",
+    "credentials",
+    "domain",
+    "provider",
+];
+
+/// Translates an IP change notification into the regular parameters, e.g.
+/// `{"event": "wan_up", "old_ip": "198.51.100.1", "new_ip": "203.0.113.7"}`.
+/// - The first IPv4 and IPv6 address among the known address fields are used
+/// - The domains default to the configured webhook domains, so senders which can't add fields
+///   to their payload only need the URL
+pub(crate) fn translate_parameters(params: &QueryMap, webhook_domains: &[String]) -> QueryMap {
+    let mut parameters: HashMap<String, String> = PASSED_PARAMETERS
+        .iter()
+        .filter_map(|name| Some((name.to_string(), params.first(name)?.to_string())))
+        .collect();
+    if !parameters.contains_key("domain") && !webhook_domains.is_empty() {
+        parameters.insert("domain".to_string(), webhook_domains.join(","));
+    }
+    for field in ADDRESS_FIELDS {
+        let Some(address) = params.first(field).map(str::trim) else {
+            continue;
+        };
+        let name = match validate_and_classify_ip(address) {
+            Ok(IpType::V4) => "ip",
+            Ok(IpType::V6) => "ipv6",
+            Err(_) => continue,
+        };
+        parameters
+            .entry(name.to_string())
+            .or_insert(address.to_string());
+    }
+    QueryMap::from(parameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query_map(parameters: &[(&str, &str)]) -> QueryMap {
+        QueryMap::from(
+            parameters
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    #[test]
+    fn test_translate_payload() {
+        let domains = vec![
+            "home.example.org".to_string(),
+            "vpn.example.org".to_string(),
+        ];
+        let translated = translate_parameters(
+            &query_map(&[
+                ("token", "router-token"),
+                ("event", "wan_up"),
+                ("action", "ip_changed"),
+                ("old_ip", "198.51.100.1"),
+                ("new_ip", "203.0.113.7"),
+                ("ipv6", "2001:db8::7"),
+                ("interface", "pppoe0"),
+            ]),
+            &domains,
+        );
+        assert_eq!(
+            translated.first("domain"),
+            Some("home.example.org,vpn.example.org")
+        );
+        assert_eq!(translated.first("ip"), Some("203.0.113.7"));
+        assert_eq!(translated.first("ipv6"), Some("2001:db8::7"));
+        assert_eq!(translated.first("token"), Some("router-token"));
+        assert_eq!(translated.first("action"), None);
+        assert_eq!(translated.first("old_ip"), None);
+    }
+
+    #[test]
+    fn test_translate_explicit_domain() {
+        let translated = translate_parameters(
+            &query_map(&[("domain", "nas.example.org"), ("address", "not an address")]),
+            &["home.example.org".to_string()],
+        );
+        assert_eq!(translated.first("domain"), Some("nas.example.org"));
+        assert_eq!(translated.first("ip"), None);
+        assert_eq!(translated.first("ipv6"), None);
+    }
+}